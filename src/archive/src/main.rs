@@ -36,6 +36,19 @@
 //! - prefix scan with anchor to retrieve entries by anchor
 //! - prefix scan with (anchor, timestamp) to narrow down on the time period for a specific anchor
 //! - prefix scan with (anchor, timestamp, log index) to do pagination (with the key of the first entry not included in the previous set)
+//!
+//! ## Retention
+//! If a retention period is configured, entries older than the retention period are pruned periodically.
+//! Since the [Log] is append-only, pruned entries are not removed from the log but marked as pruned
+//! using a [PruningWatermark]: all log entries with an index lower than the watermark are no longer
+//! served (i.e. returned as `None`) and their anchor index entries are removed. The data of pruned
+//! entries is then erased by overwriting it with zeros (see [erase_pruned_entries]), so that it is
+//! deleted from stable memory. The space used by erased entries is not reclaimed.
+//!
+//! The [Log] does not expose where the data of an entry is stored. Erasing relies on the data of the
+//! entries being stored back to back, which is verified for every entry before overwriting it: if
+//! the data of an entry is not found where expected, nothing is overwritten and the entries are only
+//! pruned.
 use candid::{candid_method, CandidType, Deserialize, Principal};
 use ic_cdk::api::call::CallResult;
 use ic_cdk::api::management_canister::main::{canister_status, CanisterIdRecord};
@@ -43,7 +56,7 @@ use ic_cdk::api::stable::stable64_size;
 use ic_cdk::api::time;
 use ic_cdk::{call, caller, id, print, trap};
 use ic_cdk_macros::{init, post_upgrade, query, update};
-use ic_cdk_timers::set_timer_interval;
+use ic_cdk_timers::{set_timer, set_timer_interval};
 use ic_metrics_encoder::MetricsEncoder;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{
@@ -53,9 +66,11 @@ use ic_stable_structures::{
 use internet_identity_interface::archive::types::*;
 use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
 use internet_identity_interface::internet_identity::types::*;
+use internet_identity_interface::metrics::MetricsFormat;
 use serde_bytes::ByteBuf;
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 #[cfg(test)]
@@ -76,6 +91,18 @@ const MAX_STABLE_MEMORY_SIZE: u64 = 32 * GIB;
 /// The maximum number of Wasm pages that we allow to use for the stable storage.
 const MAX_WASM_PAGES: u64 = MAX_STABLE_MEMORY_SIZE / WASM_PAGE_SIZE;
 
+/// Interval at which expired entries are pruned (if a retention period is configured).
+const PRUNING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Maximum number of entries pruned in a single message to stay within the instruction limit.
+const MAX_ENTRIES_PRUNED_PER_CALL: u64 = 5_000;
+
+/// Maximum number of entries scanned (i.e. decoded) by a single [query_anchor_entries] call to stay
+/// within the instruction limit.
+const MAX_ENTRIES_SCANNED_PER_QUERY: usize = 1_000;
+
+/// Maximum number of sequence gaps to keep (older gaps are dropped first).
+const MAX_SEQUENCE_GAPS: usize = 100;
+
 /// Memory ids of memory managed by the memory manager.
 const LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(0);
 const LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(1);
@@ -173,6 +200,27 @@ struct ArchiveConfig {
     error_buffer_limit: Option<u16>,
     /// Highest sequence number of any entry that was archived.
     highest_sequence_number: Option<u64>,
+    /// Whether entries are fetched from II (pull) or written by II (push).
+    archive_integration: Option<ArchiveIntegration>,
+    /// Entries older than this period (in nanoseconds) are pruned. Kept forever if not set.
+    retention_period_ns: Option<u64>,
+    /// Boundary up to which entries have been pruned, if any.
+    pruning_watermark: Option<PruningWatermark>,
+    /// Prefix of the log whose entries have been erased, see [erase_pruned_entries].
+    erased_log_prefix: Option<ErasedLogPrefix>,
+    /// The most recent gaps in the sequence numbers of archived entries.
+    sequence_gaps: Option<Vec<SequenceGap>>,
+    /// Total number of sequence numbers that were never archived.
+    missing_entries_count: Option<u64>,
+}
+
+/// Prefix of the log whose entries have been erased (i.e. overwritten with zeros).
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct ErasedLogPrefix {
+    /// Log index of the oldest entry not erased.
+    log_index: u64,
+    /// Offset of the data of that entry in the data memory of the log.
+    data_offset: u64,
 }
 
 impl Storable for ConfigState {
@@ -295,35 +343,7 @@ async fn fetch_entries() {
     }
 
     let entries_count = entries.len();
-    let lowest_seq_nr = entries.first().unwrap().sequence_number;
-    let highest_seq_nr = entries.last().unwrap().sequence_number;
-    // For the very first fetch the sequence number is not known thus we default on the one we get back from II.
-    let expected_seq_nr = highest_archived_sequence_number()
-        .map(|seq_nr| seq_nr + 1)
-        .unwrap_or(lowest_seq_nr);
-
-    if lowest_seq_nr > expected_seq_nr {
-        // Unfortunately there is nothing further we can do as the missing entries have already been
-        // pruned on the II side.
-        print(format!(
-            "Gap in archive entries: entries {} to {} were never archived!",
-            expected_seq_nr,
-            lowest_seq_nr - 1
-        ))
-    }
-
-    // If this condition is false, all entries have already been archived by another invocation of fetch_entries.
-    // This can happen if the fetch interval is too short or on call failures, e.g. if the last acknowledge message got rejected.
-    // In such cases just the acknowledge is sent again.
-    if highest_seq_nr >= expected_seq_nr {
-        entries
-            .into_iter()
-            // due to the overlapping calls, also just parts of the entries could already have been archived
-            // --> filter those out
-            .filter(|e| e.sequence_number >= expected_seq_nr)
-            .for_each(|e| write_entry_internal(e.anchor_number, e.timestamp, e.entry));
-        set_highest_archived_sequence_number(highest_seq_nr);
-    }
+    let highest_seq_nr = archive_entries(entries);
 
     let call_time = time();
     let result: CallResult<()> =
@@ -352,6 +372,102 @@ async fn fetch_entries() {
     };
 }
 
+/// Writes a batch of entries buffered by II. Used by II if the archive integration is set to push.
+/// Entries that have already been archived are skipped so that II can safely retry failed calls.
+#[update]
+#[candid_method]
+fn write_entries(entries: Vec<BufferedEntry>) {
+    with_config(|config| {
+        if config.ii_canister != caller() {
+            trap(&format!(
+                "Only {} is allowed to write entries.",
+                config.ii_canister
+            ))
+        }
+    });
+
+    if entries.is_empty() {
+        return;
+    }
+    let entries_count = entries.len();
+    archive_entries(entries);
+
+    with_call_info_mut(|info| {
+        info.last_successful_fetch = Some(FetchInfo {
+            timestamp: time(),
+            number_of_entries: entries_count as u16,
+        })
+    });
+}
+
+/// Archives the given entries (sorted ascending by sequence number) and returns the highest
+/// sequence number of the batch. Entries that have already been archived are skipped.
+/// *Note:* `entries` must not be empty.
+fn archive_entries(entries: Vec<BufferedEntry>) -> u64 {
+    let lowest_seq_nr = entries.first().unwrap().sequence_number;
+    let highest_seq_nr = entries.last().unwrap().sequence_number;
+    // For the very first batch the sequence number is not known thus we default on the one we get from II.
+    let expected_seq_nr = highest_archived_sequence_number()
+        .map(|seq_nr| seq_nr + 1)
+        .unwrap_or(lowest_seq_nr);
+
+    if lowest_seq_nr > expected_seq_nr {
+        // Unfortunately there is nothing further we can do as the missing entries have already been
+        // pruned on the II side. Record the gap to allow reconciliation with II.
+        print(format!(
+            "Gap in archive entries: entries {} to {} were never archived!",
+            expected_seq_nr,
+            lowest_seq_nr - 1
+        ));
+        record_sequence_gap(SequenceGap {
+            first_sequence_number: expected_seq_nr,
+            last_sequence_number: lowest_seq_nr - 1,
+            detected_at: time(),
+            timestamp_before: last_archived_entry_timestamp(),
+            timestamp_after: entries.first().unwrap().timestamp,
+        });
+    }
+
+    // If this condition is false, all entries have already been archived by another invocation of fetch_entries
+    // or write_entries.
+    // This can happen if the fetch interval is too short or on call failures, e.g. if the last acknowledge message
+    // got rejected or II retries a push because the response got lost.
+    if highest_seq_nr >= expected_seq_nr {
+        entries
+            .into_iter()
+            // due to the overlapping calls, also just parts of the entries could already have been archived
+            // --> filter those out
+            .filter(|e| e.sequence_number >= expected_seq_nr)
+            .for_each(|e| write_entry_internal(e.anchor_number, e.timestamp, e.entry));
+        set_highest_archived_sequence_number(highest_seq_nr);
+    }
+    highest_seq_nr
+}
+
+fn record_sequence_gap(gap: SequenceGap) {
+    // stable cell does not allow modifying values in place --> copy and swap
+    let mut config = with_config(|config| config.clone());
+    *config.missing_entries_count.get_or_insert(0) +=
+        gap.last_sequence_number - gap.first_sequence_number + 1;
+    let gaps = config.sequence_gaps.get_or_insert_with(Vec::new);
+    if gaps.len() >= MAX_SEQUENCE_GAPS {
+        gaps.remove(0);
+    }
+    gaps.push(gap);
+    write_config(config);
+}
+
+/// Timestamp of the most recently archived entry, if any (and if it can be decoded).
+fn last_archived_entry_timestamp() -> Option<Timestamp> {
+    with_log(|log| {
+        let last_idx = log.len().checked_sub(1)?;
+        let entry = log.get(last_idx)?;
+        candid::decode_one::<Entry>(&entry)
+            .ok()
+            .map(|entry| entry.timestamp)
+    })
+}
+
 fn write_entry_internal(anchor: AnchorNumber, timestamp: Timestamp, entry: ByteBuf) {
     let idx = with_log(|log| {
         log.append(&entry.into_vec())
@@ -392,12 +508,18 @@ fn get_entries(index: Option<u64>, limit: Option<u16>) -> Entries {
             Some(idx) => idx,
         };
 
+        let lowest_retained_idx = lowest_retained_log_index();
         let mut entries = Vec::with_capacity(limit);
         for idx in start_idx..start_idx + limit as u64 {
             let entry = match log.get(idx) {
                 None => break,
                 Some(entry) => entry,
             };
+            if idx < lowest_retained_idx {
+                // entry has been pruned
+                entries.push(None);
+                continue;
+            }
             entries.push(Some(
                 candid::decode_one(&entry).expect("failed to decode log entry"),
             ))
@@ -457,11 +579,14 @@ fn get_anchor_entries(
             timestamp: 0,
             log_index: 0,
         };
+        let lowest_retained_idx = lowest_retained_log_index();
         with_log(|log| {
             // Take one too many from the iterator to extract the cursor. This avoids having to
             // iterate twice or use next explicitly.
             let mut entries: Vec<(AnchorIndexKey, Vec<u8>)> = index
                 .range(start_key..end_key)
+                // skip entries that have been pruned but are still referenced by the index
+                .filter(|(anchor_key, _)| anchor_key.log_index >= lowest_retained_idx)
                 .take(limit + 1)
                 .map(|(anchor_key, _)| {
                     let entry = log
@@ -484,7 +609,129 @@ fn get_anchor_entries(
                 .map(|(_, entry)| candid::decode_one(entry).expect("failed to decode log entry"))
                 .collect();
 
-            AnchorEntries { entries, cursor }
+            AnchorEntries {
+                entries,
+                cursor,
+                pruning_watermark: pruning_watermark(),
+            }
+        })
+    })
+}
+
+/// Returns the decoded entries of a single anchor filtered by operation type and timestamp range
+/// (see [AnchorEntriesQuery]) together with the number of entries per operation type among the
+/// scanned entries.
+///
+/// At most [MAX_ENTRIES_SCANNED_PER_QUERY] entries are scanned per call. The returned cursor points
+/// to the first entry not scanned, so that the counts of all pages add up to the counts within the
+/// timestamp range.
+#[query]
+#[candid_method(query)]
+fn query_anchor_entries(query: AnchorEntriesQuery) -> FilteredAnchorEntries {
+    let limit = limit_or_default(query.limit);
+    let anchor = query.anchor;
+    let from_timestamp = query.from_timestamp.unwrap_or(0);
+
+    // Start (inclusive) and end (exclusive) of the index range covering the timestamp range,
+    // see get_anchor_entries for details about the index structure.
+    let range_start = AnchorIndexKey {
+        anchor,
+        timestamp: from_timestamp,
+        log_index: 0,
+    };
+    let range_end = match query.to_timestamp {
+        Some(timestamp) => AnchorIndexKey {
+            anchor,
+            timestamp,
+            log_index: 0,
+        },
+        None => AnchorIndexKey {
+            anchor: anchor + 1,
+            timestamp: 0,
+            log_index: 0,
+        },
+    };
+    let start_key = match query.cursor {
+        None => range_start.clone(),
+        Some(Cursor::NextToken { next_token }) => {
+            let index_key = AnchorIndexKey::from_bytes(Cow::from(next_token.into_vec()));
+            assert_eq!(
+                anchor, index_key.anchor,
+                "anchor does not match the next_token"
+            );
+            index_key
+        }
+        Some(Cursor::Timestamp { timestamp }) => AnchorIndexKey {
+            anchor,
+            timestamp,
+            log_index: 0,
+        },
+    }
+    .max(range_start.clone());
+
+    let pruning_watermark = pruning_watermark();
+    if start_key >= range_end {
+        return FilteredAnchorEntries {
+            entries: vec![],
+            cursor: None,
+            operation_counts: vec![],
+            pruning_watermark,
+        };
+    }
+
+    let lowest_retained_idx = lowest_retained_log_index();
+    with_anchor_index_mut(|index| {
+        with_log(|log| {
+            let mut operation_counts: BTreeMap<OperationType, OperationCount> = BTreeMap::new();
+            let mut entries = vec![];
+            let mut cursor = None;
+            let mut scanned = 0;
+            let index_keys = index
+                .range(start_key..range_end)
+                // skip entries that have been pruned but are still referenced by the index
+                .filter(|(anchor_key, _)| anchor_key.log_index >= lowest_retained_idx);
+            for (anchor_key, _) in index_keys {
+                if scanned == MAX_ENTRIES_SCANNED_PER_QUERY {
+                    cursor = Some(anchor_key);
+                    break;
+                }
+                let entry = log
+                    .get(anchor_key.log_index)
+                    .expect("bug: index to non-existing entry");
+                let entry: Entry = candid::decode_one(&entry).expect("failed to decode log entry");
+                let operation_type = OperationType::from(&entry.operation);
+                let matches = match query.operation_types {
+                    None => true,
+                    Some(ref types) => types.contains(&operation_type),
+                };
+                if matches && entries.len() == limit {
+                    cursor = Some(anchor_key);
+                    break;
+                }
+
+                scanned += 1;
+                let count = operation_counts
+                    .entry(operation_type)
+                    .or_insert(OperationCount {
+                        operation_type,
+                        count: 0,
+                        last_timestamp: entry.timestamp,
+                    });
+                count.count += 1;
+                count.last_timestamp = count.last_timestamp.max(entry.timestamp);
+                if matches {
+                    entries.push(Some(entry));
+                }
+            }
+
+            FilteredAnchorEntries {
+                entries,
+                cursor: cursor.map(|key| Cursor::NextToken {
+                    next_token: ByteBuf::from(key.to_bytes()),
+                }),
+                operation_counts: operation_counts.into_values().collect(),
+                pruning_watermark,
+            }
         })
     })
 }
@@ -497,13 +744,158 @@ fn limit_or_default(limit: Option<u16>) -> usize {
     })
 }
 
-fn highest_archived_sequence_number() -> Option<u64> {
+/// A helper function to read from the configuration if it has already been initialized.
+fn existing_config<R>(f: impl FnOnce(&ArchiveConfig) -> Option<R>) -> Option<R> {
     CONFIG.with(|config| match config.borrow().get() {
         ConfigState::Uninitialized => None,
-        ConfigState::Initialized(config) => config.highest_sequence_number,
+        ConfigState::Initialized(config) => f(config),
     })
 }
 
+fn highest_archived_sequence_number() -> Option<u64> {
+    existing_config(|config| config.highest_sequence_number)
+}
+
+fn pruning_watermark() -> Option<PruningWatermark> {
+    existing_config(|config| config.pruning_watermark.clone())
+}
+
+/// Log index of the oldest entry that has not been pruned.
+fn lowest_retained_log_index() -> u64 {
+    pruning_watermark().map_or(0, |watermark| watermark.log_index)
+}
+
+/// Prunes entries older than the configured retention period, starting with the oldest entry.
+/// Since entries are appended in order of their timestamps, pruning stops at the first entry
+/// that has not yet expired.
+/// At most [MAX_ENTRIES_PRUNED_PER_CALL] entries are pruned per call. If there are more expired
+/// entries, another call is scheduled immediately.
+fn prune_expired_entries() {
+    let Some(retention_period_ns) = with_config(|config| config.retention_period_ns) else {
+        return;
+    };
+    let cutoff_timestamp = time().saturating_sub(retention_period_ns);
+    let start_idx = lowest_retained_log_index();
+
+    let mut next_idx = start_idx;
+    with_log(|log| {
+        while next_idx < log.len() && next_idx - start_idx < MAX_ENTRIES_PRUNED_PER_CALL {
+            let entry = log.get(next_idx).expect("bug: missing log entry");
+            // Entries that cannot be decoded are not served anyway, so they can be pruned regardless
+            // of their age (the anchor index entry of such an entry is skipped when reading).
+            if let Ok(entry) = candid::decode_one::<Entry>(&entry) {
+                if entry.timestamp >= cutoff_timestamp {
+                    break;
+                }
+                with_anchor_index_mut(|index| {
+                    index.remove(&AnchorIndexKey {
+                        anchor: entry.anchor,
+                        timestamp: entry.timestamp,
+                        log_index: next_idx,
+                    })
+                });
+            }
+            next_idx += 1;
+        }
+    });
+
+    let limit_reached = next_idx - start_idx >= MAX_ENTRIES_PRUNED_PER_CALL;
+    // stable cell does not allow modifying values in place --> copy and swap
+    let mut config = with_config(|config| config.clone());
+    let watermark_timestamp = if limit_reached {
+        // not all expired entries have been pruned yet --> keep the previous timestamp
+        config.pruning_watermark.as_ref().map_or(0, |w| w.timestamp)
+    } else {
+        cutoff_timestamp
+    };
+    config.pruning_watermark = Some(PruningWatermark {
+        timestamp: watermark_timestamp,
+        log_index: next_idx,
+    });
+    let erasing_incomplete = erase_pruned_entries(&mut config);
+    write_config(config);
+
+    if limit_reached || erasing_incomplete {
+        // there might be more expired (or not yet erased) entries --> continue in a new message
+        set_timer(Duration::ZERO, prune_expired_entries);
+    }
+}
+
+/// Erases the data of the pruned entries by overwriting it with zeros, starting with the oldest entry
+/// not erased yet. The erased entries keep their log index and their (zeroed) space in the log, as
+/// the log is append-only.
+/// At most [MAX_ENTRIES_PRUNED_PER_CALL] entries are erased per call. Returns whether there are
+/// pruned entries left to be erased by another call.
+///
+/// If the data of an entry is not found where expected, the problem is logged and erasing stops
+/// (without failing the pruning). It is attempted again with the next pruning.
+fn erase_pruned_entries(config: &mut ArchiveConfig) -> bool {
+    let pruned_idx = config
+        .pruning_watermark
+        .as_ref()
+        .map_or(0, |watermark| watermark.log_index);
+    let data_memory = with_memory_manager(|manager| manager.get(LOG_DATA_MEMORY_ID));
+    let data_size = data_memory.size() * WASM_PAGE_SIZE;
+
+    let mut erased = match config.erased_log_prefix.clone() {
+        Some(erased) => erased,
+        None if pruned_idx == 0 => return false,
+        None => match with_log(|log| find_first_entry_data(log, &data_memory)) {
+            Some(data_offset) => ErasedLogPrefix {
+                log_index: 0,
+                data_offset,
+            },
+            None => {
+                print("Failed to erase pruned entries: data of the first log entry not found.");
+                return false;
+            }
+        },
+    };
+    let end_idx = pruned_idx.min(erased.log_index + MAX_ENTRIES_PRUNED_PER_CALL);
+
+    let data_found = with_log(|log| {
+        while erased.log_index < end_idx {
+            let Some(entry) = log.get(erased.log_index) else {
+                return false;
+            };
+            // make sure that only the data of the entry is overwritten
+            let end_offset = erased.data_offset + entry.len() as u64;
+            let mut data = vec![0; entry.len()];
+            if end_offset <= data_size {
+                data_memory.read(erased.data_offset, &mut data);
+            }
+            if end_offset > data_size || data != entry {
+                print(format!(
+                    "Failed to erase pruned entries: data of log entry {} not found at offset {}.",
+                    erased.log_index, erased.data_offset
+                ));
+                return false;
+            }
+            data_memory.write(erased.data_offset, &vec![0; entry.len()]);
+            erased.data_offset = end_offset;
+            erased.log_index += 1;
+        }
+        true
+    });
+
+    let incomplete = data_found && erased.log_index < pruned_idx;
+    config.erased_log_prefix = Some(erased);
+    incomplete
+}
+
+/// Returns the offset of the data of the first log entry in the data memory of the log, which
+/// follows the header of the data memory (within the first page).
+fn find_first_entry_data(log: &StableLog, data_memory: &VirtualMemory<Memory>) -> Option<u64> {
+    let entry = log.get(0).filter(|entry| !entry.is_empty())?;
+    let mut first_page =
+        vec![0; (data_memory.size() * WASM_PAGE_SIZE).min(WASM_PAGE_SIZE) as usize];
+    data_memory.read(0, &mut first_page);
+    first_page
+        .windows(entry.len())
+        .position(|data| data == entry.as_slice())
+        .map(|offset| offset as u64)
+}
+
 fn set_highest_archived_sequence_number(sequence_number: u64) {
     // stable cell does not allow modifying values in place --> copy and swap
     let mut config = with_config(|config| config.clone());
@@ -522,11 +914,24 @@ fn initialize(arg: ArchiveInit) {
         polling_interval_ns: Some(arg.polling_interval_ns),
         error_buffer_limit: Some(arg.error_buffer_limit),
         highest_sequence_number: highest_archived_sequence_number(),
+        archive_integration: arg.archive_integration.clone(),
+        retention_period_ns: arg.retention_period_ns,
+        pruning_watermark: pruning_watermark(),
+        erased_log_prefix: existing_config(|config| config.erased_log_prefix.clone()),
+        sequence_gaps: existing_config(|config| config.sequence_gaps.clone()),
+        missing_entries_count: existing_config(|config| config.missing_entries_count),
     });
 
-    set_timer_interval(Duration::from_nanos(arg.polling_interval_ns), || {
-        ic_cdk::spawn(fetch_entries())
-    });
+    // In push mode II writes the entries using write_entries --> no need to poll
+    if arg.archive_integration.unwrap_or_default() == ArchiveIntegration::Pull {
+        set_timer_interval(Duration::from_nanos(arg.polling_interval_ns), || {
+            ic_cdk::spawn(fetch_entries())
+        });
+    }
+
+    if arg.retention_period_ns.is_some() {
+        set_timer_interval(PRUNING_INTERVAL, prune_expired_entries);
+    }
 }
 
 fn write_config(config: ArchiveConfig) {
@@ -541,40 +946,41 @@ fn write_config(config: ArchiveConfig) {
 #[candid_method(query)]
 fn http_request(req: HttpRequest) -> HttpResponse {
     let parts: Vec<&str> = req.url.split('?').collect();
-    match parts[0] {
-        "/metrics" => {
-            let mut writer = MetricsEncoder::new(vec![], time() as i64 / 1_000_000);
-            match encode_metrics(&mut writer) {
-                Ok(()) => {
-                    let body = writer.into_inner();
-                    let headers = vec![
-                        ("Content-Type".to_string(), "text/plain".to_string()),
-                        ("Content-Length".to_string(), body.len().to_string()),
-                    ];
-                    HttpResponse {
-                        status_code: 200,
-                        headers,
-                        body: ByteBuf::from(body),
-                        upgrade: None,
-                        streaming_strategy: None,
-                    }
-                }
-                Err(err) => HttpResponse {
-                    status_code: 500,
-                    headers: vec![],
-                    body: ByteBuf::from(format!("Failed to encode metrics: {err}")),
+    if let Some(format) = MetricsFormat::for_request(parts[0], &req.headers) {
+        let mut writer = MetricsEncoder::new(vec![], time() as i64 / 1_000_000);
+        return match encode_metrics(&mut writer).and_then(|()| format.encode(writer.into_inner())) {
+            Ok(body) => {
+                let headers = vec![
+                    (
+                        "Content-Type".to_string(),
+                        format.content_type().to_string(),
+                    ),
+                    ("Content-Length".to_string(), body.len().to_string()),
+                    ("Vary".to_string(), "Accept".to_string()),
+                ];
+                HttpResponse {
+                    status_code: 200,
+                    headers,
+                    body: ByteBuf::from(body),
                     upgrade: None,
                     streaming_strategy: None,
-                },
+                }
             }
-        }
-        path => HttpResponse {
-            status_code: 404,
-            headers: vec![],
-            body: ByteBuf::from(format!("Asset {path} not found.")),
-            upgrade: None,
-            streaming_strategy: None,
-        },
+            Err(err) => HttpResponse {
+                status_code: 500,
+                headers: vec![],
+                body: ByteBuf::from(format!("Failed to encode metrics: {err}")),
+                upgrade: None,
+                streaming_strategy: None,
+            },
+        };
+    }
+    HttpResponse {
+        status_code: 404,
+        headers: vec![],
+        body: ByteBuf::from(format!("Asset {} not found.", parts[0])),
+        upgrade: None,
+        streaming_strategy: None,
     }
 }
 
@@ -592,6 +998,35 @@ fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
                 "Highest sequence number of any archived entry.",
             )?;
         }
+        w.encode_gauge(
+            "ii_archive_sequence_gaps_count",
+            config.sequence_gaps.as_ref().map_or(0, |gaps| gaps.len()) as f64,
+            "Number of recorded gaps in the sequence numbers of archived entries.",
+        )?;
+        w.encode_gauge(
+            "ii_archive_missing_entries_count",
+            config.missing_entries_count.unwrap_or(0) as f64,
+            "Total number of entries that were never archived due to sequence number gaps.",
+        )?;
+        if let Some(watermark) = &config.pruning_watermark {
+            w.encode_gauge(
+                "ii_archive_pruning_watermark_timestamp_seconds",
+                Duration::from_nanos(watermark.timestamp).as_secs_f64(),
+                "Entries older than this timestamp have been pruned.",
+            )?;
+            w.encode_gauge(
+                "ii_archive_pruned_entries_count",
+                watermark.log_index as f64,
+                "Number of log entries that have been pruned.",
+            )?;
+        }
+        if let Some(erased) = &config.erased_log_prefix {
+            w.encode_gauge(
+                "ii_archive_erased_entries_count",
+                erased.log_index as f64,
+                "Number of pruned log entries whose data has been erased.",
+            )?;
+        }
         Ok::<(), std::io::Error>(())
     })?;
     with_log(|log| {
@@ -673,12 +1108,16 @@ async fn status() -> ArchiveStatus {
         // --> unwrap is safe to call
         polling_interval_ns: config.polling_interval_ns.unwrap(),
         error_buffer_limit: config.error_buffer_limit.unwrap(),
+        archive_integration: config.archive_integration.clone(),
+        retention_period_ns: config.retention_period_ns,
     });
     let call_info = with_call_info(|info| info.clone());
     ArchiveStatus {
         canister_status,
         call_info,
         init: config,
+        pruning_watermark: pruning_watermark(),
+        sequence_gaps: with_config(|config| config.sequence_gaps.clone().unwrap_or_default()),
     }
 }
 
@@ -192,6 +192,100 @@ mod write_tests {
             Regex::new("Only [\\w-]+ is allowed to write entries\\.").unwrap(),
         );
     }
+
+    /// Verifies that batches of entries pushed by II are written and that already archived entries are skipped.
+    #[test]
+    fn should_write_entries_batch() -> Result<(), CallError> {
+        let env = env();
+        let canister_id = install_archive_canister(&env, ARCHIVE_WASM.clone());
+
+        api::write_entries(
+            &env,
+            canister_id,
+            principal_1(),
+            vec![buffered_entry(log_entry_1()), buffered_entry(log_entry_2())],
+        )?;
+        // II retries the push, e.g. because the response got lost
+        api::write_entries(
+            &env,
+            canister_id,
+            principal_1(),
+            vec![buffered_entry(log_entry_2())],
+        )?;
+
+        let logs = api::get_entries(&env, canister_id, None, None)?;
+        assert_eq!(logs.entries, vec![Some(log_entry_1()), Some(log_entry_2())]);
+        assert_metric(
+            &get_metrics(&env, canister_id),
+            "ii_archive_highest_sequence_number",
+            1f64,
+        );
+        Ok(())
+    }
+
+    /// Verifies that gaps in the sequence numbers of written entries are recorded.
+    #[test]
+    fn should_record_sequence_gaps() -> Result<(), CallError> {
+        let env = env();
+        let canister_id = install_archive_canister(&env, ARCHIVE_WASM.clone());
+
+        api::write_entries(
+            &env,
+            canister_id,
+            principal_1(),
+            vec![buffered_entry(log_entry_1())],
+        )?;
+        // entries with sequence numbers 1 and 2 are missing
+        let mut entry = log_entry_2();
+        entry.sequence_number = 3;
+        api::write_entries(
+            &env,
+            canister_id,
+            principal_1(),
+            vec![buffered_entry(entry)],
+        )?;
+
+        let status = api::status(&env, canister_id)?;
+        assert_eq!(
+            status.sequence_gaps,
+            vec![SequenceGap {
+                first_sequence_number: 1,
+                last_sequence_number: 2,
+                detected_at: env
+                    .get_time()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_nanos() as u64,
+                timestamp_before: Some(TIMESTAMP_1),
+                timestamp_after: TIMESTAMP_2,
+            }]
+        );
+        let metrics = get_metrics(&env, canister_id);
+        assert_metric(&metrics, "ii_archive_sequence_gaps_count", 1f64);
+        assert_metric(&metrics, "ii_archive_missing_entries_count", 2f64);
+        Ok(())
+    }
+
+    /// Verifies that only the configured ii_canister principal can write batches of entries.
+    #[test]
+    fn should_reject_write_entries_by_wrong_principal() {
+        let env = env();
+
+        // Configures principal_1 as the allowed principal for writing.
+        let canister_id = install_archive_canister(&env, ARCHIVE_WASM.clone());
+
+        let result = api::write_entries(
+            &env,
+            canister_id,
+            principal_2(),
+            vec![buffered_entry(log_entry_1())],
+        );
+        expect_user_error_with_message(
+            result,
+            CanisterCalledTrap,
+            Regex::new("Only [\\w-]+ is allowed to write entries\\.").unwrap(),
+        );
+    }
 }
 
 /// Verifies the read functionality of the archive canister.
@@ -467,6 +561,8 @@ mod read_tests {
             max_entries_per_call: 1000,
             polling_interval_ns: Duration::from_secs(1).as_nanos() as u64,
             error_buffer_limit: 1,
+            archive_integration: None,
+            retention_period_ns: None,
         })
         .unwrap();
         let canister_id = env.create_canister(None);
@@ -501,6 +597,450 @@ mod read_tests {
         }
         Ok(())
     }
+
+    /// Verifies that entries can be queried filtered by operation type and timestamp range.
+    #[test]
+    fn should_query_entries_by_operation_type_and_timestamp() -> Result<(), CallError> {
+        let env = env();
+        let canister_id = install_archive_canister(&env, ARCHIVE_WASM.clone());
+
+        let entries = vec![
+            log_entry_1(),
+            log_entry(2, TIMESTAMP_2, ANCHOR_NUMBER_1),
+            log_entry(3, TIMESTAMP_3, ANCHOR_NUMBER_1),
+            log_entry(4, TIMESTAMP_3, ANCHOR_NUMBER_2),
+        ];
+        for entry in &entries {
+            api::add_entry(
+                &env,
+                canister_id,
+                principal_1(),
+                entry.anchor,
+                entry.timestamp,
+                candid::encode_one(entry).expect("failed to encode entry"),
+            )?;
+        }
+
+        let result = api::query_anchor_entries(
+            &env,
+            canister_id,
+            AnchorEntriesQuery {
+                anchor: ANCHOR_NUMBER_1,
+                operation_types: Some(vec![OperationType::UpdateDevice]),
+                ..AnchorEntriesQuery::default()
+            },
+        )?;
+        assert_eq!(
+            result.entries,
+            vec![Some(entries[1].clone()), Some(entries[2].clone())]
+        );
+        assert!(result.cursor.is_none());
+        assert_eq!(
+            result.operation_counts,
+            vec![
+                OperationCount {
+                    operation_type: OperationType::RegisterAnchor,
+                    count: 1,
+                    last_timestamp: TIMESTAMP_1,
+                },
+                OperationCount {
+                    operation_type: OperationType::UpdateDevice,
+                    count: 2,
+                    last_timestamp: TIMESTAMP_3,
+                }
+            ]
+        );
+
+        let result = api::query_anchor_entries(
+            &env,
+            canister_id,
+            AnchorEntriesQuery {
+                anchor: ANCHOR_NUMBER_1,
+                from_timestamp: Some(TIMESTAMP_1),
+                to_timestamp: Some(TIMESTAMP_3),
+                ..AnchorEntriesQuery::default()
+            },
+        )?;
+        assert_eq!(
+            result.entries,
+            vec![Some(entries[0].clone()), Some(entries[1].clone())]
+        );
+        assert_eq!(
+            result.operation_counts,
+            vec![
+                OperationCount {
+                    operation_type: OperationType::RegisterAnchor,
+                    count: 1,
+                    last_timestamp: TIMESTAMP_1,
+                },
+                OperationCount {
+                    operation_type: OperationType::UpdateDevice,
+                    count: 1,
+                    last_timestamp: TIMESTAMP_2,
+                }
+            ]
+        );
+        Ok(())
+    }
+
+    /// Verifies that the cursor of the filtered query only points to matching entries.
+    #[test]
+    fn should_paginate_filtered_entries() -> Result<(), CallError> {
+        let env = env();
+        let canister_id = install_archive_canister(&env, ARCHIVE_WASM.clone());
+
+        api::add_entry(
+            &env,
+            canister_id,
+            principal_1(),
+            ANCHOR_NUMBER_1,
+            TIMESTAMP_1,
+            candid::encode_one(log_entry_1()).expect("failed to encode entry"),
+        )?;
+        for i in 1..4 {
+            api::add_entry(
+                &env,
+                canister_id,
+                principal_1(),
+                ANCHOR_NUMBER_1,
+                TIMESTAMP_2,
+                candid::encode_one(log_entry(i, TIMESTAMP_2, ANCHOR_NUMBER_1))
+                    .expect("failed to encode entry"),
+            )?;
+        }
+
+        let query = AnchorEntriesQuery {
+            anchor: ANCHOR_NUMBER_1,
+            operation_types: Some(vec![OperationType::UpdateDevice]),
+            limit: Some(2),
+            ..AnchorEntriesQuery::default()
+        };
+        let result = api::query_anchor_entries(&env, canister_id, query.clone())?;
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.entries[0].as_ref().unwrap().sequence_number, 1);
+        assert!(result.cursor.is_some());
+
+        let result = api::query_anchor_entries(
+            &env,
+            canister_id,
+            AnchorEntriesQuery {
+                cursor: result.cursor,
+                ..query
+            },
+        )?;
+        assert_eq!(
+            result.entries,
+            vec![Some(log_entry(3, TIMESTAMP_2, ANCHOR_NUMBER_1))]
+        );
+        assert!(result.cursor.is_none());
+        Ok(())
+    }
+
+    /// Verifies that the filtered query scans a bounded number of entries per call and returns a
+    /// cursor to continue the scan, even if no entry matched the filter yet.
+    #[test]
+    fn should_bound_entries_scanned_per_query() -> Result<(), CallError> {
+        let env = env();
+        let canister_id = install_archive_canister(&env, ARCHIVE_WASM.clone());
+
+        let mut entries: Vec<BufferedEntry> = (0..1000)
+            .map(|i| buffered_entry(log_entry(i, TIMESTAMP_1, ANCHOR_NUMBER_1)))
+            .collect();
+        entries.push(buffered_entry(Entry {
+            sequence_number: 1000,
+            timestamp: TIMESTAMP_2,
+            ..log_entry_1()
+        }));
+        api::write_entries(&env, canister_id, principal_1(), entries)?;
+
+        let query = AnchorEntriesQuery {
+            anchor: ANCHOR_NUMBER_1,
+            operation_types: Some(vec![OperationType::RegisterAnchor]),
+            ..AnchorEntriesQuery::default()
+        };
+        let result = api::query_anchor_entries(&env, canister_id, query.clone())?;
+        assert!(result.entries.is_empty());
+        assert!(result.cursor.is_some());
+        assert_eq!(
+            result.operation_counts,
+            vec![OperationCount {
+                operation_type: OperationType::UpdateDevice,
+                count: 1000,
+                last_timestamp: TIMESTAMP_1,
+            }]
+        );
+
+        let result = api::query_anchor_entries(
+            &env,
+            canister_id,
+            AnchorEntriesQuery {
+                cursor: result.cursor,
+                ..query
+            },
+        )?;
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].as_ref().unwrap().sequence_number, 1000);
+        assert!(result.cursor.is_none());
+        assert_eq!(
+            result.operation_counts,
+            vec![OperationCount {
+                operation_type: OperationType::RegisterAnchor,
+                count: 1,
+                last_timestamp: TIMESTAMP_2,
+            }]
+        );
+        Ok(())
+    }
+
+    /// Verifies that adding or replacing a recovery phrase is reported as a recovery phrase change.
+    #[test]
+    fn should_classify_recovery_phrase_changes() -> Result<(), CallError> {
+        let env = env();
+        let canister_id = install_archive_canister(&env, ARCHIVE_WASM.clone());
+
+        let Operation::AddDevice { device } = log_entry_2().operation else {
+            panic!("expected add_device operation");
+        };
+        let recovery_phrase = DeviceDataWithoutAlias {
+            key_type: KeyType::SeedPhrase,
+            purpose: Purpose::Recovery,
+            ..device
+        };
+        let entries = vec![
+            log_entry_2(),
+            Entry {
+                operation: Operation::AddDevice {
+                    device: recovery_phrase.clone(),
+                },
+                sequence_number: 2,
+                ..log_entry_2()
+            },
+            Entry {
+                operation: Operation::ReplaceDevice {
+                    old_device: recovery_phrase.pubkey.clone(),
+                    new_device: recovery_phrase,
+                },
+                sequence_number: 3,
+                timestamp: TIMESTAMP_3,
+                ..log_entry_2()
+            },
+        ];
+        api::write_entries(
+            &env,
+            canister_id,
+            principal_1(),
+            entries.iter().cloned().map(buffered_entry).collect(),
+        )?;
+
+        let result = api::query_anchor_entries(
+            &env,
+            canister_id,
+            AnchorEntriesQuery {
+                anchor: ANCHOR_NUMBER_2,
+                operation_types: Some(vec![OperationType::RecoveryPhraseChange]),
+                ..AnchorEntriesQuery::default()
+            },
+        )?;
+        assert_eq!(
+            result.entries,
+            vec![Some(entries[1].clone()), Some(entries[2].clone())]
+        );
+        assert_eq!(
+            result.operation_counts,
+            vec![
+                OperationCount {
+                    operation_type: OperationType::AddDevice,
+                    count: 1,
+                    last_timestamp: TIMESTAMP_2,
+                },
+                OperationCount {
+                    operation_type: OperationType::RecoveryPhraseChange,
+                    count: 2,
+                    last_timestamp: TIMESTAMP_3,
+                }
+            ]
+        );
+        Ok(())
+    }
+}
+
+/// Verifies the retention policy of the archive canister.
+#[cfg(test)]
+mod retention_tests {
+    use super::*;
+    use candid::Principal;
+    use pocket_ic::PocketIc;
+
+    const RETENTION_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+    // pruning of expired entries runs once per hour
+    const PRUNING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    fn install_archive_with_retention(
+        env: &PocketIc,
+        retention_period_ns: Option<u64>,
+    ) -> Principal {
+        let config = candid::encode_one(ArchiveInit {
+            ii_canister: principal_1(),
+            max_entries_per_call: 10,
+            polling_interval_ns: Duration::from_secs(1).as_nanos() as u64,
+            error_buffer_limit: 1,
+            archive_integration: None,
+            retention_period_ns,
+        })
+        .unwrap();
+        let canister_id = env.create_canister(None);
+        env.install_canister(canister_id, ARCHIVE_WASM.clone(), config, None);
+        canister_id
+    }
+
+    fn add_expired_and_recent_entry(
+        env: &PocketIc,
+        canister_id: Principal,
+    ) -> Result<Entry, CallError> {
+        // TIMESTAMP_1 is close to the unix epoch, i.e. long expired
+        api::add_entry(
+            env,
+            canister_id,
+            principal_1(),
+            ANCHOR_NUMBER_1,
+            TIMESTAMP_1,
+            candid::encode_one(log_entry_1()).expect("failed to encode entry"),
+        )?;
+        let now = env
+            .get_time()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        let recent_entry = log_entry(2, now, ANCHOR_NUMBER_1);
+        api::add_entry(
+            env,
+            canister_id,
+            principal_1(),
+            ANCHOR_NUMBER_1,
+            now,
+            candid::encode_one(recent_entry.clone()).expect("failed to encode entry"),
+        )?;
+        Ok(recent_entry)
+    }
+
+    /// Verifies that expired entries are pruned while recent entries are kept.
+    #[test]
+    fn should_prune_expired_entries() -> Result<(), CallError> {
+        let env = env();
+        let canister_id =
+            install_archive_with_retention(&env, Some(RETENTION_PERIOD.as_nanos() as u64));
+        let recent_entry = add_expired_and_recent_entry(&env, canister_id)?;
+
+        env.advance_time(PRUNING_INTERVAL);
+        // execute the timer
+        env.tick();
+
+        // pruned entries are returned as None
+        let entries = api::get_entries(&env, canister_id, None, None)?;
+        assert_eq!(entries.entries, vec![None, Some(recent_entry.clone())]);
+
+        let anchor_entries =
+            api::get_anchor_entries(&env, canister_id, ANCHOR_NUMBER_1, None, None)?;
+        assert_eq!(anchor_entries.entries, vec![Some(recent_entry)]);
+        let watermark = anchor_entries
+            .pruning_watermark
+            .expect("no pruning watermark");
+        assert_eq!(watermark.log_index, 1);
+
+        let status = api::status(&env, canister_id)?;
+        assert_eq!(status.pruning_watermark, Some(watermark));
+        assert_eq!(
+            status.init.retention_period_ns,
+            Some(RETENTION_PERIOD.as_nanos() as u64)
+        );
+        assert_metric(
+            &get_metrics(&env, canister_id),
+            "ii_archive_pruned_entries_count",
+            1f64,
+        );
+        Ok(())
+    }
+
+    /// Verifies that the data of pruned entries is erased from stable memory.
+    #[test]
+    fn should_erase_data_of_pruned_entries() -> Result<(), CallError> {
+        let env = env();
+        let canister_id =
+            install_archive_with_retention(&env, Some(RETENTION_PERIOD.as_nanos() as u64));
+        let recent_entry = add_expired_and_recent_entry(&env, canister_id)?;
+        let expired_data = candid::encode_one(log_entry_1()).expect("failed to encode entry");
+        let recent_data = candid::encode_one(recent_entry).expect("failed to encode entry");
+        let contains =
+            |memory: &[u8], data: &[u8]| memory.windows(data.len()).any(|window| window == data);
+        assert!(contains(&env.get_stable_memory(canister_id), &expired_data));
+
+        env.advance_time(PRUNING_INTERVAL);
+        // execute the timer
+        env.tick();
+
+        let stable_memory = env.get_stable_memory(canister_id);
+        assert!(!contains(&stable_memory, &expired_data));
+        assert!(contains(&stable_memory, &recent_data));
+        assert_metric(
+            &get_metrics(&env, canister_id),
+            "ii_archive_erased_entries_count",
+            1f64,
+        );
+        Ok(())
+    }
+
+    /// Verifies that the pruning watermark is kept across upgrades.
+    #[test]
+    fn should_keep_pruning_watermark_across_upgrades() -> Result<(), CallError> {
+        let env = env();
+        let retention_period_ns = Some(RETENTION_PERIOD.as_nanos() as u64);
+        let canister_id = install_archive_with_retention(&env, retention_period_ns);
+        add_expired_and_recent_entry(&env, canister_id)?;
+
+        env.advance_time(PRUNING_INTERVAL);
+        // execute the timer
+        env.tick();
+
+        let config = candid::encode_one(ArchiveInit {
+            ii_canister: principal_1(),
+            max_entries_per_call: 10,
+            polling_interval_ns: Duration::from_secs(1).as_nanos() as u64,
+            error_buffer_limit: 1,
+            archive_integration: None,
+            retention_period_ns,
+        })
+        .unwrap();
+        env.upgrade_canister(canister_id, ARCHIVE_WASM.clone(), config, None)
+            .unwrap();
+
+        let entries = api::get_entries(&env, canister_id, None, None)?;
+        assert_eq!(entries.entries.len(), 2);
+        assert!(entries.entries[0].is_none());
+        let status = api::status(&env, canister_id)?;
+        assert_eq!(status.pruning_watermark.map(|w| w.log_index), Some(1));
+        Ok(())
+    }
+
+    /// Verifies that entries are kept forever if no retention period is configured.
+    #[test]
+    fn should_not_prune_without_retention_period() -> Result<(), CallError> {
+        let env = env();
+        let canister_id = install_archive_with_retention(&env, None);
+        add_expired_and_recent_entry(&env, canister_id)?;
+
+        env.advance_time(PRUNING_INTERVAL);
+        // execute the timer
+        env.tick();
+
+        let entries = api::get_entries(&env, canister_id, None, None)?;
+        assert_eq!(entries.entries.len(), 2);
+        assert!(entries.entries.iter().all(|entry| entry.is_some()));
+        let anchor_entries =
+            api::get_anchor_entries(&env, canister_id, ANCHOR_NUMBER_1, None, None)?;
+        assert_eq!(anchor_entries.entries.len(), 2);
+        assert_eq!(anchor_entries.pruning_watermark, None);
+        Ok(())
+    }
 }
 
 /// Tests the metrics exposed via for the HTTP.
@@ -521,6 +1061,8 @@ mod metrics_tests {
             "ii_archive_virtual_memory_pages{kind=\"log_data\"}",
             "ii_archive_virtual_memory_pages{kind=\"anchor_index\"}",
             "ii_archive_stable_memory_pages",
+            "ii_archive_sequence_gaps_count",
+            "ii_archive_missing_entries_count",
             // The metrics
             //   * ii_archive_last_successful_fetch_timestamp_seconds
             //   * ii_archive_last_successful_fetch_entries_count
@@ -544,6 +1086,34 @@ mod metrics_tests {
         Ok(())
     }
 
+    /// Verifies that the metrics are served in all formats, with the same set of metrics.
+    #[test]
+    fn should_serve_metrics_in_all_formats() -> Result<(), CallError> {
+        let env = env();
+        let canister_id = install_archive_canister(&env, ARCHIVE_WASM.clone());
+
+        let (content_type, prometheus) =
+            get_metrics_with_format(&env, canister_id, "/metrics", None);
+        let expected_names = metric_family_names(&content_type, &prometheus);
+        assert!(expected_names.contains("ii_archive_stable_memory_pages"));
+
+        let requests = [
+            ("/metrics.json", None),
+            ("/metrics.openmetrics", None),
+            ("/metrics", Some("application/json")),
+            ("/metrics", Some("application/openmetrics-text")),
+        ];
+        for (path, accept) in requests {
+            let (content_type, body) = get_metrics_with_format(&env, canister_id, path, accept);
+            assert_eq!(
+                metric_family_names(&content_type, &body),
+                expected_names,
+                "metric families of {path} (Accept: {accept:?}) do not match"
+            );
+        }
+        Ok(())
+    }
+
     /// Verifies that the last upgrade timestamp is updated correctly.
     #[test]
     fn should_update_upgrade_timestamp() -> Result<(), CallError> {
@@ -1,6 +1,6 @@
 use ic_cdk::api::management_canister::main::CanisterId;
 use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
-use pocket_ic::{query_candid, CallError, PocketIc};
+use pocket_ic::{call_candid, query_candid, CallError, PocketIc};
 
 pub mod archive;
 pub mod internet_identity;
@@ -14,3 +14,11 @@ pub fn http_request(
 ) -> Result<HttpResponse, CallError> {
     query_candid(env, canister_id, "http_request", (http_request,)).map(|(x,)| x)
 }
+
+pub fn http_request_update(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    http_request: &HttpRequest,
+) -> Result<HttpResponse, CallError> {
+    call_candid(env, canister_id, "http_request_update", (http_request,)).map(|(x,)| x)
+}
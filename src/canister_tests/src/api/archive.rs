@@ -21,6 +21,15 @@ pub fn add_entry(
     )
 }
 
+pub fn write_entries(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    sender: Principal,
+    entries: Vec<BufferedEntry>,
+) -> Result<(), CallError> {
+    call_candid_as(env, canister_id, sender, "write_entries", (entries,))
+}
+
 pub fn get_entries(
     env: &PocketIc,
     canister_id: CanisterId,
@@ -46,6 +55,14 @@ pub fn get_anchor_entries(
     .map(|(x,)| x)
 }
 
+pub fn query_anchor_entries(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    query: AnchorEntriesQuery,
+) -> Result<FilteredAnchorEntries, CallError> {
+    query_candid(env, canister_id, "query_anchor_entries", (query,)).map(|(x,)| x)
+}
+
 pub fn status(env: &PocketIc, canister_id: CanisterId) -> Result<ArchiveStatus, CallError> {
     call_candid(env, canister_id, "status", ()).map(|(x,)| x)
 }
@@ -96,7 +113,9 @@ pub mod compat {
                     new_device,
                 },
                 Operation::RemoveDevice { device } => CompatOperation::RemoveDevice { device },
-                Operation::IdentityMetadataReplace { .. } => panic!("not available in compat type"),
+                Operation::IdentityMetadataReplace { .. }
+                | Operation::Authenticate { .. }
+                | Operation::PrepareDelegation { .. } => panic!("not available in compat type"),
             }
         }
     }
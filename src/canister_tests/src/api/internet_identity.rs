@@ -1,6 +1,7 @@
 use candid::Principal;
 use ic_cdk::api::management_canister::main::CanisterId;
 use internet_identity_interface::archive::types::BufferedEntry;
+use internet_identity_interface::http_gateway::{StreamingCallbackHttpResponse, Token};
 use internet_identity_interface::internet_identity::types;
 use pocket_ic::{call_candid, call_candid_as, query_candid, query_candid_as, CallError, PocketIc};
 
@@ -9,8 +10,9 @@ pub mod api_v2;
 
 /** The functions here are derived (manually) from Internet Identity's Candid file */
 
-/// A fake "health check" method that just checks the canister is alive a well.
-pub fn health_check(env: &PocketIc, canister_id: CanisterId) {
+/// Checks that the canister is alive and well. Unlike [health_check], this also works with older
+/// releases of II (e.g. to test rollbacks).
+pub fn assert_alive(env: &PocketIc, canister_id: CanisterId) {
     let user_number: types::AnchorNumber = 0;
     // XXX: we use "IDLValue" because we're just checking that the canister is sending
     // valid data, but we don't care about the actual data.
@@ -282,6 +284,34 @@ pub fn stats(
     query_candid(env, canister_id, "stats", ()).map(|(x,)| x)
 }
 
+pub fn activity_stats(
+    env: &PocketIc,
+    canister_id: CanisterId,
+) -> Result<types::InternetIdentityActivityStats, CallError> {
+    query_candid(env, canister_id, "activity_stats", ()).map(|(x,)| x)
+}
+
+pub fn dapp_activity_stats(
+    env: &PocketIc,
+    canister_id: CanisterId,
+) -> Result<types::DappActivityStats, CallError> {
+    query_candid(env, canister_id, "dapp_activity_stats", ()).map(|(x,)| x)
+}
+
+pub fn anchor_population_stats(
+    env: &PocketIc,
+    canister_id: CanisterId,
+) -> Result<types::AnchorPopulationStats, CallError> {
+    query_candid(env, canister_id, "anchor_population_stats", ()).map(|(x,)| x)
+}
+
+pub fn registration_cohort_stats(
+    env: &PocketIc,
+    canister_id: CanisterId,
+) -> Result<types::RegistrationCohortStats, CallError> {
+    query_candid(env, canister_id, "registration_cohort_stats", ()).map(|(x,)| x)
+}
+
 pub fn fetch_entries(
     env: &PocketIc,
     canister_id: CanisterId,
@@ -305,5 +335,75 @@ pub fn acknowledge_entries(
     )
 }
 
+pub fn dropped_archive_entries(
+    env: &PocketIc,
+    canister_id: CanisterId,
+) -> Result<Vec<types::DroppedArchiveEntries>, CallError> {
+    query_candid(env, canister_id, "dropped_archive_entries", ()).map(|(x,)| x)
+}
+
+pub fn http_request_streaming_callback(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    token: &Token,
+) -> Result<StreamingCallbackHttpResponse, CallError> {
+    query_candid(
+        env,
+        canister_id,
+        "http_request_streaming_callback",
+        (token,),
+    )
+    .map(|(x,)| x)
+}
+
+pub fn asset_upload(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    sender: Principal,
+    chunk: &types::AssetUploadChunk,
+) -> Result<(), CallError> {
+    call_candid_as(env, canister_id, sender, "asset_upload", (chunk,))
+}
+
+pub fn asset_delete(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    sender: Principal,
+    path: &str,
+) -> Result<(), CallError> {
+    call_candid_as(env, canister_id, sender, "asset_delete", (path,))
+}
+
+pub fn http_routes(
+    env: &PocketIc,
+    canister_id: CanisterId,
+) -> Result<types::HttpRoutes, CallError> {
+    query_candid(env, canister_id, "http_routes", ()).map(|(x,)| x)
+}
+
+pub fn http_routes_update(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    sender: Principal,
+    routes: &types::HttpRoutes,
+) -> Result<(), CallError> {
+    call_candid_as(env, canister_id, sender, "http_routes_update", (routes,))
+}
+
+pub fn health_check(
+    env: &PocketIc,
+    canister_id: CanisterId,
+) -> Result<types::HealthReport, CallError> {
+    query_candid(env, canister_id, "health_check", ()).map(|(x,)| x)
+}
+
+pub fn csp_violation_reports(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    sender: Principal,
+) -> Result<Vec<types::CspViolationReport>, CallError> {
+    query_candid_as(env, canister_id, sender, "csp_violation_reports", ()).map(|(x,)| x)
+}
+
 /// A "compatibility" module for the previous version of II to handle API changes.
 pub mod compat {}
@@ -14,7 +14,7 @@ use regex::Regex;
 use serde_bytes::ByteBuf;
 use sha2::Digest;
 use sha2::Sha256;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::env;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -172,6 +172,8 @@ pub fn arg_with_wasm_hash(wasm: Vec<u8>) -> Option<InternetIdentityInit> {
             entries_buffer_limit: 10_000,
             polling_interval_ns: Duration::from_secs(1).as_nanos() as u64,
             entries_fetch_limit: 10,
+            archive_integration: None,
+            retention_period_ns: None,
         }),
         canister_creation_cycles_cost: Some(0),
         ..InternetIdentityInit::default()
@@ -351,6 +353,18 @@ pub fn expect_user_error_with_message<T: std::fmt::Debug>(
     }
 }
 
+/// Asserts that the call was rejected by the canister (as opposed to trapping) with a message
+/// matching the given pattern.
+pub fn expect_reject_with_message<T: std::fmt::Debug>(
+    result: Result<T, CallError>,
+    message_pattern: Regex,
+) {
+    match result {
+        Err(CallError::Reject(ref message)) if message_pattern.is_match(message) => {}
+        _ => panic!("expected reject matching #{message_pattern:?}, got {result:?}"),
+    }
+}
+
 pub fn verify_security_headers(headers: &[HeaderField]) {
     let expected_headers = vec![
         ("X-Frame-Options", "DENY"),
@@ -433,19 +447,63 @@ frame-ancestors 'none';$"
 }
 
 pub fn get_metrics(env: &PocketIc, canister_id: CanisterId) -> String {
-    let response = http_request(
-        env,
-        canister_id,
-        &HttpRequest {
-            method: "GET".to_string(),
-            url: "/metrics".to_string(),
-            headers: vec![],
-            body: ByteBuf::new(),
-            certificate_version: None,
-        },
+    get_metrics_with_format(env, canister_id, "/metrics", None).1
+}
+
+/// Fetches the current metrics from the given path, negotiating the format using the `Accept`
+/// header (if provided). Returns the content type and the body of the response.
+///
+/// The metrics are requested without certification, as the certified metrics are only refreshed
+/// periodically.
+pub fn get_metrics_with_format(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    path: &str,
+    accept: Option<&str>,
+) -> (String, String) {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: path.to_string(),
+        headers: accept
+            .map(|accept| vec![("Accept".to_string(), accept.to_string())])
+            .unwrap_or_default(),
+        body: ByteBuf::new(),
+        certificate_version: None,
+    };
+    let response = http_request(env, canister_id, &request)
+        .unwrap_or_else(|err| panic!("HTTP request to {path} failed: {err:?}"));
+    assert_eq!(response.status_code, 200, "HTTP request to {path} failed");
+    let content_type = response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map(|(_, value)| value.clone())
+        .expect("metrics response without content type");
+    (
+        content_type,
+        String::from_utf8_lossy(&response.body).to_string(),
     )
-    .expect("HTTP request to /metrics failed");
-    String::from_utf8_lossy(&response.body).to_string()
+}
+
+/// Returns the names of the metric families of the given metrics, in any of the formats served
+/// by the metrics endpoints.
+pub fn metric_family_names(content_type: &str, body: &str) -> BTreeSet<String> {
+    if content_type.starts_with("application/json") {
+        let metrics: serde_json::Value =
+            serde_json::from_str(body).expect("failed to parse JSON metrics");
+        metrics["metrics"]
+            .as_array()
+            .expect("JSON metrics without metric families")
+            .iter()
+            .map(|family| family["name"].as_str().unwrap().to_string())
+            .collect()
+    } else {
+        // Prometheus and OpenMetrics text formats
+        body.lines()
+            .filter_map(|line| line.strip_prefix("# TYPE "))
+            .map(|line| line.split(' ').next().unwrap().to_string())
+            .collect()
+    }
 }
 
 pub fn parse_metric(body: &str, metric: &str) -> (f64, SystemTime) {
@@ -578,6 +636,8 @@ fn encode_config(authorized_principal: Principal) -> Vec<u8> {
         max_entries_per_call: 10,
         polling_interval_ns: Duration::from_secs(1).as_nanos() as u64,
         error_buffer_limit: 2,
+        archive_integration: None,
+        retention_period_ns: None,
     };
     candid::encode_one(config).expect("error encoding II installation arg as candid")
 }
@@ -630,6 +690,16 @@ pub fn log_entry_2() -> Entry {
     }
 }
 
+/// Wraps the given entry the same way as II does when buffering it for the archive.
+pub fn buffered_entry(entry: Entry) -> BufferedEntry {
+    BufferedEntry {
+        anchor_number: entry.anchor,
+        timestamp: entry.timestamp,
+        sequence_number: entry.sequence_number,
+        entry: ByteBuf::from(candid::encode_one(entry).expect("failed to encode entry")),
+    }
+}
+
 pub fn log_entry(idx: u64, timestamp: u64, anchor: AnchorNumber) -> Entry {
     Entry {
         timestamp,
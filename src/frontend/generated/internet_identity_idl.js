@@ -1,8 +1,38 @@
 export const idlFactory = ({ IDL }) => {
   const MetadataMap = IDL.Rec();
+  const ActivityWindowConfig = IDL.Record({
+    'name' : IDL.Text,
+    'step_ns' : IDL.Nat64,
+    'length_ns' : IDL.Nat64,
+  });
+  const ContentSecurityPolicyConfig = IDL.Record({
+    'upgrade_insecure_requests' : IDL.Opt(IDL.Bool),
+    'report_violations' : IDL.Opt(IDL.Bool),
+    'connect_sources' : IDL.Opt(IDL.Vec(IDL.Text)),
+  });
+  const HttpRedirect = IDL.Record({
+    'path' : IDL.Text,
+    'target' : IDL.Text,
+    'status_code' : IDL.Nat16,
+  });
+  const HttpAlias = IDL.Record({ 'asset_path' : IDL.Text, 'path' : IDL.Text });
+  const HttpRoutes = IDL.Record({
+    'redirects' : IDL.Vec(HttpRedirect),
+    'aliases' : IDL.Vec(HttpAlias),
+  });
+  const DevOriginFilter = IDL.Record({
+    'hostname_suffixes' : IDL.Vec(IDL.Text),
+    'ip_ranges' : IDL.Vec(IDL.Text),
+  });
+  const ArchiveIntegration = IDL.Variant({
+    'pull' : IDL.Null,
+    'push' : IDL.Null,
+  });
   const ArchiveConfig = IDL.Record({
+    'retention_period_ns' : IDL.Opt(IDL.Nat64),
     'polling_interval_ns' : IDL.Nat64,
     'entries_buffer_limit' : IDL.Nat64,
+    'archive_integration' : IDL.Opt(ArchiveIntegration),
     'module_hash' : IDL.Vec(IDL.Nat8),
     'entries_fetch_limit' : IDL.Nat16,
   });
@@ -11,13 +41,87 @@ export const idlFactory = ({ IDL }) => {
     'time_per_token_ns' : IDL.Nat64,
   });
   const InternetIdentityInit = IDL.Record({
+    'activity_windows' : IDL.Opt(IDL.Vec(ActivityWindowConfig)),
+    'content_security_policy' : IDL.Opt(ContentSecurityPolicyConfig),
     'max_num_latest_delegation_origins' : IDL.Opt(IDL.Nat64),
     'assigned_user_number_range' : IDL.Opt(IDL.Tuple(IDL.Nat64, IDL.Nat64)),
+    'http_routes' : IDL.Opt(HttpRoutes),
+    'dev_origin_filter' : IDL.Opt(DevOriginFilter),
     'max_inflight_captchas' : IDL.Opt(IDL.Nat64),
     'archive_config' : IDL.Opt(ArchiveConfig),
     'canister_creation_cycles_cost' : IDL.Opt(IDL.Nat64),
+    'ii_origins' : IDL.Opt(IDL.Vec(IDL.Text)),
     'register_rate_limit' : IDL.Opt(RateLimitConfig),
   });
+  const Timestamp = IDL.Nat64;
+  const ActiveAnchorWindow = IDL.Record({
+    'start_timestamp' : Timestamp,
+    'active_anchors' : IDL.Nat64,
+  });
+  const ActiveAnchorWindowStats = IDL.Record({
+    'name' : IDL.Text,
+    'completed' : IDL.Opt(ActiveAnchorWindow),
+    'step_ns' : IDL.Nat64,
+    'ongoing' : IDL.Vec(ActiveAnchorWindow),
+    'length_ns' : IDL.Nat64,
+  });
+  const ActiveAnchorStats = IDL.Record({
+    'ongoing_monthly' : IDL.Vec(ActiveAnchorWindow),
+    'ongoing_daily' : ActiveAnchorWindow,
+    'completed_monthly' : IDL.Opt(ActiveAnchorWindow),
+    'additional_windows' : IDL.Opt(IDL.Vec(ActiveAnchorWindowStats)),
+    'completed_daily' : IDL.Opt(ActiveAnchorWindow),
+  });
+  const ActiveAuthnMethodWindow = IDL.Record({
+    'other' : IDL.Nat64,
+    'start_timestamp' : Timestamp,
+    'recovery_phrase' : IDL.Nat64,
+    'webauthn_auth' : IDL.Nat64,
+    'webauthn_recovery' : IDL.Nat64,
+    'browser_storage_key' : IDL.Nat64,
+  });
+  const ActiveAuthnMethodWindowStats = IDL.Record({
+    'name' : IDL.Text,
+    'completed' : IDL.Opt(ActiveAuthnMethodWindow),
+    'step_ns' : IDL.Nat64,
+    'ongoing' : IDL.Vec(ActiveAuthnMethodWindow),
+    'length_ns' : IDL.Nat64,
+  });
+  const ActiveAuthnMethodStats = IDL.Record({
+    'ongoing_monthly' : IDL.Vec(ActiveAuthnMethodWindow),
+    'ongoing_daily' : ActiveAuthnMethodWindow,
+    'completed_monthly' : IDL.Opt(ActiveAuthnMethodWindow),
+    'additional_windows' : IDL.Opt(IDL.Vec(ActiveAuthnMethodWindowStats)),
+    'completed_daily' : IDL.Opt(ActiveAuthnMethodWindow),
+  });
+  const FrontendHostname = IDL.Text;
+  const DomainActiveAnchors = IDL.Record({
+    'active_anchors' : IDL.Nat64,
+    'ii_origins' : IDL.Vec(FrontendHostname),
+  });
+  const DomainActiveAnchorWindow = IDL.Record({
+    'start_timestamp' : Timestamp,
+    'domains' : IDL.Vec(DomainActiveAnchors),
+  });
+  const DomainActiveAnchorWindowStats = IDL.Record({
+    'name' : IDL.Text,
+    'completed' : IDL.Opt(DomainActiveAnchorWindow),
+    'step_ns' : IDL.Nat64,
+    'ongoing' : IDL.Vec(DomainActiveAnchorWindow),
+    'length_ns' : IDL.Nat64,
+  });
+  const DomainActiveAnchorStats = IDL.Record({
+    'ongoing_monthly' : IDL.Vec(DomainActiveAnchorWindow),
+    'ongoing_daily' : DomainActiveAnchorWindow,
+    'completed_monthly' : IDL.Opt(DomainActiveAnchorWindow),
+    'additional_windows' : IDL.Opt(IDL.Vec(DomainActiveAnchorWindowStats)),
+    'completed_daily' : IDL.Opt(DomainActiveAnchorWindow),
+  });
+  const InternetIdentityActivityStats = IDL.Record({
+    'active_anchors' : IDL.Opt(ActiveAnchorStats),
+    'active_authn_methods' : IDL.Opt(ActiveAuthnMethodStats),
+    'domain_active_anchors' : IDL.Opt(DomainActiveAnchorStats),
+  });
   const UserNumber = IDL.Nat64;
   MetadataMap.fill(
     IDL.Vec(
@@ -59,7 +163,6 @@ export const idlFactory = ({ IDL }) => {
     'purpose' : Purpose,
     'credential_id' : IDL.Opt(CredentialId),
   });
-  const Timestamp = IDL.Nat64;
   const AddTentativeDeviceResponse = IDL.Variant({
     'device_registration_mode_off' : IDL.Null,
     'another_device_tentatively_added' : IDL.Null,
@@ -68,6 +171,28 @@ export const idlFactory = ({ IDL }) => {
       'device_registration_timeout' : Timestamp,
     }),
   });
+  const DevicesByKeyType = IDL.Record({
+    'platform' : IDL.Nat64,
+    'seed_phrase' : IDL.Nat64,
+    'cross_platform' : IDL.Nat64,
+    'unknown' : IDL.Nat64,
+    'browser_storage_key' : IDL.Nat64,
+  });
+  const AnchorPopulationStats = IDL.Record({
+    'counted_anchors' : IDL.Nat64,
+    'anchors_with_recovery_device' : IDL.Nat64,
+    'anchors_by_device_count' : IDL.Vec(IDL.Nat64),
+    'protected_devices' : IDL.Nat64,
+    'anchors_with_recovery_phrase' : IDL.Nat64,
+    'devices_by_key_type' : DevicesByKeyType,
+    'unprotected_devices' : IDL.Nat64,
+  });
+  const AssetUploadChunk = IDL.Record({
+    'content' : IDL.Vec(IDL.Nat8),
+    'offset' : IDL.Nat64,
+    'file_path' : IDL.Text,
+    'total_length' : IDL.Nat64,
+  });
   const IdentityNumber = IDL.Nat64;
   const AuthnMethodProtection = IDL.Variant({
     'unprotected' : IDL.Null,
@@ -99,11 +224,39 @@ export const idlFactory = ({ IDL }) => {
     'png_base64' : IDL.Text,
     'challenge_key' : ChallengeKey,
   });
+  const CspViolationReport = IDL.Record({
+    'effective_directive' : IDL.Text,
+    'document_uri' : IDL.Text,
+    'count' : IDL.Nat64,
+    'line_number' : IDL.Opt(IDL.Nat64),
+    'source_file' : IDL.Opt(IDL.Text),
+    'blocked_uri' : IDL.Text,
+    'first_seen' : Timestamp,
+    'last_seen' : Timestamp,
+    'disposition' : IDL.Text,
+  });
+  const DappActiveAnchors = IDL.Record({
+    'frontend' : FrontendHostname,
+    'active_anchors' : IDL.Nat64,
+  });
+  const DappActivityWindow = IDL.Record({
+    'start_timestamp' : Timestamp,
+    'dapps' : IDL.Vec(DappActiveAnchors),
+  });
+  const DappActivityStats = IDL.Record({
+    'monthly' : IDL.Opt(DappActivityWindow),
+    'daily' : IDL.Opt(DappActivityWindow),
+  });
   const DeployArchiveResult = IDL.Variant({
     'creation_in_progress' : IDL.Null,
     'success' : IDL.Principal,
     'failed' : IDL.Text,
   });
+  const DroppedArchiveEntries = IDL.Record({
+    'timestamp' : Timestamp,
+    'first_sequence_number' : IDL.Nat64,
+    'last_sequence_number' : IDL.Nat64,
+  });
   const BufferedArchiveEntry = IDL.Record({
     'sequence_number' : IDL.Nat64,
     'entry' : IDL.Vec(IDL.Nat8),
@@ -138,7 +291,6 @@ export const idlFactory = ({ IDL }) => {
     'devices' : IDL.Vec(DeviceWithUsage),
     'device_registration' : IDL.Opt(DeviceRegistrationInfo),
   });
-  const FrontendHostname = IDL.Text;
   const SessionKey = PublicKey;
   const Delegation = IDL.Record({
     'pubkey' : PublicKey,
@@ -153,6 +305,21 @@ export const idlFactory = ({ IDL }) => {
     'no_such_delegation' : IDL.Null,
     'signed_delegation' : SignedDelegation,
   });
+  const HealthStatus = IDL.Variant({
+    'fail' : IDL.Null,
+    'pass' : IDL.Null,
+    'warn' : IDL.Null,
+  });
+  const HealthCheck = IDL.Record({
+    'status' : HealthStatus,
+    'name' : IDL.Text,
+    'message' : IDL.Text,
+  });
+  const HealthReport = IDL.Record({
+    'status' : HealthStatus,
+    'timestamp' : Timestamp,
+    'checks' : IDL.Vec(HealthCheck),
+  });
   const HeaderField = IDL.Tuple(IDL.Text, IDL.Text);
   const HttpRequest = IDL.Record({
     'url' : IDL.Text,
@@ -161,7 +328,12 @@ export const idlFactory = ({ IDL }) => {
     'headers' : IDL.Vec(HeaderField),
     'certificate_version' : IDL.Opt(IDL.Nat16),
   });
-  const Token = IDL.Record({});
+  const Token = IDL.Record({
+    'sha256' : IDL.Vec(IDL.Nat8),
+    'path' : IDL.Text,
+    'index' : IDL.Nat64,
+    'content_encoding' : IDL.Text,
+  });
   const StreamingCallbackHttpResponse = IDL.Record({
     'token' : IDL.Opt(Token),
     'body' : IDL.Vec(IDL.Nat8),
@@ -204,6 +376,14 @@ export const idlFactory = ({ IDL }) => {
     'canister_full' : IDL.Null,
     'registered' : IDL.Record({ 'user_number' : UserNumber }),
   });
+  const RegistrationCohort = IDL.Record({
+    'active_anchors_by_week' : IDL.Vec(IDL.Nat64),
+    'start_timestamp' : Timestamp,
+    'registrations' : IDL.Nat64,
+  });
+  const RegistrationCohortStats = IDL.Record({
+    'cohorts' : IDL.Vec(RegistrationCohort),
+  });
   const ArchiveInfo = IDL.Record({
     'archive_config' : IDL.Opt(ArchiveConfig),
     'archive_canister' : IDL.Opt(IDL.Principal),
@@ -225,12 +405,24 @@ export const idlFactory = ({ IDL }) => {
   });
   return IDL.Service({
     'acknowledge_entries' : IDL.Func([IDL.Nat64], [], []),
+    'activity_stats' : IDL.Func(
+        [],
+        [InternetIdentityActivityStats],
+        ['query'],
+      ),
     'add' : IDL.Func([UserNumber, DeviceData], [], []),
     'add_tentative_device' : IDL.Func(
         [UserNumber, DeviceData],
         [AddTentativeDeviceResponse],
         [],
       ),
+    'anchor_population_stats' : IDL.Func(
+        [],
+        [AnchorPopulationStats],
+        ['query'],
+      ),
+    'asset_delete' : IDL.Func([IDL.Text], [], []),
+    'asset_upload' : IDL.Func([AssetUploadChunk], [], []),
     'authn_method_add' : IDL.Func(
         [IdentityNumber, AuthnMethodData],
         [IDL.Opt(AuthnMethodAddResponse)],
@@ -242,7 +434,18 @@ export const idlFactory = ({ IDL }) => {
         [],
       ),
     'create_challenge' : IDL.Func([], [Challenge], []),
+    'csp_violation_reports' : IDL.Func(
+        [],
+        [IDL.Vec(CspViolationReport)],
+        ['query'],
+      ),
+    'dapp_activity_stats' : IDL.Func([], [DappActivityStats], ['query']),
     'deploy_archive' : IDL.Func([IDL.Vec(IDL.Nat8)], [DeployArchiveResult], []),
+    'dropped_archive_entries' : IDL.Func(
+        [],
+        [IDL.Vec(DroppedArchiveEntries)],
+        ['query'],
+      ),
     'enter_device_registration_mode' : IDL.Func([UserNumber], [Timestamp], []),
     'exit_device_registration_mode' : IDL.Func([UserNumber], [], []),
     'fetch_entries' : IDL.Func([], [IDL.Vec(BufferedArchiveEntry)], []),
@@ -262,8 +465,16 @@ export const idlFactory = ({ IDL }) => {
         [IDL.Principal],
         ['query'],
       ),
+    'health_check' : IDL.Func([], [HealthReport], ['query']),
     'http_request' : IDL.Func([HttpRequest], [HttpResponse], ['query']),
+    'http_request_streaming_callback' : IDL.Func(
+        [Token],
+        [StreamingCallbackHttpResponse],
+        ['query'],
+      ),
     'http_request_update' : IDL.Func([HttpRequest], [HttpResponse], []),
+    'http_routes' : IDL.Func([], [HttpRoutes], ['query']),
+    'http_routes_update' : IDL.Func([HttpRoutes], [], []),
     'identity_info' : IDL.Func(
         [IdentityNumber],
         [IDL.Opt(IdentityInfoResponse)],
@@ -286,6 +497,11 @@ export const idlFactory = ({ IDL }) => {
         [RegisterResponse],
         [],
       ),
+    'registration_cohort_stats' : IDL.Func(
+        [],
+        [RegistrationCohortStats],
+        ['query'],
+      ),
     'remove' : IDL.Func([UserNumber, DeviceKey], [], []),
     'replace' : IDL.Func([UserNumber, DeviceKey, DeviceData], [], []),
     'stats' : IDL.Func([], [InternetIdentityStats], ['query']),
@@ -298,9 +514,39 @@ export const idlFactory = ({ IDL }) => {
   });
 };
 export const init = ({ IDL }) => {
+  const ActivityWindowConfig = IDL.Record({
+    'name' : IDL.Text,
+    'step_ns' : IDL.Nat64,
+    'length_ns' : IDL.Nat64,
+  });
+  const ContentSecurityPolicyConfig = IDL.Record({
+    'upgrade_insecure_requests' : IDL.Opt(IDL.Bool),
+    'report_violations' : IDL.Opt(IDL.Bool),
+    'connect_sources' : IDL.Opt(IDL.Vec(IDL.Text)),
+  });
+  const HttpRedirect = IDL.Record({
+    'path' : IDL.Text,
+    'target' : IDL.Text,
+    'status_code' : IDL.Nat16,
+  });
+  const HttpAlias = IDL.Record({ 'asset_path' : IDL.Text, 'path' : IDL.Text });
+  const HttpRoutes = IDL.Record({
+    'redirects' : IDL.Vec(HttpRedirect),
+    'aliases' : IDL.Vec(HttpAlias),
+  });
+  const DevOriginFilter = IDL.Record({
+    'hostname_suffixes' : IDL.Vec(IDL.Text),
+    'ip_ranges' : IDL.Vec(IDL.Text),
+  });
+  const ArchiveIntegration = IDL.Variant({
+    'pull' : IDL.Null,
+    'push' : IDL.Null,
+  });
   const ArchiveConfig = IDL.Record({
+    'retention_period_ns' : IDL.Opt(IDL.Nat64),
     'polling_interval_ns' : IDL.Nat64,
     'entries_buffer_limit' : IDL.Nat64,
+    'archive_integration' : IDL.Opt(ArchiveIntegration),
     'module_hash' : IDL.Vec(IDL.Nat8),
     'entries_fetch_limit' : IDL.Nat16,
   });
@@ -309,11 +555,16 @@ export const init = ({ IDL }) => {
     'time_per_token_ns' : IDL.Nat64,
   });
   const InternetIdentityInit = IDL.Record({
+    'activity_windows' : IDL.Opt(IDL.Vec(ActivityWindowConfig)),
+    'content_security_policy' : IDL.Opt(ContentSecurityPolicyConfig),
     'max_num_latest_delegation_origins' : IDL.Opt(IDL.Nat64),
     'assigned_user_number_range' : IDL.Opt(IDL.Tuple(IDL.Nat64, IDL.Nat64)),
+    'http_routes' : IDL.Opt(HttpRoutes),
+    'dev_origin_filter' : IDL.Opt(DevOriginFilter),
     'max_inflight_captchas' : IDL.Opt(IDL.Nat64),
     'archive_config' : IDL.Opt(ArchiveConfig),
     'canister_creation_cycles_cost' : IDL.Opt(IDL.Nat64),
+    'ii_origins' : IDL.Opt(IDL.Vec(IDL.Text)),
     'register_rate_limit' : IDL.Opt(RateLimitConfig),
   });
   return [IDL.Opt(InternetIdentityInit)];
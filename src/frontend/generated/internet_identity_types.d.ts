@@ -1,6 +1,51 @@
 import type { Principal } from '@dfinity/principal';
 import type { ActorMethod } from '@dfinity/agent';
 
+export interface ActiveAnchorStats {
+  'ongoing_monthly' : Array<ActiveAnchorWindow>,
+  'ongoing_daily' : ActiveAnchorWindow,
+  'completed_monthly' : [] | [ActiveAnchorWindow],
+  'additional_windows' : [] | [Array<ActiveAnchorWindowStats>],
+  'completed_daily' : [] | [ActiveAnchorWindow],
+}
+export interface ActiveAnchorWindow {
+  'start_timestamp' : Timestamp,
+  'active_anchors' : bigint,
+}
+export interface ActiveAnchorWindowStats {
+  'name' : string,
+  'completed' : [] | [ActiveAnchorWindow],
+  'step_ns' : bigint,
+  'ongoing' : Array<ActiveAnchorWindow>,
+  'length_ns' : bigint,
+}
+export interface ActiveAuthnMethodStats {
+  'ongoing_monthly' : Array<ActiveAuthnMethodWindow>,
+  'ongoing_daily' : ActiveAuthnMethodWindow,
+  'completed_monthly' : [] | [ActiveAuthnMethodWindow],
+  'additional_windows' : [] | [Array<ActiveAuthnMethodWindowStats>],
+  'completed_daily' : [] | [ActiveAuthnMethodWindow],
+}
+export interface ActiveAuthnMethodWindow {
+  'other' : bigint,
+  'start_timestamp' : Timestamp,
+  'recovery_phrase' : bigint,
+  'webauthn_auth' : bigint,
+  'webauthn_recovery' : bigint,
+  'browser_storage_key' : bigint,
+}
+export interface ActiveAuthnMethodWindowStats {
+  'name' : string,
+  'completed' : [] | [ActiveAuthnMethodWindow],
+  'step_ns' : bigint,
+  'ongoing' : Array<ActiveAuthnMethodWindow>,
+  'length_ns' : bigint,
+}
+export interface ActivityWindowConfig {
+  'name' : string,
+  'step_ns' : bigint,
+  'length_ns' : bigint,
+}
 export type AddTentativeDeviceResponse = {
     'device_registration_mode_off' : null
   } |
@@ -16,9 +61,20 @@ export interface AnchorCredentials {
   'credentials' : Array<WebAuthnCredential>,
   'recovery_credentials' : Array<WebAuthnCredential>,
 }
+export interface AnchorPopulationStats {
+  'counted_anchors' : bigint,
+  'anchors_with_recovery_device' : bigint,
+  'anchors_by_device_count' : BigUint64Array | bigint[],
+  'protected_devices' : bigint,
+  'anchors_with_recovery_phrase' : bigint,
+  'devices_by_key_type' : DevicesByKeyType,
+  'unprotected_devices' : bigint,
+}
 export interface ArchiveConfig {
+  'retention_period_ns' : [] | [bigint],
   'polling_interval_ns' : bigint,
   'entries_buffer_limit' : bigint,
+  'archive_integration' : [] | [ArchiveIntegration],
   'module_hash' : Uint8Array | number[],
   'entries_fetch_limit' : number,
 }
@@ -26,6 +82,14 @@ export interface ArchiveInfo {
   'archive_config' : [] | [ArchiveConfig],
   'archive_canister' : [] | [Principal],
 }
+export type ArchiveIntegration = { 'pull' : null } |
+  { 'push' : null };
+export interface AssetUploadChunk {
+  'content' : Uint8Array | number[],
+  'offset' : bigint,
+  'file_path' : string,
+  'total_length' : bigint,
+}
 export type AuthnMethod = { 'webauthn' : WebAuthn } |
   { 'pubkey' : PublicKeyAuthn };
 export type AuthnMethodAddResponse = { 'ok' : null } |
@@ -56,7 +120,35 @@ export interface Challenge {
 }
 export type ChallengeKey = string;
 export interface ChallengeResult { 'key' : ChallengeKey, 'chars' : string }
+export interface ContentSecurityPolicyConfig {
+  'upgrade_insecure_requests' : [] | [boolean],
+  'report_violations' : [] | [boolean],
+  'connect_sources' : [] | [Array<string>],
+}
 export type CredentialId = Uint8Array | number[];
+export interface CspViolationReport {
+  'effective_directive' : string,
+  'document_uri' : string,
+  'count' : bigint,
+  'line_number' : [] | [bigint],
+  'source_file' : [] | [string],
+  'blocked_uri' : string,
+  'first_seen' : Timestamp,
+  'last_seen' : Timestamp,
+  'disposition' : string,
+}
+export interface DappActiveAnchors {
+  'frontend' : FrontendHostname,
+  'active_anchors' : bigint,
+}
+export interface DappActivityStats {
+  'monthly' : [] | [DappActivityWindow],
+  'daily' : [] | [DappActivityWindow],
+}
+export interface DappActivityWindow {
+  'start_timestamp' : Timestamp,
+  'dapps' : Array<DappActiveAnchors>,
+}
 export interface Delegation {
   'pubkey' : PublicKey,
   'targets' : [] | [Array<Principal>],
@@ -65,6 +157,10 @@ export interface Delegation {
 export type DeployArchiveResult = { 'creation_in_progress' : null } |
   { 'success' : Principal } |
   { 'failed' : string };
+export interface DevOriginFilter {
+  'hostname_suffixes' : Array<string>,
+  'ip_ranges' : Array<string>,
+}
 export interface DeviceData {
   'alias' : string,
   'metadata' : [] | [MetadataMap],
@@ -93,10 +189,63 @@ export interface DeviceWithUsage {
   'purpose' : Purpose,
   'credential_id' : [] | [CredentialId],
 }
+export interface DevicesByKeyType {
+  'platform' : bigint,
+  'seed_phrase' : bigint,
+  'cross_platform' : bigint,
+  'unknown' : bigint,
+  'browser_storage_key' : bigint,
+}
+export interface DomainActiveAnchorStats {
+  'ongoing_monthly' : Array<DomainActiveAnchorWindow>,
+  'ongoing_daily' : DomainActiveAnchorWindow,
+  'completed_monthly' : [] | [DomainActiveAnchorWindow],
+  'additional_windows' : [] | [Array<DomainActiveAnchorWindowStats>],
+  'completed_daily' : [] | [DomainActiveAnchorWindow],
+}
+export interface DomainActiveAnchorWindow {
+  'start_timestamp' : Timestamp,
+  'domains' : Array<DomainActiveAnchors>,
+}
+export interface DomainActiveAnchorWindowStats {
+  'name' : string,
+  'completed' : [] | [DomainActiveAnchorWindow],
+  'step_ns' : bigint,
+  'ongoing' : Array<DomainActiveAnchorWindow>,
+  'length_ns' : bigint,
+}
+export interface DomainActiveAnchors {
+  'active_anchors' : bigint,
+  'ii_origins' : Array<FrontendHostname>,
+}
+export interface DroppedArchiveEntries {
+  'timestamp' : Timestamp,
+  'first_sequence_number' : bigint,
+  'last_sequence_number' : bigint,
+}
 export type FrontendHostname = string;
 export type GetDelegationResponse = { 'no_such_delegation' : null } |
   { 'signed_delegation' : SignedDelegation };
+export interface HealthCheck {
+  'status' : HealthStatus,
+  'name' : string,
+  'message' : string,
+}
+export interface HealthReport {
+  'status' : HealthStatus,
+  'timestamp' : Timestamp,
+  'checks' : Array<HealthCheck>,
+}
+export type HealthStatus = { 'fail' : null } |
+  { 'pass' : null } |
+  { 'warn' : null };
 export type HeaderField = [string, string];
+export interface HttpAlias { 'asset_path' : string, 'path' : string }
+export interface HttpRedirect {
+  'path' : string,
+  'target' : string,
+  'status_code' : number,
+}
 export interface HttpRequest {
   'url' : string,
   'method' : string,
@@ -111,6 +260,10 @@ export interface HttpResponse {
   'streaming_strategy' : [] | [StreamingStrategy],
   'status_code' : number,
 }
+export interface HttpRoutes {
+  'redirects' : Array<HttpRedirect>,
+  'aliases' : Array<HttpAlias>,
+}
 export interface IdentityAnchorInfo {
   'devices' : Array<DeviceWithUsage>,
   'device_registration' : [] | [DeviceRegistrationInfo],
@@ -123,12 +276,22 @@ export interface IdentityInfo {
 export type IdentityInfoResponse = { 'ok' : IdentityInfo };
 export type IdentityMetadataReplaceResponse = { 'ok' : null };
 export type IdentityNumber = bigint;
+export interface InternetIdentityActivityStats {
+  'active_anchors' : [] | [ActiveAnchorStats],
+  'active_authn_methods' : [] | [ActiveAuthnMethodStats],
+  'domain_active_anchors' : [] | [DomainActiveAnchorStats],
+}
 export interface InternetIdentityInit {
+  'activity_windows' : [] | [Array<ActivityWindowConfig>],
+  'content_security_policy' : [] | [ContentSecurityPolicyConfig],
   'max_num_latest_delegation_origins' : [] | [bigint],
   'assigned_user_number_range' : [] | [[bigint, bigint]],
+  'http_routes' : [] | [HttpRoutes],
+  'dev_origin_filter' : [] | [DevOriginFilter],
   'max_inflight_captchas' : [] | [bigint],
   'archive_config' : [] | [ArchiveConfig],
   'canister_creation_cycles_cost' : [] | [bigint],
+  'ii_origins' : [] | [Array<string>],
   'register_rate_limit' : [] | [RateLimitConfig],
 }
 export interface InternetIdentityStats {
@@ -164,6 +327,14 @@ export interface RateLimitConfig {
 export type RegisterResponse = { 'bad_challenge' : null } |
   { 'canister_full' : null } |
   { 'registered' : { 'user_number' : UserNumber } };
+export interface RegistrationCohort {
+  'active_anchors_by_week' : BigUint64Array | bigint[],
+  'start_timestamp' : Timestamp,
+  'registrations' : bigint,
+}
+export interface RegistrationCohortStats {
+  'cohorts' : Array<RegistrationCohort>,
+}
 export type SessionKey = PublicKey;
 export interface SignedDelegation {
   'signature' : Uint8Array | number[],
@@ -177,7 +348,12 @@ export type StreamingStrategy = {
     'Callback' : { 'token' : Token, 'callback' : [Principal, string] }
   };
 export type Timestamp = bigint;
-export type Token = {};
+export interface Token {
+  'sha256' : Uint8Array | number[],
+  'path' : string,
+  'index' : bigint,
+  'content_encoding' : string,
+}
 export type UserKey = PublicKey;
 export type UserNumber = bigint;
 export type VerifyTentativeDeviceResponse = {
@@ -196,11 +372,15 @@ export interface WebAuthnCredential {
 }
 export interface _SERVICE {
   'acknowledge_entries' : ActorMethod<[bigint], undefined>,
+  'activity_stats' : ActorMethod<[], InternetIdentityActivityStats>,
   'add' : ActorMethod<[UserNumber, DeviceData], undefined>,
   'add_tentative_device' : ActorMethod<
     [UserNumber, DeviceData],
     AddTentativeDeviceResponse
   >,
+  'anchor_population_stats' : ActorMethod<[], AnchorPopulationStats>,
+  'asset_delete' : ActorMethod<[string], undefined>,
+  'asset_upload' : ActorMethod<[AssetUploadChunk], undefined>,
   'authn_method_add' : ActorMethod<
     [IdentityNumber, AuthnMethodData],
     [] | [AuthnMethodAddResponse]
@@ -210,7 +390,10 @@ export interface _SERVICE {
     [] | [AuthnMethodRemoveResponse]
   >,
   'create_challenge' : ActorMethod<[], Challenge>,
+  'csp_violation_reports' : ActorMethod<[], Array<CspViolationReport>>,
+  'dapp_activity_stats' : ActorMethod<[], DappActivityStats>,
   'deploy_archive' : ActorMethod<[Uint8Array | number[]], DeployArchiveResult>,
+  'dropped_archive_entries' : ActorMethod<[], Array<DroppedArchiveEntries>>,
   'enter_device_registration_mode' : ActorMethod<[UserNumber], Timestamp>,
   'exit_device_registration_mode' : ActorMethod<[UserNumber], undefined>,
   'fetch_entries' : ActorMethod<[], Array<BufferedArchiveEntry>>,
@@ -221,8 +404,15 @@ export interface _SERVICE {
     GetDelegationResponse
   >,
   'get_principal' : ActorMethod<[UserNumber, FrontendHostname], Principal>,
+  'health_check' : ActorMethod<[], HealthReport>,
   'http_request' : ActorMethod<[HttpRequest], HttpResponse>,
+  'http_request_streaming_callback' : ActorMethod<
+    [Token],
+    StreamingCallbackHttpResponse
+  >,
   'http_request_update' : ActorMethod<[HttpRequest], HttpResponse>,
+  'http_routes' : ActorMethod<[], HttpRoutes>,
+  'http_routes_update' : ActorMethod<[HttpRoutes], undefined>,
   'identity_info' : ActorMethod<[IdentityNumber], [] | [IdentityInfoResponse]>,
   'identity_metadata_replace' : ActorMethod<
     [IdentityNumber, MetadataMap],
@@ -238,6 +428,7 @@ export interface _SERVICE {
     [DeviceData, ChallengeResult, [] | [Principal]],
     RegisterResponse
   >,
+  'registration_cohort_stats' : ActorMethod<[], RegistrationCohortStats>,
   'remove' : ActorMethod<[UserNumber, DeviceKey], undefined>,
   'replace' : ActorMethod<[UserNumber, DeviceKey, DeviceData], undefined>,
   'stats' : ActorMethod<[], InternetIdentityStats>,
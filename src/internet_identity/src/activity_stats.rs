@@ -1,24 +1,103 @@
-use crate::activity_stats::activity_counter::domain_active_anchor_counter::DomainActivityContext;
+use crate::activity_stats::activity_counter::active_anchor_counter::ActiveAnchorCounter;
+use crate::activity_stats::activity_counter::authn_method_counter::AuthnMethodCounter;
+use crate::activity_stats::activity_counter::dapp_active_anchor_counter::{
+    DappActiveAnchorCounter, DappActivityContext,
+};
+use crate::activity_stats::activity_counter::domain_active_anchor_counter::{
+    DomainActiveAnchorCounter, DomainActivityContext,
+};
 use crate::activity_stats::activity_counter::ActivityCounter;
-use crate::state;
+use crate::activity_stats::cohort_stats::CohortStats;
+use crate::state::PersistentState;
 use crate::storage::anchor::{Anchor, Device};
+use crate::{ii_domain, state, DAY_NS};
 use candid::{CandidType, Deserialize};
 use ic_cdk::api::time;
-use internet_identity_interface::internet_identity::types::Timestamp;
+use internet_identity_interface::internet_identity::types::{
+    ActiveAnchorStats, ActiveAnchorWindow, ActiveAnchorWindowStats, ActiveAuthnMethodStats,
+    ActiveAuthnMethodWindow, ActiveAuthnMethodWindowStats, ActivityWindowConfig, AnchorNumber,
+    DappActiveAnchors, DappActivityStats, DappActivityWindow, DomainActiveAnchorStats,
+    DomainActiveAnchorWindow, DomainActiveAnchorWindowStats, DomainActiveAnchors, FrontendHostname,
+    InternetIdentityActivityStats, RegistrationCohortStats, Timestamp,
+};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub mod activity_counter;
+pub mod cohort_stats;
 mod stats_maintenance;
 
+/// Name of the built-in 24h collection window.
+pub const DAILY_WINDOW: &str = "daily";
+/// Name of the built-in 30-day collection window (sliding in 24h steps).
+pub const MONTHLY_WINDOW: &str = "monthly";
+/// Length of the built-in monthly window.
+const MONTH_NS: u64 = 30 * DAY_NS;
+
+/// Maximum length of a collection window.
+pub const MAX_WINDOW_LENGTH_NS: u64 = 90 * DAY_NS;
+/// Maximum number of ongoing counters of a collection window (i.e. `length_ns / step_ns`).
+const MAX_ONGOING_COUNTERS: u64 = 30;
+/// Maximum number of collection windows that can be configured in addition to the built-in ones.
+const MAX_ADDITIONAL_WINDOWS: usize = 4;
+
 #[derive(Clone, CandidType, Deserialize, Eq, PartialEq, Debug)]
 pub struct ActivityStats<T: ActivityCounter> {
-    // Stats for the last completed collection period for daily and monthly activity
+    // Stats of the built-in daily and monthly windows for the last completed collection period.
+    // The built-in windows are kept in the representation of previous releases so that the stats
+    // can still be decoded after a rollback.
     pub completed: CompletedActivityStats<T>,
-    // ongoing collection periods for daily and monthly activity
+    // Ongoing collection periods of the built-in daily and monthly windows.
     pub ongoing: OngoingActivityStats<T>,
+    // Stats of the additional collection windows configured using the init argument, by name.
+    pub windows: Option<BTreeMap<String, WindowStats<T>>>,
+}
+
+/// Counters of a collection window of length `length_ns`. A new collection window is started
+/// every `step_ns`, i.e. there are up to `length_ns / step_ns` overlapping ongoing windows.
+#[derive(Clone, CandidType, Deserialize, Eq, PartialEq, Debug)]
+pub struct WindowStats<T: ActivityCounter> {
+    pub length_ns: u64,
+    pub step_ns: u64,
+    // Counter of the last completed collection window
+    pub completed: Option<T>,
+    // Ongoing collection windows, each offset by `step_ns`.
+    // The vec is sorted, new collection windows are added at the end.
+    pub ongoing: Vec<T>,
+}
+
+impl<T: ActivityCounter> WindowStats<T> {
+    fn new(config: &ActivityWindowConfig, time: Timestamp) -> Self {
+        Self {
+            length_ns: config.length_ns,
+            step_ns: config.step_ns,
+            completed: None,
+            ongoing: vec![ActivityCounter::new(time)],
+        }
+    }
+}
+
+/// Borrowed view of the counters of a collection window, regardless of whether it is a built-in
+/// window or an additional one (see [ActivityStats]).
+pub struct WindowView<'a, T: ActivityCounter> {
+    pub length_ns: u64,
+    pub step_ns: u64,
+    pub completed: Option<&'a T>,
+    pub ongoing: &'a [T],
+}
+
+impl<'a, T: ActivityCounter> From<&'a WindowStats<T>> for WindowView<'a, T> {
+    fn from(window: &'a WindowStats<T>) -> Self {
+        Self {
+            length_ns: window.length_ns,
+            step_ns: window.step_ns,
+            completed: window.completed.as_ref(),
+            ongoing: &window.ongoing,
+        }
+    }
 }
 
 impl<T: ActivityCounter> ActivityStats<T> {
-    fn new(time: Timestamp) -> Self {
+    fn new(additional_windows: &[ActivityWindowConfig], time: Timestamp) -> Self {
         Self {
             completed: CompletedActivityStats {
                 daily_events: None,
@@ -28,86 +107,486 @@ impl<T: ActivityCounter> ActivityStats<T> {
                 daily_events: ActivityCounter::new(time),
                 monthly_events: vec![ActivityCounter::new(time)],
             },
+            windows: Some(
+                additional_windows
+                    .iter()
+                    .map(|config| (config.name.clone(), WindowStats::new(config, time)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the stats of the given collection window (if configured).
+    pub fn window(&self, name: &str) -> Option<WindowView<'_, T>> {
+        match name {
+            DAILY_WINDOW => Some(WindowView {
+                length_ns: DAY_NS,
+                step_ns: DAY_NS,
+                completed: self.completed.daily_events.as_ref(),
+                ongoing: std::slice::from_ref(&self.ongoing.daily_events),
+            }),
+            MONTHLY_WINDOW => Some(WindowView {
+                length_ns: MONTH_NS,
+                step_ns: DAY_NS,
+                completed: self.completed.monthly_events.as_ref(),
+                ongoing: &self.ongoing.monthly_events,
+            }),
+            _ => self
+                .windows
+                .as_ref()
+                .and_then(|windows| windows.get(name))
+                .map(WindowView::from),
+        }
+    }
+
+    /// Returns the stats of all collection windows: the built-in daily and monthly windows
+    /// followed by the additional windows sorted by name.
+    pub fn windows(&self) -> impl Iterator<Item = (&str, WindowView<'_, T>)> {
+        [DAILY_WINDOW, MONTHLY_WINDOW]
+            .into_iter()
+            .filter_map(move |name| self.window(name).map(|window| (name, window)))
+            .chain(self.additional_windows())
+    }
+
+    /// Returns the stats of the additional collection windows, sorted by name.
+    pub fn additional_windows(&self) -> impl Iterator<Item = (&str, WindowView<'_, T>)> {
+        self.windows
+            .iter()
+            .flat_map(|windows| windows.iter())
+            .map(|(name, window)| (name.as_str(), WindowView::from(window)))
+    }
+
+    /// Returns the counter of the last completed collection window with the given name (if any).
+    pub fn completed(&self, name: &str) -> Option<&T> {
+        self.window(name).and_then(|window| window.completed)
+    }
+
+    /// Brings the additional collection windows in line with the given configuration: windows
+    /// that are no longer configured are dropped and windows that are new (or have a changed
+    /// length or step) start collecting from scratch.
+    fn configure_windows(&mut self, additional_windows: &[ActivityWindowConfig], time: Timestamp) {
+        let window_stats = self.windows.get_or_insert_with(BTreeMap::new);
+        window_stats.retain(|name, stats| {
+            additional_windows.iter().any(|config| {
+                &config.name == name
+                    && config.length_ns == stats.length_ns
+                    && config.step_ns == stats.step_ns
+            })
+        });
+        for config in additional_windows {
+            window_stats
+                .entry(config.name.clone())
+                .or_insert_with(|| WindowStats::new(config, time));
         }
     }
 
     /// Updates all ongoing counters with the given context.
-    /// Also performs maintenance on the stats, e.g. removing old monthly counters.
+    /// Also performs maintenance on the stats, e.g. removing expired counters.
     fn update_counters(&mut self, context: &T::CountingContext<'_>) {
-        stats_maintenance::process_stats(self);
-        self.ongoing.daily_events.count_event(context);
-        self.ongoing
-            .monthly_events
+        let update_window = |window: &mut WindowStats<T>| {
+            stats_maintenance::process_window(window);
+            window
+                .ongoing
+                .iter_mut()
+                .for_each(|counter| counter.count_event(context));
+        };
+
+        // The built-in windows are converted to and from WindowStats in order to share the
+        // maintenance logic with the additional windows.
+        let mut daily = WindowStats {
+            length_ns: DAY_NS,
+            step_ns: DAY_NS,
+            completed: self.completed.daily_events.take(),
+            ongoing: vec![std::mem::replace(
+                &mut self.ongoing.daily_events,
+                ActivityCounter::new(0),
+            )],
+        };
+        update_window(&mut daily);
+        self.completed.daily_events = daily.completed;
+        self.ongoing.daily_events = daily
+            .ongoing
+            .pop()
+            .expect("the daily window always has an ongoing counter");
+
+        let mut monthly = WindowStats {
+            length_ns: MONTH_NS,
+            step_ns: DAY_NS,
+            completed: self.completed.monthly_events.take(),
+            ongoing: std::mem::take(&mut self.ongoing.monthly_events),
+        };
+        update_window(&mut monthly);
+        self.completed.monthly_events = monthly.completed;
+        self.ongoing.monthly_events = monthly.ongoing;
+
+        self.windows
             .iter_mut()
-            .for_each(|counter| counter.count_event(context));
+            .flat_map(|windows| windows.values_mut())
+            .for_each(update_window);
     }
 }
 
+/// Completed stats of the built-in daily and monthly windows.
 #[derive(Clone, CandidType, Deserialize, Eq, PartialEq, Debug)]
 pub struct CompletedActivityStats<T: ActivityCounter> {
     // Completed daily activity counter.
     //
     // For legacy reasons / stable memory compatibility the old name is kept when serializing.
-    // This will be cleaned up when the stats are moved out of the persistent state directly
-    // into the stable memory.
     #[serde(rename = "daily_active_anchors")]
     pub daily_events: Option<T>,
 
     // Completed monthly activity counter.
     //
     // For legacy reasons / stable memory compatibility the old name is kept when serializing.
-    // This will be cleaned up when the stats are moved out of the persistent state directly
-    // into the stable memory.
     #[serde(rename = "monthly_active_anchors")]
     pub monthly_events: Option<T>,
 }
 
+/// Ongoing stats of the built-in daily and monthly windows.
 #[derive(Clone, CandidType, Deserialize, Eq, PartialEq, Debug)]
 pub struct OngoingActivityStats<T: ActivityCounter> {
     // Ongoing activity counter for the current 24 h time bucket.
     //
     // For legacy reasons / stable memory compatibility the old name is kept when serializing.
-    // This will be cleaned up when the stats are moved out of the persistent state directly
-    // into the stable memory.
     #[serde(rename = "daily_active_anchors")]
     pub daily_events: T,
 
-    // Monthly activity is collected using 30-day sliding windows.
-    // This vec contains up to 30 30-day active windows each offset by one day.
-    // The vec is sorted, new collection windows are added at the end.
+    // 30-day sliding windows, each offset by one day.
     //
     // For legacy reasons / stable memory compatibility the old name is kept when serializing.
-    // This will be cleaned up when the stats are moved out of the persistent state directly
-    // into the stable memory.
     #[serde(rename = "monthly_active_anchors")]
     pub monthly_events: Vec<T>,
 }
 
-pub fn update_activity_stats(anchor: &Anchor, current_device: &Device) {
+/// Returns the collection windows configured using the init argument in addition to the
+/// built-in daily and monthly windows.
+pub fn additional_activity_windows(
+    persistent_state: &PersistentState,
+) -> Vec<ActivityWindowConfig> {
+    persistent_state
+        .activity_windows
+        .clone()
+        .unwrap_or_default()
+}
+
+/// Checks that the given additional collection windows can be configured.
+pub fn validate_activity_windows(windows: &[ActivityWindowConfig]) -> Result<(), String> {
+    if windows.len() > MAX_ADDITIONAL_WINDOWS {
+        return Err(format!(
+            "at most {MAX_ADDITIONAL_WINDOWS} additional activity windows can be configured"
+        ));
+    }
+    let mut names = BTreeSet::from([DAILY_WINDOW, MONTHLY_WINDOW]);
+    for window in windows {
+        let name = window.name.as_str();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(format!("invalid activity window name \"{name}\": only lowercase letters, digits and underscores are allowed"));
+        }
+        if !names.insert(name) {
+            return Err(format!("duplicate activity window name \"{name}\""));
+        }
+        if window.step_ns == 0
+            || window.length_ns == 0
+            || window.length_ns % window.step_ns != 0
+            || window.length_ns / window.step_ns > MAX_ONGOING_COUNTERS
+        {
+            return Err(format!("invalid activity window \"{name}\": length_ns must be a multiple of step_ns and at most {MAX_ONGOING_COUNTERS} times as long"));
+        }
+        if window.length_ns > MAX_WINDOW_LENGTH_NS {
+            return Err(format!(
+                "invalid activity window \"{name}\": length_ns must not exceed {MAX_WINDOW_LENGTH_NS}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Applies the configured additional collection windows to all activity stats.
+pub fn configure_activity_windows() {
+    let now = time();
     state::persistent_state_mut(|persistent_state| {
+        let windows = additional_activity_windows(persistent_state);
+        if let Some(ref mut stats) = persistent_state.active_anchor_stats {
+            stats.configure_windows(&windows, now);
+        }
+        if let Some(ref mut stats) = persistent_state.domain_active_anchor_stats {
+            stats.configure_windows(&windows, now);
+        }
+        if let Some(ref mut stats) = persistent_state.active_authn_method_stats {
+            stats.configure_windows(&windows, now);
+        }
+        if let Some(ref mut stats) = persistent_state.dapp_active_anchor_stats {
+            stats.configure_windows(&windows, now);
+        }
+    })
+}
+
+pub fn update_activity_stats(
+    anchor_number: AnchorNumber,
+    anchor: &Anchor,
+    current_device: &Device,
+) {
+    // Resolved upfront because the II domains are looked up in the persistent state, which cannot
+    // be borrowed again while it is borrowed mutably below.
+    let ii_origins = ii_domain::ii_origins();
+    let current_domain = current_device.ii_domain_among(&ii_origins);
+    state::persistent_state_mut(|persistent_state| {
+        let windows = additional_activity_windows(persistent_state);
+
+        // Retention of the registration cohorts (only tracked for anchors registered after
+        // the first recorded registration)
+        if let Some(ref mut cohort_stats) = persistent_state.registration_cohort_stats {
+            cohort_stats.record_activity(anchor_number, anchor.last_activity(), time());
+        }
+
         // Active anchor stats across all domains
         let active_anchor_stats = persistent_state
             .active_anchor_stats
-            .get_or_insert_with(|| ActivityStats::new(time()));
+            .get_or_insert_with(|| ActivityStats::new(&windows, time()));
         active_anchor_stats.update_counters(&anchor.last_activity());
 
         // Active anchor stats, II domains only
-        if let Some(domain) = current_device.ii_domain() {
+        if let Some(domain) = current_domain {
             let context = DomainActivityContext {
                 anchor,
                 current_domain: domain,
+                ii_origins: &ii_origins,
             };
             let domain_active_anchor_stats = persistent_state
                 .domain_active_anchor_stats
-                .get_or_insert_with(|| ActivityStats::new(time()));
+                .get_or_insert_with(|| ActivityStats::new(&windows, time()));
             domain_active_anchor_stats.update_counters(&context);
 
             // Active authn methods stats, II domains only
             let authn_method_stats = persistent_state
                 .active_authn_method_stats
-                .get_or_insert_with(|| ActivityStats::new(time()));
+                .get_or_insert_with(|| ActivityStats::new(&windows, time()));
 
             authn_method_stats.update_counters(&current_device);
         }
     })
 }
+
+/// Adds a newly registered anchor to the registration cohort of the current week.
+pub fn update_registration_cohort_stats(anchor_number: AnchorNumber) {
+    state::persistent_state_mut(|persistent_state| {
+        persistent_state
+            .registration_cohort_stats
+            .get_or_insert_with(CohortStats::default)
+            .record_registration(anchor_number, time());
+    })
+}
+
+/// Returns the retention statistics of the registration cohorts.
+pub fn registration_cohort_stats() -> RegistrationCohortStats {
+    state::persistent_state(|persistent_state| {
+        persistent_state
+            .registration_cohort_stats
+            .as_ref()
+            .map(RegistrationCohortStats::from)
+            .unwrap_or(RegistrationCohortStats { cohorts: vec![] })
+    })
+}
+
+/// Updates the per-dapp activity stats for a delegation prepared for the given frontend and
+/// records the delegation on the anchor.
+///
+/// Note: the anchor needs to be written back to storage for the delegation to be taken into
+/// account for future events.
+pub fn update_dapp_activity_stats(anchor: &mut Anchor, frontend: &FrontendHostname) {
+    let context = DappActivityContext {
+        frontend,
+        previous_delegation_timestamp: anchor.last_delegation_timestamp(frontend),
+    };
+    state::persistent_state_mut(|persistent_state| {
+        let windows = additional_activity_windows(persistent_state);
+        let dapp_active_anchor_stats = persistent_state
+            .dapp_active_anchor_stats
+            .get_or_insert_with(|| ActivityStats::new(&windows, time()));
+        dapp_active_anchor_stats.update_counters(&context);
+    });
+    anchor.record_delegation(frontend, time());
+}
+
+/// Returns the per-dapp activity stats of the last completed collection windows.
+pub fn dapp_activity_stats() -> DappActivityStats {
+    fn to_window(counter: &DappActiveAnchorCounter) -> DappActivityWindow {
+        DappActivityWindow {
+            start_timestamp: counter.start_timestamp,
+            dapps: counter
+                .ranked_counters()
+                .into_iter()
+                .map(|(frontend, active_anchors)| DappActiveAnchors {
+                    frontend,
+                    active_anchors,
+                })
+                .collect(),
+        }
+    }
+
+    state::persistent_state(|persistent_state| {
+        let stats = persistent_state.dapp_active_anchor_stats.as_ref();
+        DappActivityStats {
+            daily: stats
+                .and_then(|stats| stats.completed(DAILY_WINDOW))
+                .map(to_window),
+            monthly: stats
+                .and_then(|stats| stats.completed(MONTHLY_WINDOW))
+                .map(to_window),
+        }
+    })
+}
+
+/// Returns the completed and ongoing activity stats.
+pub fn activity_stats() -> InternetIdentityActivityStats {
+    state::persistent_state(|persistent_state| InternetIdentityActivityStats {
+        active_anchors: persistent_state.active_anchor_stats.as_ref().map(|stats| {
+            let windows = ActivityWindows::from_stats(stats, active_anchor_window);
+            ActiveAnchorStats {
+                completed_daily: windows.completed_daily,
+                completed_monthly: windows.completed_monthly,
+                ongoing_daily: windows.ongoing_daily,
+                ongoing_monthly: windows.ongoing_monthly,
+                additional_windows: Some(
+                    windows
+                        .additional
+                        .into_iter()
+                        .map(|window| ActiveAnchorWindowStats {
+                            name: window.name,
+                            length_ns: window.length_ns,
+                            step_ns: window.step_ns,
+                            completed: window.completed,
+                            ongoing: window.ongoing,
+                        })
+                        .collect(),
+                ),
+            }
+        }),
+        domain_active_anchors: persistent_state
+            .domain_active_anchor_stats
+            .as_ref()
+            .map(|stats| {
+                let windows = ActivityWindows::from_stats(stats, domain_active_anchor_window);
+                DomainActiveAnchorStats {
+                    completed_daily: windows.completed_daily,
+                    completed_monthly: windows.completed_monthly,
+                    ongoing_daily: windows.ongoing_daily,
+                    ongoing_monthly: windows.ongoing_monthly,
+                    additional_windows: Some(
+                        windows
+                            .additional
+                            .into_iter()
+                            .map(|window| DomainActiveAnchorWindowStats {
+                                name: window.name,
+                                length_ns: window.length_ns,
+                                step_ns: window.step_ns,
+                                completed: window.completed,
+                                ongoing: window.ongoing,
+                            })
+                            .collect(),
+                    ),
+                }
+            }),
+        active_authn_methods: persistent_state
+            .active_authn_method_stats
+            .as_ref()
+            .map(|stats| {
+                let windows = ActivityWindows::from_stats(stats, authn_method_window);
+                ActiveAuthnMethodStats {
+                    completed_daily: windows.completed_daily,
+                    completed_monthly: windows.completed_monthly,
+                    ongoing_daily: windows.ongoing_daily,
+                    ongoing_monthly: windows.ongoing_monthly,
+                    additional_windows: Some(
+                        windows
+                            .additional
+                            .into_iter()
+                            .map(|window| ActiveAuthnMethodWindowStats {
+                                name: window.name,
+                                length_ns: window.length_ns,
+                                step_ns: window.step_ns,
+                                completed: window.completed,
+                                ongoing: window.ongoing,
+                            })
+                            .collect(),
+                    ),
+                }
+            }),
+    })
+}
+
+/// The collection windows of [ActivityStats] converted to the given window type of the candid
+/// interface.
+struct ActivityWindows<W> {
+    completed_daily: Option<W>,
+    completed_monthly: Option<W>,
+    ongoing_daily: W,
+    ongoing_monthly: Vec<W>,
+    additional: Vec<AdditionalWindow<W>>,
+}
+
+/// An additional collection window converted to the given window type of the candid interface.
+struct AdditionalWindow<W> {
+    name: String,
+    length_ns: u64,
+    step_ns: u64,
+    completed: Option<W>,
+    ongoing: Vec<W>,
+}
+
+impl<W> ActivityWindows<W> {
+    fn from_stats<T: ActivityCounter>(stats: &ActivityStats<T>, to_window: fn(&T) -> W) -> Self {
+        Self {
+            completed_daily: stats.completed.daily_events.as_ref().map(to_window),
+            completed_monthly: stats.completed.monthly_events.as_ref().map(to_window),
+            ongoing_daily: to_window(&stats.ongoing.daily_events),
+            ongoing_monthly: stats.ongoing.monthly_events.iter().map(to_window).collect(),
+            additional: stats
+                .additional_windows()
+                .map(|(name, window)| AdditionalWindow {
+                    name: name.to_string(),
+                    length_ns: window.length_ns,
+                    step_ns: window.step_ns,
+                    completed: window.completed.map(to_window),
+                    ongoing: window.ongoing.iter().map(to_window).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn active_anchor_window(counter: &ActiveAnchorCounter) -> ActiveAnchorWindow {
+    ActiveAnchorWindow {
+        start_timestamp: counter.start_timestamp,
+        active_anchors: counter.counter,
+    }
+}
+
+fn domain_active_anchor_window(counter: &DomainActiveAnchorCounter) -> DomainActiveAnchorWindow {
+    DomainActiveAnchorWindow {
+        start_timestamp: counter.start_timestamp,
+        domains: counter
+            .counters()
+            .into_iter()
+            .map(|(ii_origins, active_anchors)| DomainActiveAnchors {
+                ii_origins,
+                active_anchors,
+            })
+            .collect(),
+    }
+}
+
+fn authn_method_window(counter: &AuthnMethodCounter) -> ActiveAuthnMethodWindow {
+    ActiveAuthnMethodWindow {
+        start_timestamp: counter.start_timestamp,
+        webauthn_auth: counter.webauthn_auth_counter,
+        webauthn_recovery: counter.webauthn_recovery_counter,
+        recovery_phrase: counter.recovery_phrase_counter,
+        browser_storage_key: counter.browser_storage_key_counter,
+        other: counter.other_counter,
+    }
+}
@@ -2,10 +2,11 @@ use internet_identity_interface::internet_identity::types::Timestamp;
 
 pub mod active_anchor_counter;
 pub mod authn_method_counter;
+pub mod dapp_active_anchor_counter;
 pub mod domain_active_anchor_counter;
 
-/// This trait can be implemented by any counter that is used to track activity over both daily and
-/// monthly (30-day) periods.
+/// This trait can be implemented by any counter that is used to track activity over the configured
+/// collection windows (daily, monthly (30-day) and any additional windows).
 /// The counting infrastructure will take care of counter maintenance (e.g. pruning expired counters
 /// and adding new ones).
 ///
@@ -13,9 +14,11 @@ pub mod domain_active_anchor_counter;
 /// counter. This means that this infrastructure should not be used to count events that are
 /// expected to happen less frequently than once per day.
 ///
-/// There are currently two implementations of this trait:
+/// There are currently the following implementations of this trait:
 ///     - ActiveAnchorCounter: used to track unique active anchors
-///     - IIDomainCounter: used to track unique active anchors per domain
+///     - DomainActiveAnchorCounter: used to track unique active anchors per (set of) II domains
+///     - AuthnMethodCounter: used to track unique active authentication methods
+///     - DappActiveAnchorCounter: used to track unique active anchors per delegation frontend
 pub trait ActivityCounter: Clone {
     /// Context that is required for the counter to count an event.
     type CountingContext<'a>;
@@ -0,0 +1,122 @@
+use crate::activity_stats::activity_counter::ActivityCounter;
+use candid::{CandidType, Deserialize};
+use internet_identity_interface::internet_identity::types::{FrontendHostname, Timestamp};
+use std::collections::BTreeMap;
+
+/// Maximum number of frontends tracked per counter. This bounds the memory used by the per-dapp
+/// stats in the persistent state.
+const MAX_TRACKED_DAPPS: usize = 100;
+
+#[derive(Clone, CandidType, Deserialize, Eq, PartialEq, Debug)]
+pub struct DappActiveAnchorCounter {
+    pub start_timestamp: Timestamp,
+    // Number of unique active anchors per frontend, for at most MAX_TRACKED_DAPPS frontends.
+    pub counters: BTreeMap<FrontendHostname, u64>,
+}
+
+pub struct DappActivityContext<'a> {
+    pub frontend: &'a FrontendHostname,
+    // Timestamp of the previous delegation of the anchor for the frontend (if any).
+    pub previous_delegation_timestamp: Option<Timestamp>,
+}
+
+impl DappActiveAnchorCounter {
+    /// Returns the frontends with their number of unique active anchors, sorted by descending
+    /// number of active anchors.
+    pub fn ranked_counters(&self) -> Vec<(FrontendHostname, u64)> {
+        let mut counters: Vec<(FrontendHostname, u64)> = self
+            .counters
+            .iter()
+            .map(|(frontend, count)| (frontend.clone(), *count))
+            .collect();
+        counters.sort_by(|(frontend_1, count_1), (frontend_2, count_2)| {
+            count_2.cmp(count_1).then(frontend_1.cmp(frontend_2))
+        });
+        counters
+    }
+}
+
+impl ActivityCounter for DappActiveAnchorCounter {
+    type CountingContext<'a> = DappActivityContext<'a>;
+
+    fn new(start_timestamp: Timestamp) -> Self {
+        Self {
+            start_timestamp,
+            counters: BTreeMap::new(),
+        }
+    }
+
+    fn start_timestamp(&self) -> Timestamp {
+        self.start_timestamp
+    }
+
+    /// Increases the counter of the frontend if the anchor did not use the frontend since the
+    /// `start_timestamp` of this counter.
+    ///
+    /// If [MAX_TRACKED_DAPPS] frontends are already tracked, a new frontend replaces the least
+    /// active tracked frontend and takes over its counter (plus one), as in the space-saving
+    /// algorithm. This bounds the memory used while allowing frontends that become popular after
+    /// all slots have been taken to displace less active ones. As a consequence, the counter of a
+    /// frontend that replaced another one may overestimate its active anchors by at most the
+    /// counter of the replaced frontend.
+    fn count_event(&mut self, context: &Self::CountingContext<'_>) {
+        if let Some(timestamp) = context.previous_delegation_timestamp {
+            if timestamp >= self.start_timestamp {
+                // already counted
+                return;
+            }
+        }
+
+        if let Some(count) = self.counters.get_mut(context.frontend) {
+            *count += 1;
+            return;
+        }
+
+        let mut count = 1;
+        if self.counters.len() >= MAX_TRACKED_DAPPS {
+            if let Some((least_active, least_count)) = self
+                .counters
+                .iter()
+                .min_by_key(|(_, count)| **count)
+                .map(|(frontend, count)| (frontend.clone(), *count))
+            {
+                self.counters.remove(&least_active);
+                count += least_count;
+            }
+        }
+        self.counters.insert(context.frontend.clone(), count);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn count_event(counter: &mut DappActiveAnchorCounter, frontend: &str) {
+        counter.count_event(&DappActivityContext {
+            frontend: &frontend.to_string(),
+            previous_delegation_timestamp: None,
+        });
+    }
+
+    #[test]
+    fn should_let_popular_frontends_displace_least_active_ones() {
+        let mut counter = DappActiveAnchorCounter::new(0);
+        for i in 0..MAX_TRACKED_DAPPS {
+            let frontend = format!("https://dapp-{i}.com");
+            // every tracked frontend has at least two active anchors
+            count_event(&mut counter, &frontend);
+            count_event(&mut counter, &frontend);
+        }
+        count_event(&mut counter, "https://dapp-0.com");
+
+        for _ in 0..3 {
+            count_event(&mut counter, "https://new-dapp.com");
+        }
+
+        assert_eq!(counter.counters.len(), MAX_TRACKED_DAPPS);
+        assert_eq!(counter.counters.get("https://new-dapp.com"), Some(&5));
+        assert_eq!(counter.counters.get("https://dapp-0.com"), Some(&3));
+        assert_eq!(counter.ranked_counters()[0].0, "https://new-dapp.com");
+    }
+}
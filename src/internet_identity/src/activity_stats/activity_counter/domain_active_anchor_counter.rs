@@ -1,38 +1,118 @@
 use crate::activity_stats::activity_counter::ActivityCounter;
 use crate::ii_domain::IIDomain;
 use crate::storage::anchor::{Anchor, DomainActivity};
+use crate::{IC0_APP_ORIGIN, INTERNETCOMPUTER_ORG_ORIGIN};
 use candid::{CandidType, Deserialize};
-use internet_identity_interface::internet_identity::types::Timestamp;
+use internet_identity_interface::internet_identity::types::{FrontendHostname, Timestamp};
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Clone, CandidType, Deserialize, Eq, PartialEq, Debug)]
 pub struct DomainActiveAnchorCounter {
     pub start_timestamp: Timestamp,
+    // Counters of the default II domains. These are kept up to date alongside ii_domains_counters
+    // so that the counters remain valid for a previous release (which only knows these).
     pub ic0_app_counter: u64,
     pub internetcomputer_org_counter: u64,
     pub both_ii_domains_counter: u64,
+    // Number of unique active anchors by the (sorted) set of II origins the anchors were active on.
+    // Not present on counters created by a previous release, in which case it is initialized from
+    // the counters above on the next event counted.
+    pub ii_domains_counters: Option<BTreeMap<Vec<FrontendHostname>, u64>>,
 }
 
 pub struct DomainActivityContext<'a> {
     pub anchor: &'a Anchor,
     pub current_domain: IIDomain,
+    // The configured II origins, resolved before borrowing the persistent state.
+    pub ii_origins: &'a [FrontendHostname],
 }
 
 impl DomainActiveAnchorCounter {
-    fn increment_counter_for_domain(&mut self, domain: &IIDomain) {
-        match domain {
-            IIDomain::Ic0AppDomain => self.ic0_app_counter += 1,
-            IIDomain::InternetComputerOrgDomain => self.internetcomputer_org_counter += 1,
+    /// Returns the number of unique active anchors by the (sorted) set of II origins the anchors
+    /// were active on.
+    pub fn counters(&self) -> BTreeMap<Vec<FrontendHostname>, u64> {
+        let mut counters = self
+            .ii_domains_counters
+            .clone()
+            .unwrap_or_else(|| self.legacy_counters());
+        counters.retain(|_, count| *count > 0);
+        counters
+    }
+
+    /// Returns the counters of the default II domains keyed the same way as ii_domains_counters.
+    fn legacy_counters(&self) -> BTreeMap<Vec<FrontendHostname>, u64> {
+        [
+            (vec![IC0_APP_ORIGIN], self.ic0_app_counter),
+            (
+                vec![INTERNETCOMPUTER_ORG_ORIGIN],
+                self.internetcomputer_org_counter,
+            ),
+            (
+                vec![IC0_APP_ORIGIN, INTERNETCOMPUTER_ORG_ORIGIN],
+                self.both_ii_domains_counter,
+            ),
+        ]
+        .into_iter()
+        .map(|(origins, count)| (origins.iter().map(|o| o.to_string()).collect(), count))
+        .collect()
+    }
+
+    /// Returns the counter of the default II domains corresponding to the given key (if any).
+    fn legacy_counter_mut(&mut self, key: &[FrontendHostname]) -> Option<&mut u64> {
+        match key {
+            [origin] if origin == IC0_APP_ORIGIN => Some(&mut self.ic0_app_counter),
+            [origin] if origin == INTERNETCOMPUTER_ORG_ORIGIN => {
+                Some(&mut self.internetcomputer_org_counter)
+            }
+            [first, second] if first == IC0_APP_ORIGIN && second == INTERNETCOMPUTER_ORG_ORIGIN => {
+                Some(&mut self.both_ii_domains_counter)
+            }
+            _ => None,
         }
     }
 
-    fn decrement_counter_for_domain(&mut self, domain: &IIDomain) {
-        match domain {
-            IIDomain::Ic0AppDomain => self.ic0_app_counter -= 1,
-            IIDomain::InternetComputerOrgDomain => self.internetcomputer_org_counter -= 1,
+    fn increment_counter_for_domains(&mut self, domains: &BTreeSet<IIDomain>) {
+        let key = counter_key(domains);
+        if let Some(count) = self.legacy_counter_mut(&key) {
+            *count += 1;
         }
+        *self.counters_mut().entry(key).or_default() += 1;
+    }
+
+    fn decrement_counter_for_domains(&mut self, domains: &BTreeSet<IIDomain>) {
+        let key = counter_key(domains);
+        if let Some(count) = self.legacy_counter_mut(&key) {
+            *count = count.saturating_sub(1);
+        }
+        let counters = self.counters_mut();
+        if let Some(count) = counters.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                counters.remove(&key);
+            }
+        }
+    }
+
+    /// Returns the counters by set of II origins, initializing them from the counters of the
+    /// default II domains if necessary.
+    fn counters_mut(&mut self) -> &mut BTreeMap<Vec<FrontendHostname>, u64> {
+        if self.ii_domains_counters.is_none() {
+            let mut counters = self.legacy_counters();
+            counters.retain(|_, count| *count > 0);
+            self.ii_domains_counters = Some(counters);
+        }
+        self.ii_domains_counters.get_or_insert_with(BTreeMap::new)
     }
 }
 
+/// The key of a counter: the sorted origins of the given domains.
+fn counter_key(domains: &BTreeSet<IIDomain>) -> Vec<FrontendHostname> {
+    domains
+        .iter()
+        .map(|domain| domain.origin().to_string())
+        .collect()
+}
+
 impl ActivityCounter for DomainActiveAnchorCounter {
     type CountingContext<'a> = DomainActivityContext<'a>;
 
@@ -42,6 +122,7 @@ impl ActivityCounter for DomainActiveAnchorCounter {
             ic0_app_counter: 0,
             internetcomputer_org_counter: 0,
             both_ii_domains_counter: 0,
+            ii_domains_counters: Some(BTreeMap::new()),
         }
     }
 
@@ -49,36 +130,36 @@ impl ActivityCounter for DomainActiveAnchorCounter {
         self.start_timestamp
     }
 
-    /// Increases the counter on a counter for a single II domain if the there was no activity before.
+    /// Increases the counter for the current II domain if there was no activity on II domains before.
     ///
-    /// If there has been activity on an II domain and this is activity on the _other II_ domain, then
-    /// we decrement the counter for the previous single domain and instead increment the counter for
-    /// both II domains.
+    /// If there has been activity on other II domains but not yet on the current one, then we
+    /// decrement the counter for the previous set of II domains and instead increment the counter
+    /// for the previous set of II domains extended by the current domain.
     ///
-    /// If the anchor was already active on both domains, the activity has been counted in this counter
-    /// already and no action needs to be taken.
+    /// If the anchor was already active on the current domain, the activity has been counted in
+    /// this counter already and no action needs to be taken.
     ///
     /// Only called if `current_domain` corresponds to an II domain.
     fn count_event(&mut self, context: &Self::CountingContext<'_>) {
-        let previous_domain_activity = context.anchor.domain_activity_since(self.start_timestamp);
+        let previous_domain_activity = context
+            .anchor
+            .domain_activity_since(self.start_timestamp, context.ii_origins);
 
         match previous_domain_activity {
             DomainActivity::None | DomainActivity::NonIIDomain => {
-                self.increment_counter_for_domain(&context.current_domain);
-            }
-            DomainActivity::Ic0App | DomainActivity::InternetComputerOrg => {
-                if !context
+                self.increment_counter_for_domains(&BTreeSet::from([context
                     .current_domain
-                    .is_same_domain(&previous_domain_activity)
-                {
-                    // the anchor switched from being active on only one II domain to being active on both
-                    // --> total active remains the same, but the anchor switches to the both domains bucket
-                    self.decrement_counter_for_domain(&context.current_domain.other_ii_domain());
-                    self.both_ii_domains_counter += 1;
-                }
+                    .clone()]));
             }
-            DomainActivity::BothIIDomains => {
-                // already counted => do nothing
+            DomainActivity::IIDomains(mut domains) => {
+                if !domains.contains(&context.current_domain) {
+                    // the anchor switched from being active on one set of II domains to another
+                    // --> total active remains the same, but the anchor switches to the bucket
+                    // including the current domain
+                    self.decrement_counter_for_domains(&domains);
+                    domains.insert(context.current_domain.clone());
+                    self.increment_counter_for_domains(&domains);
+                }
             }
         }
     }
@@ -0,0 +1,177 @@
+//! Retention statistics of weekly registration cohorts.
+//!
+//! A cohort consists of all anchors registered in the same week (weeks start at multiples of
+//! [WEEK_NS] since the Unix epoch). For each cohort, the number of distinct anchors with activity
+//! in each of the following [MAX_TRACKED_WEEKS] weeks is counted.
+//!
+//! Anchor numbers are allocated sequentially, so the cohort of an anchor is determined by the
+//! number of the first anchor registered in each cohort (i.e. no per-anchor data is required).
+//! Whether an anchor has already been counted in a week is derived from its last activity timestamp,
+//! the same way as for the [ActiveAnchorCounter](crate::activity_stats::activity_counter::active_anchor_counter::ActiveAnchorCounter).
+use crate::DAY_NS;
+use candid::{CandidType, Deserialize};
+use internet_identity_interface::internet_identity::types::{
+    AnchorNumber, RegistrationCohort, RegistrationCohortStats, Timestamp,
+};
+
+pub const WEEK_NS: u64 = 7 * DAY_NS;
+/// Maximum number of cohorts that are kept, the oldest cohorts are dropped first.
+pub const MAX_COHORTS: usize = 26;
+/// Number of weeks after the registration week for which the activity of a cohort is counted.
+pub const MAX_TRACKED_WEEKS: u64 = 12;
+
+#[derive(Clone, Default, CandidType, Deserialize, Eq, PartialEq, Debug)]
+pub struct CohortStats {
+    // Sorted by registration week (and hence by first anchor number).
+    pub cohorts: Vec<Cohort>,
+}
+
+#[derive(Clone, CandidType, Deserialize, Eq, PartialEq, Debug)]
+pub struct Cohort {
+    pub week_start_timestamp: Timestamp,
+    // Number of the first anchor registered in this week. All anchors up to the first anchor of
+    // the next cohort belong to this cohort.
+    pub first_anchor_number: AnchorNumber,
+    pub registrations: u64,
+    // The element at index `i` is the number of anchors with activity in week `i + 1` after the
+    // registration week. Weeks without any activity at the end are omitted.
+    pub active_anchors_by_week: Vec<u64>,
+}
+
+fn week_start(timestamp: Timestamp) -> Timestamp {
+    timestamp - timestamp % WEEK_NS
+}
+
+impl CohortStats {
+    /// Adds a newly registered anchor to the cohort of the current week.
+    pub fn record_registration(&mut self, anchor_number: AnchorNumber, now: Timestamp) {
+        let week_start_timestamp = week_start(now);
+        match self.cohorts.last_mut() {
+            Some(cohort) if cohort.week_start_timestamp == week_start_timestamp => {
+                cohort.registrations += 1;
+            }
+            _ => {
+                self.cohorts.push(Cohort {
+                    week_start_timestamp,
+                    first_anchor_number: anchor_number,
+                    registrations: 1,
+                    active_anchors_by_week: vec![],
+                });
+                if self.cohorts.len() > MAX_COHORTS {
+                    self.cohorts.remove(0);
+                }
+            }
+        }
+    }
+
+    /// Counts the activity of an anchor in the current week, unless the anchor had already been
+    /// active in the current week (according to `previous_activity_timestamp`). Activity in the
+    /// registration week and of anchors that do not belong to a tracked cohort is ignored.
+    pub fn record_activity(
+        &mut self,
+        anchor_number: AnchorNumber,
+        previous_activity_timestamp: Option<Timestamp>,
+        now: Timestamp,
+    ) {
+        let current_week_start = week_start(now);
+        if previous_activity_timestamp.map_or(false, |timestamp| timestamp >= current_week_start) {
+            return;
+        }
+        let Some(cohort) = self
+            .cohorts
+            .iter_mut()
+            .rev()
+            .find(|cohort| cohort.first_anchor_number <= anchor_number)
+        else {
+            return;
+        };
+        let week = (current_week_start - cohort.week_start_timestamp) / WEEK_NS;
+        if week == 0 || week > MAX_TRACKED_WEEKS {
+            return;
+        }
+        let index = (week - 1) as usize;
+        if cohort.active_anchors_by_week.len() <= index {
+            cohort.active_anchors_by_week.resize(index + 1, 0);
+        }
+        cohort.active_anchors_by_week[index] += 1;
+    }
+}
+
+impl From<&CohortStats> for RegistrationCohortStats {
+    fn from(stats: &CohortStats) -> Self {
+        RegistrationCohortStats {
+            cohorts: stats
+                .cohorts
+                .iter()
+                .map(|cohort| RegistrationCohort {
+                    start_timestamp: cohort.week_start_timestamp,
+                    registrations: cohort.registrations,
+                    active_anchors_by_week: cohort.active_anchors_by_week.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WEEK_1: Timestamp = 100 * WEEK_NS;
+
+    #[test]
+    fn should_group_registrations_by_week() {
+        let mut stats = CohortStats::default();
+        stats.record_registration(10, WEEK_1);
+        stats.record_registration(11, WEEK_1 + 6 * DAY_NS);
+        stats.record_registration(12, WEEK_1 + WEEK_NS);
+
+        assert_eq!(stats.cohorts.len(), 2);
+        assert_eq!(stats.cohorts[0].first_anchor_number, 10);
+        assert_eq!(stats.cohorts[0].registrations, 2);
+        assert_eq!(stats.cohorts[1].first_anchor_number, 12);
+        assert_eq!(stats.cohorts[1].week_start_timestamp, WEEK_1 + WEEK_NS);
+    }
+
+    #[test]
+    fn should_count_each_anchor_once_per_week() {
+        let mut stats = CohortStats::default();
+        stats.record_registration(10, WEEK_1);
+        stats.record_registration(11, WEEK_1);
+
+        // activity in the registration week is not counted
+        stats.record_activity(10, Some(WEEK_1), WEEK_1 + DAY_NS);
+        // week 1
+        stats.record_activity(10, Some(WEEK_1 + DAY_NS), WEEK_1 + WEEK_NS);
+        stats.record_activity(10, Some(WEEK_1 + WEEK_NS), WEEK_1 + WEEK_NS + DAY_NS);
+        stats.record_activity(11, Some(WEEK_1), WEEK_1 + WEEK_NS + DAY_NS);
+        // week 3
+        stats.record_activity(11, Some(WEEK_1 + WEEK_NS), WEEK_1 + 3 * WEEK_NS);
+
+        assert_eq!(stats.cohorts[0].active_anchors_by_week, vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn should_ignore_untracked_anchors_and_weeks() {
+        let mut stats = CohortStats::default();
+        stats.record_registration(10, WEEK_1);
+
+        // registered before the first tracked cohort
+        stats.record_activity(9, None, WEEK_1 + WEEK_NS);
+        // too long after the registration
+        stats.record_activity(10, None, WEEK_1 + (MAX_TRACKED_WEEKS + 1) * WEEK_NS);
+
+        assert!(stats.cohorts[0].active_anchors_by_week.is_empty());
+    }
+
+    #[test]
+    fn should_keep_bounded_number_of_cohorts() {
+        let mut stats = CohortStats::default();
+        for week in 0..(MAX_COHORTS as u64 + 5) {
+            stats.record_registration(week, WEEK_1 + week * WEEK_NS);
+        }
+
+        assert_eq!(stats.cohorts.len(), MAX_COHORTS);
+        assert_eq!(stats.cohorts[0].first_anchor_number, 5);
+    }
+}
@@ -59,11 +59,15 @@ pub fn get_anchor_info(anchor_number: AnchorNumber) -> IdentityAnchorInfo {
 /// Note: modifies the anchor but not does not write to storage. It is the responsibility of the
 /// caller to persist the changes. This allows anchor operations to write to storage only once,
 /// combining the modifications for bookkeeping reasons (made here) with other changes to the anchor.
-pub fn activity_bookkeeping(anchor: &mut Anchor, current_device_key: &DeviceKey) {
+pub fn activity_bookkeeping(
+    anchor_number: AnchorNumber,
+    anchor: &mut Anchor,
+    current_device_key: &DeviceKey,
+) {
     let device = anchor
         .device(current_device_key)
         .unwrap_or_else(|| trap(&format!("bug: device {:?} not found", current_device_key)));
-    activity_stats::update_activity_stats(anchor, device);
+    activity_stats::update_activity_stats(anchor_number, anchor, device);
     anchor
         .set_device_usage_timestamp(current_device_key, time())
         .expect("unable to update last usage timestamp");
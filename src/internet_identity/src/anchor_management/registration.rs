@@ -2,7 +2,7 @@ use crate::anchor_management::{activity_bookkeeping, post_operation_bookkeeping}
 use crate::state::{ChallengeInfo, MAX_INFLIGHT_CAPTCHAS};
 use crate::storage::anchor::Device;
 use crate::storage::Salt;
-use crate::{secs_to_nanos, state};
+use crate::{activity_stats, secs_to_nanos, state};
 use candid::Principal;
 use ic_cdk::api::time;
 use ic_cdk::{call, caller, trap};
@@ -19,20 +19,27 @@ use lazy_static::lazy_static;
 
 mod rate_limit;
 
-pub async fn create_challenge() -> Challenge {
+/// Creates a new captcha challenge.
+///
+/// Returns an error message if the call should be rejected because there are too many inflight
+/// captchas. Rejecting (as opposed to trapping) keeps the rejection counted in the metrics.
+pub async fn create_challenge() -> Result<Challenge, String> {
+    let limit_reached = state::inflight_challenges_mut(|inflight_challenges| {
+        prune_expired_challenges(inflight_challenges);
+        inflight_captcha_limit_reached(inflight_challenges)
+    });
+    if limit_reached {
+        state::usage_metrics_mut(|metrics| metrics.captcha_limit_rejections += 1);
+        return Err("too many inflight captchas".to_string());
+    }
+
     let mut rng = make_rng().await;
 
     state::inflight_challenges_mut(|inflight_challenges| {
         prune_expired_challenges(inflight_challenges);
 
-        // Error out if there are too many inflight challenges
-        if inflight_challenges.len()
-            >= state::persistent_state_mut(|state| {
-                *state
-                    .max_inflight_captchas
-                    .get_or_insert(MAX_INFLIGHT_CAPTCHAS)
-            }) as usize
-        {
+        // Error out if other challenges were created while awaiting the randomness
+        if inflight_captcha_limit_reached(inflight_challenges) {
             trap("too many inflight captchas");
         }
 
@@ -57,10 +64,11 @@ pub async fn create_challenge() -> Challenge {
                     },
                 );
 
-                return Challenge {
+                state::usage_metrics_mut(|metrics| metrics.captchas_created += 1);
+                return Ok(Challenge {
                     png_base64,
                     challenge_key,
-                };
+                });
             }
         }
 
@@ -68,12 +76,28 @@ pub async fn create_challenge() -> Challenge {
     })
 }
 
+fn inflight_captcha_limit_reached(
+    inflight_challenges: &HashMap<ChallengeKey, ChallengeInfo>,
+) -> bool {
+    inflight_challenges.len()
+        >= state::persistent_state_mut(|state| {
+            *state
+                .max_inflight_captchas
+                .get_or_insert(MAX_INFLIGHT_CAPTCHAS)
+        }) as usize
+}
+
 /// Remove challenges older than CAPTCHA_CHALLENGE_LIFETIME from the inflight challenges map
 fn prune_expired_challenges(inflight_challenges: &mut HashMap<ChallengeKey, ChallengeInfo>) {
     // 5 mins
     const CAPTCHA_CHALLENGE_LIFETIME_NS: u64 = secs_to_nanos(300);
 
+    let inflight_before = inflight_challenges.len();
     inflight_challenges.retain(|_, v| v.created > time() - CAPTCHA_CHALLENGE_LIFETIME_NS);
+    let expired = inflight_before - inflight_challenges.len();
+    if expired > 0 {
+        state::usage_metrics_mut(|metrics| metrics.captchas_expired += expired as u64);
+    }
 }
 
 // Get a random number generator based on 'raw_rand'
@@ -197,6 +221,7 @@ fn create_captcha<T: RngCore>(rng: T) -> (Base64, String) {
 fn check_challenge(res: ChallengeAttempt) -> Result<(), ()> {
     // avoid processing too many characters
     if res.chars.len() > CAPTCHA_LENGTH {
+        state::usage_metrics_mut(|metrics| metrics.captchas_failed += 1);
         return Err(());
     }
     // Normalize challenge attempts by replacing characters that are not in the captcha character set
@@ -215,7 +240,7 @@ fn check_challenge(res: ChallengeAttempt) -> Result<(), ()> {
         })
         .collect();
 
-    state::inflight_challenges_mut(|inflight_challenges| {
+    let result = state::inflight_challenges_mut(|inflight_challenges| {
         prune_expired_challenges(inflight_challenges);
 
         match inflight_challenges.remove(&res.key) {
@@ -227,19 +252,32 @@ fn check_challenge(res: ChallengeAttempt) -> Result<(), ()> {
             }
             None => Err(()),
         }
-    })
+    });
+    state::usage_metrics_mut(|metrics| match result {
+        Ok(()) => metrics.captchas_solved += 1,
+        Err(()) => metrics.captchas_failed += 1,
+    });
+    result
 }
 
+/// Registers a new anchor.
+///
+/// Returns an error message if the call should be rejected because the rate limit has been
+/// reached. Rejecting (as opposed to trapping) keeps the rejection counted in the metrics.
 pub fn register(
     device_data: DeviceData,
     challenge_result: ChallengeAttempt,
     // A temporary key than can be used in lieu of 'device_data' for a brief period of time
     // The key is optional for backwards compatibility
     temp_key: Option<Principal>,
-) -> RegisterResponse {
-    rate_limit::process_rate_limit();
+) -> Result<RegisterResponse, String> {
+    if let Err(()) = rate_limit::process_rate_limit() {
+        state::usage_metrics_mut(|metrics| metrics.registration_rate_limit_rejections += 1);
+        return Err("rate limit reached, try again later".to_string());
+    }
     if let Err(()) = check_challenge(challenge_result) {
-        return RegisterResponse::BadChallenge;
+        state::usage_metrics_mut(|metrics| metrics.registrations_bad_challenge += 1);
+        return Ok(RegisterResponse::BadChallenge);
     }
 
     let device = Device::from(device_data);
@@ -256,13 +294,15 @@ pub fn register(
 
     let allocation = state::storage_borrow_mut(|storage| storage.allocate_anchor());
     let Some((anchor_number, mut anchor)) = allocation else {
-        return RegisterResponse::CanisterFull;
+        state::usage_metrics_mut(|metrics| metrics.registrations_canister_full += 1);
+        return Ok(RegisterResponse::CanisterFull);
     };
 
     anchor
         .add_device(device.clone())
         .unwrap_or_else(|err| trap(&format!("failed to register anchor {anchor_number}: {err}")));
-    activity_bookkeeping(&mut anchor, &device.pubkey);
+    activity_stats::update_registration_cohort_stats(anchor_number);
+    activity_bookkeeping(anchor_number, &mut anchor, &device.pubkey);
 
     // write anchor to stable memory
     state::storage_borrow_mut(|storage| {
@@ -279,15 +319,17 @@ pub fn register(
         state::with_temp_keys_mut(|temp_keys| {
             temp_keys.add_temp_key(&device.pubkey, anchor_number, temp_key)
         });
+        state::usage_metrics_mut(|metrics| metrics.temp_keys_added += 1);
     }
+    state::usage_metrics_mut(|metrics| metrics.registrations_succeeded += 1);
 
     let operation = Operation::RegisterAnchor {
         device: DeviceDataWithoutAlias::from(device),
     };
     post_operation_bookkeeping(anchor_number, operation);
-    RegisterResponse::Registered {
+    Ok(RegisterResponse::Registered {
         user_number: anchor_number,
-    }
+    })
 }
 
 /// Perform a sanity check on the caller. If the caller is neither the device nor the temporary key, then we
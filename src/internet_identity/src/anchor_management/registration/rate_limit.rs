@@ -1,24 +1,23 @@
 use crate::state;
 use crate::state::RateLimitState;
 use ic_cdk::api::time;
-use ic_cdk::trap;
 use internet_identity_interface::internet_identity::types::RateLimitConfig;
 use std::cmp::min;
 
 /// Processes the registration rate limit:
 ///   1. Check if the rate limit is enabled
 ///   2. Initialize / update the token count
-///   3. Verify that the current call is not rate limited
+///   3. Verify that the current call is not rate limited, returns an error otherwise
 ///
 /// The rate limit is based on `tokens`. Each call uses one token. Tokens replenish over time,
 /// every `time_per_token_ns` a new token is added. If tokens is 0 no further calls are allowed until
 /// tokens have replenished.
 /// There is a maximum of `max_tokens` tokens, when reached the tokens not increase any further.
 /// This is the maximum number of calls that can be handled in a burst.
-pub fn process_rate_limit() {
+pub fn process_rate_limit() -> Result<(), ()> {
     let Some(config) = state::persistent_state(|ps| ps.registration_rate_limit.clone()) else {
         // rate limit disabled -> nothing to do
-        return;
+        return Ok(());
     };
 
     state::registration_rate_limit_mut(|state_opt| {
@@ -37,8 +36,9 @@ pub fn process_rate_limit() {
         // deduct a token for the current call
         if state.tokens > 0 {
             state.tokens -= 1;
+            Ok(())
         } else {
-            trap("rate limit reached, try again later");
+            Err(())
         }
     })
 }
@@ -8,7 +8,8 @@ use ic_cdk::api::management_canister::main::{
     InstallCodeArgument,
 };
 use ic_cdk::api::time;
-use ic_cdk::{call, caller, id, trap};
+use ic_cdk::{call, caller, id, print, trap};
+use ic_cdk_timers::set_timer;
 use internet_identity_interface::archive::types::*;
 use internet_identity_interface::internet_identity::types::*;
 use serde_bytes::ByteBuf;
@@ -60,6 +61,14 @@ pub struct ArchiveData {
     // The limit is configurable (entries_buffer_limit).
     // This is an Rc to avoid unnecessary copies of (potentially) a lot of data when cloning.
     pub entries_buffer: Rc<Vec<BufferedEntry>>,
+    // Highest sequence number of any entry that was handed to the archive (either fetched by or
+    // pushed to the archive).
+    pub highest_transferred_sequence_number: Option<u64>,
+    // Timestamp of the most recent fetch by (or successful push to) the archive.
+    pub last_transfer_timestamp: Option<Timestamp>,
+    // The most recent ranges of entries that were never transferred to the archive, see
+    // [dropped_archive_entries].
+    pub dropped_entries: Option<Vec<DroppedArchiveEntries>>,
 }
 
 /// Cached archive status information
@@ -74,6 +83,23 @@ pub struct ArchiveStatusCache {
     pub init: Option<ArchiveInit>,
 }
 
+/// Bookkeeping of pushing entries to the archive (only used with [ArchiveIntegration::Push]).
+/// Not persisted across upgrades.
+#[derive(Clone, Debug, Default)]
+pub struct ArchivePushState {
+    // Whether a push of buffered entries is already scheduled or in progress.
+    pub push_scheduled: bool,
+    // Number of consecutive failed attempts to push entries, used to compute the retry delay.
+    pub consecutive_failures: u32,
+}
+
+// Delay of the first retry after a failed push. Doubled on every subsequent failure.
+const PUSH_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+// Upper bound of the delay between two push attempts.
+pub const PUSH_RETRY_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+// Maximum number of dropped entries ranges to keep (older ranges are discarded first).
+const MAX_DROPPED_ENTRIES_RANGES: usize = 100;
+
 struct VerifiedWasm(Vec<u8>);
 
 pub async fn deploy_archive(wasm: ByteBuf) -> DeployArchiveResult {
@@ -191,6 +217,9 @@ async fn create_archive(config: ArchiveConfig) -> Result<Principal, String> {
                         sequence_number: 0,
                         archive_canister: canister_id,
                         entries_buffer: Rc::new(vec![]),
+                        highest_transferred_sequence_number: None,
+                        last_transfer_timestamp: None,
+                        dropped_entries: None,
                     },
                     config,
                 }
@@ -263,6 +292,8 @@ fn config_to_init(config: &ArchiveConfig) -> ArchiveInit {
         max_entries_per_call: ENTRIES_PER_CALL,
         polling_interval_ns: config.polling_interval_ns,
         error_buffer_limit: CALL_ERROR_BUFFER_SIZE,
+        archive_integration: config.archive_integration.clone(),
+        retention_period_ns: config.retention_period_ns,
     }
 }
 
@@ -344,7 +375,115 @@ pub fn archive_operation(anchor_number: AnchorNumber, caller: Principal, operati
 
     state::archive_data_mut(|data| {
         data.sequence_number += 1;
-    })
+    });
+
+    schedule_push_if_required(Duration::ZERO);
+}
+
+/// Archives an (opt-in) authentication event.
+/// Unlike [archive_operation], this does not trap if the archive entries buffer is full: users must
+/// not be prevented from authenticating because their authentication events cannot be archived.
+/// Such events are dropped instead, see [dropped_archive_entries].
+pub fn archive_authn_event(anchor_number: AnchorNumber, caller: Principal, operation: Operation) {
+    let Created { data, config } = state::archive_state() else {
+        // nothing to archive if the archive has not been deployed yet
+        return;
+    };
+    if data.entries_buffer.len() as u64 >= config.entries_buffer_limit {
+        // The dropped event still gets a sequence number, so that the gap detected by the archive
+        // can be reconciled with the dropped entries.
+        state::archive_data_mut(|data| {
+            let sequence_number = data.sequence_number;
+            record_dropped_entries(data, sequence_number, sequence_number);
+            data.sequence_number += 1;
+        });
+        return;
+    }
+    drop(data);
+    archive_operation(anchor_number, caller, operation);
+}
+
+/// Schedules a push of the buffered entries to the archive after the given delay.
+/// Does nothing if the archive integration is not [ArchiveIntegration::Push], if there is
+/// nothing to push or if a push is already scheduled.
+pub fn schedule_push_if_required(delay: Duration) {
+    let Created { data, config } = state::archive_state() else {
+        return;
+    };
+    if config.archive_integration.unwrap_or_default() != ArchiveIntegration::Push
+        || data.entries_buffer.is_empty()
+    {
+        return;
+    }
+
+    let already_scheduled = state::archive_push_state_mut(|push_state| {
+        std::mem::replace(&mut push_state.push_scheduled, true)
+    });
+    if already_scheduled {
+        return;
+    }
+    set_timer(delay, || ic_cdk::spawn(push_entries()));
+}
+
+/// Pushes a batch of buffered entries to the archive and removes them from the buffer once the
+/// archive has accepted them. Failed pushes are retried with exponential backoff.
+/// The archive skips entries it has already archived, so pushing an entry twice is harmless.
+async fn push_entries() {
+    const WRITE_ENTRIES_METHOD: &str = "write_entries";
+
+    let Created { data, config } = state::archive_state() else {
+        state::archive_push_state_mut(|push_state| push_state.push_scheduled = false);
+        return;
+    };
+
+    let archive_canister = data.archive_canister;
+    // buffered entries are ordered by sequence number
+    // i.e. this takes the lowest entries_fetch_limit many entries
+    let entries: Vec<BufferedEntry> = data
+        .entries_buffer
+        .iter()
+        .take(config.entries_fetch_limit as usize)
+        .cloned()
+        .collect();
+    // release the reference to the buffer so that it does not need to be copied when modified later
+    drop(data);
+
+    let retry_delay = match entries.last().map(|entry| entry.sequence_number) {
+        None => Duration::ZERO,
+        Some(highest_sequence_number) => {
+            let result: CallResult<()> =
+                call(archive_canister, WRITE_ENTRIES_METHOD, (entries,)).await;
+            match result {
+                Ok(()) => {
+                    record_transferred_entries(highest_sequence_number);
+                    remove_archived_entries(highest_sequence_number);
+                    state::archive_push_state_mut(|push_state| push_state.consecutive_failures = 0);
+                    Duration::ZERO
+                }
+                Err((code, message)) => {
+                    let failures = state::archive_push_state_mut(|push_state| {
+                        push_state.consecutive_failures += 1;
+                        push_state.consecutive_failures
+                    });
+                    print(format!(
+                        "failed to push archive entries (attempt {failures}): {code:?} {message}"
+                    ));
+                    push_retry_delay(failures)
+                }
+            }
+        }
+    };
+
+    state::archive_push_state_mut(|push_state| push_state.push_scheduled = false);
+    // reschedule if there are entries left (either because of a failure or because new entries
+    // were added in the meantime)
+    schedule_push_if_required(retry_delay);
+}
+
+fn push_retry_delay(consecutive_failures: u32) -> Duration {
+    PUSH_RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(consecutive_failures.saturating_sub(1)))
+        .min(PUSH_RETRY_MAX_DELAY)
 }
 
 pub fn fetch_entries() -> Vec<BufferedEntry> {
@@ -355,25 +494,95 @@ pub fn fetch_entries() -> Vec<BufferedEntry> {
 
     // buffered entries are ordered by sequence number
     // i.e. this takes the lowest entries_fetch_limit many entries
-    data.entries_buffer
+    let entries: Vec<BufferedEntry> = data
+        .entries_buffer
         .iter()
         .take(config.entries_fetch_limit as usize)
         .cloned()
-        .collect()
+        .collect();
+    drop(data);
+
+    match entries.last() {
+        Some(entry) => record_transferred_entries(entry.sequence_number),
+        // the archive is still polling, even though there is nothing to transfer
+        None => state::archive_data_mut(|data| data.last_transfer_timestamp = Some(time())),
+    }
+    entries
+}
+
+fn record_transferred_entries(sequence_number: u64) {
+    state::archive_data_mut(|data| {
+        data.last_transfer_timestamp = Some(time());
+        let highest = data
+            .highest_transferred_sequence_number
+            .get_or_insert(sequence_number);
+        *highest = (*highest).max(sequence_number);
+    });
+}
+
+/// Returns the ranges of entries that were never transferred to the archive, either because they
+/// were removed from the buffer before being transferred or because the buffer was full (see
+/// [archive_authn_event]).
+pub fn dropped_archive_entries() -> Vec<DroppedArchiveEntries> {
+    match state::archive_state() {
+        Created { data, .. } => data.dropped_entries.unwrap_or_default(),
+        NotConfigured | Configured { .. } | CreationInProgress { .. } => vec![],
+    }
 }
 
 pub fn acknowledge_entries(sequence_number: u64) {
-    state::persistent_state_mut(|ps| {
-        let Created { ref mut data, .. } = ps.archive_state else {
-            trap("no archive deployed!");
-        };
-        trap_if_caller_not_archive(data);
+    let Created { data, .. } = state::archive_state() else {
+        trap("no archive deployed!");
+    };
+    trap_if_caller_not_archive(&data);
+    remove_archived_entries(sequence_number);
+}
+
+fn remove_archived_entries(sequence_number: u64) {
+    state::archive_data_mut(|data| {
+        // Entries that are removed but have never been handed to the archive are lost.
+        // Keep track of them so that they can be reconciled with the gaps reported by the archive.
+        let dropped: Vec<u64> = data
+            .entries_buffer
+            .iter()
+            .map(|e| e.sequence_number)
+            .filter(|seq_nr| {
+                *seq_nr <= sequence_number
+                    && data
+                        .highest_transferred_sequence_number
+                        .map_or(true, |highest| *seq_nr > highest)
+            })
+            .collect();
+        if let (Some(first), Some(last)) = (dropped.first(), dropped.last()) {
+            record_dropped_entries(data, *first, *last);
+        }
 
-        // Only keep entries with higher sequence number as the highest acknowledged.
+        // Only keep entries with higher sequence number as the highest archived.
         Rc::make_mut(&mut data.entries_buffer).retain(|e| e.sequence_number > sequence_number)
     });
 }
 
+/// Records that the entries with the given (inclusive) range of sequence numbers will never be
+/// transferred to the archive. Extends the most recent range if the ranges are adjacent.
+fn record_dropped_entries(data: &mut ArchiveData, first: u64, last: u64) {
+    let dropped_entries = data.dropped_entries.get_or_insert_with(Vec::new);
+    if let Some(latest) = dropped_entries.last_mut() {
+        if latest.last_sequence_number + 1 == first {
+            latest.last_sequence_number = last;
+            latest.timestamp = time();
+            return;
+        }
+    }
+    if dropped_entries.len() >= MAX_DROPPED_ENTRIES_RANGES {
+        dropped_entries.remove(0);
+    }
+    dropped_entries.push(DroppedArchiveEntries {
+        first_sequence_number: first,
+        last_sequence_number: last,
+        timestamp: time(),
+    });
+}
+
 fn trap_if_caller_not_archive(data: &ArchiveData) {
     if caller() != data.archive_canister {
         trap(&format!(
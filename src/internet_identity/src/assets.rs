@@ -2,35 +2,127 @@
 //
 // This file describes which assets are used and how (content, content type and content encoding).
 
+use crate::assets::localization::{
+    localize, localized_shell_expr_path, localized_shell_path, shell_locale, DEFAULT_LOCALE,
+    LOCALES, SHELL_PATH,
+};
 use crate::hash::{hash_of_map, Value};
+use crate::http::routes::ExactRoute;
 use crate::http::{security_headers, IC_CERTIFICATE_EXPRESSION_HEADER};
-use crate::nested_tree::NestedTree;
-use crate::{http, state};
+use crate::nested_tree::{merge_hash_trees, NestedTree};
+use crate::{http, ii_domain, state};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 use ic_cdk::api;
 use ic_certified_map::{
     fork, fork_hash, labeled, labeled_hash, AsHashTree, Hash, HashTree, RbTree,
 };
-use include_dir::{include_dir, Dir, File};
+use include_dir::{include_dir, Dir};
 use internet_identity_interface::http_gateway::HeaderField;
+use internet_identity_interface::internet_identity::types::{
+    HealthReport, HttpRedirect, HttpRoutes,
+};
 use lazy_static::lazy_static;
 use sha2::Digest;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+
+pub mod localization;
+pub mod uploads;
 
 const LABEL_ASSETS_V1: &[u8] = b"http_assets";
 const LABEL_ASSETS_V2: &[u8] = b"http_expr";
 const STATUS_CODE_PSEUDO_HEADER: &str = ":ic-cert-status";
 pub const EXACT_MATCH_TERMINATOR: &str = "<$>";
+pub const WILDCARD_MATCH_TERMINATOR: &str = "<*>";
 pub const IC_CERTIFICATE_EXPRESSION: &str =
     "default_certification(ValidationArgs{certification:Certification{no_request_certification: Empty{},\
     response_certification:ResponseCertification{response_header_exclusions:ResponseHeaderList{headers:[]}}}})";
 
+/// Directory of the JS bundles with content hashes in their file names (see `vite.config.ts`).
+pub const HASHED_BUNDLES_DIR: &str = "/bundles/";
+
+/// Path of the WebAuthn related origins document, see [ii_domain::related_origins_document].
+pub const WEBAUTHN_RELATED_ORIGINS_PATH: &str = "/.well-known/webauthn";
+
 #[derive(Debug, Default, Clone)]
 pub struct CertifiedAssets {
-    pub assets: HashMap<String, (Vec<HeaderField>, Vec<u8>)>,
+    /// The assets by path, each with one variant per available content encoding.
+    pub assets: HashMap<String, BTreeMap<ContentEncoding, Asset>>,
     pub certification_v1: RbTree<String, Hash>,
     pub certification_v2: NestedTree<Vec<u8>, Vec<u8>>,
+    /// The metrics (in the Prometheus text format) currently certified in `certification_v2`, see
+    /// [http::init_certification_refresh].
+    pub certified_metrics: Option<Vec<u8>>,
+    /// The health report currently certified in `certification_v2`.
+    pub certified_health: Option<HealthReport>,
+    /// The redirects and aliases currently certified, see [certify_routes].
+    pub routes: HttpRoutes,
+}
+
+/// Maximum size of a response body. Larger assets are split into chunks of this size, which are
+/// either streamed (see [http::http_request_streaming_callback]) or served individually in response
+/// to range requests.
+pub const ASSET_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The asset headers included in `304 Not Modified` responses.
+const NOT_MODIFIED_HEADERS: [&str; 3] = ["Cache-Control", "ETag", "Vary"];
+
+#[derive(Debug, Clone)]
+pub struct Asset {
+    pub headers: Vec<HeaderField>,
+    pub content: Vec<u8>,
+    pub body_hash: Hash,
+}
+
+impl Asset {
+    /// Whether the asset is too large to be served in a single response.
+    pub fn is_chunked(&self) -> bool {
+        self.content.len() > ASSET_CHUNK_SIZE
+    }
+
+    pub fn num_chunks(&self) -> usize {
+        self.content.len().div_ceil(ASSET_CHUNK_SIZE).max(1)
+    }
+
+    /// Returns the byte range of the chunk with the given index.
+    pub fn chunk_range(&self, index: usize) -> Range<usize> {
+        let start = index * ASSET_CHUNK_SIZE;
+        start..self.content.len().min(start + ASSET_CHUNK_SIZE)
+    }
+
+    /// Returns the headers of the `304 Not Modified` response to a conditional request for the
+    /// asset, i.e. the headers describing the cached representation.
+    pub fn not_modified_headers(&self) -> Vec<HeaderField> {
+        self.headers
+            .iter()
+            .filter(|(header, _)| NOT_MODIFIED_HEADERS.contains(&header.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    pub fn etag(&self) -> &str {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == "ETag")
+            .map(|(_, value)| value.as_str())
+            .expect("bug: asset without ETag")
+    }
+
+    /// Returns the `Content-Range` header of the partial response (206) serving the chunk with the
+    /// given index.
+    pub fn content_range_header(&self, index: usize) -> HeaderField {
+        let range = self.chunk_range(index);
+        (
+            "Content-Range".to_string(),
+            format!(
+                "bytes {}-{}/{}",
+                range.start,
+                range.end - 1,
+                self.content.len()
+            ),
+        )
+    }
 }
 
 impl CertifiedAssets {
@@ -54,14 +146,28 @@ impl CertifiedAssets {
         )
     }
 
-    pub fn witness_v2(&self, absolute_path: &str) -> HashTree {
-        assert!(absolute_path.starts_with('/'));
+    /// Returns a witness for the given expression path, which must be one of the
+    /// [potential_expr_paths] of the request path. In addition to the expression path itself,
+    /// the witness proves the absence of all more specific potential expression paths, as
+    /// required by the verification of the response.
+    pub fn witness_v2(&self, request_path: &str, expr_path: &[String]) -> HashTree {
+        let potential_expr_paths = potential_expr_paths(request_path);
+        let Some(position) = potential_expr_paths
+            .iter()
+            .position(|path| path == expr_path)
+        else {
+            panic!("{expr_path:?} is not a valid expression path for {request_path}");
+        };
 
-        let mut path: Vec<String> = absolute_path.split('/').map(str::to_string).collect();
-        path.remove(0); // remove leading empty string due to absolute path
-        path.push(EXACT_MATCH_TERMINATOR.to_string());
-        let path_bytes: Vec<Vec<u8>> = path.iter().map(String::as_bytes).map(Vec::from).collect();
-        let witness = self.certification_v2.witness(&path_bytes);
+        let witness = potential_expr_paths[..=position]
+            .iter()
+            .map(|path| {
+                let path_bytes: Vec<Vec<u8>> =
+                    path.iter().map(String::as_bytes).map(Vec::from).collect();
+                self.certification_v2.witness(&path_bytes)
+            })
+            .reduce(merge_hash_trees)
+            .expect("at least one expression path");
 
         fork(
             HashTree::Pruned(labeled_hash(
@@ -71,12 +177,78 @@ impl CertifiedAssets {
             labeled(LABEL_ASSETS_V2, witness),
         )
     }
+
+    /// Replaces all responses certified for the given expression path.
+    pub fn certify_v2(
+        &mut self,
+        expr_path: &[String],
+        responses: &[(u16, Vec<HeaderField>, Hash)],
+    ) {
+        let path_bytes: Vec<Vec<u8>> = expr_path
+            .iter()
+            .map(String::as_bytes)
+            .map(Vec::from)
+            .collect();
+        self.certification_v2.delete(&path_bytes);
+        for (status_code, headers, body_hash) in responses {
+            add_certification_v2(self, expr_path, *status_code, headers, *body_hash);
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// Returns the expression path matching exactly the given absolute path.
+pub fn exact_expr_path(absolute_path: &str) -> Vec<String> {
+    let mut path = path_segments(absolute_path);
+    path.push(EXACT_MATCH_TERMINATOR.to_string());
+    path
+}
+
+/// Returns the expression path of the response served for requests not matching any other
+/// (more specific) expression path.
+pub fn fallback_expr_path() -> Vec<String> {
+    vec![WILDCARD_MATCH_TERMINATOR.to_string()]
+}
+
+/// Returns the expression paths that may certify a response to the given request path, ordered
+/// from the most to the least specific one. For `/foo/bar` these are `foo/bar/<$>`,
+/// `foo/bar/<*>`, `foo/<*>` and `<*>`.
+pub fn potential_expr_paths(request_path: &str) -> Vec<Vec<String>> {
+    let segments = path_segments(request_path);
+    let mut paths = vec![exact_expr_path(request_path)];
+    for len in (0..=segments.len()).rev() {
+        let mut path = segments[..len].to_vec();
+        path.push(WILDCARD_MATCH_TERMINATOR.to_string());
+        paths.push(path);
+    }
+    paths
+}
+
+pub fn path_segments(absolute_path: &str) -> Vec<String> {
+    assert!(absolute_path.starts_with('/'));
+    let mut segments: Vec<String> = absolute_path.split('/').map(str::to_string).collect();
+    segments.remove(0); // remove leading empty string due to absolute path
+    segments
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum ContentEncoding {
     Identity,
     GZip,
+    Brotli,
+}
+
+impl ContentEncoding {
+    /// The encoding of the variant certified with certification v1, which covers only a single
+    /// body per path. Gzip is preferred for backwards compatibility.
+    pub fn for_certification_v1(available: &BTreeMap<ContentEncoding, Asset>) -> Option<Self> {
+        [
+            ContentEncoding::GZip,
+            ContentEncoding::Identity,
+            ContentEncoding::Brotli,
+        ]
+        .into_iter()
+        .find(|encoding| available.contains_key(encoding))
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -129,44 +301,296 @@ lazy_static! {
 
 // used both in init and post_upgrade
 pub fn init_assets() {
+    let uploaded_assets = state::storage_borrow(|storage| storage.uploaded_assets());
+    let routes = http::routes::configured_routes();
     state::assets_mut(|certified_assets| {
         for (path, content, content_encoding, content_type) in get_static_assets() {
-            let body_hash = sha2::Sha256::digest(&content).into();
-            add_certification_v1(certified_assets, &path, body_hash);
-
-            let mut headers = match content_encoding {
-                ContentEncoding::Identity => vec![],
-                ContentEncoding::GZip => {
-                    vec![("Content-Encoding".to_string(), "gzip".to_string())]
-                }
-            };
+            certify_asset(
+                certified_assets,
+                path,
+                content,
+                content_encoding,
+                content_type,
+            );
+        }
+
+        // Uploaded assets take precedence over the built-in ones. The built-in variants of a path
+        // with uploaded variants are not served, as their content differs from the uploaded one.
+        let uploaded_paths: HashSet<String> = uploaded_assets
+            .iter()
+            .map(|asset| file_to_asset_path(&asset.file_path))
+            .collect();
+        for path in &uploaded_paths {
+            certified_assets.assets.remove(path);
+        }
+        for asset in uploaded_assets {
+            if let Some((path, content, content_encoding, content_type)) =
+                prepare_asset(&asset.file_path, asset.content.into_vec())
+            {
+                certify_asset(
+                    certified_assets,
+                    path,
+                    content,
+                    content_encoding,
+                    content_type,
+                );
+            }
+        }
+
+        // redirects and aliases take precedence over the assets served at the same path
+        certify_routes(certified_assets, routes);
+        certified_assets.certify_v2(
+            &fallback_expr_path(),
+            &[(
+                404,
+                security_headers(),
+                sha2::Sha256::digest(http::NOT_FOUND_BODY).into(),
+            )],
+        );
+    });
+}
+
+/// Adds the asset to the certified assets, replacing the variant with the same content encoding
+/// previously served at the same path (if any).
+pub fn certify_asset(
+    certified_assets: &mut CertifiedAssets,
+    path: String,
+    content: Vec<u8>,
+    content_encoding: ContentEncoding,
+    content_type: ContentType,
+) {
+    // the localized shells are certified first, as they are also served at the shell path
+    if path == SHELL_PATH && content_encoding == ContentEncoding::Identity {
+        certify_localized_shells(certified_assets, &content);
+    }
+
+    let mut headers = match content_encoding.to_header_value() {
+        None => vec![],
+        Some(encoding) => vec![("Content-Encoding".to_string(), encoding.to_string())],
+    };
+    headers.push((
+        "Content-Type".to_string(),
+        content_type.to_mime_type_string(),
+    ));
+    match shell_locale(&path) {
+        // the shell served at the shell path also depends on the Accept-Language header
+        Some(locale) => {
+            headers.push(("Content-Language".to_string(), locale.lang.to_string()));
             headers.push((
-                "Content-Type".to_string(),
-                content_type.to_mime_type_string(),
+                "Vary".to_string(),
+                "Accept-Encoding, Accept-Language".to_string(),
             ));
+        }
+        // the variant served depends on the Accept-Encoding header of the request
+        None => headers.push(("Vary".to_string(), "Accept-Encoding".to_string())),
+    }
 
-            // Add caching header for fonts only
-            if content_type == ContentType::WOFF2 {
-                headers.push((
-                    "Cache-Control".to_string(),
-                    "public, max-age=604800".to_string(), // cache for 1 week
-                ));
-            }
+    headers.push((
+        "Cache-Control".to_string(),
+        cache_control(&path, content_type).to_string(),
+    ));
+    // the related origins document is fetched by the browser from the other II origins
+    if path == WEBAUTHN_RELATED_ORIGINS_PATH {
+        headers.push(("Access-Control-Allow-Origin".to_string(), "*".to_string()));
+    }
+    // only chunked assets are served in parts, see [http::http_request]
+    if content.len() > ASSET_CHUNK_SIZE {
+        headers.push(("Accept-Ranges".to_string(), "bytes".to_string()));
+    }
 
-            add_certification_v2(
-                certified_assets,
-                &path,
-                &security_headers()
-                    .iter()
-                    .chain(headers.iter())
-                    .cloned()
-                    .collect::<Vec<_>>(),
+    let body_hash: Hash = sha2::Sha256::digest(&content).into();
+    // strong validator for conditional requests, differs between the variants of an asset
+    headers.push((
+        "ETag".to_string(),
+        format!("\"{}\"", hex::encode(body_hash)),
+    ));
+
+    certified_assets
+        .assets
+        .entry(path.clone())
+        .or_default()
+        .insert(
+            content_encoding,
+            Asset {
+                headers,
+                content,
                 body_hash,
-            );
+            },
+        );
+    certify_path_and_aliases(certified_assets, &path);
+}
+
+/// Certifies the HTML shell (i.e. the content served at [SHELL_PATH]) localized for every locale,
+/// see [localization].
+fn certify_localized_shells(certified_assets: &mut CertifiedAssets, shell: &[u8]) {
+    let shell = String::from_utf8_lossy(shell);
+    for locale in LOCALES.iter() {
+        certify_asset(
+            certified_assets,
+            localized_shell_path(locale),
+            localize(&shell, locale).into_bytes(),
+            ContentEncoding::Identity,
+            ContentType::HTML,
+        );
+    }
+}
+
+/// Returns the `Cache-Control` header value for the asset served at the given path.
+///
+/// The bundles in [HASHED_BUNDLES_DIR] have the hash of their content in the file name and never
+/// change, all other assets are served at stable paths and must be revalidated (using the `ETag`)
+/// on every use, except for the fonts.
+fn cache_control(path: &str, content_type: ContentType) -> &'static str {
+    if path.starts_with(HASHED_BUNDLES_DIR) {
+        return "public, max-age=31536000, immutable";
+    }
+    match content_type {
+        ContentType::WOFF2 => "public, max-age=604800", // cache for 1 week
+        _ => "no-cache",
+    }
+}
 
-            certified_assets.assets.insert(path, (headers, content));
+/// Removes all variants of the asset served at the given path. If there is a built-in asset for the
+/// path, the built-in asset is served again.
+pub fn remove_asset(certified_assets: &mut CertifiedAssets, path: &str) {
+    if path == SHELL_PATH {
+        for locale in LOCALES.iter() {
+            let shell_path = localized_shell_path(locale);
+            certified_assets.assets.remove(&shell_path);
+            certify_path_and_aliases(certified_assets, &shell_path);
         }
-    });
+    }
+    certified_assets.assets.remove(path);
+    certify_path_and_aliases(certified_assets, path);
+
+    let built_in_variants = get_static_assets()
+        .into_iter()
+        .filter(|(asset_path, _, _, _)| asset_path == path);
+    for (path, content, content_encoding, content_type) in built_in_variants {
+        certify_asset(
+            certified_assets,
+            path,
+            content,
+            content_encoding,
+            content_type,
+        );
+    }
+}
+
+/// Replaces the redirects and aliases served in addition to the assets, and their certification.
+/// Paths no longer routed are certified with the assets served at them again (if any).
+pub fn certify_routes(certified_assets: &mut CertifiedAssets, routes: HttpRoutes) {
+    let previous_routes = std::mem::replace(&mut certified_assets.routes, routes);
+    let routes = certified_assets.routes.clone();
+    for redirect in previous_routes.redirects.iter().chain(&routes.redirects) {
+        if http::routes::is_redirect_pattern(redirect) {
+            let responses: Vec<_> = routes
+                .redirects
+                .contains(redirect)
+                .then(|| redirect_certification(redirect))
+                .into_iter()
+                .collect();
+            certified_assets.certify_v2(&http::routes::redirect_expr_path(redirect), &responses);
+        } else {
+            certify_path(certified_assets, &redirect.path);
+        }
+    }
+    for alias in previous_routes.aliases.iter().chain(&routes.aliases) {
+        certify_path(certified_assets, &alias.path);
+    }
+}
+
+fn redirect_certification(redirect: &HttpRedirect) -> (u16, Vec<HeaderField>, Hash) {
+    (
+        redirect.status_code,
+        http::redirect_headers(&redirect.target),
+        sha2::Sha256::digest(b"").into(),
+    )
+}
+
+/// Certifies the given path as well as the aliases of the asset at the given path.
+fn certify_path_and_aliases(certified_assets: &mut CertifiedAssets, path: &str) {
+    certify_path(certified_assets, path);
+    let alias_paths: Vec<String> = certified_assets
+        .routes
+        .aliases
+        .iter()
+        .filter(|alias| alias.asset_path == path)
+        .map(|alias| alias.path.clone())
+        .collect();
+    for alias_path in alias_paths {
+        certify_path(certified_assets, &alias_path);
+    }
+}
+
+/// Replaces the certification of the given path with the certification of the response currently
+/// served at the path: the redirect or the aliased asset if the path is routed, the asset served
+/// at the path otherwise.
+///
+/// Every variant of an asset is certified with v2, but only a single one with v1. In addition to
+/// the complete response, each chunk of chunked variants is certified with v2 as a partial
+/// response (206), so that range requests for the chunks can be served certified, as well as the
+/// `304 Not Modified` response to conditional requests for the variant.
+///
+/// The variants of the localized shells are certified with v2 at the shell path too, as the shell
+/// served there is negotiated using the `Accept-Language` header, and for all paths with their
+/// `/<lang>/` prefix, see [localization].
+fn certify_path(certified_assets: &mut CertifiedAssets, path: &str) {
+    let asset_path = match http::routes::exact_route(&certified_assets.routes, path) {
+        Some(ExactRoute::Redirect(redirect)) => {
+            // redirects are only certified with v2
+            certified_assets.certification_v1.delete(path.as_bytes());
+            certified_assets
+                .certify_v2(&exact_expr_path(path), &[redirect_certification(&redirect)]);
+            return;
+        }
+        Some(ExactRoute::Alias(alias)) => alias.asset_path,
+        None => path.to_string(),
+    };
+    let variants = certified_assets
+        .assets
+        .get(&asset_path)
+        .cloned()
+        .unwrap_or_default();
+
+    match ContentEncoding::for_certification_v1(&variants) {
+        Some(encoding) => {
+            add_certification_v1(certified_assets, path, variants[&encoding].body_hash)
+        }
+        None => certified_assets.certification_v1.delete(path.as_bytes()),
+    }
+
+    let mut responses = vec![];
+    let mut assets: Vec<&Asset> = variants.values().collect();
+    if asset_path == SHELL_PATH {
+        for locale in LOCALES.iter().filter(|locale| *locale != DEFAULT_LOCALE) {
+            if let Some(shell_variants) = certified_assets.assets.get(&localized_shell_path(locale))
+            {
+                assets.extend(shell_variants.values());
+            }
+        }
+    }
+    for asset in assets {
+        let mut headers = security_headers();
+        headers.extend(asset.headers.iter().cloned());
+        if asset.is_chunked() {
+            for index in 0..asset.num_chunks() {
+                let mut chunk_headers = headers.clone();
+                chunk_headers.push(asset.content_range_header(index));
+                let chunk_hash = sha2::Sha256::digest(&asset.content[asset.chunk_range(index)]);
+                responses.push((206, chunk_headers, chunk_hash.into()));
+            }
+        }
+        let mut not_modified_headers = security_headers();
+        not_modified_headers.extend(asset.not_modified_headers());
+        responses.push((304, not_modified_headers, sha2::Sha256::digest(b"").into()));
+        responses.push((200, headers, asset.body_hash));
+    }
+    certified_assets.certify_v2(&exact_expr_path(path), &responses);
+    // the localized shells are also served at all paths with their `/<lang>/` prefix
+    if let Some(locale) = shell_locale(path).filter(|_| path != SHELL_PATH) {
+        certified_assets.certify_v2(&localized_shell_expr_path(locale), &responses);
+    }
 }
 
 fn add_certification_v1(certified_assets: &mut CertifiedAssets, path: &str, body_hash: Hash) {
@@ -177,27 +601,24 @@ fn add_certification_v1(certified_assets: &mut CertifiedAssets, path: &str, body
 
 fn add_certification_v2(
     certified_assets: &mut CertifiedAssets,
-    absolute_path: &str,
+    expr_path: &[String],
+    status_code: u16,
     headers: &[HeaderField],
     body_hash: Hash,
 ) {
-    assert!(absolute_path.starts_with('/'));
-
-    let mut segments: Vec<Vec<u8>> = absolute_path
-        .split('/')
-        .map(str::as_bytes)
+    let mut segments: Vec<Vec<u8>> = expr_path
+        .iter()
+        .map(String::as_bytes)
         .map(Vec::from)
         .collect();
-    segments.remove(0); // remove leading empty string due to absolute path
-    segments.push(EXACT_MATCH_TERMINATOR.as_bytes().to_vec());
     segments.push(Vec::from(EXPR_HASH.as_slice()));
     segments.push(vec![]);
-    segments.push(Vec::from(response_hash(headers, &body_hash)));
+    segments.push(Vec::from(response_hash(status_code, headers, &body_hash)));
 
     certified_assets.certification_v2.insert(&segments, vec![])
 }
 
-fn response_hash(headers: &[HeaderField], body_hash: &Hash) -> Hash {
+fn response_hash(status_code: u16, headers: &[HeaderField], body_hash: &Hash) -> Hash {
     let mut response_metadata = HashMap::from_iter(
         headers
             .iter()
@@ -208,7 +629,10 @@ fn response_hash(headers: &[HeaderField], body_hash: &Hash) -> Hash {
         IC_CERTIFICATE_EXPRESSION_HEADER.to_ascii_lowercase(),
         Value::String(IC_CERTIFICATE_EXPRESSION),
     );
-    response_metadata.insert(STATUS_CODE_PSEUDO_HEADER.to_string(), Value::U64(200));
+    response_metadata.insert(
+        STATUS_CODE_PSEUDO_HEADER.to_string(),
+        Value::U64(status_code as u64),
+    );
     let mut response_metadata_hash: Vec<u8> = hash_of_map(response_metadata).into();
     response_metadata_hash.extend_from_slice(body_hash);
     let response_hash: Hash = sha2::Sha256::digest(&response_metadata_hash).into();
@@ -221,14 +645,25 @@ static ASSET_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/../../dist");
 fn get_static_assets() -> Vec<(String, Vec<u8>, ContentEncoding, ContentType)> {
     let mut assets = collect_assets_recursive(&ASSET_DIR);
 
-    // Required to make II available on the identity.internetcomputer.org domain.
+    // Required to make II available on custom domains (e.g. identity.internetcomputer.org).
     // See https://internetcomputer.org/docs/current/developer-docs/production/custom-domain/#custom-domains-on-the-boundary-nodes
+    let custom_domain_origins = ii_domain::custom_domain_ii_origins();
+    let custom_domains: Vec<&str> = custom_domain_origins
+        .iter()
+        .map(|origin| ii_domain::hostname(origin))
+        .collect();
     assets.push((
         "/.well-known/ic-domains".to_string(),
-        b"identity.internetcomputer.org".to_vec(),
+        custom_domains.join("\n").into_bytes(),
         ContentEncoding::Identity,
         ContentType::OCTETSTREAM,
     ));
+    assets.push((
+        WEBAUTHN_RELATED_ORIGINS_PATH.to_string(),
+        ii_domain::related_origins_document(),
+        ContentEncoding::Identity,
+        ContentType::JSON,
+    ));
 
     assets
 }
@@ -242,36 +677,55 @@ fn collect_assets_recursive(dir: &Dir) -> Vec<(String, Vec<u8>, ContentEncoding,
 }
 
 fn collect_assets_from_dir(dir: &Dir) -> Vec<(String, Vec<u8>, ContentEncoding, ContentType)> {
-    let mut assets: Vec<(String, Vec<u8>, ContentEncoding, ContentType)> = vec![];
-    for asset in dir.files() {
-        let file_bytes = asset.contents().to_vec();
-        let (content, encoding, content_type) = match file_extension(asset) {
-            "css" => (file_bytes, ContentEncoding::Identity, ContentType::CSS),
-            "html" => (
-                fixup_html(String::from_utf8_lossy(&file_bytes).as_ref())
-                    .as_bytes()
-                    .to_vec(),
-                ContentEncoding::Identity,
-                ContentType::HTML,
-            ),
-            "ico" => (file_bytes, ContentEncoding::Identity, ContentType::ICO),
-            "json" => (file_bytes, ContentEncoding::Identity, ContentType::JSON),
-            "js.gz" => (file_bytes, ContentEncoding::GZip, ContentType::JS),
-            "png" => (file_bytes, ContentEncoding::Identity, ContentType::PNG),
-            "svg" => (file_bytes, ContentEncoding::Identity, ContentType::SVG),
-            "webp" => (file_bytes, ContentEncoding::Identity, ContentType::WEBP),
-            "woff2" => (file_bytes, ContentEncoding::Identity, ContentType::WOFF2),
-            "woff2.gz" => (file_bytes, ContentEncoding::GZip, ContentType::WOFF2),
-            ext => panic!(
-                "Unknown asset type '{}' for asset '{}'",
-                ext,
-                asset.path().display()
-            ),
-        };
+    dir.files()
+        .map(|asset| {
+            let file_path = "/".to_string() + asset.path().to_str().unwrap();
+            prepare_asset(&file_path, asset.contents().to_vec()).unwrap_or_else(|| {
+                panic!(
+                    "Unknown asset type '{}' for asset '{}'",
+                    file_extension(&file_path),
+                    asset.path().display()
+                )
+            })
+        })
+        .collect()
+}
 
-        assets.push((file_to_asset_path(asset), content, encoding, content_type));
-    }
-    assets
+/// Prepares the content of the given asset file for serving. Returns the path the asset is served
+/// at, the (fixed up) content, the content encoding and the content type, or `None` if the file
+/// type is not supported.
+pub fn prepare_asset(
+    file_path: &str,
+    file_bytes: Vec<u8>,
+) -> Option<(String, Vec<u8>, ContentEncoding, ContentType)> {
+    let (content, encoding, content_type) = match file_extension(file_path) {
+        "css" => (file_bytes, ContentEncoding::Identity, ContentType::CSS),
+        "html" => (
+            fixup_html(String::from_utf8_lossy(&file_bytes).as_ref())
+                .as_bytes()
+                .to_vec(),
+            ContentEncoding::Identity,
+            ContentType::HTML,
+        ),
+        "ico" => (file_bytes, ContentEncoding::Identity, ContentType::ICO),
+        "json" => (file_bytes, ContentEncoding::Identity, ContentType::JSON),
+        "js" => (file_bytes, ContentEncoding::Identity, ContentType::JS),
+        "js.gz" => (file_bytes, ContentEncoding::GZip, ContentType::JS),
+        "js.br" => (file_bytes, ContentEncoding::Brotli, ContentType::JS),
+        "png" => (file_bytes, ContentEncoding::Identity, ContentType::PNG),
+        "svg" => (file_bytes, ContentEncoding::Identity, ContentType::SVG),
+        "webp" => (file_bytes, ContentEncoding::Identity, ContentType::WEBP),
+        "woff2" => (file_bytes, ContentEncoding::Identity, ContentType::WOFF2),
+        "woff2.gz" => (file_bytes, ContentEncoding::GZip, ContentType::WOFF2),
+        "woff2.br" => (file_bytes, ContentEncoding::Brotli, ContentType::WOFF2),
+        _ => return None,
+    };
+    Some((
+        file_to_asset_path(file_path),
+        content,
+        encoding,
+        content_type,
+    ))
 }
 
 /// Returns the portion of the filename after the first dot.
@@ -279,26 +733,20 @@ fn collect_assets_from_dir(dir: &Dir) -> Vec<(String, Vec<u8>, ContentEncoding,
 ///
 /// The builtin `extension` method on `Path` does not work for file extensions with multiple dots
 /// such as `.js.gz`.
-fn file_extension<'a>(asset: &'a File) -> &'a str {
-    asset
-        .path()
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
+fn file_extension(file_path: &str) -> &str {
+    let file_name = file_path.rsplit('/').next().unwrap_or_default();
+    file_name
         .split_once('.')
-        .unwrap()
-        .1
+        .map(|(_, extension)| extension)
+        .unwrap_or_default()
 }
 
-/// Returns the asset path for a given file:
-/// * make relative path absolute
+/// Returns the asset path for a given (absolute) file path:
 /// * map **/index.html to **/
 /// * map **/<foo>.html to **/foo
-/// * map **/<foo>.js.gz to **/<foo>.js
-fn file_to_asset_path(asset: &File) -> String {
-    // make path absolute
-    let mut file_path = "/".to_string() + asset.path().to_str().unwrap();
+/// * map **/<foo>.js.gz and **/<foo>.js.br to **/<foo>.js
+pub fn file_to_asset_path(file_path: &str) -> String {
+    let mut file_path = file_path.to_string();
 
     if file_path.ends_with("index.html") {
         // drop index.html filename (i.e. maps **/index.html to **/)
@@ -312,8 +760,8 @@ fn file_to_asset_path(asset: &File) -> String {
             .chars()
             .take(file_path.len() - ".html".len())
             .collect()
-    } else if file_path.ends_with(".gz") {
-        // drop .gz for .foo.gz files (i.e. maps **/<foo>.js.gz to **/<foo>.js)
+    } else if file_path.ends_with(".gz") || file_path.ends_with(".br") {
+        // drop .gz and .br for .foo.gz and .foo.br files (i.e. maps **/<foo>.js.gz to **/<foo>.js)
         file_path = file_path
             .chars()
             .take(file_path.len() - ".gz".len())
@@ -0,0 +1,194 @@
+//! Language specific versions of the HTML shell (i.e. the `index.html` served at `/`), so that the
+//! page is in the right language before the JS bundle is loaded.
+//!
+//! A shell is served for every [Locale] at `/<lang>/`, as well as for all other paths with the
+//! `/<lang>/` prefix that do not serve anything more specific (see [path_prefix_locale]). The shell
+//! served at [SHELL_PATH] is negotiated using the `Accept-Language` header. As certification v1
+//! only supports a single response per path (and no wildcards), the [DEFAULT_LOCALE] is served
+//! at [SHELL_PATH] with certification v1 and the prefixed paths are only served with v2.
+use crate::assets::WILDCARD_MATCH_TERMINATOR;
+use internet_identity_interface::http_gateway::parse_quality_values;
+
+/// The path of the HTML shell the localized shells are derived from.
+pub const SHELL_PATH: &str = "/";
+
+/// The copy of the HTML shell in a given language.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Locale {
+    /// The language tag, as used in the `lang` attribute and the `Content-Language` header.
+    pub lang: &'static str,
+    pub title: &'static str,
+    /// The content of the `<noscript>` element.
+    pub noscript: &'static str,
+}
+
+/// The supported locales. The copy of the default (first) locale must match the `index.html` of
+/// the frontend, as it is replaced by the copy of the other locales.
+pub static LOCALES: [Locale; 5] = [
+    Locale {
+        lang: "en",
+        title: "Internet Identity",
+        noscript: "Internet Identity requires JavaScript. Please enable JavaScript in your browser and reload the page.",
+    },
+    Locale {
+        lang: "de",
+        title: "Internet Identity",
+        noscript: "Internet Identity benötigt JavaScript. Bitte aktivieren Sie JavaScript in Ihrem Browser und laden Sie die Seite neu.",
+    },
+    Locale {
+        lang: "es",
+        title: "Internet Identity",
+        noscript: "Internet Identity requiere JavaScript. Activa JavaScript en tu navegador y vuelve a cargar la página.",
+    },
+    Locale {
+        lang: "fr",
+        title: "Internet Identity",
+        noscript: "Internet Identity nécessite JavaScript. Veuillez activer JavaScript dans votre navigateur et recharger la page.",
+    },
+    Locale {
+        lang: "it",
+        title: "Internet Identity",
+        noscript: "Internet Identity richiede JavaScript. Attiva JavaScript nel tuo browser e ricarica la pagina.",
+    },
+];
+
+pub static DEFAULT_LOCALE: &Locale = &LOCALES[0];
+
+/// Returns the path the shell of the given locale is served at.
+pub fn localized_shell_path(locale: &Locale) -> String {
+    format!("/{}/", locale.lang)
+}
+
+/// Returns the locale whose `/<lang>/` prefix the given path has, if any.
+pub fn path_prefix_locale(path: &str) -> Option<&'static Locale> {
+    LOCALES
+        .iter()
+        .find(|locale| path.starts_with(&localized_shell_path(locale)))
+}
+
+/// Returns the expression path the shell of the given locale is certified with for all paths with
+/// the `/<lang>/` prefix, i.e. `<lang>/<*>`.
+pub fn localized_shell_expr_path(locale: &Locale) -> Vec<String> {
+    vec![
+        locale.lang.to_string(),
+        WILDCARD_MATCH_TERMINATOR.to_string(),
+    ]
+}
+
+/// Returns the locale of the shell served at the given path, `None` if the path does not serve a
+/// shell.
+pub fn shell_locale(path: &str) -> Option<&'static Locale> {
+    if path == SHELL_PATH {
+        return Some(DEFAULT_LOCALE);
+    }
+    LOCALES
+        .iter()
+        .find(|locale| localized_shell_path(locale) == path)
+}
+
+/// Replaces the copy of the [DEFAULT_LOCALE] in the given HTML shell with the copy of the given
+/// locale.
+pub fn localize(html: &str, locale: &Locale) -> String {
+    html.replace(
+        &format!(r#"<html lang="{}">"#, DEFAULT_LOCALE.lang),
+        &format!(r#"<html lang="{}">"#, locale.lang),
+    )
+    .replace(
+        &format!("<title>{}</title>", DEFAULT_LOCALE.title),
+        &format!("<title>{}</title>", locale.title),
+    )
+    .replace(
+        &format!("<noscript>{}</noscript>", DEFAULT_LOCALE.noscript),
+        &format!("<noscript>{}</noscript>", locale.noscript),
+    )
+}
+
+/// Returns the locale best matching the `Accept-Language` header value (e.g.
+/// `de-CH, de;q=0.9, en;q=0.8`), falling back to the [DEFAULT_LOCALE]. Language ranges are matched
+/// by their primary language subtag, on ties the range listed first wins.
+pub fn negotiate(accept_language: Option<&str>) -> &'static Locale {
+    let mut best: Option<(&'static Locale, f32)> = None;
+    for (tag, quality) in parse_quality_values(accept_language.unwrap_or_default()) {
+        if quality <= 0.0 {
+            continue;
+        }
+        let primary_subtag = tag.split('-').next().unwrap_or_default();
+        let locale = if primary_subtag == "*" {
+            Some(DEFAULT_LOCALE)
+        } else {
+            LOCALES
+                .iter()
+                .find(|locale| locale.lang.eq_ignore_ascii_case(primary_subtag))
+        };
+        if let Some(locale) = locale {
+            if best.map_or(true, |(_, best_quality)| quality > best_quality) {
+                best = Some((locale, quality));
+            }
+        }
+    }
+    best.map_or(DEFAULT_LOCALE, |(locale, _)| locale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiated_lang(accept_language: Option<&str>) -> &'static str {
+        negotiate(accept_language).lang
+    }
+
+    #[test]
+    fn should_negotiate_locale() {
+        assert_eq!(negotiated_lang(None), "en");
+        assert_eq!(negotiated_lang(Some("")), "en");
+        assert_eq!(negotiated_lang(Some("de")), "de");
+        assert_eq!(negotiated_lang(Some("de-CH")), "de");
+        assert_eq!(negotiated_lang(Some("FR-fr")), "fr");
+        assert_eq!(negotiated_lang(Some("ja, it;q=0.5")), "it");
+        assert_eq!(negotiated_lang(Some("de;q=0.5, fr;q=0.8")), "fr");
+        assert_eq!(negotiated_lang(Some("de, fr")), "de");
+        assert_eq!(negotiated_lang(Some("de;q=0, es")), "es");
+        assert_eq!(negotiated_lang(Some("ja, *;q=0.1")), "en");
+        assert_eq!(negotiated_lang(Some("ja")), "en");
+    }
+
+    #[test]
+    fn should_serve_shells_at_locale_paths() {
+        assert_eq!(shell_locale("/"), Some(DEFAULT_LOCALE));
+        assert_eq!(shell_locale("/de/").map(|locale| locale.lang), Some("de"));
+        assert_eq!(shell_locale("/de"), None);
+        assert_eq!(shell_locale("/ja/"), None);
+    }
+
+    #[test]
+    fn should_match_locale_path_prefixes() {
+        assert_eq!(
+            path_prefix_locale("/de/").map(|locale| locale.lang),
+            Some("de")
+        );
+        assert_eq!(
+            path_prefix_locale("/fr/some/page").map(|locale| locale.lang),
+            Some("fr")
+        );
+        assert_eq!(path_prefix_locale("/de"), None);
+        assert_eq!(path_prefix_locale("/deutsch/"), None);
+        assert_eq!(path_prefix_locale("/ja/page"), None);
+        assert_eq!(path_prefix_locale("/"), None);
+    }
+
+    #[test]
+    fn should_localize_shell() {
+        let html = format!(
+            r#"<html lang="en"><head><title>{}</title></head><body><noscript>{}</noscript></body></html>"#,
+            DEFAULT_LOCALE.title, DEFAULT_LOCALE.noscript
+        );
+        let locale = &LOCALES[1];
+        assert_eq!(
+            localize(&html, locale),
+            format!(
+                r#"<html lang="de"><head><title>{}</title></head><body><noscript>{}</noscript></body></html>"#,
+                locale.title, locale.noscript
+            )
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Assets uploaded by the controllers at runtime.
+//!
+//! Uploaded assets are served in addition to the built-in assets (see [crate::assets]) and take
+//! precedence over built-in assets served at the same path. This allows changing e.g. translations
+//! or logos without upgrading the canister.
+//!
+//! Assets are uploaded in chunks. Once complete, an asset is kept in stable memory (see
+//! [crate::storage::uploaded_assets]) so that it survives upgrades. Incomplete uploads are only
+//! kept on the heap.
+use crate::assets::localization::{shell_locale, SHELL_PATH};
+use crate::assets::{
+    certify_asset, file_to_asset_path, prepare_asset, remove_asset, ContentEncoding,
+};
+use crate::storage::uploaded_assets::MAX_FILE_PATH_LENGTH;
+use crate::{state, update_root_hash};
+use ic_cdk::trap;
+use internet_identity_interface::internet_identity::types::AssetUploadChunk;
+use serde_bytes::ByteBuf;
+
+/// Maximum size of a single uploaded asset.
+pub const MAX_UPLOADED_ASSET_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Maximum total size of all uploaded assets.
+pub const MAX_UPLOADED_ASSETS_TOTAL_SIZE: u64 = 100 * 1024 * 1024;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UploadedAsset {
+    /// Path of the asset file, see [AssetUploadChunk::file_path].
+    pub file_path: String,
+    /// Content as uploaded, i.e. without any of the changes applied when serving it.
+    pub content: ByteBuf,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PendingAssetUpload {
+    total_length: u64,
+    content: Vec<u8>,
+}
+
+/// Adds the chunk to the upload of the asset. Once the upload is complete, the asset is stored and
+/// served (replacing the asset previously served at the same path).
+pub fn upload_chunk(chunk: AssetUploadChunk) {
+    if !chunk.file_path.starts_with('/')
+        || chunk.file_path.len() > MAX_FILE_PATH_LENGTH
+        || variant_of(&chunk.file_path).is_none()
+    {
+        trap(&format!(
+            "Unsupported asset file path '{}'.",
+            chunk.file_path
+        ));
+    }
+    // the localized shells are derived from the shell, see [crate::assets::localization]
+    let path = file_to_asset_path(&chunk.file_path);
+    if path != SHELL_PATH && shell_locale(&path).is_some() {
+        trap(&format!(
+            "Asset path '{path}' is reserved for the localized HTML shell."
+        ));
+    }
+    if chunk.total_length > MAX_UPLOADED_ASSET_SIZE {
+        trap(&format!(
+            "Asset size {} exceeds the maximum of {MAX_UPLOADED_ASSET_SIZE} bytes.",
+            chunk.total_length
+        ));
+    }
+    if chunk.offset == 0 {
+        check_total_size(&chunk.file_path, chunk.total_length);
+    }
+
+    let completed_content = state::pending_asset_uploads_mut(|uploads| {
+        if chunk.offset == 0 {
+            uploads.insert(
+                chunk.file_path.clone(),
+                PendingAssetUpload {
+                    total_length: chunk.total_length,
+                    content: vec![],
+                },
+            );
+        }
+        let Some(upload) = uploads.get_mut(&chunk.file_path) else {
+            trap(&format!(
+                "No upload in progress for '{}', the first chunk must have offset 0.",
+                chunk.file_path
+            ));
+        };
+        if upload.total_length != chunk.total_length || upload.content.len() as u64 != chunk.offset
+        {
+            trap(&format!(
+                "Unexpected chunk for '{}': expected offset {} of {} bytes.",
+                chunk.file_path,
+                upload.content.len(),
+                upload.total_length
+            ));
+        }
+        if chunk.offset + chunk.content.len() as u64 > upload.total_length {
+            trap(&format!(
+                "Chunk exceeds the total length of {} bytes.",
+                upload.total_length
+            ));
+        }
+        upload.content.extend_from_slice(&chunk.content);
+
+        if upload.content.len() as u64 == upload.total_length {
+            uploads
+                .remove(&chunk.file_path)
+                .map(|upload| upload.content)
+        } else {
+            None
+        }
+    });
+
+    if let Some(content) = completed_content {
+        store_asset(UploadedAsset {
+            file_path: chunk.file_path,
+            content: ByteBuf::from(content),
+        });
+    }
+}
+
+/// Removes all uploaded variants of the asset served at the given path. If there is a built-in asset
+/// for the path, it is served again.
+pub fn delete_asset(path: &str) {
+    let removed = state::storage_borrow_mut(|storage| {
+        let file_paths: Vec<String> = storage
+            .uploaded_asset_sizes()
+            .into_iter()
+            .map(|(file_path, _)| file_path)
+            .filter(|file_path| file_to_asset_path(file_path) == path)
+            .collect();
+        for file_path in &file_paths {
+            storage.remove_uploaded_asset(file_path);
+        }
+        !file_paths.is_empty()
+    });
+    if !removed {
+        trap(&format!("No uploaded asset is served at '{path}'."));
+    }
+
+    state::assets_mut(|certified_assets| remove_asset(certified_assets, path));
+    update_root_hash();
+}
+
+/// Traps if storing an asset of the given size in the given file would exceed
+/// [MAX_UPLOADED_ASSETS_TOTAL_SIZE]. The uploaded variant replaced by the asset (if any) does not
+/// count towards the total size.
+fn check_total_size(file_path: &str, size: u64) {
+    let variant = variant_of(file_path);
+    let total_size: u64 = state::storage_borrow(|storage| {
+        let replaced_size: u64 = storage
+            .uploaded_asset_sizes()
+            .into_iter()
+            .filter(|(existing, _)| variant_of(existing) == variant)
+            .map(|(_, size)| size)
+            .sum();
+        storage.uploaded_assets_total_size() - replaced_size
+    });
+    if total_size + size > MAX_UPLOADED_ASSETS_TOTAL_SIZE {
+        trap(&format!(
+            "Uploading {size} bytes would exceed the maximum total size of uploaded assets of \
+             {MAX_UPLOADED_ASSETS_TOTAL_SIZE} bytes ({total_size} bytes are used by other assets)."
+        ));
+    }
+}
+
+fn store_asset(asset: UploadedAsset) {
+    // other uploads might have been completed since this upload was started
+    check_total_size(&asset.file_path, asset.content.len() as u64);
+    let variant = variant_of(&asset.file_path);
+    state::storage_borrow_mut(|storage| {
+        // replaces the variant previously uploaded for the same path and encoding (if any)
+        for (file_path, _) in storage.uploaded_asset_sizes() {
+            if variant_of(&file_path) == variant {
+                storage.remove_uploaded_asset(&file_path);
+            }
+        }
+        storage.store_uploaded_asset(&asset);
+    });
+
+    let (path, content, content_encoding, content_type) =
+        prepare_asset(&asset.file_path, asset.content.into_vec())
+            .expect("bug: file path has been checked before upload");
+    let uploaded_encodings = uploaded_encodings(&path);
+    state::assets_mut(|certified_assets| {
+        // The built-in variants (e.g. the pre-compressed ones) have a different content than the
+        // uploaded variants, so only the uploaded variants are served at the path.
+        if let Some(variants) = certified_assets.assets.get_mut(&path) {
+            variants.retain(|encoding, _| uploaded_encodings.contains(encoding));
+        }
+        certify_asset(
+            certified_assets,
+            path,
+            content,
+            content_encoding,
+            content_type,
+        )
+    });
+    update_root_hash();
+}
+
+/// Returns the content encodings of the uploaded variants of the asset served at the given path.
+fn uploaded_encodings(path: &str) -> Vec<ContentEncoding> {
+    state::storage_borrow(|storage| {
+        storage
+            .uploaded_asset_sizes()
+            .into_iter()
+            .filter_map(|(file_path, _)| variant_of(&file_path))
+            .filter(|(variant_path, _)| variant_path == path)
+            .map(|(_, encoding)| encoding)
+            .collect()
+    })
+}
+
+/// Returns the path and the content encoding of the asset variant stored in the given file, or
+/// `None` if the file type is not supported.
+fn variant_of(file_path: &str) -> Option<(String, ContentEncoding)> {
+    prepare_asset(file_path, vec![]).map(|(path, _, content_encoding, _)| (path, content_encoding))
+}
@@ -1,8 +1,8 @@
 use crate::assets::CertifiedAssets;
 use crate::ii_domain::IIDomain;
-use crate::state::persistent_state_mut;
+use crate::state::{persistent_state_mut, PersistentState};
 use crate::{hash, state, update_root_hash, DAY_NS, LABEL_SIG, MINUTE_NS};
-use candid::Principal;
+use candid::{CandidType, Deserialize, Principal};
 use canister_sig_util::signature_map::SignatureMap;
 use ic_cdk::api::{data_certificate, time};
 use ic_cdk::{id, trap};
@@ -25,6 +25,22 @@ const MAX_EXPIRATION_PERIOD_NS: u64 = 30 * DAY_NS;
 #[allow(clippy::identity_op)]
 const SIGNATURE_EXPIRATION_PERIOD_NS: u64 = 1 * MINUTE_NS;
 
+// Half-life of the usage count of the latest delegation origins
+const ORIGIN_USAGE_HALF_LIFE_NS: u64 = 7 * DAY_NS;
+
+/// Hostname suffixes of development origins if no [DevOriginFilter] is configured.
+pub const DEFAULT_DEV_HOSTNAME_SUFFIXES: [&str; 1] = ["localhost"];
+
+/// IP ranges of development origins if no [DevOriginFilter] is configured: private and loopback
+/// IPv4 addresses as well as the IPv6 loopback address.
+pub const DEFAULT_DEV_IP_RANGES: [&str; 5] = [
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "127.0.0.0/8",
+    "::1/128",
+];
+
 pub async fn prepare_delegation(
     anchor_number: AnchorNumber,
     frontend: FrontendHostname,
@@ -67,25 +83,135 @@ fn delegation_bookkeeping(frontend: FrontendHostname, ii_domain: &Option<IIDomai
 }
 
 /// Filter out derivation origins that most likely point to development setups.
-/// This is not bullet proof but given the data we collected so far it should be good for now.
-fn is_dev_frontend(frontend: &FrontendHostname) -> bool {
-    if frontend.starts_with("http://") || frontend.contains("localhost") {
-        // we don't care about insecure origins or localhost
+/// Insecure (`http://`) origins are always considered development origins, the rules for the
+/// other origins can be configured using [DevOriginFilter] (see [DEFAULT_DEV_HOSTNAME_SUFFIXES] and
+/// [DEFAULT_DEV_IP_RANGES] for the defaults).
+pub fn is_dev_frontend(frontend: &FrontendHostname) -> bool {
+    state::persistent_state(|persistent_state| {
+        matches_dev_origin_filter(&dev_origin_filter(persistent_state), frontend)
+    })
+}
+
+fn matches_dev_origin_filter(filter: &DevOriginFilter, frontend: &FrontendHostname) -> bool {
+    if frontend.starts_with("http://") {
+        // we don't care about insecure origins
         return true;
     }
+    let Some(hostname) = https_hostname(frontend) else {
+        return false;
+    };
+
+    if let Ok(addr) = hostname.parse::<IpAddr>() {
+        return filter
+            .ip_ranges
+            .iter()
+            .filter_map(|range| IpRange::parse(range).ok())
+            .any(|range| range.contains(&addr));
+    }
+    filter.hostname_suffixes.iter().any(|suffix| {
+        let suffix = suffix.trim_start_matches('.').to_ascii_lowercase();
+        hostname == suffix || hostname.ends_with(&format!(".{suffix}"))
+    })
+}
+
+/// Returns the (lowercase) hostname of an `https://` origin. IPv6 addresses are returned without
+/// the enclosing brackets.
+fn https_hostname(frontend: &FrontendHostname) -> Option<String> {
+    let authority = frontend.strip_prefix("https://")?.split('/').next()?;
+    let hostname = match authority.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => authority.split(':').next()?,
+    };
+    Some(hostname.to_ascii_lowercase())
+}
 
-    // lets check for local IP addresses
-    if let Some(hostname) = frontend
-        .strip_prefix("https://")
-        .and_then(|s| s.split(':').next())
+/// Returns the configured dev origin filter or the default filter if none is configured.
+pub fn dev_origin_filter(persistent_state: &PersistentState) -> DevOriginFilter {
+    persistent_state
+        .dev_origin_filter
+        .clone()
+        .unwrap_or_else(|| DevOriginFilter {
+            hostname_suffixes: DEFAULT_DEV_HOSTNAME_SUFFIXES
+                .iter()
+                .map(|suffix| suffix.to_string())
+                .collect(),
+            ip_ranges: DEFAULT_DEV_IP_RANGES
+                .iter()
+                .map(|range| range.to_string())
+                .collect(),
+        })
+}
+
+pub fn validate_dev_origin_filter(filter: &DevOriginFilter) -> Result<(), String> {
+    if let Some(suffix) = filter
+        .hostname_suffixes
+        .iter()
+        .find(|suffix| suffix.trim_start_matches('.').is_empty())
     {
-        return match hostname.parse::<IpAddr>() {
-            Ok(IpAddr::V4(addr)) => addr.is_private() || addr.is_loopback(),
-            Ok(IpAddr::V6(addr)) => addr.is_loopback(),
-            Err(_) => false,
+        return Err(format!("invalid dev origin hostname suffix: \"{suffix}\""));
+    }
+    for range in &filter.ip_ranges {
+        IpRange::parse(range)?;
+    }
+    Ok(())
+}
+
+/// IP address range given in CIDR notation, e.g. `10.0.0.0/8` or `fc00::/7`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct IpRange {
+    network: IpAddr,
+    prefix_len: u32,
+}
+
+impl IpRange {
+    fn parse(range: &str) -> Result<Self, String> {
+        let err = || format!("invalid dev origin IP range: \"{range}\"");
+        let (network, prefix_len) = range.split_once('/').ok_or_else(err)?;
+        let network: IpAddr = network.parse().map_err(|_| err())?;
+        let prefix_len: u32 = prefix_len.parse().map_err(|_| err())?;
+        let max_prefix_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
         };
+        if prefix_len > max_prefix_len {
+            return Err(err());
+        }
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+
+    fn contains(&self, addr: &IpAddr) -> bool {
+        // IPv4 addresses are compared as the most significant 32 bits of a u128
+        let (network, addr) = match (self.network, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => (
+                (u32::from(network) as u128) << 96,
+                (u32::from(*addr) as u128) << 96,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(addr)) => (u128::from(network), u128::from(*addr)),
+            _ => return false,
+        };
+        let mask = u128::MAX.checked_shl(128 - self.prefix_len).unwrap_or(0);
+        (network ^ addr) & mask == 0
+    }
+}
+
+/// Usage of a delegation origin in the list of latest used delegation origins.
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct DelegationOriginUsage {
+    pub last_usage_timestamp: Timestamp,
+    /// Number of delegations prepared for the origin, exponentially decayed (with a half-life of
+    /// [ORIGIN_USAGE_HALF_LIFE_NS]) as of `last_usage_timestamp`.
+    pub decayed_count: f64,
+}
+
+impl DelegationOriginUsage {
+    /// Returns the decayed count as of the given time.
+    pub fn decayed_count_at(&self, now_ns: Timestamp) -> f64 {
+        let elapsed_ns = now_ns.saturating_sub(self.last_usage_timestamp);
+        self.decayed_count * (-(elapsed_ns as f64) / ORIGIN_USAGE_HALF_LIFE_NS as f64).exp2()
     }
-    false
 }
 
 /// Add the current front-end to the list of latest used front-end origins.
@@ -94,31 +220,75 @@ fn update_latest_delegation_origins(frontend: FrontendHostname) {
 
     persistent_state_mut(|persistent_state| {
         let latest_delegation_origins = persistent_state
-            .latest_delegation_origins
-            .get_or_insert(HashMap::new());
-
-        if let Some(timestamp_ns) = latest_delegation_origins.get_mut(&frontend) {
-            *timestamp_ns = now_ns;
-        } else {
-            latest_delegation_origins.insert(frontend, now_ns);
-        };
+            .delegation_origin_usage
+            .get_or_insert_with(HashMap::new);
+
+        let usage = latest_delegation_origins
+            .entry(frontend)
+            .or_insert(DelegationOriginUsage {
+                last_usage_timestamp: now_ns,
+                decayed_count: 0.0,
+            });
+        usage.decayed_count = usage.decayed_count_at(now_ns) + 1.0;
+        usage.last_usage_timestamp = now_ns;
 
         // drop entries older than 30 days
-        latest_delegation_origins.retain(|_, timestamp_ns| now_ns - *timestamp_ns < 30 * DAY_NS);
+        latest_delegation_origins
+            .retain(|_, usage| now_ns - usage.last_usage_timestamp < 30 * DAY_NS);
 
-        // if we still have too many entries, drop the oldest
-        if latest_delegation_origins.len() as u64
+        // if we still have too many entries, drop the least used
+        while latest_delegation_origins.len() as u64
             > persistent_state.max_num_latest_delegation_origins.unwrap()
         {
             // if this case is hit often (i.e. we routinely have more than 1000 entries), we should
             // consider using a more efficient data structure
-            let mut values: Vec<_> = latest_delegation_origins.clone().into_iter().collect();
-            values.sort_by(|(_, timestamp_1), (_, timestamp_2)| timestamp_1.cmp(timestamp_2));
-            latest_delegation_origins.remove(&values[0].0);
-        };
+            let Some(least_used) = ranked_origins(latest_delegation_origins, now_ns).pop() else {
+                break;
+            };
+            latest_delegation_origins.remove(&least_used);
+        }
     });
 }
 
+/// Returns the latest used delegation origins, ordered by decreasing (decayed) usage count.
+pub fn latest_delegation_origins(persistent_state: &PersistentState) -> Vec<FrontendHostname> {
+    persistent_state
+        .delegation_origin_usage
+        .as_ref()
+        .map(|origins| ranked_origins(origins, time()))
+        .unwrap_or_default()
+}
+
+/// Orders the origins by decreasing decayed count, ties are resolved in favour of the most recently
+/// used origin.
+fn ranked_origins(
+    origins: &HashMap<FrontendHostname, DelegationOriginUsage>,
+    now_ns: Timestamp,
+) -> Vec<FrontendHostname> {
+    let mut ranked: Vec<_> = origins
+        .iter()
+        .map(|(origin, usage)| {
+            (
+                usage.decayed_count_at(now_ns),
+                usage.last_usage_timestamp,
+                origin,
+            )
+        })
+        .collect();
+    ranked.sort_by(
+        |(count_1, timestamp_1, origin_1), (count_2, timestamp_2, origin_2)| {
+            count_2
+                .total_cmp(count_1)
+                .then(timestamp_2.cmp(timestamp_1))
+                .then(origin_1.cmp(origin_2))
+        },
+    );
+    ranked
+        .into_iter()
+        .map(|(_, _, origin)| origin.clone())
+        .collect()
+}
+
 pub fn get_delegation(
     anchor_number: AnchorNumber,
     frontend: FrontendHostname,
@@ -298,3 +468,86 @@ fn check_frontend_length(frontend: &FrontendHostname) {
         ));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn default_filter() -> DevOriginFilter {
+        dev_origin_filter(&PersistentState::default())
+    }
+
+    #[test]
+    fn should_match_dev_origins() {
+        let filter = default_filter();
+        for origin in [
+            "http://some-dapp.com",
+            "https://localhost:8443",
+            "https://app.localhost",
+            "https://172.17.5.233:8443",
+            "https://127.0.0.1",
+            "https://[::1]:8443",
+        ] {
+            assert!(
+                matches_dev_origin_filter(&filter, &origin.to_string()),
+                "{origin}"
+            );
+        }
+    }
+
+    #[test]
+    fn should_not_match_production_origins() {
+        let filter = default_filter();
+        for origin in [
+            "https://some-dapp.com",
+            "https://notlocalhost.com",
+            "https://172.32.0.1",
+            "https://[2001:db8::1]",
+            "some-dapp",
+        ] {
+            assert!(
+                !matches_dev_origin_filter(&filter, &origin.to_string()),
+                "{origin}"
+            );
+        }
+    }
+
+    #[test]
+    fn should_validate_ip_ranges() {
+        assert!(IpRange::parse("0.0.0.0/0").is_ok());
+        assert!(IpRange::parse("fc00::/7").is_ok());
+        assert!(IpRange::parse("10.0.0.0/33").is_err());
+        assert!(IpRange::parse("10.0.0.0").is_err());
+        assert!(IpRange::parse("localhost/8").is_err());
+    }
+
+    #[test]
+    fn should_rank_origins_by_decayed_count() {
+        let origins = HashMap::from([
+            (
+                "https://old.com".to_string(),
+                DelegationOriginUsage {
+                    last_usage_timestamp: 0,
+                    decayed_count: 4.0,
+                },
+            ),
+            (
+                "https://new.com".to_string(),
+                DelegationOriginUsage {
+                    last_usage_timestamp: 2 * ORIGIN_USAGE_HALF_LIFE_NS,
+                    decayed_count: 2.0,
+                },
+            ),
+        ]);
+
+        // old.com: 4 uses decayed over two half-lives => 1
+        assert_eq!(
+            ranked_origins(&origins, 2 * ORIGIN_USAGE_HALF_LIFE_NS),
+            vec!["https://new.com".to_string(), "https://old.com".to_string()]
+        );
+        assert_eq!(
+            origins["https://old.com"].decayed_count_at(2 * ORIGIN_USAGE_HALF_LIFE_NS),
+            1.0
+        );
+    }
+}
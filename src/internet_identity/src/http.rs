@@ -1,16 +1,41 @@
-use crate::assets::{ContentType, EXACT_MATCH_TERMINATOR, IC_CERTIFICATE_EXPRESSION};
+use crate::assets::localization::{
+    localized_shell_expr_path, localized_shell_path, negotiate, path_prefix_locale, DEFAULT_LOCALE,
+    SHELL_PATH,
+};
+use crate::assets::{
+    exact_expr_path, fallback_expr_path, Asset, CertifiedAssets, ContentEncoding, ContentType,
+    ASSET_CHUNK_SIZE, IC_CERTIFICATE_EXPRESSION,
+};
+use crate::http::health::{encode_json, health_report, HEALTH_PATH};
 use crate::http::metrics::metrics;
-use crate::{assets, state, LABEL_SIG};
+use crate::http::routes::ExactRoute;
+use crate::{assets, ii_domain, state, update_root_hash, LABEL_SIG};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
-use ic_cdk::api::data_certificate;
+use ic_cdk::api::{data_certificate, id};
 use ic_cdk::trap;
-use ic_certified_map::HashTree;
-use internet_identity_interface::http_gateway::{HeaderField, HttpRequest, HttpResponse};
+use ic_cdk_timers::set_timer_interval;
+use ic_certified_map::{Hash, HashTree};
+use internet_identity_interface::http_gateway::{
+    parse_quality_values, HeaderField, HttpRequest, HttpResponse, StreamingCallbackFunction,
+    StreamingCallbackHttpResponse, StreamingStrategy, Token,
+};
+use internet_identity_interface::internet_identity::types::{
+    HealthReport, HealthStatus, HttpRedirect,
+};
+use internet_identity_interface::metrics::{
+    MetricsFormat, METRICS_JSON_PATH, METRICS_OPENMETRICS_PATH, METRICS_PATH,
+};
 use serde::Serialize;
 use serde_bytes::ByteBuf;
+use sha2::Digest;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
+pub mod csp;
+pub mod health;
 mod metrics;
+pub mod routes;
 
 pub const IC_CERTIFICATE_HEADER: &str = "IC-Certificate";
 pub const IC_CERTIFICATE_EXPRESSION_HEADER: &str = "IC-CertificateExpression";
@@ -33,89 +58,624 @@ impl ContentType {
     }
 }
 
+impl ContentEncoding {
+    /// Returns the name of the encoding as used in the `Accept-Encoding` header.
+    pub fn name(self) -> &'static str {
+        self.to_header_value().unwrap_or("identity")
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ContentEncoding::Identity,
+            ContentEncoding::GZip,
+            ContentEncoding::Brotli,
+        ]
+        .into_iter()
+        .find(|encoding| encoding.name() == name)
+    }
+
+    /// Returns the value of the `Content-Encoding` header, `None` for identity.
+    pub fn to_header_value(self) -> Option<&'static str> {
+        match self {
+            ContentEncoding::Identity => None,
+            ContentEncoding::GZip => Some("gzip"),
+            ContentEncoding::Brotli => Some("br"),
+        }
+    }
+
+    /// Selects the encoding with the highest quality value in the given `Accept-Encoding` header
+    /// among the available ones. Ties are resolved in favour of the better compression. Falls back
+    /// to identity if none of the available encodings is acceptable (or if there is no header).
+    ///
+    /// As a last resort (if no identity variant is available either), the variant certified with
+    /// certification v1 is returned.
+    ///
+    /// Panics if there is no available encoding.
+    pub fn negotiate(
+        accept_encoding: Option<&str>,
+        available: impl Iterator<Item = ContentEncoding>,
+    ) -> Self {
+        let mut available: Vec<ContentEncoding> = available.collect();
+        available.sort();
+        let codings = parse_quality_values(accept_encoding.unwrap_or_default());
+        let quality = |encoding: ContentEncoding| {
+            let name = encoding.name();
+            codings
+                .iter()
+                .find(|(coding, _)| coding == name)
+                .or_else(|| codings.iter().find(|(coding, _)| coding == "*"))
+                .map(|(_, quality)| *quality)
+                // identity is acceptable unless excluded explicitly
+                .unwrap_or(if encoding == ContentEncoding::Identity {
+                    1.0
+                } else {
+                    0.0
+                })
+        };
+
+        let mut best: Option<(ContentEncoding, f32)> = None;
+        // iterate from the best to the worst compression, so that ties keep the better compression
+        for encoding in available.iter().rev() {
+            let quality = quality(*encoding);
+            if quality > 0.0 && best.map_or(true, |(_, best_quality)| quality > best_quality) {
+                best = Some((*encoding, quality));
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+            .or_else(|| {
+                available
+                    .contains(&ContentEncoding::Identity)
+                    .then_some(ContentEncoding::Identity)
+            })
+            .or_else(|| {
+                [ContentEncoding::GZip, ContentEncoding::Brotli]
+                    .into_iter()
+                    .find(|encoding| available.contains(encoding))
+            })
+            .expect("no content encoding available")
+    }
+}
+
+/// Returns the value of the first header with the given name (case-insensitive).
+fn header_value<'a>(headers: &'a [HeaderField], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Body of the response to requests that do not match any asset. The body does not depend on the
+/// request so that a single certified response covers all such requests.
+pub const NOT_FOUND_BODY: &str = "Not found.";
+
+/// The metrics paths and the formats served on each of them.
+pub const METRICS_FORMATS: [(&str, &[MetricsFormat]); 3] = [
+    (
+        METRICS_PATH,
+        &[
+            MetricsFormat::Prometheus,
+            MetricsFormat::OpenMetrics,
+            MetricsFormat::Json,
+        ],
+    ),
+    (METRICS_JSON_PATH, &[MetricsFormat::Json]),
+    (METRICS_OPENMETRICS_PATH, &[MetricsFormat::OpenMetrics]),
+];
+
+/// Interval at which the metrics and the health report are re-certified, see
+/// [init_certification_refresh].
+pub const CERTIFICATION_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Serves all HTTP requests. All responses are certified with certification v2 (see
+/// [assets::init_assets] and [init_certification_refresh]), certification v1 only covers the
+/// assets.
+///
+/// The precedence of the responses matches the precedence of the expression paths they are
+/// certified with: responses at the exact request path (routes before assets) come first, then
+/// the redirect pattern with the longest matching prefix and finally the 404 fallback.
 pub fn http_request(req: HttpRequest) -> HttpResponse {
     let parts: Vec<&str> = req.url.split('?').collect();
-    match parts[0] {
-        // The FAQ used to live in '/faq' but we now use an external website. We redirect in order to not
-        // break existing links in the wild.
-        "/faq" => HttpResponse {
-            status_code: 301,
-            headers: vec![(
-                "location".to_string(),
-                "https://identitysupport.dfinity.org/hc/en-us".to_string(),
-            )],
+    let path = parts[0];
+    if let Some(format) = MetricsFormat::for_request(path, &req.headers) {
+        return metrics_response(path, format, req.certificate_version);
+    }
+    if path == HEALTH_PATH {
+        return health_response(req.certificate_version);
+    }
+    if is_csp_report_request(&req, path) {
+        // reports are recorded in http_request_update
+        return HttpResponse {
+            status_code: 200,
+            headers: vec![],
             body: ByteBuf::new(),
-            // Redirects are not allowed as query because certification V1 does not cover headers.
-            // Upgrading to update fixes this. This flag can be removed when switching to
-            // certification V2.
             upgrade: Some(true),
             streaming_strategy: None,
-        },
-        "/metrics" => match metrics() {
-            Ok(body) => {
-                let mut headers = vec![
-                    (
-                        "Content-Type".to_string(),
-                        "text/plain; version=0.0.4".to_string(),
-                    ),
-                    ("Content-Length".to_string(), body.len().to_string()),
-                ];
-                headers.append(&mut security_headers());
-                HttpResponse {
-                    status_code: 200,
-                    headers,
-                    body: ByteBuf::from(body),
-                    upgrade: None,
-                    streaming_strategy: None,
-                }
+        };
+    }
+    state::assets(|certified_assets| {
+        let asset_path = match routes::exact_route(&certified_assets.routes, path) {
+            Some(ExactRoute::Redirect(redirect)) => {
+                return redirect_response(&req, path, &redirect);
             }
-            Err(err) => HttpResponse {
-                status_code: 500,
-                headers: security_headers(),
-                body: ByteBuf::from(format!("Failed to encode metrics: {err}")),
+            Some(ExactRoute::Alias(alias)) => alias.asset_path,
+            None => path.to_string(),
+        };
+        let asset_path =
+            negotiated_shell_path(certified_assets, &req, &asset_path).unwrap_or(asset_path);
+        if let Some(variants) = certified_assets
+            .assets
+            .get(&asset_path)
+            .filter(|variants| !variants.is_empty())
+        {
+            return asset_response(&req, path, &asset_path, variants, &exact_expr_path(path));
+        }
+
+        let redirect = routes::matching_redirect_pattern(&certified_assets.routes, path);
+        let shell = prefixed_shell(certified_assets, &req, path);
+        match (redirect, shell) {
+            // the more specific expression path takes precedence
+            (Some(redirect), Some((_, _, shell_expr_path)))
+                if routes::redirect_expr_path(redirect).len() > shell_expr_path.len() =>
+            {
+                redirect_response(&req, path, redirect)
+            }
+            (_, Some((shell_path, variants, shell_expr_path))) => {
+                asset_response(&req, path, &shell_path, variants, &shell_expr_path)
+            }
+            (Some(redirect), None) => redirect_response(&req, path, redirect),
+            (None, None) => not_found_response(&req, path),
+        }
+    })
+}
+
+/// Returns the path, the variants and the expression path of the localized shell served for a
+/// request for `path` without a more specific response, if the path has a `/<lang>/` prefix. Only
+/// certification v2 certifies the localized shells for such paths, see [assets::certify_path].
+fn prefixed_shell<'a>(
+    certified_assets: &'a CertifiedAssets,
+    req: &HttpRequest,
+    path: &str,
+) -> Option<(String, &'a BTreeMap<ContentEncoding, Asset>, Vec<String>)> {
+    if req.certificate_version != Some(2) {
+        return None;
+    }
+    let locale = path_prefix_locale(path)?;
+    let shell_path = localized_shell_path(locale);
+    let variants = certified_assets
+        .assets
+        .get(&shell_path)
+        .filter(|variants| !variants.is_empty())?;
+    Some((shell_path, variants, localized_shell_expr_path(locale)))
+}
+
+/// Returns the path of the localized shell to serve instead of the shell at `asset_path`, if the
+/// language negotiated using the `Accept-Language` header is not the default one. Only
+/// certification v2 certifies the localized shells at the shell path, see [assets::certify_path].
+fn negotiated_shell_path(
+    certified_assets: &CertifiedAssets,
+    req: &HttpRequest,
+    asset_path: &str,
+) -> Option<String> {
+    if asset_path != SHELL_PATH || req.certificate_version != Some(2) {
+        return None;
+    }
+    let locale = negotiate(header_value(&req.headers, "Accept-Language"));
+    if locale == DEFAULT_LOCALE {
+        return None;
+    }
+    let shell_path = localized_shell_path(locale);
+    certified_assets
+        .assets
+        .contains_key(&shell_path)
+        .then_some(shell_path)
+}
+
+/// Serves the asset at `asset_path` (in the content encoding negotiated) for a request for `path`,
+/// which differs from `asset_path` for aliases and the localized shells served at prefixed paths.
+/// With certification v2, the response is certified with the given expression path.
+fn asset_response(
+    req: &HttpRequest,
+    path: &str,
+    asset_path: &str,
+    variants: &BTreeMap<ContentEncoding, Asset>,
+    expr_path: &[String],
+) -> HttpResponse {
+    let (encoding, mut certificate_headers) = match req.certificate_version {
+        None | Some(1) => (
+            ContentEncoding::for_certification_v1(variants),
+            certificate_headers_v1(path),
+        ),
+        Some(2) => (
+            Some(ContentEncoding::negotiate(
+                header_value(&req.headers, "Accept-Encoding"),
+                variants.keys().copied(),
+            )),
+            certificate_headers_v2(path, expr_path),
+        ),
+        _ => trap("Unsupported certificate version."),
+    };
+    let encoding = encoding.expect("bug: no variant to serve");
+    let asset = &variants[&encoding];
+    let mut headers = security_headers();
+    headers.append(&mut certificate_headers);
+
+    // 304 responses are only certified with certification v2, see [assets::certify_path]
+    if req.certificate_version == Some(2)
+        && is_not_modified(header_value(&req.headers, "If-None-Match"), asset.etag())
+    {
+        headers.append(&mut asset.not_modified_headers());
+        return HttpResponse {
+            status_code: 304,
+            headers,
+            body: ByteBuf::new(),
+            upgrade: None,
+            streaming_strategy: None,
+        };
+    }
+    headers.append(&mut asset.headers.clone());
+
+    // only chunks are certified as partial responses, and only with certification v2
+    if req.certificate_version == Some(2) && asset.is_chunked() {
+        if let Some(index) = requested_chunk(asset, &req.headers) {
+            headers.push(asset.content_range_header(index));
+            return HttpResponse {
+                status_code: 206,
+                headers,
+                body: ByteBuf::from(&asset.content[asset.chunk_range(index)]),
                 upgrade: None,
                 streaming_strategy: None,
-            },
-        },
-        probably_an_asset => {
-            state::assets(
-                |certified_assets| match certified_assets.assets.get(probably_an_asset) {
-                    Some((asset_headers, data)) => {
-                        let mut headers = security_headers();
-                        let mut certificate_headers = match req.certificate_version {
-                            None | Some(1) => asset_certificate_headers_v1(probably_an_asset),
-                            Some(2) => asset_certificate_headers_v2(probably_an_asset),
-                            _ => trap("Unsupported certificate version."),
-                        };
-                        headers.append(&mut certificate_headers);
-                        headers.append(&mut asset_headers.clone());
-
-                        HttpResponse {
-                            status_code: 200,
-                            headers,
-                            body: ByteBuf::from(data.clone()),
-                            upgrade: None,
-                            streaming_strategy: None,
-                        }
-                    }
-                    None => HttpResponse {
-                        status_code: 404,
-                        headers: security_headers(),
-                        body: ByteBuf::from(format!("Asset {probably_an_asset} not found.")),
-                        upgrade: None,
-                        streaming_strategy: None,
-                    },
-                },
-            )
+            };
         }
     }
+
+    HttpResponse {
+        status_code: 200,
+        headers,
+        body: ByteBuf::from(&asset.content[asset.chunk_range(0)]),
+        upgrade: None,
+        streaming_strategy: streaming_strategy(asset_path, encoding, asset, 1),
+    }
+}
+
+fn redirect_response(req: &HttpRequest, path: &str, redirect: &HttpRedirect) -> HttpResponse {
+    let mut headers = redirect_headers(&redirect.target);
+    headers.append(&mut certificate_headers(
+        req.certificate_version,
+        path,
+        &routes::redirect_expr_path(redirect),
+    ));
+    HttpResponse {
+        status_code: redirect.status_code,
+        headers,
+        body: ByteBuf::new(),
+        upgrade: None,
+        streaming_strategy: None,
+    }
+}
+
+fn not_found_response(req: &HttpRequest, path: &str) -> HttpResponse {
+    let mut headers = security_headers();
+    headers.append(&mut certificate_headers(
+        req.certificate_version,
+        path,
+        &fallback_expr_path(),
+    ));
+    HttpResponse {
+        status_code: 404,
+        headers,
+        body: ByteBuf::from(NOT_FOUND_BODY),
+        upgrade: None,
+        streaming_strategy: None,
+    }
+}
+
+/// Whether the `If-None-Match` header value of a request matches the given entity tag, i.e. whether
+/// the client already has the current representation of the asset. As per RFC 9110, the weak
+/// comparison is used for `If-None-Match`.
+fn is_not_modified(if_none_match: Option<&str>, etag: &str) -> bool {
+    let Some(if_none_match) = if_none_match else {
+        return false;
+    };
+    if_none_match.split(',').map(str::trim).any(|entity_tag| {
+        entity_tag == "*" || entity_tag.strip_prefix("W/").unwrap_or(entity_tag) == etag
+    })
+}
+
+/// Returns the next chunk of a streamed asset, see [streaming_strategy].
+pub fn http_request_streaming_callback(token: Token) -> StreamingCallbackHttpResponse {
+    let Some(encoding) = ContentEncoding::from_name(&token.content_encoding) else {
+        trap(&format!(
+            "Unknown content encoding '{}'.",
+            token.content_encoding
+        ));
+    };
+    state::assets(|certified_assets| {
+        let Some(asset) = certified_assets
+            .assets
+            .get(&token.path)
+            .and_then(|variants| variants.get(&encoding))
+            .filter(|asset| asset.body_hash.as_slice() == token.sha256.as_slice())
+        else {
+            trap(&format!(
+                "Asset '{}' has changed or has been removed while streaming.",
+                token.path
+            ));
+        };
+        let index = token.index as usize;
+        if index >= asset.num_chunks() {
+            trap(&format!(
+                "Chunk {} of asset '{}' does not exist.",
+                index, token.path
+            ));
+        }
+        StreamingCallbackHttpResponse {
+            body: ByteBuf::from(&asset.content[asset.chunk_range(index)]),
+            token: next_token(&token.path, encoding, asset, index + 1),
+        }
+    })
+}
+
+/// Returns the strategy to stream the remaining chunks of the asset starting with the chunk with the
+/// given index, `None` if there are no chunks left. The complete body is certified, so streaming is
+/// transparent to the certification.
+fn streaming_strategy(
+    path: &str,
+    encoding: ContentEncoding,
+    asset: &Asset,
+    index: usize,
+) -> Option<StreamingStrategy> {
+    next_token(path, encoding, asset, index).map(|token| StreamingStrategy::Callback {
+        callback: StreamingCallbackFunction::new(
+            id(),
+            "http_request_streaming_callback".to_string(),
+        ),
+        token,
+    })
+}
+
+fn next_token(path: &str, encoding: ContentEncoding, asset: &Asset, index: usize) -> Option<Token> {
+    (index < asset.num_chunks()).then(|| Token {
+        path: path.to_string(),
+        content_encoding: encoding.name().to_string(),
+        index: index as u64,
+        sha256: ByteBuf::from(asset.body_hash.to_vec()),
+    })
+}
+
+/// Returns the index of the chunk requested with the `Range` header, if any. Only single ranges
+/// starting at a chunk boundary and not ending before the end of that chunk are supported (these
+/// are the ranges that can be served certified). Other ranges are ignored and the complete asset is
+/// served instead, as allowed by RFC 9110.
+fn requested_chunk(asset: &Asset, headers: &[HeaderField]) -> Option<usize> {
+    let (start, end) = header_value(headers, "Range")?
+        .trim()
+        .strip_prefix("bytes=")?
+        .split_once('-')?;
+    let start: usize = start.trim().parse().ok()?;
+    let end: Option<usize> = match end.trim() {
+        "" => None,
+        end => Some(end.parse().ok()?),
+    };
+    if start % ASSET_CHUNK_SIZE != 0 || start >= asset.content.len() {
+        return None;
+    }
+    let index = start / ASSET_CHUNK_SIZE;
+    match end {
+        Some(end) if end < asset.chunk_range(index).end - 1 => None,
+        _ => Some(index),
+    }
+}
+
+fn is_csp_report_request(req: &HttpRequest, path: &str) -> bool {
+    path == csp::CSP_REPORT_PATH
+        && req.method.eq_ignore_ascii_case("POST")
+        && csp::report_violations()
+}
+
+/// Serves HTTP requests upgraded to an update call, i.e. records CSP violation reports (see [csp]).
+pub fn http_request_update(req: HttpRequest) -> HttpResponse {
+    let path = req.url.split('?').next().unwrap_or_default();
+    if is_csp_report_request(&req, path) {
+        return csp::record_reported_violations(&req);
+    }
+    http_request(req)
+}
+
+pub fn redirect_headers(location: &str) -> Vec<HeaderField> {
+    let mut headers = security_headers();
+    headers.push(("Location".to_string(), location.to_string()));
+    headers
+}
+
+/// Certifies the current metrics and health report and re-certifies them every
+/// [CERTIFICATION_REFRESH_INTERVAL]. Timers do not survive upgrades, so this is required both in
+/// init and post_upgrade.
+pub fn init_certification_refresh() {
+    certify_metrics();
+    certify_health();
+    set_timer_interval(CERTIFICATION_REFRESH_INTERVAL, || {
+        certify_metrics();
+        certify_health();
+        update_root_hash();
+    });
+}
+
+/// Certifies the current metrics in all formats, replacing the previously certified metrics.
+///
+/// The metrics change with almost every call, so they are re-certified periodically rather than on
+/// every change (see [init_certification_refresh]). Queries serve the metrics as of the last
+/// certification, with the samples timestamped accordingly.
+fn certify_metrics() {
+    // errors are reported when serving the metrics
+    let Ok(prometheus_text) = metrics() else {
+        return;
+    };
+    let Ok(responses_by_path) = METRICS_FORMATS
+        .iter()
+        .map(|(path, formats)| {
+            let responses = formats
+                .iter()
+                .map(|format| {
+                    let body = format.encode(prometheus_text.clone())?;
+                    let mut headers = metrics_headers(*format, &body);
+                    headers.append(&mut security_headers());
+                    let body_hash: Hash = sha2::Sha256::digest(&body).into();
+                    Ok((200, headers, body_hash))
+                })
+                .collect::<std::io::Result<Vec<_>>>()?;
+            Ok((*path, responses))
+        })
+        .collect::<std::io::Result<Vec<_>>>()
+    else {
+        return;
+    };
+
+    state::assets_mut(|certified_assets| {
+        for (path, responses) in responses_by_path {
+            certified_assets.certify_v2(&exact_expr_path(path), &responses);
+        }
+        certified_assets.certified_metrics = Some(prometheus_text);
+    });
+}
+
+/// Certifies the current health report, replacing the previously certified report. Like the
+/// metrics (see [certify_metrics]), the report is re-certified periodically: it contains time
+/// dependent checks, so it changes even without any change of the state.
+fn certify_health() {
+    let report = health_report();
+    let (status_code, headers) = health_response_parts(&report);
+    let body_hash: Hash = sha2::Sha256::digest(encode_json(&report)).into();
+    state::assets_mut(|certified_assets| {
+        certified_assets.certify_v2(
+            &exact_expr_path(HEALTH_PATH),
+            &[(status_code, headers, body_hash)],
+        );
+        certified_assets.certified_health = Some(report);
+    });
+}
+
+/// Serves the certified health report to queries with certification v2 (see [certify_health]) and
+/// the current report otherwise.
+fn health_response(certificate_version: Option<u16>) -> HttpResponse {
+    let certified = match certificate_version {
+        // certification v1 does not cover the health report
+        None | Some(1) => false,
+        // update calls do not need certificates
+        Some(2) => data_certificate().is_some(),
+        _ => trap("Unsupported certificate version."),
+    };
+    let certified_report = if certified {
+        state::assets(|assets| assets.certified_health.clone())
+    } else {
+        None
+    };
+    let (report, certificate_headers) = match certified_report {
+        Some(report) => (
+            report,
+            certificate_headers_v2(HEALTH_PATH, &exact_expr_path(HEALTH_PATH)),
+        ),
+        None => (health_report(), vec![]),
+    };
+    let (status_code, mut headers) = health_response_parts(&report);
+    headers.extend(certificate_headers);
+    HttpResponse {
+        status_code,
+        headers,
+        body: ByteBuf::from(encode_json(&report)),
+        upgrade: None,
+        streaming_strategy: None,
+    }
+}
+
+/// Returns the status code and headers of the response serving the given report.
+/// Failing health checks result in a `503 Service Unavailable` response.
+fn health_response_parts(report: &HealthReport) -> (u16, Vec<HeaderField>) {
+    let status_code = match report.status {
+        HealthStatus::Pass | HealthStatus::Warn => 200,
+        HealthStatus::Fail => 503,
+    };
+    let body = encode_json(report);
+    let mut headers = vec![
+        ("Content-Type".to_string(), "application/json".to_string()),
+        ("Content-Length".to_string(), body.len().to_string()),
+        ("Cache-Control".to_string(), "no-store".to_string()),
+    ];
+    headers.append(&mut security_headers());
+    (status_code, headers)
+}
+
+/// Serves the certified metrics to queries with certification v2 (see [certify_metrics]) and the
+/// current metrics otherwise.
+fn metrics_response(
+    path: &str,
+    format: MetricsFormat,
+    certificate_version: Option<u16>,
+) -> HttpResponse {
+    match certificate_version {
+        // certification v1 does not cover the metrics
+        None | Some(1) => return encode_metrics_response(format, metrics(), vec![]),
+        Some(2) => (),
+        _ => trap("Unsupported certificate version."),
+    };
+    // update calls do not need certificates
+    if data_certificate().is_none() {
+        return encode_metrics_response(format, metrics(), vec![]);
+    }
+    match state::assets(|assets| assets.certified_metrics.clone()) {
+        Some(prometheus_text) => encode_metrics_response(
+            format,
+            Ok(prometheus_text),
+            certificate_headers_v2(path, &exact_expr_path(path)),
+        ),
+        // the metrics could not be encoded when they were last certified
+        None => encode_metrics_response(format, metrics(), vec![]),
+    }
+}
+
+fn encode_metrics_response(
+    format: MetricsFormat,
+    prometheus_text: std::io::Result<Vec<u8>>,
+    mut certificate_headers: Vec<HeaderField>,
+) -> HttpResponse {
+    match prometheus_text.and_then(|metrics| format.encode(metrics)) {
+        Ok(body) => {
+            let mut headers = metrics_headers(format, &body);
+            headers.append(&mut security_headers());
+            headers.append(&mut certificate_headers);
+            HttpResponse {
+                status_code: 200,
+                headers,
+                body: ByteBuf::from(body),
+                upgrade: None,
+                streaming_strategy: None,
+            }
+        }
+        Err(err) => HttpResponse {
+            status_code: 500,
+            headers: security_headers(),
+            body: ByteBuf::from(format!("Failed to encode metrics: {err}")),
+            upgrade: None,
+            streaming_strategy: None,
+        },
+    }
+}
+
+fn metrics_headers(format: MetricsFormat, body: &[u8]) -> Vec<HeaderField> {
+    vec![
+        (
+            "Content-Type".to_string(),
+            format.content_type().to_string(),
+        ),
+        ("Content-Length".to_string(), body.len().to_string()),
+        ("Vary".to_string(), "Accept".to_string()),
+    ]
 }
 
 /// List of recommended security headers as per https://owasp.org/www-project-secure-headers/
 /// These headers enable browser security features (like limit access to platform apis and set
 /// iFrame policies, etc.).
 pub fn security_headers() -> Vec<HeaderField> {
-    vec![
+    let mut headers = vec![
         ("X-Frame-Options".to_string(), "DENY".to_string()),
         ("X-Content-Type-Options".to_string(), "nosniff".to_string()),
         (
@@ -174,16 +734,37 @@ pub fn security_headers() -> Vec<HeaderField> {
              xr-spatial-tracking=()"
                 .to_string(),
         ),
-    ]
+    ];
+    if csp::report_violations() {
+        headers.push((
+            "Reporting-Endpoints".to_string(),
+            format!(
+                "{}=\"{}\"",
+                csp::CSP_REPORT_ENDPOINT_NAME,
+                csp::CSP_REPORT_PATH
+            ),
+        ));
+    }
+    headers
 }
 
 /// Full content security policy delivered via HTTP response header.
 ///
-/// This policy also includes the `frame-ancestors` directive in addition to the policies included in the HTML `meta` tag.
-/// We deliver the CSP by header _and_ meta tag because the headers are not yet certified.
+/// This policy also includes the `frame-ancestors` directive (and the reporting directives, if
+/// enabled) in addition to the policies included in the HTML `meta` tag, which does not support
+/// them. The header is certified with certification v2 only, so the CSP is also delivered by meta
+/// tag, which covers clients that still verify the responses with certification v1.
 fn content_security_policy_header() -> String {
     let meta_policy = content_security_policy_meta();
-    format!("{meta_policy}frame-ancestors 'none';")
+    let mut csp = format!("{meta_policy}frame-ancestors 'none';");
+    if csp::report_violations() {
+        csp.push_str(&format!(
+            "report-uri {};report-to {};",
+            csp::CSP_REPORT_PATH,
+            csp::CSP_REPORT_ENDPOINT_NAME
+        ));
+    }
+    csp
 }
 
 /// Stripped down content security policy for the HTML `meta` tag, where not all directives are supported.
@@ -202,10 +783,13 @@ fn content_security_policy_header() -> String {
 ///
 /// connect-src is used to ensure fetch requests can only be made against known domains:
 ///     * 'self': used fetch the JS bundles
-///     * https://icp-api.io: the official IC HTTP API domain for canister calls to the canister
-///     * https://*.icp0.io: HTTP fetches for checking /.well-known/ii-alternative-origins on
-///     other canisters (authenticating canisters setting a derivationOrigin)
-///     * https://*.ic0.app: same as above, but legacy
+///     * the II origins on custom domains (see [ii_domain::custom_domain_ii_origins]), e.g.
+///     https://identity.internetcomputer.org
+///     * the configured connect sources (see [csp::connect_sources]), by default:
+///         * https://icp-api.io: the official IC HTTP API domain for canister calls to the canister
+///         * https://*.icp0.io: HTTP fetches for checking /.well-known/ii-alternative-origins on
+///         other canisters (authenticating canisters setting a derivationOrigin)
+///         * https://*.ic0.app: same as above, but legacy
 ///
 /// style-src 'unsafe-inline' is currently required due to the way styles are handled by the
 /// application. Adding hashes would require a big restructuring of the application and build
@@ -216,13 +800,18 @@ fn content_security_policy_header() -> String {
 /// because Firefox does not support SRI with CSP: https://bugzilla.mozilla.org/show_bug.cgi?id=1409200
 /// Instead, we add the hash of the inline script to the CSP policy.
 ///
-/// upgrade-insecure-requests is omitted when building in dev mode to allow loading II on localhost
-/// with Safari.
+/// upgrade-insecure-requests is configurable (see [csp::upgrade_insecure_requests]) and omitted by
+/// default when building in dev mode to allow loading II on localhost with Safari.
 pub fn content_security_policy_meta() -> String {
     let hash = assets::JS_SETUP_SCRIPT_SRI_HASH.to_string();
+    let connect_sources: String = ii_domain::custom_domain_ii_origins()
+        .into_iter()
+        .chain(csp::connect_sources())
+        .map(|source| format!(" {source}"))
+        .collect();
     let csp = format!(
         "default-src 'none';\
-         connect-src 'self' https://identity.internetcomputer.org https://icp-api.io https://*.icp0.io https://*.ic0.app;\
+         connect-src 'self'{connect_sources};\
          img-src 'self' data:;\
          script-src '{hash}' 'unsafe-inline' 'unsafe-eval' 'strict-dynamic' https:;\
          base-uri 'none';\
@@ -231,15 +820,31 @@ pub fn content_security_policy_meta() -> String {
          style-src-elem 'self' 'unsafe-inline';\
          font-src 'self';"
     );
-    #[cfg(not(feature = "insecure_requests"))]
-    let csp = format!("{csp}upgrade-insecure-requests;");
+    if csp::upgrade_insecure_requests() {
+        return format!("{csp}upgrade-insecure-requests;");
+    }
     csp
 }
 
-fn asset_certificate_headers_v1(asset_name: &str) -> Vec<(String, String)> {
-    let certificate = data_certificate().unwrap_or_else(|| {
-        trap("data certificate is only available in query calls");
-    });
+/// Returns the certificate headers for responses that are only certified with certification v2,
+/// i.e. all responses other than assets.
+fn certificate_headers(
+    certificate_version: Option<u16>,
+    request_path: &str,
+    expr_path: &[String],
+) -> Vec<HeaderField> {
+    match certificate_version {
+        None | Some(1) => vec![],
+        Some(2) => certificate_headers_v2(request_path, expr_path),
+        _ => trap("Unsupported certificate version."),
+    }
+}
+
+fn certificate_headers_v1(asset_name: &str) -> Vec<HeaderField> {
+    // responses to update calls are certified by consensus
+    let Some(certificate) = data_certificate() else {
+        return vec![];
+    };
     state::assets_and_signatures(|assets, sigs| {
         let tree = ic_certified_map::fork(
             assets.witness_v1(asset_name),
@@ -260,21 +865,18 @@ fn asset_certificate_headers_v1(asset_name: &str) -> Vec<(String, String)> {
     })
 }
 
-fn asset_certificate_headers_v2(absolute_path: &str) -> Vec<(String, String)> {
-    assert!(absolute_path.starts_with('/'));
-
-    let certificate = data_certificate().unwrap_or_else(|| {
-        trap("data certificate is only available in query calls");
-    });
+fn certificate_headers_v2(request_path: &str, expr_path: &[String]) -> Vec<HeaderField> {
+    // responses to update calls are certified by consensus
+    let Some(certificate) = data_certificate() else {
+        return vec![];
+    };
 
-    let mut path: Vec<String> = absolute_path.split('/').map(str::to_string).collect();
-    // replace the first empty split segment (due to absolute path) with "http_expr"
-    *path.get_mut(0).unwrap() = LABEL_HTTP_EXPR.to_string();
-    path.push(EXACT_MATCH_TERMINATOR.to_string());
+    let mut path: Vec<String> = vec![LABEL_HTTP_EXPR.to_string()];
+    path.extend_from_slice(expr_path);
 
     state::assets_and_signatures(|assets, sigs| {
         let tree = ic_certified_map::fork(
-            assets.witness_v2(absolute_path),
+            assets.witness_v2(request_path, expr_path),
             HashTree::Pruned(ic_certified_map::labeled_hash(LABEL_SIG, &sigs.root_hash())),
         );
 
@@ -305,3 +907,74 @@ fn asset_certificate_headers_v2(absolute_path: &str) -> Vec<(String, String)> {
         ]
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ContentEncoding::{Brotli, GZip, Identity};
+
+    const ALL: [ContentEncoding; 3] = [Identity, GZip, Brotli];
+
+    fn negotiate(accept_encoding: Option<&str>, available: &[ContentEncoding]) -> ContentEncoding {
+        ContentEncoding::negotiate(accept_encoding, available.iter().copied())
+    }
+
+    #[test]
+    fn should_serve_identity_without_accept_encoding() {
+        assert_eq!(negotiate(None, &ALL), Identity);
+        assert_eq!(negotiate(Some(""), &ALL), Identity);
+    }
+
+    #[test]
+    fn should_serve_accepted_encoding() {
+        assert_eq!(negotiate(Some("gzip"), &ALL), GZip);
+        assert_eq!(negotiate(Some("br"), &ALL), Brotli);
+        assert_eq!(negotiate(Some("identity"), &ALL), Identity);
+        assert_eq!(negotiate(Some("GZIP"), &ALL), GZip);
+        assert_eq!(negotiate(Some("deflate"), &ALL), Identity);
+    }
+
+    #[test]
+    fn should_prefer_better_compression_on_ties() {
+        assert_eq!(negotiate(Some("gzip, deflate, br"), &ALL), Brotli);
+        assert_eq!(negotiate(Some("*"), &ALL), Brotli);
+        assert_eq!(negotiate(Some("gzip, br"), &[Identity, GZip]), GZip);
+    }
+
+    #[test]
+    fn should_respect_quality_values() {
+        assert_eq!(negotiate(Some("br;q=0.5, gzip;q=0.8"), &ALL), GZip);
+        assert_eq!(negotiate(Some("br;q=0, *"), &ALL), GZip);
+        assert_eq!(
+            negotiate(Some("gzip;q=0.5, identity;q=0.9"), &ALL),
+            Identity
+        );
+        assert_eq!(negotiate(Some("identity;q=0, gzip"), &ALL), GZip);
+    }
+
+    #[test]
+    fn should_fall_back_to_identity() {
+        assert_eq!(negotiate(Some("br"), &[Identity, GZip]), Identity);
+        assert_eq!(negotiate(Some("gzip;q=0, br;q=0"), &ALL), Identity);
+        assert_eq!(negotiate(Some("identity;q=0"), &ALL), Identity);
+    }
+
+    #[test]
+    fn should_serve_available_encoding_if_identity_is_missing() {
+        assert_eq!(negotiate(None, &[GZip]), GZip);
+        assert_eq!(negotiate(Some("identity"), &[GZip, Brotli]), GZip);
+        assert_eq!(negotiate(Some("br"), &[GZip, Brotli]), Brotli);
+    }
+
+    #[test]
+    fn should_match_if_none_match() {
+        const ETAG: &str = r#""abc""#;
+        assert!(is_not_modified(Some(r#""abc""#), ETAG));
+        assert!(is_not_modified(Some(r#"W/"abc""#), ETAG));
+        assert!(is_not_modified(Some(r#""xyz", "abc""#), ETAG));
+        assert!(is_not_modified(Some("*"), ETAG));
+        assert!(!is_not_modified(None, ETAG));
+        assert!(!is_not_modified(Some(r#""xyz""#), ETAG));
+        assert!(!is_not_modified(Some("abc"), ETAG));
+    }
+}
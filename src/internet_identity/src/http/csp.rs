@@ -0,0 +1,390 @@
+//! Configuration of the Content-Security-Policy and collection of the CSP violations reported by
+//! browsers.
+//!
+//! If enabled, browsers report violations with POST requests to [CSP_REPORT_PATH], either in the
+//! legacy `report-uri` format (a single `csp-report` object) or in the Reporting API format used
+//! by `report-to` (a list of reports). These requests are upgraded to update calls (see
+//! [crate::http::http_request_update]). Reports of the same violation are counted in a single
+//! entry and only the [MAX_CSP_VIOLATION_REPORTS] most recently reported violations are kept.
+use crate::http::security_headers;
+use crate::state;
+use candid::{CandidType, Deserialize};
+use ic_cdk::api::time;
+use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
+use internet_identity_interface::internet_identity::types::{
+    ContentSecurityPolicyConfig, CspViolationReport, Timestamp,
+};
+use serde_bytes::ByteBuf;
+use serde_json::Value;
+
+pub const CSP_REPORT_PATH: &str = "/csp-report";
+/// Name of the endpoint used in the `report-to` directive (see the `Reporting-Endpoints` header).
+pub const CSP_REPORT_ENDPOINT_NAME: &str = "csp-endpoint";
+pub const MAX_CSP_VIOLATION_REPORTS: usize = 100;
+/// Maximum size of the body of a report request.
+const MAX_REPORT_BODY_SIZE: usize = 16 * 1024;
+/// Maximum number of reports recorded per request (the Reporting API sends reports in batches).
+const MAX_REPORTS_PER_REQUEST: usize = 10;
+/// Fields of the reports are truncated to this many characters.
+const MAX_FIELD_LENGTH: usize = 512;
+const MAX_CONNECT_SOURCES: usize = 32;
+pub const DEFAULT_CONNECT_SOURCES: [&str; 3] = [
+    "https://icp-api.io",
+    "https://*.icp0.io",
+    "https://*.ic0.app",
+];
+
+fn config() -> ContentSecurityPolicyConfig {
+    state::persistent_state(|persistent_state| persistent_state.content_security_policy.clone())
+        .unwrap_or_default()
+}
+
+/// Sources allowed in the `connect-src` directive in addition to `'self'` and the II origins on
+/// custom domains.
+pub fn connect_sources() -> Vec<String> {
+    config().connect_sources.unwrap_or_else(|| {
+        DEFAULT_CONNECT_SOURCES
+            .iter()
+            .map(|source| source.to_string())
+            .collect()
+    })
+}
+
+/// Whether to include the `upgrade-insecure-requests` directive. By default, it is omitted when
+/// building in dev mode to allow loading II on localhost with Safari.
+pub fn upgrade_insecure_requests() -> bool {
+    config()
+        .upgrade_insecure_requests
+        .unwrap_or(cfg!(not(feature = "insecure_requests")))
+}
+
+pub fn report_violations() -> bool {
+    config().report_violations.unwrap_or(false)
+}
+
+pub fn validate_csp_config(config: &ContentSecurityPolicyConfig) -> Result<(), String> {
+    let Some(sources) = &config.connect_sources else {
+        return Ok(());
+    };
+    if sources.len() > MAX_CONNECT_SOURCES {
+        return Err(format!(
+            "At most {MAX_CONNECT_SOURCES} connect sources are supported."
+        ));
+    }
+    for source in sources {
+        // sources must not be able to add directives (or headers) to the policy
+        if source.is_empty()
+            || source
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || c == ';' || c == ',')
+        {
+            return Err(format!("Invalid CSP source '{}'.", source.escape_debug()));
+        }
+    }
+    Ok(())
+}
+
+/// The most recently reported CSP violations, most recent last.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Eq, PartialEq)]
+pub struct CspViolationReports {
+    reports: Vec<CspViolationReport>,
+}
+
+impl CspViolationReports {
+    pub fn reports(&self) -> &[CspViolationReport] {
+        &self.reports
+    }
+
+    /// Records a reported violation, counting it with a previous report of the same violation (if
+    /// any). The oldest report is dropped if there are more than [MAX_CSP_VIOLATION_REPORTS].
+    pub fn record(&mut self, violation: CspViolation, now: Timestamp) {
+        let previous = self.reports.iter().position(|report| {
+            report.document_uri == violation.document_uri
+                && report.effective_directive == violation.effective_directive
+                && report.blocked_uri == violation.blocked_uri
+                && report.source_file == violation.source_file
+                && report.line_number == violation.line_number
+                && report.disposition == violation.disposition
+        });
+        let report = match previous {
+            Some(index) => {
+                let mut report = self.reports.remove(index);
+                report.count += 1;
+                report.last_seen = now;
+                report
+            }
+            None => CspViolationReport {
+                document_uri: violation.document_uri,
+                effective_directive: violation.effective_directive,
+                blocked_uri: violation.blocked_uri,
+                source_file: violation.source_file,
+                line_number: violation.line_number,
+                disposition: violation.disposition,
+                count: 1,
+                first_seen: now,
+                last_seen: now,
+            },
+        };
+        self.reports.push(report);
+        if self.reports.len() > MAX_CSP_VIOLATION_REPORTS {
+            self.reports.remove(0);
+        }
+    }
+}
+
+/// A violation as reported by a browser (with the fields normalized and truncated).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CspViolation {
+    pub document_uri: String,
+    pub effective_directive: String,
+    pub blocked_uri: String,
+    pub source_file: Option<String>,
+    pub line_number: Option<u64>,
+    pub disposition: String,
+}
+
+/// Records the violations reported in the body of the request. Only called for POST requests to
+/// [CSP_REPORT_PATH] with violation reporting enabled.
+pub fn record_reported_violations(req: &HttpRequest) -> HttpResponse {
+    if req.body.len() > MAX_REPORT_BODY_SIZE {
+        return report_response(413);
+    }
+    let Ok(violations) = parse_violations(&req.body) else {
+        return report_response(400);
+    };
+    let now = time();
+    state::persistent_state_mut(|persistent_state| {
+        let reports = persistent_state
+            .csp_violation_reports
+            .get_or_insert_with(CspViolationReports::default);
+        for violation in violations.into_iter().take(MAX_REPORTS_PER_REQUEST) {
+            reports.record(violation, now);
+        }
+    });
+    report_response(204)
+}
+
+fn report_response(status_code: u16) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: security_headers(),
+        body: ByteBuf::new(),
+        upgrade: None,
+        streaming_strategy: None,
+    }
+}
+
+/// Parses the violations in either the `report-uri` or the Reporting API format. Reports of other
+/// types than CSP violations are skipped.
+pub fn parse_violations(body: &[u8]) -> Result<Vec<CspViolation>, String> {
+    let json: Value = serde_json::from_slice(body).map_err(|err| err.to_string())?;
+    match json {
+        // report-uri: { "csp-report": { "document-uri": ..., ... } }
+        Value::Object(ref object) => {
+            let report = object
+                .get("csp-report")
+                .ok_or_else(|| "missing csp-report".to_string())?;
+            Ok(vec![violation(
+                report,
+                [
+                    "document-uri",
+                    "effective-directive",
+                    "blocked-uri",
+                    "source-file",
+                    "line-number",
+                    "disposition",
+                ],
+            )
+            .or_else(|| violation_with_violated_directive(report))
+            .ok_or_else(|| "incomplete csp-report".to_string())?])
+        }
+        // report-to: [{ "type": "csp-violation", "body": { "documentURL": ..., ... } }, ...]
+        Value::Array(reports) => Ok(reports
+            .iter()
+            .filter(|report| report.get("type").and_then(Value::as_str) == Some("csp-violation"))
+            .filter_map(|report| report.get("body"))
+            .filter_map(|body| {
+                violation(
+                    body,
+                    [
+                        "documentURL",
+                        "effectiveDirective",
+                        "blockedURL",
+                        "sourceFile",
+                        "lineNumber",
+                        "disposition",
+                    ],
+                )
+            })
+            .collect()),
+        _ => Err("unexpected report format".to_string()),
+    }
+}
+
+/// Reads a violation from the fields with the given names (in the order of the [CspViolation]
+/// fields).
+fn violation(report: &Value, fields: [&str; 6]) -> Option<CspViolation> {
+    let [document_uri, effective_directive, blocked_uri, source_file, line_number, disposition] =
+        fields;
+    Some(CspViolation {
+        document_uri: strip_query_and_fragment(&string_field(report, document_uri)?),
+        effective_directive: string_field(report, effective_directive)?,
+        blocked_uri: strip_query_and_fragment(
+            &string_field(report, blocked_uri).unwrap_or_default(),
+        ),
+        source_file: string_field(report, source_file).map(|file| strip_query_and_fragment(&file)),
+        line_number: report.get(line_number).and_then(Value::as_u64),
+        disposition: string_field(report, disposition).unwrap_or_else(|| "enforce".to_string()),
+    })
+}
+
+/// Older browsers only send the `violated-directive` in `report-uri` reports.
+fn violation_with_violated_directive(report: &Value) -> Option<CspViolation> {
+    violation(
+        report,
+        [
+            "document-uri",
+            "violated-directive",
+            "blocked-uri",
+            "source-file",
+            "line-number",
+            "disposition",
+        ],
+    )
+    .map(|violation| CspViolation {
+        // e.g. "script-src 'self'" -> "script-src"
+        effective_directive: violation
+            .effective_directive
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        ..violation
+    })
+}
+
+fn string_field(report: &Value, name: &str) -> Option<String> {
+    report
+        .get(name)
+        .and_then(Value::as_str)
+        .map(|value| value.chars().take(MAX_FIELD_LENGTH).collect())
+}
+
+/// Query strings and fragments are removed as they might contain user data.
+fn strip_query_and_fragment(uri: &str) -> String {
+    uri.split(['?', '#']).next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn violation(document_uri: &str) -> CspViolation {
+        CspViolation {
+            document_uri: document_uri.to_string(),
+            effective_directive: "script-src-elem".to_string(),
+            blocked_uri: "https://evil.com/script.js".to_string(),
+            source_file: None,
+            line_number: None,
+            disposition: "enforce".to_string(),
+        }
+    }
+
+    #[test]
+    fn should_parse_report_uri_reports() {
+        let body = br#"{"csp-report": {
+            "document-uri": "https://identity.ic0.app/?x=1#authorize",
+            "violated-directive": "script-src-elem 'self'",
+            "blocked-uri": "https://evil.com/script.js?token=secret",
+            "source-file": "https://identity.ic0.app/index.js",
+            "line-number": 12,
+            "original-policy": "default-src 'none'"
+        }}"#;
+
+        assert_eq!(
+            parse_violations(body),
+            Ok(vec![CspViolation {
+                document_uri: "https://identity.ic0.app/".to_string(),
+                effective_directive: "script-src-elem".to_string(),
+                blocked_uri: "https://evil.com/script.js".to_string(),
+                source_file: Some("https://identity.ic0.app/index.js".to_string()),
+                line_number: Some(12),
+                disposition: "enforce".to_string(),
+            }])
+        );
+    }
+
+    #[test]
+    fn should_parse_reporting_api_reports() {
+        let body = br#"[
+            {"type": "csp-violation", "age": 10, "url": "https://identity.ic0.app/", "body": {
+                "documentURL": "https://identity.ic0.app/",
+                "effectiveDirective": "connect-src",
+                "blockedURL": "https://api.example.com/",
+                "disposition": "report"
+            }},
+            {"type": "deprecation", "body": {"id": "something"}}
+        ]"#;
+
+        assert_eq!(
+            parse_violations(body),
+            Ok(vec![CspViolation {
+                document_uri: "https://identity.ic0.app/".to_string(),
+                effective_directive: "connect-src".to_string(),
+                blocked_uri: "https://api.example.com/".to_string(),
+                source_file: None,
+                line_number: None,
+                disposition: "report".to_string(),
+            }])
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_reports() {
+        assert!(parse_violations(b"not json").is_err());
+        assert!(parse_violations(br#"{"something": {}}"#).is_err());
+        assert!(parse_violations(br#"{"csp-report": {"blocked-uri": "x"}}"#).is_err());
+    }
+
+    #[test]
+    fn should_deduplicate_reports() {
+        let mut reports = CspViolationReports::default();
+        reports.record(violation("https://a.com/"), 1);
+        reports.record(violation("https://b.com/"), 2);
+        reports.record(violation("https://a.com/"), 3);
+
+        let reports = reports.reports();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].document_uri, "https://b.com/");
+        assert_eq!(reports[1].document_uri, "https://a.com/");
+        assert_eq!(reports[1].count, 2);
+        assert_eq!(reports[1].first_seen, 1);
+        assert_eq!(reports[1].last_seen, 3);
+    }
+
+    #[test]
+    fn should_keep_bounded_number_of_reports() {
+        let mut reports = CspViolationReports::default();
+        for i in 0..(MAX_CSP_VIOLATION_REPORTS + 5) {
+            reports.record(violation(&format!("https://{i}.com/")), i as u64);
+        }
+
+        assert_eq!(reports.reports().len(), MAX_CSP_VIOLATION_REPORTS);
+        assert_eq!(reports.reports()[0].document_uri, "https://5.com/");
+    }
+
+    #[test]
+    fn should_reject_sources_adding_directives() {
+        for source in ["https://a.com; script-src *", "https://a.com b.com", ""] {
+            let config = ContentSecurityPolicyConfig {
+                connect_sources: Some(vec![source.to_string()]),
+                ..Default::default()
+            };
+            assert!(validate_csp_config(&config).is_err());
+        }
+        assert_eq!(
+            validate_csp_config(&ContentSecurityPolicyConfig::default()),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Self-checks of the canister, returned by the `health_check` query and served (as JSON) at
+//! [HEALTH_PATH].
+//!
+//! Every check results in a [HealthStatus]: `warn` indicates a condition that requires attention
+//! but does not (yet) affect users, `fail` a condition that does. The status of the report is the
+//! most severe status of all checks.
+use crate::archive::{ArchiveState, PUSH_RETRY_MAX_DELAY};
+use crate::state;
+use crate::state::MAX_INFLIGHT_CAPTCHAS;
+use crate::storage::{stable_memory_headroom, STABLE_MEMORY_RESERVE};
+use ic_cdk::api::stable::stable64_size;
+use ic_cdk::api::time;
+use internet_identity_interface::internet_identity::types::{
+    ArchiveIntegration, HealthCheck, HealthReport, HealthStatus, Timestamp,
+};
+use std::time::Duration;
+use HealthStatus::{Fail, Pass, Warn};
+
+pub const HEALTH_PATH: &str = "/health";
+
+/// Usage of a limit (in percent) from which the usage is reported as `warn`.
+const WARN_USAGE_PERCENT: u64 = 80;
+/// Number of transfer intervals without a transfer to the archive (while entries are buffered)
+/// after which the archive is reported as lagging behind (`warn`) or stuck (`fail`). The transfer
+/// interval is the polling interval of the archive with [ArchiveIntegration::Pull] and the maximum
+/// delay between two push attempts with [ArchiveIntegration::Push].
+const ARCHIVE_LAG_WARN_INTERVALS: u64 = 10;
+const ARCHIVE_LAG_FAIL_INTERVALS: u64 = 100;
+/// Number of signatures from which the signatures are reported as not being pruned fast enough.
+/// Signatures expire after a minute and expired signatures are pruned on every delegation.
+const SIGNATURE_COUNT_WARN_THRESHOLD: usize = 10_000;
+const MB: u64 = 1 << 20;
+
+/// Runs all the checks at the current time.
+pub fn health_report() -> HealthReport {
+    let timestamp = time();
+    let mut checks = vec![storage_check()];
+    if state::storage_initialised() {
+        checks.push(salt_check());
+    }
+    checks.push(stable_memory_check());
+    checks.extend(archive_checks(timestamp));
+    checks.push(signatures_check());
+    checks.push(captcha_check());
+
+    HealthReport {
+        status: checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(Pass),
+        checks,
+        timestamp,
+    }
+}
+
+/// Encodes the report as JSON, e.g.
+/// `{"status":"pass","checks":[{"name":"storage","status":"pass","message":"..."},...],"timestamp":...}`
+pub fn encode_json(report: &HealthReport) -> Vec<u8> {
+    let checks: Vec<_> = report
+        .checks
+        .iter()
+        .map(|check| {
+            serde_json::json!({
+                "name": check.name,
+                "status": status_name(check.status),
+                "message": check.message,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "status": status_name(report.status),
+        "checks": checks,
+        "timestamp": report.timestamp,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+fn status_name(status: HealthStatus) -> &'static str {
+    match status {
+        Pass => "pass",
+        Warn => "warn",
+        Fail => "fail",
+    }
+}
+
+fn check(name: &str, status: HealthStatus, message: String) -> HealthCheck {
+    HealthCheck {
+        name: name.to_string(),
+        status,
+        message,
+    }
+}
+
+/// Returns the status of a usage of the given limit.
+fn usage_status(usage: u64, limit: u64) -> HealthStatus {
+    if usage >= limit {
+        Fail
+    } else if usage.saturating_mul(100) >= limit.saturating_mul(WARN_USAGE_PERCENT) {
+        Warn
+    } else {
+        Pass
+    }
+}
+
+fn storage_check() -> HealthCheck {
+    if !state::storage_initialised() {
+        return check("storage", Fail, "Storage is not initialised.".to_string());
+    }
+    let (anchor_count, max_entries) =
+        state::storage_borrow(|storage| (storage.anchor_count(), storage.max_entries()));
+    check(
+        "storage",
+        Pass,
+        format!("Storage is initialised, {anchor_count} of {max_entries} anchors are in use."),
+    )
+}
+
+fn salt_check() -> HealthCheck {
+    match state::storage_borrow(|storage| storage.salt().is_some()) {
+        true => check("salt", Pass, "Salt is set.".to_string()),
+        // the salt is initialised lazily by the first call requiring it
+        false => check("salt", Warn, "Salt is not set yet.".to_string()),
+    }
+}
+
+fn stable_memory_check() -> HealthCheck {
+    let headroom = stable_memory_headroom(stable64_size());
+    let status = if headroom <= STABLE_MEMORY_RESERVE {
+        Fail
+    } else if headroom <= 2 * STABLE_MEMORY_RESERVE {
+        Warn
+    } else {
+        Pass
+    };
+    check(
+        "stable_memory",
+        status,
+        format!(
+            "{} MB of stable memory are unused, of which {} MB are reserved.",
+            headroom / MB,
+            STABLE_MEMORY_RESERVE / MB
+        ),
+    )
+}
+
+fn archive_checks(timestamp: Timestamp) -> Vec<HealthCheck> {
+    let ArchiveState::Created { data, config } = state::archive_state() else {
+        return vec![
+            check("archive_buffer", Pass, "No archive deployed.".to_string()),
+            check("archive_fetch", Pass, "No archive deployed.".to_string()),
+        ];
+    };
+
+    let buffered = data.entries_buffer.len() as u64;
+    let buffer_check = check(
+        "archive_buffer",
+        usage_status(buffered, config.entries_buffer_limit),
+        format!(
+            "{buffered} of at most {} entries are buffered.",
+            config.entries_buffer_limit
+        ),
+    );
+
+    let (transferred, transfer_interval_ns, interval_name) =
+        match config.archive_integration.unwrap_or_default() {
+            ArchiveIntegration::Pull => (
+                "fetched by the archive",
+                config.polling_interval_ns,
+                "polling interval",
+            ),
+            // entries are pushed right away, failed pushes are retried with increasing delays
+            ArchiveIntegration::Push => (
+                "pushed to the archive",
+                PUSH_RETRY_MAX_DELAY.as_nanos() as u64,
+                "maximum push retry delay",
+            ),
+        };
+    // entries only need to be transferred if there are entries to be archived
+    let fetch_check = match data.entries_buffer.first() {
+        None => check(
+            "archive_fetch",
+            Pass,
+            "No entries to be archived.".to_string(),
+        ),
+        Some(oldest_entry) => {
+            let waiting_since = data
+                .last_transfer_timestamp
+                .map_or(oldest_entry.timestamp, |last| {
+                    last.max(oldest_entry.timestamp)
+                });
+            let waiting_ns = timestamp.saturating_sub(waiting_since);
+            let lag_intervals = |intervals: u64| intervals.saturating_mul(transfer_interval_ns);
+            let status = if waiting_ns >= lag_intervals(ARCHIVE_LAG_FAIL_INTERVALS) {
+                Fail
+            } else if waiting_ns >= lag_intervals(ARCHIVE_LAG_WARN_INTERVALS) {
+                Warn
+            } else {
+                Pass
+            };
+            check(
+                "archive_fetch",
+                status,
+                format!(
+                    "Buffered entries have not been {transferred} for {} seconds ({interval_name}: {} seconds).",
+                    Duration::from_nanos(waiting_ns).as_secs(),
+                    Duration::from_nanos(transfer_interval_ns).as_secs()
+                ),
+            )
+        }
+    };
+    vec![buffer_check, fetch_check]
+}
+
+fn signatures_check() -> HealthCheck {
+    let count = state::signature_map(|sigs| sigs.len());
+    let status = if count >= SIGNATURE_COUNT_WARN_THRESHOLD {
+        Warn
+    } else {
+        Pass
+    };
+    check(
+        "signatures",
+        status,
+        format!("{count} signatures are active."),
+    )
+}
+
+fn captcha_check() -> HealthCheck {
+    let inflight = state::inflight_challenges(|challenges| challenges.len()) as u64;
+    let limit = state::persistent_state(|persistent_state| {
+        persistent_state
+            .max_inflight_captchas
+            .unwrap_or(MAX_INFLIGHT_CAPTCHAS)
+    });
+    check(
+        "captcha_backlog",
+        usage_status(inflight, limit),
+        format!("{inflight} of at most {limit} captcha challenges are inflight."),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_report_usage_status() {
+        assert_eq!(usage_status(0, 100), Pass);
+        assert_eq!(usage_status(79, 100), Pass);
+        assert_eq!(usage_status(80, 100), Warn);
+        assert_eq!(usage_status(99, 100), Warn);
+        assert_eq!(usage_status(100, 100), Fail);
+        assert_eq!(usage_status(0, 0), Fail);
+    }
+
+    #[test]
+    fn should_encode_report_as_json() {
+        let report = HealthReport {
+            status: Warn,
+            checks: vec![
+                check("storage", Pass, "ok".to_string()),
+                check("salt", Warn, "not set".to_string()),
+            ],
+            timestamp: 1234,
+        };
+        let json: serde_json::Value = serde_json::from_slice(&encode_json(&report)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "status": "warn",
+                "checks": [
+                    {"name": "storage", "status": "pass", "message": "ok"},
+                    {"name": "salt", "status": "warn", "message": "not set"},
+                ],
+                "timestamp": 1234,
+            })
+        );
+    }
+}
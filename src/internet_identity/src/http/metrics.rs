@@ -1,11 +1,13 @@
 use crate::activity_stats::activity_counter::ActivityCounter;
 use crate::activity_stats::ActivityStats;
 use crate::archive::ArchiveState;
-use crate::state::PersistentState;
-use crate::{state, IC0_APP_DOMAIN, INTERNETCOMPUTER_ORG_DOMAIN};
+use crate::instruction_profiling::UNPROFILED_QUERY_ENDPOINTS;
+use crate::state::{PersistentState, UsageMetrics};
+use crate::{ii_domain, state, DAY_NS, IC0_APP_ORIGIN, INTERNETCOMPUTER_ORG_ORIGIN};
 use ic_cdk::api::stable::stable64_size;
 use ic_cdk::api::time;
 use ic_metrics_encoder::{LabeledMetricsBuilder, MetricsEncoder};
+use internet_identity_interface::internet_identity::types::AnchorPopulationStats;
 use std::time::Duration;
 
 /// Collects the various metrics exposed by the Internet Identity canister.
@@ -35,6 +37,14 @@ fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
             "The highest Identity Anchor that can be served by this canister.",
         )
     })?;
+    state::storage_borrow(|storage| {
+        let stats = storage.population_stats();
+        // only report complete stats (i.e. not while the stats are being backfilled)
+        if stats.counted_anchors < storage.anchor_count() as u64 {
+            return Ok(());
+        }
+        anchor_population_metrics(w, stats)
+    })?;
     state::signature_map(|sigs| {
         w.encode_gauge(
             "internet_identity_signature_count",
@@ -71,17 +81,21 @@ fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
             "The number of users in registration mode",
         )
     })?;
-    state::usage_metrics(|usage_metrics| {
-        w.encode_gauge(
-            "internet_identity_delegation_counter",
-            usage_metrics.delegation_counter as f64,
-            "The number of delegations created since last upgrade",
-        )?;
-        w.encode_gauge(
-            "internet_identity_anchor_operations_counter",
-            usage_metrics.anchor_operation_counter as f64,
-            "The number of anchor operations since last upgrade",
-        )
+    state::usage_metrics(|usage_metrics| usage_metrics_metrics(w, usage_metrics))?;
+    state::instruction_histograms(|histograms| {
+        for (endpoint, histogram) in histograms {
+            w.encode_histogram(
+                &format!("internet_identity_{}_instructions", endpoint.name()),
+                histogram.buckets(),
+                histogram.sum() as f64,
+                &format!(
+                    "Histogram of the instructions used by `{}` update calls since the canister was last installed or upgraded (not persisted). The query endpoints {} are not profiled, as state changes of query calls are discarded.",
+                    endpoint.name(),
+                    UNPROFILED_QUERY_ENDPOINTS.join(", ")
+                ),
+            )?;
+        }
+        Ok::<(), std::io::Error>(())
     })?;
     if let ArchiveState::Created { ref data, config } = state::archive_state() {
         w.encode_gauge(
@@ -141,63 +155,57 @@ fn persistent_state_metrics(
         )?;
     }
     if let Some(ref stats) = persistent_state.active_anchor_stats {
-        if let Some(ref daily_active_anchor_stats) = stats.completed.daily_events {
-            w.encode_gauge(
-                "internet_identity_daily_active_anchors",
-                daily_active_anchor_stats.counter as f64,
-                "The number of unique active anchors in the last completed 24h collection window.",
-            )?;
-            w.encode_gauge(
-                "internet_identity_daily_active_anchors_start_timestamp_seconds",
-                Duration::from_nanos(daily_active_anchor_stats.start_timestamp).as_secs() as f64,
-                "Timestamp of the last completed 24h collection window for unique active anchors.",
-            )?;
-        }
-        if let Some(ref monthly_active_anchor_stats) = stats.completed.monthly_events {
-            w.encode_gauge(
-                "internet_identity_monthly_active_anchors",
-                monthly_active_anchor_stats.counter as f64,
-                "The number of unique active anchors in the last completed 30-day collection window.",
-            )?;
-            w.encode_gauge(
-                "internet_identity_monthly_active_anchors_start_timestamp_seconds",
-                Duration::from_nanos(monthly_active_anchor_stats.start_timestamp).as_secs() as f64,
-                "Timestamp of the last completed 30-day collection window for unique active anchors.",
-            )?;
+        for (name, window) in stats.windows() {
+            if let Some(active_anchor_stats) = window.completed {
+                let description = window_description(window.length_ns);
+                w.encode_gauge(
+                    &format!("internet_identity_{name}_active_anchors"),
+                    active_anchor_stats.counter as f64,
+                    &format!("The number of unique active anchors in the last completed {description} collection window."),
+                )?;
+                w.encode_gauge(
+                    &format!("internet_identity_{name}_active_anchors_start_timestamp_seconds"),
+                    Duration::from_nanos(active_anchor_stats.start_timestamp).as_secs() as f64,
+                    &format!("Timestamp of the last completed {description} collection window for unique active anchors."),
+                )?;
+            }
         }
     };
     if let Some(ref stats) = persistent_state.domain_active_anchor_stats {
-        const BOTH_DOMAINS: &str = "both_ii_domains";
-
         let labels = ActivityMetricsLabels {
-            daily_stats_label: "internet_identity_daily_active_anchors_by_domain",
-            daily_stats_doc: "The number of unique active anchors in the last completed 24h collection window aggregated by II domains used.",
-            monthly_stats_label: "internet_identity_monthly_active_anchors_by_domain",
-            monthly_stats_doc: "The number of unique active anchors in the last completed 30-day collection window aggregated by II domains used.",
+            metric_suffix: "active_anchors_by_domain",
+            doc: "The number of unique active anchors in the last completed {window} collection window aggregated by II domains used.",
         };
-        labelled_activity_metrics(w, stats, labels, |counter, encoder| {
-            encoder
-                .value(
-                    &[("domain", IC0_APP_DOMAIN)],
-                    counter.ic0_app_counter as f64,
-                )?
-                .value(
-                    &[("domain", INTERNETCOMPUTER_ORG_DOMAIN)],
-                    counter.internetcomputer_org_counter as f64,
-                )?
-                .value(
-                    &[("domain", BOTH_DOMAINS)],
-                    counter.both_ii_domains_counter as f64,
+        let ii_origins = ii_domain::ii_origins();
+        labelled_activity_metrics(w, stats, labels, |counter, mut encoder| {
+            let mut counters = counter.counters();
+            // always report the single domain buckets of all configured II domains
+            for origin in &ii_origins {
+                counters.entry(vec![origin.clone()]).or_default();
+            }
+            if ii_origins.contains(&IC0_APP_ORIGIN.to_string())
+                && ii_origins.contains(&INTERNETCOMPUTER_ORG_ORIGIN.to_string())
+            {
+                counters
+                    .entry(vec![
+                        IC0_APP_ORIGIN.to_string(),
+                        INTERNETCOMPUTER_ORG_ORIGIN.to_string(),
+                    ])
+                    .or_default();
+            }
+            for (origins, count) in counters {
+                encoder = encoder.value(
+                    &[("domain", domains_label(&origins).as_str())],
+                    count as f64,
                 )?;
+            }
             Ok(())
         })?;
     };
     if let Some(ref stats) = persistent_state.active_authn_method_stats {
         let labels = ActivityMetricsLabels {
-            daily_stats_label: "internet_identity_daily_active_authn_methods",
-            daily_stats_doc: "The number of unique authentication methods used in the last completed 24h collection window on II domains.",
-            monthly_stats_label: "internet_identity_monthly_active_authn_methods",
-            monthly_stats_doc: "The number of unique authentication methods used in the last completed 30-day collection window on II domains.",
+            metric_suffix: "active_authn_methods",
+            doc: "The number of unique authentication methods used in the last completed {window} collection window on II domains.",
         };
         labelled_activity_metrics(w, stats, labels, |counter, encoder| {
             encoder
@@ -221,6 +229,51 @@ fn persistent_state_metrics(
             Ok(())
         })?;
     };
+    if let Some(ref stats) = persistent_state.dapp_active_anchor_stats {
+        let labels = ActivityMetricsLabels {
+            metric_suffix: "active_anchors_by_dapp",
+            doc: "The number of unique anchors that prepared a delegation for a frontend in the last completed {window} collection window (only for the most active frontends).",
+        };
+        labelled_activity_metrics(w, stats, labels, |counter, mut encoder| {
+            for (frontend, count) in counter.ranked_counters() {
+                encoder = encoder.value(&[("frontend", frontend.as_str())], count as f64)?;
+            }
+            Ok(())
+        })?;
+    };
+    if let Some(ref cohort_stats) = persistent_state.registration_cohort_stats {
+        // cohorts are labelled with the start of the registration week in seconds since the epoch
+        let cohorts: Vec<_> = cohort_stats
+            .cohorts
+            .iter()
+            .map(|cohort| {
+                (
+                    (cohort.week_start_timestamp / 1_000_000_000).to_string(),
+                    cohort,
+                )
+            })
+            .collect();
+        let mut encoder = w.gauge_vec(
+            "internet_identity_registration_cohort_size",
+            "The number of anchors registered in the week starting at the cohort timestamp.",
+        )?;
+        for (start, cohort) in &cohorts {
+            encoder = encoder.value(&[("cohort", start.as_str())], cohort.registrations as f64)?;
+        }
+        let mut encoder = w.gauge_vec(
+            "internet_identity_registration_cohort_active_anchors",
+            "The number of anchors of a registration cohort that were active in the given week after the registration week.",
+        )?;
+        for (start, cohort) in &cohorts {
+            for (week, count) in cohort.active_anchors_by_week.iter().enumerate() {
+                let week = (week + 1).to_string();
+                encoder = encoder.value(
+                    &[("cohort", start.as_str()), ("week", week.as_str())],
+                    *count as f64,
+                )?;
+            }
+        }
+    }
     if let Some(delegation_origins_limit) = persistent_state.max_num_latest_delegation_origins {
         w.encode_gauge(
             "internet_identity_max_num_latest_delegation_origins",
@@ -231,11 +284,152 @@ fn persistent_state_metrics(
     Ok(())
 }
 
+fn usage_metrics_metrics(
+    w: &mut MetricsEncoder<Vec<u8>>,
+    usage_metrics: &UsageMetrics,
+) -> Result<(), std::io::Error> {
+    w.encode_gauge(
+        "internet_identity_delegation_counter",
+        usage_metrics.delegation_counter as f64,
+        "The number of delegations created.",
+    )?;
+    w.encode_gauge(
+        "internet_identity_anchor_operations_counter",
+        usage_metrics.anchor_operation_counter as f64,
+        "The number of anchor operations.",
+    )?;
+    w.gauge_vec(
+        "internet_identity_captchas",
+        "The number of CAPTCHA challenges by lifecycle event.",
+    )?
+    .value(
+        &[("event", "created")],
+        usage_metrics.captchas_created as f64,
+    )?
+    .value(
+        &[("event", "expired")],
+        usage_metrics.captchas_expired as f64,
+    )?
+    .value(&[("event", "solved")], usage_metrics.captchas_solved as f64)?
+    .value(&[("event", "failed")], usage_metrics.captchas_failed as f64)?
+    .value(
+        &[("event", "rejected_inflight_limit")],
+        usage_metrics.captcha_limit_rejections as f64,
+    )?;
+    w.gauge_vec(
+        "internet_identity_registrations",
+        "The number of `register` calls by outcome.",
+    )?
+    .value(
+        &[("outcome", "registered")],
+        usage_metrics.registrations_succeeded as f64,
+    )?
+    .value(
+        &[("outcome", "bad_challenge")],
+        usage_metrics.registrations_bad_challenge as f64,
+    )?
+    .value(
+        &[("outcome", "canister_full")],
+        usage_metrics.registrations_canister_full as f64,
+    )?
+    .value(
+        &[("outcome", "rate_limited")],
+        usage_metrics.registration_rate_limit_rejections as f64,
+    )?;
+    w.gauge_vec(
+        "internet_identity_temp_keys",
+        "The number of temporary keys added on registration and calls authenticated using a temporary key.",
+    )?
+    .value(&[("event", "added")], usage_metrics.temp_keys_added as f64)?
+    .value(
+        &[("event", "authenticated")],
+        usage_metrics.temp_key_authentications as f64,
+    )?;
+    Ok(())
+}
+
+fn anchor_population_metrics(
+    w: &mut MetricsEncoder<Vec<u8>>,
+    stats: &AnchorPopulationStats,
+) -> Result<(), std::io::Error> {
+    let total_devices: u64 = stats
+        .anchors_by_device_count
+        .iter()
+        .enumerate()
+        .map(|(devices, anchors)| devices as u64 * anchors)
+        .sum();
+    w.encode_histogram(
+        "internet_identity_anchors_by_device_count",
+        stats
+            .anchors_by_device_count
+            .iter()
+            .enumerate()
+            .map(|(devices, anchors)| (devices as f64, *anchors as f64)),
+        total_devices as f64,
+        "Histogram of the number of devices per anchor.",
+    )?;
+    w.encode_gauge(
+        "internet_identity_anchors_with_recovery_phrase",
+        stats.anchors_with_recovery_phrase as f64,
+        "The number of anchors with a recovery phrase.",
+    )?;
+    w.encode_gauge(
+        "internet_identity_anchors_with_recovery_device",
+        stats.anchors_with_recovery_device as f64,
+        "The number of anchors with a recovery device (other than a recovery phrase).",
+    )?;
+    let key_types = &stats.devices_by_key_type;
+    w.gauge_vec(
+        "internet_identity_devices_by_key_type",
+        "The number of devices by key type.",
+    )?
+    .value(&[("type", "unknown")], key_types.unknown as f64)?
+    .value(&[("type", "platform")], key_types.platform as f64)?
+    .value(
+        &[("type", "cross_platform")],
+        key_types.cross_platform as f64,
+    )?
+    .value(&[("type", "seed_phrase")], key_types.seed_phrase as f64)?
+    .value(
+        &[("type", "browser_storage_key")],
+        key_types.browser_storage_key as f64,
+    )?;
+    w.gauge_vec(
+        "internet_identity_devices_by_protection",
+        "The number of devices by protection mode.",
+    )?
+    .value(
+        &[("protection", "protected")],
+        stats.protected_devices as f64,
+    )?
+    .value(
+        &[("protection", "unprotected")],
+        stats.unprotected_devices as f64,
+    )?;
+    Ok(())
+}
+
 struct ActivityMetricsLabels<'a> {
-    daily_stats_label: &'a str,
-    daily_stats_doc: &'a str,
-    monthly_stats_label: &'a str,
-    monthly_stats_doc: &'a str,
+    // The metric of a collection window is named `internet_identity_<window name>_<metric_suffix>`.
+    metric_suffix: &'a str,
+    // `{window}` is replaced with the description of the collection window (e.g. `24h`).
+    doc: &'a str,
+}
+
+/// Label for a bucket of anchors active on the given (sorted) II origins.
+/// Anchors active on multiple II domains are labelled with the hostnames joined by `+`, except for
+/// the combination of the two default II domains, which keeps its legacy label `both_ii_domains`.
+fn domains_label(origins: &[String]) -> String {
+    const BOTH_DOMAINS: &str = "both_ii_domains";
+
+    if origins == [IC0_APP_ORIGIN, INTERNETCOMPUTER_ORG_ORIGIN] {
+        return BOTH_DOMAINS.to_string();
+    }
+    origins
+        .iter()
+        .map(|origin| ii_domain::hostname(origin))
+        .collect::<Vec<_>>()
+        .join("+")
 }
 
 fn labelled_activity_metrics<T: ActivityCounter>(
@@ -244,13 +438,27 @@ fn labelled_activity_metrics<T: ActivityCounter>(
     labels: ActivityMetricsLabels<'_>,
     encoding: impl Fn(&T, LabeledMetricsBuilder<Vec<u8>>) -> Result<(), std::io::Error>,
 ) -> Result<(), std::io::Error> {
-    if let Some(ref daily_stats) = stats.completed.daily_events {
-        let builder = w.gauge_vec(labels.daily_stats_label, labels.daily_stats_doc)?;
-        encoding(daily_stats, builder)?;
-    }
-    if let Some(ref monthly_stats) = stats.completed.monthly_events {
-        let builder = w.gauge_vec(labels.monthly_stats_label, labels.monthly_stats_doc)?;
-        encoding(monthly_stats, builder)?;
+    for (name, window) in stats.windows() {
+        if let Some(completed_stats) = window.completed {
+            let builder = w.gauge_vec(
+                &format!("internet_identity_{name}_{}", labels.metric_suffix),
+                &labels
+                    .doc
+                    .replace("{window}", &window_description(window.length_ns)),
+            )?;
+            encoding(completed_stats, builder)?;
+        }
     }
     Ok(())
 }
+
+/// Human readable length of a collection window, e.g. `24h` or `30-day`.
+fn window_description(length_ns: u64) -> String {
+    if length_ns == DAY_NS {
+        "24h".to_string()
+    } else if length_ns % DAY_NS == 0 {
+        format!("{}-day", length_ns / DAY_NS)
+    } else {
+        format!("{}h", Duration::from_nanos(length_ns).as_secs() / 3600)
+    }
+}
@@ -0,0 +1,314 @@
+//! Redirects and aliases served by the HTTP interface in addition to the assets.
+//!
+//! Routes at exact paths take precedence over the assets served at the same path. Redirect
+//! patterns (e.g. `/docs/*`) are certified with wildcard expression paths (e.g. `docs/<*>`), so
+//! they only apply to paths without a response certified more specifically (see
+//! [crate::assets::certify_routes]).
+use crate::assets::localization::{localized_shell_expr_path, shell_locale, LOCALES, SHELL_PATH};
+use crate::assets::{exact_expr_path, path_segments, WILDCARD_MATCH_TERMINATOR};
+use crate::http::csp::CSP_REPORT_PATH;
+use crate::http::health::HEALTH_PATH;
+use crate::http::METRICS_FORMATS;
+use crate::state;
+use internet_identity_interface::internet_identity::types::{HttpAlias, HttpRedirect, HttpRoutes};
+use std::collections::HashSet;
+
+/// Maximum number of redirects and aliases (combined).
+pub const MAX_HTTP_ROUTES: usize = 100;
+const REDIRECT_STATUS_CODES: [u16; 3] = [301, 302, 308];
+const PATTERN_SUFFIX: &str = "/*";
+
+/// A route served at an exact path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ExactRoute {
+    Redirect(HttpRedirect),
+    Alias(HttpAlias),
+}
+
+/// The routes served if none are configured.
+pub fn default_routes() -> HttpRoutes {
+    HttpRoutes {
+        redirects: vec![HttpRedirect {
+            // The FAQ used to live in '/faq' but we now use an external website. We redirect in
+            // order to not break existing links in the wild.
+            path: "/faq".to_string(),
+            target: "https://identitysupport.dfinity.org/hc/en-us".to_string(),
+            status_code: 301,
+        }],
+        aliases: vec![],
+    }
+}
+
+/// Returns the configured routes, or the [default_routes] if none are configured.
+pub fn configured_routes() -> HttpRoutes {
+    state::persistent_state(|persistent_state| persistent_state.http_routes.clone())
+        .unwrap_or_else(default_routes)
+}
+
+pub fn exact_route(routes: &HttpRoutes, path: &str) -> Option<ExactRoute> {
+    routes
+        .redirects
+        .iter()
+        .find(|redirect| redirect.path == path)
+        .map(|redirect| ExactRoute::Redirect(redirect.clone()))
+        .or_else(|| {
+            routes
+                .aliases
+                .iter()
+                .find(|alias| alias.path == path)
+                .map(|alias| ExactRoute::Alias(alias.clone()))
+        })
+}
+
+/// Returns the redirect pattern with the longest prefix matching the given path, if any.
+pub fn matching_redirect_pattern<'a>(
+    routes: &'a HttpRoutes,
+    path: &str,
+) -> Option<&'a HttpRedirect> {
+    let segments = path_segments(path);
+    routes
+        .redirects
+        .iter()
+        .filter_map(|redirect| pattern_prefix(&redirect.path).map(|prefix| (redirect, prefix)))
+        .filter(|(_, prefix)| segments.starts_with(prefix))
+        .max_by_key(|(_, prefix)| prefix.len())
+        .map(|(redirect, _)| redirect)
+}
+
+/// Returns the expression path the redirect is certified with.
+pub fn redirect_expr_path(redirect: &HttpRedirect) -> Vec<String> {
+    match pattern_prefix(&redirect.path) {
+        Some(mut path) => {
+            path.push(WILDCARD_MATCH_TERMINATOR.to_string());
+            path
+        }
+        None => exact_expr_path(&redirect.path),
+    }
+}
+
+pub fn is_redirect_pattern(redirect: &HttpRedirect) -> bool {
+    redirect.path.ends_with(PATTERN_SUFFIX)
+}
+
+/// Returns the path segments of the prefix of a redirect pattern, `None` for exact paths.
+fn pattern_prefix(path: &str) -> Option<Vec<String>> {
+    path.strip_suffix(PATTERN_SUFFIX).map(path_segments)
+}
+
+pub fn validate_routes(routes: &HttpRoutes) -> Result<(), String> {
+    if routes.redirects.len() + routes.aliases.len() > MAX_HTTP_ROUTES {
+        return Err(format!(
+            "At most {MAX_HTTP_ROUTES} redirects and aliases are supported."
+        ));
+    }
+
+    let mut paths = HashSet::new();
+    for redirect in &routes.redirects {
+        validate_path(
+            redirect
+                .path
+                .strip_suffix(PATTERN_SUFFIX)
+                .unwrap_or(&redirect.path),
+        )?;
+        if LOCALES
+            .iter()
+            .any(|locale| redirect_expr_path(redirect) == localized_shell_expr_path(locale))
+        {
+            return Err(format!(
+                "Redirect pattern '{}' is reserved for the localized shells.",
+                redirect.path
+            ));
+        }
+        if !REDIRECT_STATUS_CODES.contains(&redirect.status_code) {
+            return Err(format!(
+                "Unsupported status code {} for redirect '{}', expected one of {REDIRECT_STATUS_CODES:?}.",
+                redirect.status_code, redirect.path
+            ));
+        }
+        if redirect.target.is_empty() || redirect.target.chars().any(char::is_control) {
+            return Err(format!(
+                "Invalid target '{}' for redirect '{}'.",
+                redirect.target.escape_debug(),
+                redirect.path
+            ));
+        }
+        validate_not_localized_shell(&redirect.path)?;
+        if !paths.insert(redirect.path.as_str()) {
+            return Err(format!("Duplicate route for '{}'.", redirect.path));
+        }
+    }
+    for alias in &routes.aliases {
+        validate_path(&alias.path)?;
+        validate_path(&alias.asset_path)?;
+        validate_not_localized_shell(&alias.path)?;
+        if !paths.insert(alias.path.as_str()) {
+            return Err(format!("Duplicate route for '{}'.", alias.path));
+        }
+    }
+    for alias in &routes.aliases {
+        if exact_route(routes, &alias.asset_path).is_some() {
+            return Err(format!(
+                "Alias '{}' must point to an asset, but '{}' is a route.",
+                alias.path, alias.asset_path
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_path(path: &str) -> Result<(), String> {
+    if !path.starts_with('/')
+        || path.contains(['?', '#', '*'])
+        || path.chars().any(char::is_control)
+    {
+        return Err(format!("Invalid route path '{}'.", path.escape_debug()));
+    }
+    if METRICS_FORMATS
+        .iter()
+        .any(|(metrics_path, _)| *metrics_path == path)
+    {
+        return Err(format!("Route path '{path}' is reserved for the metrics."));
+    }
+    if path == HEALTH_PATH {
+        return Err(format!(
+            "Route path '{path}' is reserved for the health report."
+        ));
+    }
+    if path == CSP_REPORT_PATH {
+        return Err(format!(
+            "Route path '{path}' is reserved for CSP violation reports."
+        ));
+    }
+    Ok(())
+}
+
+/// Routes must not shadow the localized shells, which are served at `/<lang>/`.
+fn validate_not_localized_shell(path: &str) -> Result<(), String> {
+    if path != SHELL_PATH && shell_locale(path).is_some() {
+        return Err(format!(
+            "Route path '{path}' is reserved for the localized shells."
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect(path: &str) -> HttpRedirect {
+        HttpRedirect {
+            path: path.to_string(),
+            target: format!("https://example.com{path}"),
+            status_code: 301,
+        }
+    }
+
+    #[test]
+    fn should_match_longest_redirect_pattern() {
+        let routes = HttpRoutes {
+            redirects: vec![
+                redirect("/docs/*"),
+                redirect("/docs/old/*"),
+                redirect("/faq"),
+            ],
+            aliases: vec![],
+        };
+
+        for (path, expected) in [
+            ("/docs", Some("/docs/*")),
+            ("/docs/", Some("/docs/*")),
+            ("/docs/new/page", Some("/docs/*")),
+            ("/docs/old", Some("/docs/old/*")),
+            ("/docs/old/page", Some("/docs/old/*")),
+            ("/docsx", None),
+            ("/faq", None),
+            ("/", None),
+        ] {
+            assert_eq!(
+                matching_redirect_pattern(&routes, path).map(|redirect| redirect.path.as_str()),
+                expected,
+                "unexpected redirect for {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn should_certify_patterns_with_wildcard_expr_paths() {
+        assert_eq!(
+            redirect_expr_path(&redirect("/docs/old/*")),
+            vec!["docs", "old", "<*>"]
+        );
+        assert_eq!(redirect_expr_path(&redirect("/faq")), vec!["faq", "<$>"]);
+    }
+
+    #[test]
+    fn should_accept_default_routes() {
+        assert_eq!(validate_routes(&default_routes()), Ok(()));
+    }
+
+    #[test]
+    fn should_reject_invalid_routes() {
+        let alias = |path: &str, asset_path: &str| HttpAlias {
+            path: path.to_string(),
+            asset_path: asset_path.to_string(),
+        };
+        for routes in [
+            HttpRoutes {
+                redirects: vec![redirect("/*")],
+                aliases: vec![],
+            },
+            HttpRoutes {
+                redirects: vec![redirect("/docs/*/page")],
+                aliases: vec![],
+            },
+            HttpRoutes {
+                redirects: vec![HttpRedirect {
+                    status_code: 307,
+                    ..redirect("/faq")
+                }],
+                aliases: vec![],
+            },
+            HttpRoutes {
+                redirects: vec![HttpRedirect {
+                    target: "https://example.com\r\nSet-Cookie: x".to_string(),
+                    ..redirect("/faq")
+                }],
+                aliases: vec![],
+            },
+            HttpRoutes {
+                redirects: vec![redirect("/about")],
+                aliases: vec![alias("/about", "/index.html")],
+            },
+            HttpRoutes {
+                redirects: vec![redirect("/faq")],
+                aliases: vec![alias("/about", "/faq")],
+            },
+            HttpRoutes {
+                redirects: vec![],
+                aliases: vec![alias("/metrics", "/index.html")],
+            },
+            HttpRoutes {
+                redirects: vec![],
+                aliases: vec![alias("about", "/index.html")],
+            },
+            HttpRoutes {
+                redirects: vec![redirect("/de/")],
+                aliases: vec![],
+            },
+            HttpRoutes {
+                redirects: vec![redirect("/fr/*")],
+                aliases: vec![],
+            },
+            HttpRoutes {
+                redirects: vec![],
+                aliases: vec![alias("/it/", "/index.html")],
+            },
+        ] {
+            assert!(
+                validate_routes(&routes).is_err(),
+                "expected {routes:?} to be rejected"
+            );
+        }
+    }
+}
@@ -1,26 +1,32 @@
-use crate::storage::anchor::DomainActivity;
+use crate::state;
 use crate::{IC0_APP_ORIGIN, INTERNETCOMPUTER_ORG_ORIGIN};
-#[derive(Eq, PartialEq)]
-pub enum IIDomain {
-    Ic0AppDomain,
-    InternetComputerOrgDomain,
-}
+use internet_identity_interface::internet_identity::types::FrontendHostname;
+
+/// II origins used if no origins have been configured using the init argument.
+pub const DEFAULT_II_ORIGINS: [&str; 2] = [IC0_APP_ORIGIN, INTERNETCOMPUTER_ORG_ORIGIN];
+
+/// Domains on the IC which are served by the boundary nodes without being registered as a custom domain.
+const IC_DOMAIN_SUFFIXES: [&str; 2] = [".ic0.app", ".icp0.io"];
+
+/// A domain II is served on, identified by its origin (e.g. `https://identity.ic0.app`).
+/// The set of II domains is configurable, see [ii_origins].
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub struct IIDomain(FrontendHostname);
 
 impl IIDomain {
-    pub fn is_same_domain(&self, activity: &DomainActivity) -> bool {
-        match self {
-            IIDomain::Ic0AppDomain => matches!(activity, DomainActivity::Ic0App),
-            IIDomain::InternetComputerOrgDomain => {
-                matches!(activity, DomainActivity::InternetComputerOrg)
-            }
-        }
+    pub fn origin(&self) -> &str {
+        &self.0
     }
 
-    pub fn other_ii_domain(&self) -> IIDomain {
-        match self {
-            IIDomain::Ic0AppDomain => IIDomain::InternetComputerOrgDomain,
-            IIDomain::InternetComputerOrgDomain => IIDomain::Ic0AppDomain,
-        }
+    /// Returns the II domain of the given origin, if it is one of the given II origins.
+    ///
+    /// Unlike [IIDomain::try_from], this does not access the persistent state and can thus be used
+    /// while the state is borrowed mutably.
+    pub fn from_origin(origin: &str, ii_origins: &[FrontendHostname]) -> Option<Self> {
+        ii_origins
+            .iter()
+            .any(|ii_origin| ii_origin == origin)
+            .then(|| IIDomain(origin.to_string()))
     }
 }
 
@@ -28,10 +34,77 @@ impl TryFrom<&str> for IIDomain {
     type Error = ();
 
     fn try_from(origin: &str) -> Result<Self, Self::Error> {
-        match origin {
-            IC0_APP_ORIGIN => Ok(IIDomain::Ic0AppDomain),
-            INTERNETCOMPUTER_ORG_ORIGIN => Ok(IIDomain::InternetComputerOrgDomain),
-            _ => Err(()),
+        IIDomain::from_origin(origin, &ii_origins()).ok_or(())
+    }
+}
+
+/// Checks that the given origins can be configured as II origins: there must be at least one and
+/// each must be an `https://` origin (i.e. a lowercase hostname, optionally followed by a port, but
+/// no path).
+pub fn validate_ii_origins(origins: &[FrontendHostname]) -> Result<(), String> {
+    if origins.is_empty() {
+        return Err("ii_origins must contain at least one origin".to_string());
+    }
+    for origin in origins {
+        let is_valid = origin.strip_prefix("https://").is_some_and(|authority| {
+            let (host, port) = match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            };
+            host.split('.').all(|label| {
+                !label.is_empty()
+                    && label
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            }) && port.map_or(true, |port| port.parse::<u16>().is_ok())
+        });
+        if !is_valid {
+            return Err(format!(
+                "invalid II origin \"{origin}\": expected an https:// origin without path"
+            ));
         }
     }
+    Ok(())
+}
+
+/// Returns the origins II is served on.
+pub fn ii_origins() -> Vec<FrontendHostname> {
+    state::persistent_state(|persistent_state| match persistent_state.ii_origins {
+        Some(ref origins) => origins.clone(),
+        None => DEFAULT_II_ORIGINS
+            .iter()
+            .map(|origin| origin.to_string())
+            .collect(),
+    })
+}
+
+/// Returns the II origins that are custom domains (i.e. not subdomains of the IC domains).
+/// Custom domains need to be listed in `/.well-known/ic-domains` and are not covered by the
+/// wildcard sources of the CSP.
+pub fn custom_domain_ii_origins() -> Vec<FrontendHostname> {
+    ii_origins()
+        .into_iter()
+        .filter(|origin| {
+            !IC_DOMAIN_SUFFIXES
+                .iter()
+                .any(|suffix| hostname(origin).ends_with(suffix))
+        })
+        .collect()
+}
+
+/// Returns the WebAuthn related origins document served at `/.well-known/webauthn`, listing all the
+/// II origins. This allows passkeys registered on one II domain to be used on the others.
+/// See https://github.com/w3c/webauthn/wiki/Explainer:-Related-origin-requests
+pub fn related_origins_document() -> Vec<u8> {
+    serde_json::json!({ "origins": ii_origins() })
+        .to_string()
+        .into_bytes()
+}
+
+/// Returns the given origin without the scheme.
+pub fn hostname(origin: &str) -> &str {
+    origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"))
+        .unwrap_or(origin)
 }
@@ -0,0 +1,130 @@
+//! Lightweight profiling of the instructions used by selected canister endpoints.
+//!
+//! At the end of a call, the endpoint records the reading of the instruction counter
+//! (see [record_instructions]) into a fixed-bucket histogram which is exported in `/metrics`.
+//!
+//! Note:
+//! * The instruction counter is reset on every await that results in an inter-canister call, so
+//!   for such calls only the instructions of the last message execution are recorded.
+//! * State changes of query calls are discarded, so only update endpoints can be profiled. The
+//!   query endpoints [UNPROFILED_QUERY_ENDPOINTS] are not profiled, only HTTP requests upgraded to
+//!   update calls are (as `http_request_update`). This gap is stated in the help text of the
+//!   histograms.
+//! * The histograms are not persisted across upgrades.
+use crate::state;
+use ic_cdk::api::performance_counter;
+
+/// Upper bounds (inclusive) of the histogram buckets. Instruction counts above the last bound are
+/// counted in an additional overflow bucket.
+pub const INSTRUCTION_BUCKETS: [u64; 10] = [
+    100_000,
+    300_000,
+    1_000_000,
+    3_000_000,
+    10_000_000,
+    30_000_000,
+    100_000_000,
+    300_000_000,
+    1_000_000_000,
+    3_000_000_000,
+];
+
+/// Query endpoints whose instructions are not profiled, as the histograms of query calls would be
+/// discarded together with all other state changes of the call.
+pub const UNPROFILED_QUERY_ENDPOINTS: [&str; 2] = ["get_delegation", "http_request"];
+
+/// The profiled endpoints.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum Endpoint {
+    CreateChallenge,
+    Register,
+    PrepareDelegation,
+    HttpRequestUpdate,
+}
+
+impl Endpoint {
+    /// Name of the endpoint as used in the metric names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Endpoint::CreateChallenge => "create_challenge",
+            Endpoint::Register => "register",
+            Endpoint::PrepareDelegation => "prepare_delegation",
+            Endpoint::HttpRequestUpdate => "http_request_update",
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct InstructionHistogram {
+    // The element at index `i` is the number of calls that fall into the bucket
+    // `INSTRUCTION_BUCKETS[i]`, the last element counts the calls above all bounds.
+    bucket_counts: [u64; INSTRUCTION_BUCKETS.len() + 1],
+    sum: u64,
+}
+
+impl Default for InstructionHistogram {
+    fn default() -> Self {
+        Self {
+            bucket_counts: [0; INSTRUCTION_BUCKETS.len() + 1],
+            sum: 0,
+        }
+    }
+}
+
+impl InstructionHistogram {
+    pub fn observe(&mut self, instructions: u64) {
+        let bucket = INSTRUCTION_BUCKETS
+            .iter()
+            .position(|bound| instructions <= *bound)
+            .unwrap_or(INSTRUCTION_BUCKETS.len());
+        self.bucket_counts[bucket] += 1;
+        self.sum = self.sum.saturating_add(instructions);
+    }
+
+    /// Returns the (non-cumulative) bucket counts together with the upper bound of each bucket,
+    /// the overflow bucket has the bound `f64::INFINITY`.
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        INSTRUCTION_BUCKETS
+            .iter()
+            .map(|bound| *bound as f64)
+            .chain(std::iter::once(f64::INFINITY))
+            .zip(self.bucket_counts.iter().map(|count| *count as f64))
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+}
+
+/// Records the instructions used so far by the current message execution for the given endpoint.
+/// Must be called at the end of the endpoint.
+pub fn record_instructions(endpoint: Endpoint) {
+    let instructions = performance_counter(0);
+    state::instruction_histograms_mut(|histograms| {
+        histograms
+            .entry(endpoint)
+            .or_default()
+            .observe(instructions)
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_count_instructions_in_matching_bucket() {
+        let mut histogram = InstructionHistogram::default();
+        histogram.observe(0);
+        histogram.observe(100_000);
+        histogram.observe(100_001);
+        histogram.observe(5_000_000_000);
+
+        let buckets: Vec<(f64, f64)> = histogram.buckets().collect();
+        assert_eq!(buckets.len(), INSTRUCTION_BUCKETS.len() + 1);
+        assert_eq!(buckets[0], (100_000f64, 2f64));
+        assert_eq!(buckets[1], (300_000f64, 1f64));
+        assert_eq!(buckets[INSTRUCTION_BUCKETS.len()], (f64::INFINITY, 1f64));
+        assert_eq!(histogram.sum(), 5_000_200_001);
+    }
+}
@@ -2,15 +2,20 @@ use crate::anchor_management::{post_operation_bookkeeping, tentative_device_regi
 use crate::archive::ArchiveState;
 use crate::assets::init_assets;
 use crate::ii_domain::IIDomain;
+use crate::instruction_profiling::{record_instructions, Endpoint};
 use crate::storage::anchor::Anchor;
 use candid::{candid_method, Principal};
-use ic_cdk::api::{caller, set_certified_data, trap};
+use ic_cdk::api::call::ManualReply;
+use ic_cdk::api::{caller, is_controller, set_certified_data, trap};
 use ic_cdk_macros::{init, post_upgrade, pre_upgrade, query, update};
 use internet_identity_interface::archive::types::{BufferedEntry, Operation};
-use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
+use internet_identity_interface::http_gateway::{
+    HttpRequest, HttpResponse, StreamingCallbackHttpResponse, Token,
+};
 use internet_identity_interface::internet_identity::types::*;
 use serde_bytes::ByteBuf;
 use std::collections::HashMap;
+use std::time::Duration;
 use storage::{Salt, Storage};
 
 mod activity_stats;
@@ -21,6 +26,7 @@ mod delegation;
 mod hash;
 mod http;
 mod ii_domain;
+mod instruction_profiling;
 /// Infrastructure to help building nested certification trees.
 mod nested_tree;
 mod state;
@@ -36,10 +42,7 @@ const DAY_NS: u64 = 24 * HOUR_NS;
 
 const LABEL_SIG: &[u8] = b"sig";
 
-// Note: concatenating const &str is a hassle in rust. It seemed easiest to just repeat.
-const IC0_APP_DOMAIN: &str = "identity.ic0.app";
 const IC0_APP_ORIGIN: &str = "https://identity.ic0.app";
-const INTERNETCOMPUTER_ORG_DOMAIN: &str = "identity.internetcomputer.org";
 const INTERNETCOMPUTER_ORG_ORIGIN: &str = "https://identity.internetcomputer.org";
 
 #[update]
@@ -86,20 +89,30 @@ fn verify_tentative_device(
     })
 }
 
-#[update]
+#[update(manual_reply = true)]
 #[candid_method]
-async fn create_challenge() -> Challenge {
-    anchor_management::registration::create_challenge().await
+async fn create_challenge() -> ManualReply<Challenge> {
+    let result = anchor_management::registration::create_challenge().await;
+    record_instructions(Endpoint::CreateChallenge);
+    match result {
+        Ok(challenge) => ManualReply::one(challenge),
+        Err(message) => ManualReply::reject(message),
+    }
 }
 
-#[update]
+#[update(manual_reply = true)]
 #[candid_method]
 fn register(
     device_data: DeviceData,
     challenge_result: ChallengeAttempt,
     temp_key: Option<Principal>,
-) -> RegisterResponse {
-    anchor_management::registration::register(device_data, challenge_result, temp_key)
+) -> ManualReply<RegisterResponse> {
+    let result = anchor_management::registration::register(device_data, challenge_result, temp_key);
+    record_instructions(Endpoint::Register);
+    match result {
+        Ok(response) => ManualReply::one(response),
+        Err(message) => ManualReply::reject(message),
+    }
 }
 
 #[update]
@@ -218,15 +231,24 @@ async fn prepare_delegation(
     session_key: SessionKey,
     max_time_to_live: Option<u64>,
 ) -> (UserKey, Timestamp) {
-    let ii_domain = authenticate_and_record_activity(anchor_number);
-    delegation::prepare_delegation(
+    let ii_domain = authenticate_and_record_activity_with_event(
+        anchor_number,
+        |device| Operation::PrepareDelegation {
+            device,
+            frontend: frontend.clone(),
+        },
+        Some(&frontend),
+    );
+    let result = delegation::prepare_delegation(
         anchor_number,
         frontend,
         session_key,
         max_time_to_live,
         &ii_domain,
     )
-    .await
+    .await;
+    record_instructions(Endpoint::PrepareDelegation);
+    result
 }
 
 #[query]
@@ -249,10 +271,65 @@ fn http_request(req: HttpRequest) -> HttpResponse {
     http::http_request(req)
 }
 
+#[query]
+#[candid_method(query)]
+fn http_request_streaming_callback(token: Token) -> StreamingCallbackHttpResponse {
+    http::http_request_streaming_callback(token)
+}
+
 #[update]
 #[candid_method]
 fn http_request_update(req: HttpRequest) -> HttpResponse {
-    http::http_request(req)
+    let response = http::http_request_update(req);
+    record_instructions(Endpoint::HttpRequestUpdate);
+    response
+}
+
+#[update]
+#[candid_method]
+fn asset_upload(chunk: AssetUploadChunk) {
+    check_controller();
+    assets::uploads::upload_chunk(chunk);
+}
+
+#[update]
+#[candid_method]
+fn asset_delete(path: String) {
+    check_controller();
+    assets::uploads::delete_asset(&path);
+}
+
+#[query]
+#[candid_method(query)]
+fn http_routes() -> HttpRoutes {
+    http::routes::configured_routes()
+}
+
+#[update]
+#[candid_method]
+fn http_routes_update(routes: HttpRoutes) {
+    check_controller();
+    if let Err(err) = http::routes::validate_routes(&routes) {
+        trap(&err);
+    }
+    state::persistent_state_mut(|persistent_state| {
+        persistent_state.http_routes = Some(routes.clone());
+    });
+    state::assets_mut(|certified_assets| assets::certify_routes(certified_assets, routes));
+    update_root_hash();
+}
+
+#[query]
+#[candid_method(query)]
+fn csp_violation_reports() -> Vec<CspViolationReport> {
+    check_controller();
+    state::persistent_state(|persistent_state| {
+        persistent_state
+            .csp_violation_reports
+            .as_ref()
+            .map(|reports| reports.reports().to_vec())
+            .unwrap_or_default()
+    })
 }
 
 #[query]
@@ -280,15 +357,8 @@ fn stats() -> InternetIdentityStats {
 
     let (latest_delegation_origins, max_num_latest_delegation_origins) =
         state::persistent_state(|persistent_state| {
-            let origins = persistent_state
-                .latest_delegation_origins
-                .as_ref()
-                .map(|latest_delegation_origins| {
-                    latest_delegation_origins.keys().cloned().collect()
-                })
-                .unwrap_or_default();
             (
-                origins,
+                delegation::latest_delegation_origins(persistent_state),
                 persistent_state.max_num_latest_delegation_origins.unwrap(),
             )
         });
@@ -304,6 +374,36 @@ fn stats() -> InternetIdentityStats {
     })
 }
 
+#[query]
+#[candid_method(query)]
+fn activity_stats() -> InternetIdentityActivityStats {
+    activity_stats::activity_stats()
+}
+
+#[query]
+#[candid_method(query)]
+fn anchor_population_stats() -> AnchorPopulationStats {
+    state::storage_borrow(|storage| storage.population_stats().clone())
+}
+
+#[query]
+#[candid_method(query)]
+fn registration_cohort_stats() -> RegistrationCohortStats {
+    activity_stats::registration_cohort_stats()
+}
+
+#[query]
+#[candid_method(query)]
+fn dapp_activity_stats() -> DappActivityStats {
+    activity_stats::dapp_activity_stats()
+}
+
+#[query]
+#[candid_method(query)]
+fn health_check() -> HealthReport {
+    http::health::health_report()
+}
+
 #[update]
 #[candid_method]
 async fn deploy_archive(wasm: ByteBuf) -> DeployArchiveResult {
@@ -327,13 +427,22 @@ fn acknowledge_entries(sequence_number: u64) {
     archive::acknowledge_entries(sequence_number)
 }
 
+/// Returns the ranges of archive entries that were never transferred to the archive.
+#[query]
+#[candid_method(query)]
+fn dropped_archive_entries() -> Vec<DroppedArchiveEntries> {
+    archive::dropped_archive_entries()
+}
+
 #[init]
 #[candid_method(init)]
 fn init(maybe_arg: Option<InternetIdentityInit>) {
-    init_assets();
     state::init_new();
 
     apply_install_arg(maybe_arg);
+    // the assets depend on the configured II origins
+    init_assets();
+    http::init_certification_refresh();
 
     // make sure the fully initialized storage configuration is written to stable memory
     state::storage_borrow_mut(|storage| storage.flush());
@@ -342,16 +451,25 @@ fn init(maybe_arg: Option<InternetIdentityInit>) {
 
 #[post_upgrade]
 fn post_upgrade(maybe_arg: Option<InternetIdentityInit>) {
-    init_assets();
     state::init_from_stable_memory();
 
-    // We drop all the signatures on upgrade, users will
-    // re-request them if needed.
-    update_root_hash();
     // load the persistent state after initializing storage, otherwise the memory address to load it from cannot be calculated
     state::load_persistent_state();
 
     apply_install_arg(maybe_arg);
+    // the assets depend on the configured II origins
+    init_assets();
+    activity_stats::configure_activity_windows();
+    http::init_certification_refresh();
+
+    // We drop all the signatures on upgrade, users will
+    // re-request them if needed.
+    update_root_hash();
+
+    // timers do not survive upgrades --> resume pushing entries that are still buffered (if any)
+    archive::schedule_push_if_required(Duration::ZERO);
+    // count the anchors that are not yet included in the population stats (if any)
+    storage::population_stats::schedule_backfill();
 }
 
 fn apply_install_arg(maybe_arg: Option<InternetIdentityInit>) {
@@ -384,6 +502,46 @@ fn apply_install_arg(maybe_arg: Option<InternetIdentityInit>) {
                 persistent_state.max_inflight_captchas = Some(limit);
             })
         }
+        if let Some(origins) = arg.ii_origins {
+            if let Err(err) = ii_domain::validate_ii_origins(&origins) {
+                trap(&err);
+            }
+            state::persistent_state_mut(|persistent_state| {
+                persistent_state.ii_origins = Some(origins);
+            })
+        }
+        if let Some(windows) = arg.activity_windows {
+            if let Err(err) = activity_stats::validate_activity_windows(&windows) {
+                trap(&err);
+            }
+            state::persistent_state_mut(|persistent_state| {
+                persistent_state.activity_windows = Some(windows);
+            })
+        }
+        if let Some(filter) = arg.dev_origin_filter {
+            if let Err(err) = delegation::validate_dev_origin_filter(&filter) {
+                trap(&err);
+            }
+            state::persistent_state_mut(|persistent_state| {
+                persistent_state.dev_origin_filter = Some(filter);
+            })
+        }
+        if let Some(config) = arg.content_security_policy {
+            if let Err(err) = http::csp::validate_csp_config(&config) {
+                trap(&err);
+            }
+            state::persistent_state_mut(|persistent_state| {
+                persistent_state.content_security_policy = Some(config);
+            })
+        }
+        if let Some(routes) = arg.http_routes {
+            if let Err(err) = http::routes::validate_routes(&routes) {
+                trap(&err);
+            }
+            state::persistent_state_mut(|persistent_state| {
+                persistent_state.http_routes = Some(routes);
+            })
+        }
     }
 }
 
@@ -417,20 +575,54 @@ fn update_root_hash() {
     })
 }
 
+/// Traps if the caller is not a controller of this canister.
+fn check_controller() {
+    let caller = caller();
+    if !is_controller(&caller) {
+        trap(&format!("{caller} is not a controller of this canister."));
+    }
+}
+
 /// Authenticates the caller (traps if not authenticated) and updates the device used to authenticate
 /// reflecting the current activity. Also updates the aggregated stats on daily and monthly active users.
 ///
 /// Note: this function reads / writes the anchor from / to stable memory. It is intended to be used by functions that
 /// do not further modify the anchor.
 fn authenticate_and_record_activity(anchor_number: AnchorNumber) -> Option<IIDomain> {
+    authenticate_and_record_activity_with_event(
+        anchor_number,
+        |device| Operation::Authenticate { device },
+        None,
+    )
+}
+
+/// Same as [authenticate_and_record_activity] but allows to specify the authentication event to be
+/// archived (given the key of the device used to authenticate) if the anchor opted in to archiving
+/// authentication events.
+/// If a delegation frontend is given, the per-dapp activity stats are updated as well.
+fn authenticate_and_record_activity_with_event(
+    anchor_number: AnchorNumber,
+    authn_event: impl FnOnce(DeviceKey) -> Operation,
+    delegation_frontend: Option<&FrontendHostname>,
+) -> Option<IIDomain> {
     let Ok((mut anchor, device_key)) = check_authentication(anchor_number) else {
         trap(&format!("{} could not be authenticated.", caller()));
     };
     let domain = anchor.device(&device_key).unwrap().ii_domain();
-    anchor_management::activity_bookkeeping(&mut anchor, &device_key);
+    anchor_management::activity_bookkeeping(anchor_number, &mut anchor, &device_key);
+    if let Some(frontend) = delegation_frontend {
+        // same filter as for the latest delegation origins
+        if domain.is_some() && !delegation::is_dev_frontend(frontend) {
+            activity_stats::update_dapp_activity_stats(&mut anchor, frontend);
+        }
+    }
+    let archive_authn_events = anchor.archive_authn_events();
     state::storage_borrow_mut(|storage| storage.write(anchor_number, anchor)).unwrap_or_else(
         |err| panic!("last_usage_timestamp update: unable to update anchor {anchor_number}: {err}"),
     );
+    if archive_authn_events {
+        archive::archive_authn_event(anchor_number, caller(), authn_event(device_key));
+    }
     domain
 }
 
@@ -451,7 +643,7 @@ fn authenticated_anchor_operation<R>(
     let Ok((mut anchor, device_key)) = check_authentication(anchor_number) else {
         trap(&format!("{} could not be authenticated.", caller()));
     };
-    anchor_management::activity_bookkeeping(&mut anchor, &device_key);
+    anchor_management::activity_bookkeeping(anchor_number, &mut anchor, &device_key);
 
     let result = op(&mut anchor);
 
@@ -476,13 +668,15 @@ fn check_authentication(anchor_number: AnchorNumber) -> Result<(Anchor, DeviceKe
     let caller = caller();
 
     for device in anchor.devices() {
-        if caller == Principal::self_authenticating(&device.pubkey)
-            || state::with_temp_keys_mut(|temp_keys| {
-                temp_keys
-                    .check_temp_key(&caller, &device.pubkey, anchor_number)
-                    .is_ok()
-            })
-        {
+        if caller == Principal::self_authenticating(&device.pubkey) {
+            return Ok((anchor.clone(), device.pubkey.clone()));
+        }
+        if state::with_temp_keys_mut(|temp_keys| {
+            temp_keys
+                .check_temp_key(&caller, &device.pubkey, anchor_number)
+                .is_ok()
+        }) {
+            state::usage_metrics_mut(|metrics| metrics.temp_key_authentications += 1);
             return Ok((anchor.clone(), device.pubkey.clone()));
         }
     }
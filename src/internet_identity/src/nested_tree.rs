@@ -112,6 +112,14 @@ impl<K: NestedTreeKeyRequirements, V: NestedTreeValueRequirements> NestedTree<K,
                 NestedTree::Leaf(_) => {}
                 NestedTree::Nested(tree) => {
                     tree.modify(key.as_ref(), |child| child.delete(&path[1..]));
+                    // remove empty subtrees, so that their absence can be proven
+                    let child_is_empty = matches!(
+                        tree.get(key.as_ref()),
+                        Some(NestedTree::Nested(child)) if child.is_empty()
+                    );
+                    if child_is_empty {
+                        tree.delete(key.as_ref());
+                    }
                 }
             }
         } else {
@@ -194,6 +202,7 @@ fn nested_tree_operation() {
     assert_eq!(tree.get(&["one", "three"]), None);
     assert!(tree.contains_leaf(&["one", "two"]));
     assert!(!tree.contains_leaf(&["one", "three"]));
+    assert!(!tree.contains_path(&["one", "three"]));
 
     // deleting subtree works
     tree.delete(&["one"]);
@@ -201,4 +210,5 @@ fn nested_tree_operation() {
     assert_eq!(tree.get(&["one"]), None);
     assert!(!tree.contains_leaf(&["one", "two"]));
     assert!(!tree.contains_leaf(&["one"]));
+    assert!(!tree.contains_path(&["one"]));
 }
@@ -1,9 +1,15 @@
 use crate::activity_stats::activity_counter::active_anchor_counter::ActiveAnchorCounter;
 use crate::activity_stats::activity_counter::authn_method_counter::AuthnMethodCounter;
+use crate::activity_stats::activity_counter::dapp_active_anchor_counter::DappActiveAnchorCounter;
 use crate::activity_stats::activity_counter::domain_active_anchor_counter::DomainActiveAnchorCounter;
+use crate::activity_stats::cohort_stats::CohortStats;
 use crate::activity_stats::ActivityStats;
-use crate::archive::{ArchiveData, ArchiveState, ArchiveStatusCache};
+use crate::archive::{ArchiveData, ArchivePushState, ArchiveState, ArchiveStatusCache};
+use crate::assets::uploads::PendingAssetUpload;
 use crate::assets::CertifiedAssets;
+use crate::delegation::DelegationOriginUsage;
+use crate::http::csp::CspViolationReports;
+use crate::instruction_profiling::{Endpoint, InstructionHistogram};
 use crate::state::temp_keys::TempKeys;
 use crate::storage::anchor::Anchor;
 use crate::storage::DEFAULT_RANGE_SIZE;
@@ -15,7 +21,7 @@ use ic_cdk::{call, trap};
 use ic_stable_structures::DefaultMemoryImpl;
 use internet_identity_interface::internet_identity::types::*;
 use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::{Deref, DerefMut};
 use std::time::Duration;
 
@@ -47,12 +53,34 @@ pub enum RegistrationState {
     },
 }
 
-#[derive(Default)]
+#[derive(Clone, Default, CandidType, Deserialize, Eq, PartialEq, Debug)]
 pub struct UsageMetrics {
-    // number of prepare_delegation calls since last upgrade
+    // number of prepare_delegation calls
     pub delegation_counter: u64,
-    // number of anchor operations (register, add, remove, update) since last upgrade
+    // number of anchor operations (register, add, remove, update)
     pub anchor_operation_counter: u64,
+    // number of captchas created by create_challenge
+    pub captchas_created: u64,
+    // number of captchas that expired without a solution being submitted
+    pub captchas_expired: u64,
+    // number of captchas solved correctly
+    pub captchas_solved: u64,
+    // number of solutions submitted for unknown (e.g. expired) captchas or with the wrong characters
+    pub captchas_failed: u64,
+    // number of create_challenge calls rejected because of too many inflight captchas
+    pub captcha_limit_rejections: u64,
+    // number of register calls that created a new anchor
+    pub registrations_succeeded: u64,
+    // number of register calls rejected because of a bad challenge result
+    pub registrations_bad_challenge: u64,
+    // number of register calls rejected because the canister is full
+    pub registrations_canister_full: u64,
+    // number of register calls rejected by the registration rate limit
+    pub registration_rate_limit_rejections: u64,
+    // number of registrations that added a temporary key
+    pub temp_keys_added: u64,
+    // number of calls authenticated using a temporary key
+    pub temp_key_authentications: u64,
 }
 
 // The challenges we store and check against
@@ -77,7 +105,7 @@ pub struct Challenge {
     pub challenge_key: ChallengeKey,
 }
 
-#[derive(Clone, CandidType, Deserialize, Eq, PartialEq, Debug)]
+#[derive(Clone, CandidType, Deserialize, PartialEq, Debug)]
 pub struct PersistentState {
     // Information related to the archive
     pub archive_state: ArchiveState,
@@ -91,12 +119,36 @@ pub struct PersistentState {
     pub domain_active_anchor_stats: Option<ActivityStats<DomainActiveAnchorCounter>>,
     // Daily and monthly active authentication methods on the II domains.
     pub active_authn_method_stats: Option<ActivityStats<AuthnMethodCounter>>,
-    // Hashmap of last used delegation origins
+    // Daily and monthly active anchors per delegation frontend (for the most active frontends).
+    pub dapp_active_anchor_stats: Option<ActivityStats<DappActiveAnchorCounter>>,
+    // Hashmap of last used delegation origins, only set while the state is persisted across an
+    // upgrade to allow rolling back to a release without delegation_origin_usage
     pub latest_delegation_origins: Option<HashMap<FrontendHostname, Timestamp>>,
+    // Hashmap of last used delegation origins with their usage
+    pub delegation_origin_usage: Option<HashMap<FrontendHostname, DelegationOriginUsage>>,
     // Maximum number of latest delegation origins to store
     pub max_num_latest_delegation_origins: Option<u64>,
     // Maximum number of inflight captchas
     pub max_inflight_captchas: Option<u64>,
+    // Origins II is served on (see ii_domain::DEFAULT_II_ORIGINS if not set)
+    pub ii_origins: Option<Vec<FrontendHostname>>,
+    // Activity stats collection windows in addition to the built-in daily and monthly windows
+    pub activity_windows: Option<Vec<ActivityWindowConfig>>,
+    // Retention statistics of the weekly registration cohorts
+    pub registration_cohort_stats: Option<CohortStats>,
+    // Rules to filter out development delegation origins (see delegation::dev_origin_filter if not set)
+    pub dev_origin_filter: Option<DevOriginFilter>,
+    // Redirects and aliases served by the HTTP interface (see http::routes::default_routes if not set)
+    pub http_routes: Option<HttpRoutes>,
+    // Content-Security-Policy configuration (see http::csp for the defaults)
+    pub content_security_policy: Option<ContentSecurityPolicyConfig>,
+    // Most recently reported CSP violations
+    pub csp_violation_reports: Option<CspViolationReports>,
+    // Anchor population stats, only set while the state is persisted across an upgrade
+    // (see Storage::population_stats)
+    pub anchor_population_stats: Option<AnchorPopulationStats>,
+    // Usage metrics, only set while the state is persisted across an upgrade (see usage_metrics)
+    pub usage_metrics: Option<UsageMetrics>,
 }
 
 impl Default for PersistentState {
@@ -108,9 +160,20 @@ impl Default for PersistentState {
             active_anchor_stats: None,
             domain_active_anchor_stats: None,
             active_authn_method_stats: None,
+            dapp_active_anchor_stats: None,
             latest_delegation_origins: None,
+            delegation_origin_usage: None,
             max_num_latest_delegation_origins: Some(MAX_NUM_DELEGATION_ORIGINS),
             max_inflight_captchas: Some(MAX_INFLIGHT_CAPTCHAS),
+            ii_origins: None,
+            activity_windows: None,
+            registration_cohort_stats: None,
+            dev_origin_filter: None,
+            http_routes: None,
+            content_security_policy: None,
+            csp_violation_reports: None,
+            anchor_population_stats: None,
+            usage_metrics: None,
         }
     }
 }
@@ -142,8 +205,10 @@ struct State {
     // tentative device registrations, not persisted across updates
     // if an anchor number is present in this map then registration mode is active until expiration
     tentative_device_registrations: RefCell<HashMap<AnchorNumber, TentativeDeviceRegistration>>,
-    // additional usage metrics, NOT persisted across updates (but probably should be in the future)
+    // additional usage metrics, persisted across upgrades using the persistent state
     usage_metrics: RefCell<UsageMetrics>,
+    // instruction histograms of the profiled endpoints, NOT persisted across upgrades
+    instruction_histograms: RefCell<BTreeMap<Endpoint, InstructionHistogram>>,
     // State that is temporarily persisted in stable memory during upgrades using
     // pre- and post-upgrade hooks.
     // This must remain small as it is serialized and deserialized on pre- and post-upgrade.
@@ -151,8 +216,12 @@ struct State {
     persistent_state: RefCell<PersistentState>,
     // Cache of the archive status (to make unwanted calls to deploy_archive cheap to dismiss).
     archive_status_cache: RefCell<Option<ArchiveStatusCache>>,
+    // Bookkeeping of pushing entries to the archive. Not persisted across upgrades.
+    archive_push_state: RefCell<ArchivePushState>,
     // Tracking data for the registration rate limit, if any. Not persisted across upgrades.
     registration_rate_limit: RefCell<Option<RateLimitState>>,
+    // Assets that are being uploaded (by file path). Not persisted across upgrades.
+    pending_asset_uploads: RefCell<HashMap<String, PendingAssetUpload>>,
 }
 
 impl Default for State {
@@ -165,9 +234,12 @@ impl Default for State {
             inflight_challenges: RefCell::new(HashMap::new()),
             tentative_device_registrations: RefCell::new(HashMap::new()),
             usage_metrics: RefCell::new(UsageMetrics::default()),
+            instruction_histograms: RefCell::new(BTreeMap::new()),
             persistent_state: RefCell::new(PersistentState::default()),
             archive_status_cache: RefCell::new(None),
+            archive_push_state: RefCell::new(ArchivePushState::default()),
             registration_rate_limit: RefCell::new(None),
+            pending_asset_uploads: RefCell::new(HashMap::new()),
         }
     }
 }
@@ -246,7 +318,21 @@ pub fn init_from_stable_memory() {
 
 pub fn save_persistent_state() {
     STATE.with(|s| {
-        storage_borrow_mut(|storage| storage.write_persistent_state(&s.persistent_state.borrow()))
+        storage_borrow_mut(|storage| {
+            let mut persistent_state = s.persistent_state.borrow_mut();
+            persistent_state.anchor_population_stats = Some(storage.population_stats().clone());
+            persistent_state.usage_metrics = Some(s.usage_metrics.borrow().clone());
+            persistent_state.latest_delegation_origins = persistent_state
+                .delegation_origin_usage
+                .as_ref()
+                .map(|origins| {
+                    origins
+                        .iter()
+                        .map(|(origin, usage)| (origin.clone(), usage.last_usage_timestamp))
+                        .collect()
+                });
+            storage.write_persistent_state(&persistent_state)
+        })
     })
 }
 
@@ -258,6 +344,41 @@ pub fn load_persistent_state() {
         })
     });
 
+    // The anchor population stats are maintained by the storage while the canister is running.
+    // If they are missing (e.g. after an upgrade from a release without them), they are
+    // backfilled by scanning all anchors.
+    if let Some(stats) =
+        persistent_state_mut(|persistent_state| persistent_state.anchor_population_stats.take())
+    {
+        storage_borrow_mut(|storage| storage.set_population_stats(stats));
+    }
+    if let Some(metrics) =
+        persistent_state_mut(|persistent_state| persistent_state.usage_metrics.take())
+    {
+        usage_metrics_mut(|usage_metrics| *usage_metrics = metrics);
+    }
+
+    // Releases without delegation_origin_usage only kept the last usage timestamp of the latest
+    // delegation origins. Such origins are counted as used once.
+    persistent_state_mut(|persistent_state| {
+        if let Some(origins) = persistent_state.latest_delegation_origins.take() {
+            persistent_state
+                .delegation_origin_usage
+                .get_or_insert_with(|| {
+                    origins
+                        .into_iter()
+                        .map(|(origin, timestamp)| {
+                            let usage = DelegationOriginUsage {
+                                last_usage_timestamp: timestamp,
+                                decayed_count: 1.0,
+                            };
+                            (origin, usage)
+                        })
+                        .collect()
+                });
+        }
+    });
+
     // Initialize a sensible default for max_latest_delegation_origins
     // if it is not set in the persistent state.
     // This will allow us to later drop the opt and make the field u64.
@@ -296,6 +417,10 @@ pub fn archive_data_mut<R>(f: impl FnOnce(&mut ArchiveData) -> R) -> R {
     })
 }
 
+pub fn archive_push_state_mut<R>(f: impl FnOnce(&mut ArchivePushState) -> R) -> R {
+    STATE.with(|s| f(&mut s.archive_push_state.borrow_mut()))
+}
+
 pub fn tentative_device_registrations<R>(
     f: impl FnOnce(&HashMap<AnchorNumber, TentativeDeviceRegistration>) -> R,
 ) -> R {
@@ -335,6 +460,15 @@ pub fn storage_borrow<R>(f: impl FnOnce(&Storage<DefaultMemoryImpl>) -> R) -> R
     })
 }
 
+pub fn storage_initialised() -> bool {
+    STATE.with(|s| {
+        matches!(
+            s.storage_state.borrow().deref(),
+            StorageState::Initialised(_)
+        )
+    })
+}
+
 pub fn storage_borrow_mut<R>(f: impl FnOnce(&mut Storage<DefaultMemoryImpl>) -> R) -> R {
     STATE.with(|s| match s.storage_state.borrow_mut().deref_mut() {
         StorageState::Uninitialised => trap("Storage not initialized."),
@@ -362,6 +496,18 @@ pub fn usage_metrics_mut<R>(f: impl FnOnce(&mut UsageMetrics) -> R) -> R {
     STATE.with(|s| f(&mut s.usage_metrics.borrow_mut()))
 }
 
+pub fn instruction_histograms<R>(
+    f: impl FnOnce(&BTreeMap<Endpoint, InstructionHistogram>) -> R,
+) -> R {
+    STATE.with(|s| f(&s.instruction_histograms.borrow()))
+}
+
+pub fn instruction_histograms_mut<R>(
+    f: impl FnOnce(&mut BTreeMap<Endpoint, InstructionHistogram>) -> R,
+) -> R {
+    STATE.with(|s| f(&mut s.instruction_histograms.borrow_mut()))
+}
+
 pub fn inflight_challenges<R>(f: impl FnOnce(&HashMap<ChallengeKey, ChallengeInfo>) -> R) -> R {
     STATE.with(|s| f(&s.inflight_challenges.borrow()))
 }
@@ -392,6 +538,12 @@ pub fn registration_rate_limit_mut<R>(f: impl FnOnce(&mut Option<RateLimitState>
     STATE.with(|s| f(&mut s.registration_rate_limit.borrow_mut()))
 }
 
+pub fn pending_asset_uploads_mut<R>(
+    f: impl FnOnce(&mut HashMap<String, PendingAssetUpload>) -> R,
+) -> R {
+    STATE.with(|s| f(&mut s.pending_asset_uploads.borrow_mut()))
+}
+
 pub fn cached_archive_status() -> Option<ArchiveStatusCache> {
     STATE.with(|s| match *s.archive_status_cache.borrow() {
         None => None,
@@ -63,6 +63,12 @@
 //! The [PersistentState] is serialized at the end of stable memory to allow for variable sized data
 //! without the risk of running out of space (which might easily happen if the RESERVED_HEADER_BYTES
 //! were used instead).
+//!
+//! ## Uploaded Assets
+//!
+//! Assets uploaded at runtime are kept in two additional virtual memories of the memory manager
+//! (the anchor records above live in the first one): one holds the index of the assets, the other
+//! one their content, see [uploaded_assets].
 use std::convert::TryInto;
 use std::fmt;
 use std::io::{Read, Write};
@@ -76,10 +82,14 @@ use ic_stable_structures::{Memory, RestrictedMemory};
 
 use internet_identity_interface::internet_identity::types::*;
 
+use crate::assets::uploads::UploadedAsset;
 use crate::state::PersistentState;
 use crate::storage::anchor::Anchor;
+use crate::storage::uploaded_assets::UploadedAssets;
 
 pub mod anchor;
+pub mod population_stats;
+pub mod uploaded_assets;
 
 #[cfg(test)]
 mod tests;
@@ -106,13 +116,17 @@ const MAX_WASM_PAGES: u64 = MAX_STABLE_MEMORY_SIZE / WASM_PAGE_SIZE;
 /// This limit has last been raised when it was still 32 GB.
 const STABLE_MEMORY_SIZE: u64 = 32 * GB;
 /// We reserve the last ~800 MB of stable memory for later new features.
-const STABLE_MEMORY_RESERVE: u64 = 8 * GB / 10;
+pub const STABLE_MEMORY_RESERVE: u64 = 8 * GB / 10;
 
 const PERSISTENT_STATE_MAGIC: [u8; 4] = *b"IIPS"; // II Persistent State
 
 /// MemoryManager parameters.
 const ANCHOR_MEMORY_INDEX: u8 = 0u8;
 const ANCHOR_MEMORY_ID: MemoryId = MemoryId::new(ANCHOR_MEMORY_INDEX);
+const ASSET_MEMORY_INDEX: u8 = 1u8;
+const ASSET_MEMORY_ID: MemoryId = MemoryId::new(ASSET_MEMORY_INDEX);
+const ASSET_CHUNK_MEMORY_INDEX: u8 = 2u8;
+const ASSET_CHUNK_MEMORY_ID: MemoryId = MemoryId::new(ASSET_CHUNK_MEMORY_INDEX);
 // The bucket size 128 is relatively low, to avoid wasting memory when using
 // multiple virtual memories for smaller amounts of data.
 // This value results in 256 GB of total managed memory, which should be enough
@@ -123,6 +137,12 @@ const BUCKET_SIZE_IN_PAGES: u16 = 128;
 pub const DEFAULT_RANGE_SIZE: u64 =
     (STABLE_MEMORY_SIZE - ENTRY_OFFSET - STABLE_MEMORY_RESERVE) / DEFAULT_ENTRY_SIZE as u64;
 
+/// Returns the stable memory (in bytes) not yet allocated, given the number of allocated stable
+/// memory pages. The anchors never use the last [STABLE_MEMORY_RESERVE] bytes of it.
+pub fn stable_memory_headroom(stable_memory_pages: u64) -> u64 {
+    STABLE_MEMORY_SIZE.saturating_sub(stable_memory_pages.saturating_mul(WASM_PAGE_SIZE))
+}
+
 pub type Salt = [u8; 32];
 
 /// Data type responsible for managing anchor data in stable memory.
@@ -130,6 +150,11 @@ pub struct Storage<M: Memory> {
     header: Header,
     header_memory: RestrictedMemory<M>,
     anchor_memory: VirtualMemory<RestrictedMemory<M>>,
+    // Assets uploaded at runtime, see [Storage::uploaded_assets].
+    uploaded_assets: UploadedAssets<VirtualMemory<RestrictedMemory<M>>>,
+    // Aggregate statistics over the anchors, updated on every write.
+    // Kept in the persistent state across upgrades (see [Storage::set_population_stats]).
+    population_stats: AnchorPopulationStats,
 }
 
 #[repr(packed)]
@@ -171,6 +196,10 @@ impl<M: Memory + Clone> Storage<M> {
             BUCKET_SIZE_IN_PAGES,
         );
         let anchor_memory = memory_manager.get(ANCHOR_MEMORY_ID);
+        let uploaded_assets = UploadedAssets::init(
+            memory_manager.get(ASSET_MEMORY_ID),
+            memory_manager.get(ASSET_CHUNK_MEMORY_ID),
+        );
         let version: u8 = 7;
 
         let mut storage = Self {
@@ -186,6 +215,8 @@ impl<M: Memory + Clone> Storage<M> {
             },
             header_memory,
             anchor_memory,
+            uploaded_assets,
+            population_stats: AnchorPopulationStats::default(),
         };
         storage.flush();
         storage
@@ -253,10 +284,17 @@ impl<M: Memory + Clone> Storage<M> {
                 let memory_manager =
                     MemoryManager::init_with_bucket_size(managed_memory, BUCKET_SIZE_IN_PAGES);
                 let anchor_memory = memory_manager.get(ANCHOR_MEMORY_ID);
+                let uploaded_assets = UploadedAssets::init(
+                    memory_manager.get(ASSET_MEMORY_ID),
+                    memory_manager.get(ASSET_CHUNK_MEMORY_ID),
+                );
                 Some(Self {
                     header,
                     header_memory,
                     anchor_memory,
+                    uploaded_assets,
+                    // restored from the persistent state, see [Storage::set_population_stats]
+                    population_stats: AnchorPopulationStats::default(),
                 })
             }
             _ => trap(&format!("unsupported header version: {}", header.version)),
@@ -278,10 +316,30 @@ impl<M: Memory + Clone> Storage<M> {
     }
 
     /// Writes the data of the specified anchor to stable memory.
+    /// Also updates the anchor population stats by diffing the previous and the new anchor data.
     pub fn write(&mut self, anchor_number: AnchorNumber, data: Anchor) -> Result<(), StorageError> {
         let record_number = self.anchor_number_to_record(anchor_number)?;
-        let buf = candid::encode_one(data).map_err(StorageError::SerializationError)?;
-        self.write_entry_bytes(record_number, buf)
+        let buf = candid::encode_one(&data).map_err(StorageError::SerializationError)?;
+
+        let counted_anchors = self.population_stats.counted_anchors;
+        let previous = if (record_number as u64) < counted_anchors {
+            self.read_anchor_record(record_number)
+        } else {
+            None
+        };
+        self.write_entry_bytes(record_number, buf)?;
+
+        if let Some(previous) = previous {
+            population_stats::uncount_anchor(&mut self.population_stats, &previous);
+        }
+        if (record_number as u64) < counted_anchors {
+            population_stats::count_anchor(&mut self.population_stats, &data);
+        } else if record_number as u64 == counted_anchors {
+            // the anchor is next in line to be counted (e.g. a newly registered anchor)
+            population_stats::count_anchor(&mut self.population_stats, &data);
+            self.population_stats.counted_anchors += 1;
+        }
+        Ok(())
     }
 
     fn write_entry_bytes(&mut self, record_number: u32, buf: Vec<u8>) -> Result<(), StorageError> {
@@ -309,6 +367,19 @@ impl<M: Memory + Clone> Storage<M> {
         candid::decode_one(&data_buf).map_err(StorageError::DeserializationError)
     }
 
+    /// Reads the anchor of the given record, returning `None` (instead of trapping) if the record
+    /// does not contain a valid anchor.
+    fn read_anchor_record(&self, record_number: u32) -> Option<Anchor> {
+        let address = self.record_address(record_number);
+        let mut len_buf = [0; 2];
+        self.anchor_memory.read(address, &mut len_buf);
+        let len = u16::from_le_bytes(len_buf) as usize;
+        if len == 0 || len > self.candid_entry_size_limit() {
+            return None;
+        }
+        candid::decode_one(&self.read_entry_bytes(record_number)).ok()
+    }
+
     fn read_entry_bytes(&self, record_number: u32) -> Vec<u8> {
         let address = self.record_address(record_number);
         // the reader will check stable memory bounds
@@ -353,6 +424,65 @@ impl<M: Memory + Clone> Storage<M> {
         writer.write_all(slice).expect("bug: failed to grow memory");
     }
 
+    /// Returns the anchor population stats. The stats only include the first
+    /// `counted_anchors` anchors.
+    pub fn population_stats(&self) -> &AnchorPopulationStats {
+        &self.population_stats
+    }
+
+    /// Replaces the anchor population stats, e.g. with the stats restored from the persistent state
+    /// on upgrade.
+    pub fn set_population_stats(&mut self, stats: AnchorPopulationStats) {
+        self.population_stats = stats;
+    }
+
+    /// Adds up to `max_anchors` anchors that are not yet included in the population stats to the
+    /// stats. Returns `true` if all anchors are included.
+    pub fn backfill_population_stats(&mut self, max_anchors: u32) -> bool {
+        let num_anchors = self.header.num_anchors as u64;
+        let end = num_anchors.min(self.population_stats.counted_anchors + max_anchors as u64);
+        while self.population_stats.counted_anchors < end {
+            let record_number = self.population_stats.counted_anchors as u32;
+            if let Some(anchor) = self.read_anchor_record(record_number) {
+                population_stats::count_anchor(&mut self.population_stats, &anchor);
+            }
+            self.population_stats.counted_anchors += 1;
+        }
+        self.population_stats.counted_anchors >= num_anchors
+    }
+
+    /// Returns the assets uploaded at runtime (see [crate::assets::uploads]).
+    pub fn uploaded_assets(&self) -> Vec<UploadedAsset> {
+        self.uploaded_assets
+            .sizes()
+            .into_iter()
+            .filter_map(|(file_path, _)| self.uploaded_assets.get(&file_path))
+            .collect()
+    }
+
+    /// Returns the file paths and sizes of the assets uploaded at runtime, without reading their
+    /// content.
+    pub fn uploaded_asset_sizes(&self) -> Vec<(String, u64)> {
+        self.uploaded_assets.sizes()
+    }
+
+    /// Returns the total size of the assets uploaded at runtime.
+    pub fn uploaded_assets_total_size(&self) -> u64 {
+        self.uploaded_assets.total_size()
+    }
+
+    /// Stores an asset uploaded at runtime, replacing the asset previously uploaded with the same
+    /// file path (if any). Only the data of the given asset is written.
+    pub fn store_uploaded_asset(&mut self, asset: &UploadedAsset) {
+        self.uploaded_assets.insert(asset);
+    }
+
+    /// Removes the asset uploaded at runtime with the given file path. Returns whether there was
+    /// such an asset.
+    pub fn remove_uploaded_asset(&mut self, file_path: &str) -> bool {
+        self.uploaded_assets.remove(file_path)
+    }
+
     pub fn anchor_count(&self) -> usize {
         self.header.num_anchors as usize
     }
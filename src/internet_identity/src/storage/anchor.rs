@@ -1,14 +1,47 @@
+use crate::activity_stats::MAX_WINDOW_LENGTH_NS;
+use crate::ii_domain;
 use crate::ii_domain::IIDomain;
-use crate::{IC0_APP_ORIGIN, INTERNETCOMPUTER_ORG_ORIGIN};
 use candid::{CandidType, Deserialize, Principal};
 use internet_identity_interface::archive::types::DeviceDataWithoutAlias;
 use internet_identity_interface::internet_identity::types::*;
-use std::collections::HashMap;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
 use std::{fmt, iter};
 
 #[cfg(test)]
 mod tests;
 
+/// Identity metadata key to opt in to archiving authentication and delegation events.
+/// The events are archived if the key is set to the string value `"true"`.
+pub const ARCHIVE_AUTHN_EVENTS_METADATA_KEY: &str = "archive_authn_events";
+
+/// Maximum number of frontends per anchor for which the last delegation timestamp is kept.
+/// Anchors using more frontends within [DELEGATION_ACTIVITY_RETENTION_NS] might be counted more
+/// than once per frontend in the per-dapp activity stats.
+const MAX_DELEGATION_ACTIVITY_ENTRIES: usize = 10;
+
+/// Duration for which the last delegation timestamp of a frontend is kept. Covers the longest
+/// activity stats collection window that can be configured.
+const DELEGATION_ACTIVITY_RETENTION_NS: u64 = MAX_WINDOW_LENGTH_NS;
+
+/// Number of bytes of the hash of a frontend kept in [DelegationActivity].
+const DELEGATION_FRONTEND_HASH_LEN: usize = 8;
+
+/// One device can fill more than one tenth of the available space for a single anchor (4 KB)
+/// with the variable length fields alone.
+/// In order to not give away all the anchor space to the device vector and identity metadata,
+/// we limit the sum of the size of all variable fields of all devices plus the identity metadata.
+/// This ensures that we have the flexibility to expand or change anchors in the future.
+/// The value 2500 was chosen so to accommodate pre-memory-migration anchors (limited to 2048 bytes)
+/// plus an additional 452 bytes to fit new fields introduced since.
+const VARIABLE_FIELDS_LIMIT: usize = 2500;
+
+/// Space reserved for the [DelegationActivity] entries on top of [VARIABLE_FIELDS_LIMIT], i.e.
+/// the size of [MAX_DELEGATION_ACTIVITY_ENTRIES] entries (frontend hash and timestamp).
+const DELEGATION_ACTIVITY_LIMIT: usize = MAX_DELEGATION_ACTIVITY_ENTRIES
+    * (DELEGATION_FRONTEND_HASH_LEN + std::mem::size_of::<Timestamp>());
+
 /// Internal representation of the anchor.
 /// The anchor has limited visibility for the constructor to make sure it is loaded from storage.
 /// The devices can only be modified by the exposed functions which keeps invariant checking local
@@ -17,6 +50,16 @@ mod tests;
 pub struct Anchor {
     devices: Vec<Device>,
     metadata: Option<HashMap<String, MetadataEntry>>,
+    // Timestamps of the last delegations per (hashed) frontend, used for the per-dapp activity stats.
+    delegation_activity: Option<Vec<DelegationActivity>>,
+}
+
+/// Timestamp of the last delegation prepared for a frontend.
+/// Only a truncated hash of the frontend is kept in order to not store the dapps used by an anchor.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DelegationActivity {
+    frontend_hash: ByteBuf,
+    last_delegation_timestamp: Timestamp,
 }
 
 impl Device {
@@ -104,6 +147,7 @@ impl Anchor {
         Self {
             devices: vec![],
             metadata: None,
+            delegation_activity: None,
         }
     }
 
@@ -219,6 +263,55 @@ impl Anchor {
         timestamps.pop().unwrap_or_default()
     }
 
+    /// Returns the timestamp of the last delegation prepared for the given frontend (within the last
+    /// 30 days), if any.
+    pub fn last_delegation_timestamp(&self, frontend: &FrontendHostname) -> Option<Timestamp> {
+        let frontend_hash = delegation_frontend_hash(frontend);
+        self.delegation_activity
+            .iter()
+            .flatten()
+            .find(|activity| activity.frontend_hash == frontend_hash)
+            .map(|activity| activity.last_delegation_timestamp)
+    }
+
+    /// Records a delegation prepared for the given frontend. Entries older than
+    /// [DELEGATION_ACTIVITY_RETENTION_NS] are dropped and at most [MAX_DELEGATION_ACTIVITY_ENTRIES]
+    /// entries are kept (dropping the oldest).
+    /// **Note:** Does not check the anchor invariants. Instead, the oldest entries are dropped if
+    /// the variable fields would exceed [VARIABLE_FIELDS_LIMIT] plus the space reserved for the
+    /// delegation activity ([DELEGATION_ACTIVITY_LIMIT]), so that recording a delegation never
+    /// makes the anchor too large to be written.
+    pub fn record_delegation(&mut self, frontend: &FrontendHostname, timestamp: Timestamp) {
+        let activity_limit = (VARIABLE_FIELDS_LIMIT + DELEGATION_ACTIVITY_LIMIT)
+            .saturating_sub(self.devices_and_metadata_len());
+        let frontend_hash = delegation_frontend_hash(frontend);
+        let activity = self.delegation_activity.get_or_insert_with(Vec::new);
+        activity.retain(|activity| {
+            activity.frontend_hash != frontend_hash
+                && timestamp.saturating_sub(activity.last_delegation_timestamp)
+                    < DELEGATION_ACTIVITY_RETENTION_NS
+        });
+        activity.push(DelegationActivity {
+            frontend_hash,
+            last_delegation_timestamp: timestamp,
+        });
+        // entries are sorted by timestamp, oldest first
+        while activity.len() > MAX_DELEGATION_ACTIVITY_ENTRIES
+            || delegation_activity_len(activity) > activity_limit
+        {
+            activity.remove(0);
+        }
+    }
+
+    /// Returns the size of the variable length fields of all devices plus the identity metadata.
+    fn devices_and_metadata_len(&self) -> usize {
+        self.devices
+            .iter()
+            .map(|device| device.variable_fields_len())
+            .sum::<usize>()
+            + self.metadata.as_ref().map_or(0, metadata_len)
+    }
+
     /// Returns information about the domains this anchor was active on since the given timestamp.
     /// Activity on unknown / other domain will be dropped if there is also activity on an II domain
     /// for the following reasons:
@@ -228,16 +321,17 @@ impl Anchor {
     ///   active on the II domain (but also does non-standard / unsupported things to their anchor).
     ///   If we are interested in this user group, we might consider extending this function to give
     ///   them their own [DomainActivity] value.
-    pub fn domain_activity_since(&self, timestamp: Timestamp) -> DomainActivity {
-        #[derive(Default)]
-        struct Accumulator {
-            ic0_app: bool,
-            internet_computer_org: bool,
-            non_ii: bool,
-        }
+    ///
+    /// The II domains are determined using the given II origins (see [ii_domain::ii_origins]).
+    pub fn domain_activity_since(
+        &self,
+        timestamp: Timestamp,
+        ii_origins: &[FrontendHostname],
+    ) -> DomainActivity {
+        let mut ii_domains = BTreeSet::new();
+        let mut non_ii = false;
 
-        let result = self
-            .devices
+        self.devices
             .iter()
             // filter devices with no activity
             .filter(|d| {
@@ -246,28 +340,22 @@ impl Anchor {
                     .unwrap_or(false)
             })
             // assign domain activity
-            .fold(Accumulator::default(), |mut acc, device| {
-                let Some(ref origin) = device.origin else {
-                    acc.non_ii = true;
-                    return acc;
-                };
-                match origin.as_str() {
-                    IC0_APP_ORIGIN => acc.ic0_app = true,
-                    INTERNETCOMPUTER_ORG_ORIGIN => acc.internet_computer_org = true,
-                    _ => acc.non_ii = true,
-                };
-                acc
+            .for_each(|device| match device.ii_domain_among(ii_origins) {
+                Some(domain) => {
+                    ii_domains.insert(domain);
+                }
+                None => non_ii = true,
             });
 
         // Activity on other domains is discarded if there is also activity on an II domain.
         // The reason is that II might not have complete information since domain information was
         // only introduced recently.
-        match (result.ic0_app, result.internet_computer_org, result.non_ii) {
-            (true, true, _) => DomainActivity::BothIIDomains,
-            (true, false, _) => DomainActivity::Ic0App,
-            (false, true, _) => DomainActivity::InternetComputerOrg,
-            (false, false, true) => DomainActivity::NonIIDomain,
-            (false, false, false) => DomainActivity::None,
+        if !ii_domains.is_empty() {
+            DomainActivity::IIDomains(ii_domains)
+        } else if non_ii {
+            DomainActivity::NonIIDomain
+        } else {
+            DomainActivity::None
         }
     }
 
@@ -277,6 +365,17 @@ impl Anchor {
         &self.metadata
     }
 
+    /// Returns whether the anchor has opted in to archiving authentication and delegation events
+    /// (see [ARCHIVE_AUTHN_EVENTS_METADATA_KEY]).
+    pub fn archive_authn_events(&self) -> bool {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get(ARCHIVE_AUTHN_EVENTS_METADATA_KEY))
+            .map_or(false, |entry| {
+                entry == &MetadataEntry::String("true".to_string())
+            })
+    }
+
     /// Replaces the existing identity metadata map (which is independent of devices / authentication
     /// methods) with the provided one.
     pub fn replace_identity_metadata(
@@ -290,18 +389,26 @@ impl Anchor {
     }
 }
 
+fn delegation_frontend_hash(frontend: &FrontendHostname) -> ByteBuf {
+    let hash = Sha256::digest(frontend.as_bytes());
+    ByteBuf::from(&hash[..DELEGATION_FRONTEND_HASH_LEN])
+}
+
+fn delegation_activity_len(activity: &[DelegationActivity]) -> usize {
+    activity
+        .iter()
+        .map(|activity| activity.frontend_hash.len() + std::mem::size_of::<Timestamp>())
+        .sum()
+}
+
 /// Possible outcomes of domain bound activity for an anchor since a specific timestamp.
 pub enum DomainActivity {
     // no activity at all
     None,
     // only active on non-ii domains
     NonIIDomain,
-    // only active on the identity.ic0.app domain
-    Ic0App,
-    // only active on the identity.internetcomputer.org domain
-    InternetComputerOrg,
-    // activity on both identity.ic0.app and identity.internetcomputer.org
-    BothIIDomains,
+    // active on the given (non-empty) set of II domains
+    IIDomains(BTreeSet<IIDomain>),
 }
 
 /// This is an internal version of `DeviceData` useful to provide a
@@ -331,9 +438,14 @@ impl Device {
     }
 
     pub fn ii_domain(&self) -> Option<IIDomain> {
+        self.ii_domain_among(&ii_domain::ii_origins())
+    }
+
+    /// Same as [Device::ii_domain] but with the II origins given explicitly.
+    pub fn ii_domain_among(&self, ii_origins: &[FrontendHostname]) -> Option<IIDomain> {
         self.origin
             .as_ref()
-            .and_then(|origin| IIDomain::try_from(origin.as_str()).ok())
+            .and_then(|origin| IIDomain::from_origin(origin, ii_origins))
     }
 }
 
@@ -400,15 +512,6 @@ fn check_anchor_invariants(
     /// due to the `VARIABLE_FIELDS_LIMIT`.
     const MAX_DEVICES_PER_ANCHOR: usize = 10;
 
-    /// One device can fill more than one tenth of the available space for a single anchor (4 KB)
-    /// with the variable length fields alone.
-    /// In order to not give away all the anchor space to the device vector and identity metadata,
-    /// we limit the sum of the size of all variable fields of all devices plus the identity metadata.
-    /// This ensures that we have the flexibility to expand or change anchors in the future.
-    /// The value 2500 was chosen so to accommodate pre-memory-migration anchors (limited to 2048 bytes)
-    /// plus an additional 452 bytes to fit new fields introduced since.
-    const VARIABLE_FIELDS_LIMIT: usize = 2500;
-
     if devices.len() > MAX_DEVICES_PER_ANCHOR {
         return Err(AnchorError::TooManyDevices {
             num_devices: devices.len(),
@@ -1,4 +1,7 @@
-use crate::storage::anchor::{Anchor, AnchorError, Device};
+use crate::storage::anchor::{
+    Anchor, AnchorError, Device, ARCHIVE_AUTHN_EVENTS_METADATA_KEY,
+    DELEGATION_ACTIVITY_RETENTION_NS, MAX_DELEGATION_ACTIVITY_ENTRIES,
+};
 use candid::Principal;
 use internet_identity_interface::internet_identity::types::{
     DeviceData, DeviceProtection, KeyType, MetadataEntry, Purpose, Timestamp,
@@ -225,6 +228,7 @@ fn should_prevent_mutation_when_invariants_are_violated() {
             recovery_phrase(2, DeviceProtection::Unprotected),
         ],
         metadata: None,
+        delegation_activity: None,
     };
 
     device1.alias = "new alias".to_string();
@@ -241,6 +245,7 @@ fn should_prevent_addition_when_invariants_are_violated() {
             recovery_phrase(2, DeviceProtection::Unprotected),
         ],
         metadata: None,
+        delegation_activity: None,
     };
 
     let result = anchor.add_device(sample_device());
@@ -257,6 +262,7 @@ fn should_allow_removal_when_invariants_are_violated() {
             recovery_phrase(2, DeviceProtection::Unprotected),
         ],
         metadata: None,
+        delegation_activity: None,
     };
 
     anchor.remove_device(&device1.pubkey).unwrap();
@@ -477,6 +483,61 @@ fn should_not_allow_reserved_metadata_key() {
     }
 }
 
+/// Tests that archiving authentication events is only enabled if explicitly opted in.
+#[test]
+fn should_only_archive_authn_events_if_opted_in() {
+    let mut anchor = Anchor::new();
+    assert!(!anchor.archive_authn_events());
+
+    for (value, expected) in [
+        (MetadataEntry::String("true".to_string()), true),
+        (MetadataEntry::String("false".to_string()), false),
+        (MetadataEntry::Bytes(ByteBuf::from("true")), false),
+    ] {
+        anchor
+            .replace_identity_metadata(HashMap::from([(
+                ARCHIVE_AUTHN_EVENTS_METADATA_KEY.to_string(),
+                value,
+            )]))
+            .unwrap();
+        assert_eq!(anchor.archive_authn_events(), expected);
+    }
+}
+
+/// Tests that the delegation activity per frontend is bounded in time and number of frontends.
+#[test]
+fn should_record_bounded_delegation_activity() {
+    let mut anchor = Anchor::new();
+    let frontend = "https://some-dapp.com".to_string();
+    assert_eq!(anchor.last_delegation_timestamp(&frontend), None);
+
+    anchor.record_delegation(&frontend, 1);
+    anchor.record_delegation(&frontend, 2);
+    assert_eq!(anchor.last_delegation_timestamp(&frontend), Some(2));
+    assert_eq!(anchor.delegation_activity.as_ref().unwrap().len(), 1);
+
+    // entries older than the retention period are dropped
+    anchor.record_delegation(
+        &"https://other-dapp.com".to_string(),
+        2 + DELEGATION_ACTIVITY_RETENTION_NS,
+    );
+    assert_eq!(anchor.last_delegation_timestamp(&frontend), None);
+
+    // the oldest entries are dropped if there are too many frontends
+    anchor.record_delegation(&frontend, 3 + DELEGATION_ACTIVITY_RETENTION_NS);
+    for i in 0..MAX_DELEGATION_ACTIVITY_ENTRIES {
+        anchor.record_delegation(
+            &format!("https://dapp-{i}.com"),
+            4 + DELEGATION_ACTIVITY_RETENTION_NS,
+        );
+    }
+    assert_eq!(
+        anchor.delegation_activity.as_ref().unwrap().len(),
+        MAX_DELEGATION_ACTIVITY_ENTRIES
+    );
+    assert_eq!(anchor.last_delegation_timestamp(&frontend), None);
+}
+
 fn sample_device() -> Device {
     Device {
         pubkey: ByteBuf::from("public key of some sample device"),
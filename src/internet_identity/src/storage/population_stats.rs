@@ -0,0 +1,81 @@
+//! Aggregate statistics over all anchors (number of devices, recovery methods, key types, etc.).
+//!
+//! The statistics are maintained incrementally by [Storage::write](crate::storage::Storage::write)
+//! which diffs the previous and the new version of an anchor. Anchors written by a release that
+//! did not maintain the statistics are counted once by a timer-driven scan (see [schedule_backfill]).
+use crate::state;
+use crate::storage::anchor::Anchor;
+use ic_cdk_timers::set_timer;
+use internet_identity_interface::internet_identity::types::{
+    AnchorPopulationStats, DeviceProtection, KeyType, Purpose,
+};
+use std::time::Duration;
+
+/// Maximum number of anchors scanned per timer invocation.
+const BACKFILL_BATCH_SIZE: u32 = 1_000;
+
+/// Adds the given anchor to the statistics.
+pub fn count_anchor(stats: &mut AnchorPopulationStats, anchor: &Anchor) {
+    update_counters(stats, anchor, |counter| *counter += 1);
+}
+
+/// Removes the given (previously counted) anchor from the statistics.
+pub fn uncount_anchor(stats: &mut AnchorPopulationStats, anchor: &Anchor) {
+    update_counters(stats, anchor, |counter| {
+        *counter = counter.saturating_sub(1)
+    });
+}
+
+fn update_counters(stats: &mut AnchorPopulationStats, anchor: &Anchor, update: impl Fn(&mut u64)) {
+    let devices = anchor.devices();
+
+    if stats.anchors_by_device_count.len() <= devices.len() {
+        stats.anchors_by_device_count.resize(devices.len() + 1, 0);
+    }
+    update(&mut stats.anchors_by_device_count[devices.len()]);
+
+    if devices
+        .iter()
+        .any(|device| device.key_type == KeyType::SeedPhrase)
+    {
+        update(&mut stats.anchors_with_recovery_phrase);
+    }
+    if devices
+        .iter()
+        .any(|device| device.purpose == Purpose::Recovery && device.key_type != KeyType::SeedPhrase)
+    {
+        update(&mut stats.anchors_with_recovery_device);
+    }
+
+    for device in devices {
+        let key_type_counter = match device.key_type {
+            KeyType::Unknown => &mut stats.devices_by_key_type.unknown,
+            KeyType::Platform => &mut stats.devices_by_key_type.platform,
+            KeyType::CrossPlatform => &mut stats.devices_by_key_type.cross_platform,
+            KeyType::SeedPhrase => &mut stats.devices_by_key_type.seed_phrase,
+            KeyType::BrowserStorageKey => &mut stats.devices_by_key_type.browser_storage_key,
+        };
+        update(key_type_counter);
+        let protection_counter = match device.protection {
+            DeviceProtection::Protected => &mut stats.protected_devices,
+            DeviceProtection::Unprotected => &mut stats.unprotected_devices,
+        };
+        update(protection_counter);
+    }
+}
+
+/// Schedules the scan of the anchors that are not yet included in the statistics (if any).
+/// The scan is done in batches of [BACKFILL_BATCH_SIZE] anchors, one batch per timer invocation.
+pub fn schedule_backfill() {
+    let complete = state::storage_borrow(|storage| {
+        storage.population_stats().counted_anchors >= storage.anchor_count() as u64
+    });
+    if !complete {
+        set_timer(Duration::ZERO, backfill_batch);
+    }
+}
+
+fn backfill_batch() {
+    state::storage_borrow_mut(|storage| storage.backfill_population_stats(BACKFILL_BATCH_SIZE));
+    schedule_backfill();
+}
@@ -1,16 +1,20 @@
 use crate::activity_stats::activity_counter::active_anchor_counter::ActiveAnchorCounter;
-use crate::activity_stats::{ActivityStats, CompletedActivityStats, OngoingActivityStats};
+use crate::activity_stats::{
+    ActivityStats, CompletedActivityStats, OngoingActivityStats, WindowStats,
+};
 use crate::archive::{ArchiveData, ArchiveState};
+use crate::assets::uploads::UploadedAsset;
 use crate::state::PersistentState;
 use crate::storage::anchor::{Anchor, Device};
 use crate::storage::{Header, PersistentStateError, StorageError};
-use crate::Storage;
+use crate::{Storage, DAY_NS};
 use candid::Principal;
 use ic_stable_structures::{Memory, VectorMemory};
 use internet_identity_interface::internet_identity::types::{
-    ArchiveConfig, DeviceProtection, KeyType, Purpose,
+    AnchorPopulationStats, ArchiveConfig, DeviceProtection, DevicesByKeyType, KeyType, Purpose,
 };
 use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
 use std::rc::Rc;
 
 const WASM_PAGE_SIZE: u64 = 1 << 16;
@@ -101,6 +105,38 @@ fn should_read_previous_write() {
     assert_eq!(anchor, read_anchor);
 }
 
+/// Tests that an anchor with the maximum number of devices, variable fields and delegation activity
+/// entries still fits into a storage entry.
+#[test]
+fn should_write_anchor_with_maximum_size() {
+    let memory = VectorMemory::default();
+    let mut storage = Storage::new((12345, 678910), memory);
+    let (anchor_number, mut anchor) = storage.allocate_anchor().unwrap();
+
+    // 10 devices with 250 bytes of variable fields each, i.e. 2500 bytes in total
+    for i in 0..10u8 {
+        anchor
+            .add_device(Device {
+                pubkey: ByteBuf::from(vec![i; 100]),
+                alias: "a".repeat(20),
+                credential_id: Some(ByteBuf::from(vec![i; 80])),
+                purpose: Purpose::Authentication,
+                key_type: KeyType::Unknown,
+                protection: DeviceProtection::Unprotected,
+                origin: Some(format!("https://{}.com", "a".repeat(38))),
+                last_usage_timestamp: Some(u64::MAX),
+                metadata: None,
+            })
+            .unwrap();
+    }
+    for i in 0..10 {
+        anchor.record_delegation(&format!("https://dapp-{i}.com"), u64::MAX);
+    }
+
+    storage.write(anchor_number, anchor.clone()).unwrap();
+    assert_eq!(storage.read(anchor_number).unwrap(), anchor);
+}
+
 #[test]
 fn should_serialize_first_record() {
     let memory = VectorMemory::default();
@@ -378,6 +414,192 @@ fn should_read_previously_stored_persistent_state() {
     );
 }
 
+#[test]
+fn should_store_uploaded_assets_separately_from_anchors() {
+    let memory = VectorMemory::default();
+    let mut storage = Storage::new((1, 100), memory.clone());
+    assert!(storage.uploaded_assets().is_empty());
+
+    let assets = vec![
+        UploadedAsset {
+            file_path: "/index.html".to_string(),
+            content: ByteBuf::from("<html></html>"),
+        },
+        UploadedAsset {
+            file_path: "/logo.svg".to_string(),
+            content: ByteBuf::from(vec![7u8; 10_000]),
+        },
+    ];
+    for asset in &assets {
+        storage.store_uploaded_asset(asset);
+    }
+    add_test_anchor_data(&mut storage, 3);
+
+    let storage = Storage::from_memory(memory).unwrap();
+    assert_eq!(storage.uploaded_assets(), assets);
+    assert_eq!(storage.anchor_count(), 3);
+    assert_eq!(
+        storage.read(1).unwrap().devices(),
+        &vec![sample_unique_device(1)]
+    );
+}
+
+#[test]
+fn should_replace_and_remove_uploaded_assets_individually() {
+    let mut storage = Storage::new((1, 100), VectorMemory::default());
+    let large_asset = UploadedAsset {
+        file_path: "/large.js".to_string(),
+        // spans multiple chunks
+        content: ByteBuf::from((0..300_000u32).map(|i| i as u8).collect::<Vec<u8>>()),
+    };
+    let prefixed_asset = UploadedAsset {
+        file_path: "/large.js.gz".to_string(),
+        content: ByteBuf::from(vec![1u8; 100_000]),
+    };
+    storage.store_uploaded_asset(&large_asset);
+    storage.store_uploaded_asset(&prefixed_asset);
+    assert_eq!(
+        storage.uploaded_assets(),
+        vec![large_asset.clone(), prefixed_asset.clone()]
+    );
+
+    let replacement = UploadedAsset {
+        file_path: "/large.js".to_string(),
+        content: ByteBuf::from("console.log('replaced');"),
+    };
+    storage.store_uploaded_asset(&replacement);
+    assert_eq!(
+        storage.uploaded_asset_sizes(),
+        vec![
+            ("/large.js".to_string(), replacement.content.len() as u64),
+            ("/large.js.gz".to_string(), 100_000)
+        ]
+    );
+    assert_eq!(
+        storage.uploaded_assets_total_size(),
+        replacement.content.len() as u64 + 100_000
+    );
+    assert_eq!(
+        storage.uploaded_assets(),
+        vec![replacement, prefixed_asset.clone()]
+    );
+
+    assert!(storage.remove_uploaded_asset("/large.js"));
+    assert!(!storage.remove_uploaded_asset("/large.js"));
+    assert_eq!(storage.uploaded_assets(), vec![prefixed_asset]);
+    assert_eq!(storage.uploaded_assets_total_size(), 100_000);
+}
+
+#[test]
+fn should_update_population_stats_on_write() {
+    let memory = VectorMemory::default();
+    let mut storage = Storage::new((12345, 678910), memory);
+
+    add_test_anchor_data(&mut storage, 2);
+    let (anchor_number, mut anchor) = storage.allocate_anchor().unwrap();
+    anchor.add_device(sample_device()).unwrap();
+    anchor
+        .add_device(Device {
+            pubkey: ByteBuf::from("recovery phrase"),
+            key_type: KeyType::SeedPhrase,
+            purpose: Purpose::Recovery,
+            protection: DeviceProtection::Protected,
+            credential_id: None,
+            ..sample_device()
+        })
+        .unwrap();
+    storage.write(anchor_number, anchor.clone()).unwrap();
+
+    assert_eq!(
+        storage.population_stats(),
+        &AnchorPopulationStats {
+            counted_anchors: 3,
+            anchors_by_device_count: vec![0, 2, 1],
+            anchors_with_recovery_phrase: 1,
+            anchors_with_recovery_device: 0,
+            devices_by_key_type: DevicesByKeyType {
+                unknown: 3,
+                seed_phrase: 1,
+                ..DevicesByKeyType::default()
+            },
+            protected_devices: 1,
+            unprotected_devices: 3,
+        }
+    );
+
+    // replacing the authentication device with a recovery device updates the stats of the anchor
+    anchor.remove_device(&sample_device().pubkey).unwrap();
+    anchor
+        .add_device(Device {
+            pubkey: ByteBuf::from("recovery device"),
+            key_type: KeyType::CrossPlatform,
+            purpose: Purpose::Recovery,
+            ..sample_device()
+        })
+        .unwrap();
+    storage.write(anchor_number, anchor).unwrap();
+
+    assert_eq!(
+        storage.population_stats(),
+        &AnchorPopulationStats {
+            counted_anchors: 3,
+            anchors_by_device_count: vec![0, 2, 1],
+            anchors_with_recovery_phrase: 1,
+            anchors_with_recovery_device: 1,
+            devices_by_key_type: DevicesByKeyType {
+                unknown: 2,
+                cross_platform: 1,
+                seed_phrase: 1,
+                ..DevicesByKeyType::default()
+            },
+            protected_devices: 1,
+            unprotected_devices: 3,
+        }
+    );
+}
+
+#[test]
+fn should_backfill_population_stats() {
+    let memory = VectorMemory::default();
+    let mut storage = Storage::new((12345, 678910), memory);
+    add_test_anchor_data(&mut storage, 5);
+    let expected_stats = storage.population_stats().clone();
+
+    // stats that were not restored on upgrade (e.g. upgrade from a release without the stats)
+    storage.set_population_stats(AnchorPopulationStats::default());
+
+    // writes of anchors not yet counted do not affect the stats
+    let mut anchor = storage.read(12346).unwrap();
+    anchor
+        .add_device(Device {
+            pubkey: ByteBuf::from("another public key"),
+            ..sample_device()
+        })
+        .unwrap();
+    storage.write(12346, anchor).unwrap();
+    assert_eq!(
+        storage.population_stats(),
+        &AnchorPopulationStats::default()
+    );
+
+    assert!(!storage.backfill_population_stats(3));
+    assert_eq!(storage.population_stats().counted_anchors, 3);
+    assert!(storage.backfill_population_stats(3));
+
+    assert_eq!(
+        storage.population_stats(),
+        &AnchorPopulationStats {
+            anchors_by_device_count: vec![0, 4, 1],
+            devices_by_key_type: DevicesByKeyType {
+                unknown: 6,
+                ..DevicesByKeyType::default()
+            },
+            unprotected_devices: 6,
+            ..expected_stats
+        }
+    );
+}
+
 fn sample_unique_device(id: usize) -> Device {
     Device {
         alias: format!(" #{}", id),
@@ -406,12 +628,17 @@ fn sample_persistent_state() -> PersistentState {
                 sequence_number: 39,
                 archive_canister: Principal::from_text("2h5ob-7aaaa-aaaad-aacya-cai").unwrap(),
                 entries_buffer: Rc::new(vec![]),
+                highest_transferred_sequence_number: None,
+                last_transfer_timestamp: None,
+                dropped_entries: None,
             },
             config: ArchiveConfig {
                 module_hash: [99u8; 32],
                 entries_buffer_limit: 10_000,
                 polling_interval_ns: 60_000_000_000,
                 entries_fetch_limit: 1_000,
+                archive_integration: None,
+                retention_period_ns: None,
             },
         },
         canister_creation_cycles_cost: 12_346_000_000,
@@ -433,6 +660,18 @@ fn sample_persistent_state() -> PersistentState {
                     counter: 66,
                 }],
             },
+            windows: Some(BTreeMap::from([(
+                "weekly".to_string(),
+                WindowStats {
+                    length_ns: 7 * DAY_NS,
+                    step_ns: DAY_NS,
+                    completed: None,
+                    ongoing: vec![ActiveAnchorCounter {
+                        start_timestamp: 549843248,
+                        counter: 12,
+                    }],
+                },
+            )])),
         }),
         max_num_latest_delegation_origins: None,
         ..PersistentState::default()
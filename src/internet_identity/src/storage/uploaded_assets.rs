@@ -0,0 +1,174 @@
+//! Stable storage of the assets uploaded at runtime (see [crate::assets::uploads]).
+//!
+//! Every asset is stored under its own keys, so that storing or removing an asset only reads and
+//! writes the data of that asset: an index maps the file path of each asset to its size and the
+//! content is split into chunks of at most [CHUNK_SIZE] bytes, stored by file path and chunk index.
+use crate::assets::uploads::UploadedAsset;
+use ic_stable_structures::{BoundedStorable, Memory, StableBTreeMap, Storable};
+use serde_bytes::ByteBuf;
+use std::borrow::Cow;
+
+/// Maximum length (in bytes) of the file path of an uploaded asset.
+pub const MAX_FILE_PATH_LENGTH: usize = 256;
+
+/// Size of the chunks the content of the assets is split into.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct FilePath(String);
+
+impl Storable for FilePath {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        FilePath(String::from_utf8(bytes.into_owned()).expect("invalid file path"))
+    }
+}
+
+impl BoundedStorable for FilePath {
+    const MAX_SIZE: u32 = MAX_FILE_PATH_LENGTH as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Key of a chunk. The derived order sorts the chunks of an asset by index.
+///
+/// The file path is length-prefixed in the encoding, so that the chunks of an asset are contiguous
+/// also in the order of the encoded keys (i.e. not interleaved with those of file paths the file
+/// path is a prefix of).
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct ChunkKey {
+    file_path: String,
+    index: u32,
+}
+
+impl Storable for ChunkKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = (self.file_path.len() as u16).to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.file_path.as_bytes());
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let (file_path, index) = bytes[2..].split_at(bytes.len() - 2 - 4);
+        ChunkKey {
+            file_path: String::from_utf8(file_path.to_vec()).expect("invalid file path"),
+            index: u32::from_be_bytes(index.try_into().expect("invalid chunk index")),
+        }
+    }
+}
+
+impl BoundedStorable for ChunkKey {
+    const MAX_SIZE: u32 = (2 + MAX_FILE_PATH_LENGTH + 4) as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+struct Chunk(Vec<u8>);
+
+impl Storable for Chunk {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Chunk(bytes.into_owned())
+    }
+}
+
+impl BoundedStorable for Chunk {
+    const MAX_SIZE: u32 = CHUNK_SIZE as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+pub struct UploadedAssets<M: Memory> {
+    // size of each asset by file path
+    sizes: StableBTreeMap<FilePath, u64, M>,
+    chunks: StableBTreeMap<ChunkKey, Chunk, M>,
+}
+
+impl<M: Memory> UploadedAssets<M> {
+    /// Initializes the uploaded assets from the given memories, which are empty for new storages.
+    pub fn init(sizes_memory: M, chunks_memory: M) -> Self {
+        Self {
+            sizes: StableBTreeMap::init(sizes_memory),
+            chunks: StableBTreeMap::init(chunks_memory),
+        }
+    }
+
+    /// Returns the file paths and sizes of all assets, without reading their content.
+    pub fn sizes(&self) -> Vec<(String, u64)> {
+        self.sizes
+            .iter()
+            .map(|(file_path, size)| (file_path.0, size))
+            .collect()
+    }
+
+    /// Returns the total size of all assets.
+    pub fn total_size(&self) -> u64 {
+        self.sizes.iter().map(|(_, size)| size).sum()
+    }
+
+    pub fn get(&self, file_path: &str) -> Option<UploadedAsset> {
+        let size = self.sizes.get(&FilePath(file_path.to_string()))?;
+        let mut content = Vec::with_capacity(size as usize);
+        for (_, chunk) in self.chunks.range(chunk_range(file_path)) {
+            content.extend_from_slice(&chunk.0);
+        }
+        Some(UploadedAsset {
+            file_path: file_path.to_string(),
+            content: ByteBuf::from(content),
+        })
+    }
+
+    /// Stores the asset, replacing the asset previously stored with the same file path (if any).
+    ///
+    /// Panics if the file path exceeds [MAX_FILE_PATH_LENGTH].
+    pub fn insert(&mut self, asset: &UploadedAsset) {
+        assert!(
+            asset.file_path.len() <= MAX_FILE_PATH_LENGTH,
+            "file path exceeds {MAX_FILE_PATH_LENGTH} bytes"
+        );
+        self.remove(&asset.file_path);
+        for (index, chunk) in asset.content.chunks(CHUNK_SIZE).enumerate() {
+            self.chunks.insert(
+                ChunkKey {
+                    file_path: asset.file_path.clone(),
+                    index: index as u32,
+                },
+                Chunk(chunk.to_vec()),
+            );
+        }
+        self.sizes.insert(
+            FilePath(asset.file_path.clone()),
+            asset.content.len() as u64,
+        );
+    }
+
+    /// Removes the asset with the given file path. Returns whether there was such an asset.
+    pub fn remove(&mut self, file_path: &str) -> bool {
+        let keys: Vec<ChunkKey> = self
+            .chunks
+            .range(chunk_range(file_path))
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            self.chunks.remove(&key);
+        }
+        self.sizes
+            .remove(&FilePath(file_path.to_string()))
+            .is_some()
+    }
+}
+
+/// Returns the range of the keys of all chunks of the asset with the given file path.
+fn chunk_range(file_path: &str) -> std::ops::RangeInclusive<ChunkKey> {
+    ChunkKey {
+        file_path: file_path.to_string(),
+        index: 0,
+    }..=ChunkKey {
+        file_path: file_path.to_string(),
+        index: u32::MAX,
+    }
+}
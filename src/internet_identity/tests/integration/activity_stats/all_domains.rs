@@ -3,11 +3,17 @@
 use canister_tests::api::internet_identity as api;
 use canister_tests::flows;
 use canister_tests::framework::*;
+use internet_identity_interface::internet_identity::types::{
+    ActivityWindowConfig, InternetIdentityInit,
+};
 use pocket_ic::CallError;
+use pocket_ic::ErrorCode::CanisterCalledTrap;
+use regex::Regex;
 use std::time::Duration;
 
 const DAY_SECONDS: u64 = 24 * 60 * 60;
 const MONTH_SECONDS: u64 = 30 * DAY_SECONDS;
+const DAY_NS: u64 = DAY_SECONDS * 1_000_000_000;
 
 /// Tests that daily active anchors are counted correctly.
 #[test]
@@ -177,3 +183,141 @@ fn should_have_correct_stats_after_long_inactivity() -> Result<(), CallError> {
 
     Ok(())
 }
+
+/// Tests that active anchors are counted on additionally configured collection windows.
+#[test]
+fn should_report_active_anchors_on_configured_windows() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister_with_arg(
+        &env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            activity_windows: Some(vec![weekly_window()]),
+            ..InternetIdentityInit::default()
+        }),
+    );
+
+    let anchor_number = flows::register_anchor(&env, canister_id);
+    flows::register_anchor(&env, canister_id);
+
+    // activity within the 7-day collection period should not increase the counter
+    env.advance_time(Duration::from_secs(DAY_SECONDS));
+    api::get_anchor_info(&env, canister_id, principal_1(), anchor_number)?;
+
+    env.advance_time(Duration::from_secs(6 * DAY_SECONDS));
+
+    // some activity is required to update the stats
+    api::get_anchor_info(&env, canister_id, principal_1(), anchor_number)?;
+
+    let metrics = get_metrics(&env, canister_id);
+    assert_metric(&metrics, "internet_identity_weekly_active_anchors", 2f64);
+    assert!(metrics.contains("in the last completed 7-day collection window"));
+    // the built-in windows are still collected
+    assert_metric(&metrics, "internet_identity_daily_active_anchors", 1f64);
+
+    // the additional windows are also returned by the activity_stats query
+    let active_anchors = api::activity_stats(&env, canister_id)?
+        .active_anchors
+        .expect("active anchor stats missing");
+    let additional_windows = active_anchors
+        .additional_windows
+        .expect("additional windows missing");
+    assert_eq!(additional_windows.len(), 1);
+    assert_eq!(additional_windows[0].name, "weekly");
+    assert_eq!(additional_windows[0].length_ns, 7 * DAY_NS);
+    assert_eq!(
+        additional_windows[0]
+            .completed
+            .as_ref()
+            .map(|window| window.active_anchors),
+        Some(2)
+    );
+    assert_eq!(
+        active_anchors
+            .completed_daily
+            .map(|window| window.active_anchors),
+        Some(1)
+    );
+    Ok(())
+}
+
+/// Tests that the existing stats are kept when configuring an additional window on upgrade.
+#[test]
+fn should_keep_stats_when_configuring_windows_on_upgrade() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let anchor_number = flows::register_anchor(&env, canister_id);
+    flows::register_anchor(&env, canister_id);
+
+    upgrade_ii_canister_with_arg(
+        &env,
+        canister_id,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            activity_windows: Some(vec![weekly_window()]),
+            ..InternetIdentityInit::default()
+        }),
+    )?;
+
+    env.advance_time(Duration::from_secs(DAY_SECONDS));
+
+    // some activity is required to update the stats
+    api::get_anchor_info(&env, canister_id, principal_1(), anchor_number)?;
+
+    let metrics = get_metrics(&env, canister_id);
+    assert_metric(&metrics, "internet_identity_daily_active_anchors", 2f64);
+    // the weekly window has not completed yet
+    assert!(!metrics.contains("internet_identity_weekly_active_anchors"));
+    Ok(())
+}
+
+/// Tests that invalid collection windows are rejected.
+#[test]
+fn should_reject_invalid_activity_windows() {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let invalid_windows = [
+        // reserved name
+        ActivityWindowConfig {
+            name: "daily".to_string(),
+            ..weekly_window()
+        },
+        // length not a multiple of the step
+        ActivityWindowConfig {
+            step_ns: 2 * DAY_NS,
+            ..weekly_window()
+        },
+        // too many ongoing counters
+        ActivityWindowConfig {
+            length_ns: 90 * DAY_NS,
+            step_ns: DAY_NS,
+            ..weekly_window()
+        },
+    ];
+    for window in invalid_windows {
+        let result = upgrade_ii_canister_with_arg(
+            &env,
+            canister_id,
+            II_WASM.clone(),
+            Some(InternetIdentityInit {
+                activity_windows: Some(vec![window]),
+                ..InternetIdentityInit::default()
+            }),
+        );
+        expect_user_error_with_message(
+            result,
+            CanisterCalledTrap,
+            Regex::new("activity window").unwrap(),
+        );
+    }
+}
+
+fn weekly_window() -> ActivityWindowConfig {
+    ActivityWindowConfig {
+        name: "weekly".to_string(),
+        length_ns: 7 * DAY_NS,
+        step_ns: DAY_NS,
+    }
+}
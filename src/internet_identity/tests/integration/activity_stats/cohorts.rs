@@ -0,0 +1,102 @@
+//! Tests for the retention statistics of the weekly registration cohorts.
+
+use canister_tests::api::internet_identity as api;
+use canister_tests::flows;
+use canister_tests::framework::*;
+use internet_identity_interface::internet_identity::types::{
+    RegistrationCohort, RegistrationCohortStats,
+};
+use pocket_ic::{CallError, PocketIc};
+use std::time::{Duration, UNIX_EPOCH};
+
+const WEEK_SECONDS: u64 = 7 * 24 * 60 * 60;
+const WEEK_NS: u64 = WEEK_SECONDS * 1_000_000_000;
+
+/// Tests that the anchors registered in the same week are counted as one cohort and that their
+/// activity in the following weeks is counted once per anchor and week.
+#[test]
+fn should_report_registration_cohort_retention() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let week_start = advance_to_next_week(&env);
+
+    assert_eq!(
+        api::registration_cohort_stats(&env, canister_id)?,
+        RegistrationCohortStats { cohorts: vec![] }
+    );
+
+    let anchor_1 = flows::register_anchor_with_device(&env, canister_id, &device_data_1());
+    let anchor_2 = flows::register_anchor_with_device(&env, canister_id, &device_data_2());
+    // activity in the registration week is not counted
+    api::get_anchor_info(&env, canister_id, principal_1(), anchor_1)?;
+
+    // week 1: the first anchor is active (twice)
+    env.advance_time(Duration::from_secs(WEEK_SECONDS));
+    api::get_anchor_info(&env, canister_id, principal_1(), anchor_1)?;
+    api::get_anchor_info(&env, canister_id, principal_1(), anchor_1)?;
+
+    // week 3: the second anchor is active
+    env.advance_time(Duration::from_secs(2 * WEEK_SECONDS));
+    api::get_anchor_info(&env, canister_id, principal_2(), anchor_2)?;
+
+    let expected_stats = RegistrationCohortStats {
+        cohorts: vec![RegistrationCohort {
+            start_timestamp: week_start,
+            registrations: 2,
+            active_anchors_by_week: vec![1, 0, 1],
+        }],
+    };
+    assert_eq!(
+        api::registration_cohort_stats(&env, canister_id)?,
+        expected_stats
+    );
+
+    // the stats are kept across upgrades
+    upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+    assert_eq!(
+        api::registration_cohort_stats(&env, canister_id)?,
+        expected_stats
+    );
+    Ok(())
+}
+
+/// Tests that the cohort statistics are exported as metrics.
+#[test]
+fn should_export_registration_cohort_metrics() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let week_start = advance_to_next_week(&env);
+
+    let anchor = flows::register_anchor(&env, canister_id);
+    env.advance_time(Duration::from_secs(WEEK_SECONDS));
+    api::get_anchor_info(&env, canister_id, principal_1(), anchor)?;
+
+    let cohort = week_start / 1_000_000_000;
+    let metrics = get_metrics(&env, canister_id);
+    assert_metric(
+        &metrics,
+        &format!("internet_identity_registration_cohort_size{{cohort=\"{cohort}\"}}"),
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        &format!(
+            "internet_identity_registration_cohort_active_anchors{{cohort=\"{cohort}\",week=\"1\"}}"
+        ),
+        1f64,
+    );
+    Ok(())
+}
+
+/// Advances the time to the start of the next cohort week (plus an hour to leave room for the
+/// calls of the test) and returns the start of that week in nanoseconds since the epoch.
+fn advance_to_next_week(env: &PocketIc) -> u64 {
+    let now = env
+        .get_time()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    let next_week_start = now - now % WEEK_NS + WEEK_NS;
+    env.advance_time(Duration::from_nanos(next_week_start - now) + Duration::from_secs(60 * 60));
+    next_week_start
+}
@@ -0,0 +1,126 @@
+/// Tests for the per-dapp active anchor statistics.
+use candid::Principal;
+use canister_tests::api::internet_identity as api;
+use canister_tests::flows;
+use canister_tests::framework::*;
+use internet_identity_interface::internet_identity::types::{AnchorNumber, DappActiveAnchors};
+use pocket_ic::{CallError, PocketIc};
+use serde_bytes::ByteBuf;
+use std::time::Duration;
+
+const DAY_SECONDS: u64 = 24 * 60 * 60;
+
+const ICP0_APP_ORIGIN: &str = "https://identity.ic0.app";
+const DAPP_1: &str = "https://some-dapp.com";
+const DAPP_2: &str = "https://other-dapp.com";
+
+/// Tests that daily active anchors are counted per delegation frontend.
+#[test]
+fn should_report_daily_active_anchors_by_dapp() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let device = device_with_origin(Some(ICP0_APP_ORIGIN.to_string()));
+    let principal = principal(&device);
+
+    // ensure stats are initially absent
+    assert!(
+        !get_metrics(&env, canister_id).contains("internet_identity_daily_active_anchors_by_dapp")
+    );
+    assert_eq!(api::dapp_activity_stats(&env, canister_id)?.daily, None);
+
+    let anchor_1 = flows::register_anchor_with_device(&env, canister_id, &device);
+    let anchor_2 = flows::register_anchor_with_device(&env, canister_id, &device);
+    let anchor_3 = flows::register_anchor_with_device(&env, canister_id, &device);
+
+    for anchor in [anchor_1, anchor_2, anchor_3] {
+        prepare_delegation(&env, canister_id, principal, anchor, DAPP_1)?;
+    }
+    // repeated delegations for the same dapp within the collection window are counted once
+    prepare_delegation(&env, canister_id, principal, anchor_1, DAPP_1)?;
+    prepare_delegation(&env, canister_id, principal, anchor_1, DAPP_2)?;
+    // delegations for dev frontends are not counted
+    prepare_delegation(
+        &env,
+        canister_id,
+        principal,
+        anchor_1,
+        "http://localhost:8080",
+    )?;
+
+    env.advance_time(Duration::from_secs(DAY_SECONDS));
+
+    // some activity is required to update the stats
+    prepare_delegation(&env, canister_id, principal, anchor_1, DAPP_1)?;
+
+    let metrics = get_metrics(&env, canister_id);
+    assert_metric(
+        &metrics,
+        "internet_identity_daily_active_anchors_by_dapp{frontend=\"https://some-dapp.com\"}",
+        3f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_daily_active_anchors_by_dapp{frontend=\"https://other-dapp.com\"}",
+        1f64,
+    );
+    assert!(!metrics.contains("localhost"));
+
+    let daily_stats = api::dapp_activity_stats(&env, canister_id)?
+        .daily
+        .expect("daily dapp stats missing");
+    assert_eq!(
+        daily_stats.dapps,
+        vec![
+            DappActiveAnchors {
+                frontend: DAPP_1.to_string(),
+                active_anchors: 3,
+            },
+            DappActiveAnchors {
+                frontend: DAPP_2.to_string(),
+                active_anchors: 1,
+            },
+        ]
+    );
+    Ok(())
+}
+
+/// Tests that the per-dapp stats are retained across upgrades.
+#[test]
+fn should_keep_dapp_activity_stats_across_upgrades() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let device = device_with_origin(Some(ICP0_APP_ORIGIN.to_string()));
+    let principal = principal(&device);
+
+    let anchor = flows::register_anchor_with_device(&env, canister_id, &device);
+    prepare_delegation(&env, canister_id, principal, anchor, DAPP_1)?;
+    env.advance_time(Duration::from_secs(DAY_SECONDS));
+    prepare_delegation(&env, canister_id, principal, anchor, DAPP_1)?;
+
+    let stats_before = api::dapp_activity_stats(&env, canister_id)?;
+    assert!(stats_before.daily.is_some());
+
+    upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+
+    assert_eq!(api::dapp_activity_stats(&env, canister_id)?, stats_before);
+    Ok(())
+}
+
+fn prepare_delegation(
+    env: &PocketIc,
+    canister_id: Principal,
+    sender: Principal,
+    anchor: AnchorNumber,
+    frontend: &str,
+) -> Result<(), CallError> {
+    api::prepare_delegation(
+        env,
+        canister_id,
+        sender,
+        anchor,
+        frontend,
+        &ByteBuf::from("session public key"),
+        None,
+    )?;
+    Ok(())
+}
@@ -2,7 +2,10 @@
 use canister_tests::api::internet_identity as api;
 use canister_tests::flows;
 use canister_tests::framework::*;
+use internet_identity_interface::internet_identity::types::InternetIdentityInit;
 use pocket_ic::CallError;
+use pocket_ic::ErrorCode::CanisterCalledTrap;
+use regex::Regex;
 use std::time::Duration;
 
 const DAY_SECONDS: u64 = 24 * 60 * 60;
@@ -382,3 +385,107 @@ fn should_count_activity_on_other_and_ii_domain() -> Result<(), CallError> {
     );
     Ok(())
 }
+
+/// Tests that activity is counted per configured II domain (including activity across II domains).
+#[test]
+fn should_count_activity_on_configured_ii_domains() -> Result<(), CallError> {
+    const CUSTOM_ORIGIN: &str = "https://identity.example.com";
+    let env = env();
+    let canister_id = install_ii_canister_with_arg(
+        &env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            ii_origins: Some(vec![ICP0_APP_ORIGIN.to_string(), CUSTOM_ORIGIN.to_string()]),
+            ..InternetIdentityInit::default()
+        }),
+    );
+    let ic0_app_device = device_with_origin(Some(ICP0_APP_ORIGIN.to_string()));
+    let custom_origin_device = device_with_origin(Some(CUSTOM_ORIGIN.to_string()));
+    let internetcomputer_org_device =
+        device_with_origin(Some(INTERNETCOMPUTER_ORG_ORIGIN.to_string()));
+
+    flows::register_anchor_with_device(&env, canister_id, &custom_origin_device);
+    // not an II domain in this configuration
+    flows::register_anchor_with_device(&env, canister_id, &internetcomputer_org_device);
+
+    let both_domains_anchor =
+        flows::register_anchor_with_device(&env, canister_id, &ic0_app_device);
+    api::add(
+        &env,
+        canister_id,
+        principal(&ic0_app_device),
+        both_domains_anchor,
+        &custom_origin_device,
+    )?;
+    // some activity on the other domain for the both_domains_anchor
+    api::get_anchor_info(
+        &env,
+        canister_id,
+        principal(&custom_origin_device),
+        both_domains_anchor,
+    )?;
+
+    env.advance_time(Duration::from_secs(DAY_SECONDS));
+
+    // some activity is required to update the stats
+    api::get_anchor_info(
+        &env,
+        canister_id,
+        principal(&ic0_app_device),
+        both_domains_anchor,
+    )?;
+
+    let metrics = get_metrics(&env, canister_id);
+    assert_metric(
+        &metrics,
+        "internet_identity_daily_active_anchors_by_domain{domain=\"identity.example.com\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_daily_active_anchors_by_domain{domain=\"identity.ic0.app\"}",
+        0f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_daily_active_anchors_by_domain{domain=\"identity.example.com+identity.ic0.app\"}",
+        1f64,
+    );
+    assert!(!metrics.contains("identity.internetcomputer.org"));
+    Ok(())
+}
+
+/// Tests that only https:// origins can be configured as II origins.
+#[test]
+fn should_reject_invalid_ii_origins() {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let invalid_origins = [
+        vec![],
+        vec!["http://identity.example.com".to_string()],
+        vec!["identity.example.com".to_string()],
+        vec!["https://identity.example.com/".to_string()],
+        vec!["https://Identity.example.com".to_string()],
+        vec![
+            ICP0_APP_ORIGIN.to_string(),
+            "https://identity.example.com:foo".to_string(),
+        ],
+    ];
+    for origins in invalid_origins {
+        let result = upgrade_ii_canister_with_arg(
+            &env,
+            canister_id,
+            II_WASM.clone(),
+            Some(InternetIdentityInit {
+                ii_origins: Some(origins),
+                ..InternetIdentityInit::default()
+            }),
+        );
+        expect_user_error_with_message(
+            result,
+            CanisterCalledTrap,
+            Regex::new("ii_origins must contain at least one origin|invalid II origin").unwrap(),
+        );
+    }
+}
@@ -8,3 +8,12 @@ mod ii_domains;
 
 /// Tests for the active authn method statistics on the II domains.
 mod authn_methods;
+
+/// Tests for the per-dapp active anchor statistics.
+mod dapps;
+
+/// Tests for the retention statistics of the weekly registration cohorts.
+mod cohorts;
+
+/// Tests for the activity statistics candid query.
+mod query;
@@ -0,0 +1,91 @@
+/// Tests for the `activity_stats` query.
+use canister_tests::api::internet_identity as api;
+use canister_tests::flows;
+use canister_tests::framework::*;
+use internet_identity_interface::internet_identity::types::{
+    ActiveAnchorWindow, DomainActiveAnchors, InternetIdentityActivityStats,
+};
+use pocket_ic::CallError;
+use std::time::Duration;
+
+const DAY_SECONDS: u64 = 24 * 60 * 60;
+const DAY_NS: u64 = DAY_SECONDS * 1_000_000_000;
+
+const ICP0_APP_ORIGIN: &str = "https://identity.ic0.app";
+
+/// Tests that the completed and ongoing activity counters are returned by the query.
+#[test]
+fn should_return_activity_stats() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let ic0_app_device = device_with_origin(Some(ICP0_APP_ORIGIN.to_string()));
+    let no_origin_device = device_with_origin(None);
+
+    // ensure stats are initially absent
+    assert_eq!(
+        api::activity_stats(&env, canister_id)?,
+        InternetIdentityActivityStats {
+            active_anchors: None,
+            domain_active_anchors: None,
+            active_authn_methods: None,
+        }
+    );
+
+    let anchor_number = flows::register_anchor_with_device(&env, canister_id, &ic0_app_device);
+    flows::register_anchor_with_device(&env, canister_id, &ic0_app_device);
+    flows::register_anchor_with_device(&env, canister_id, &no_origin_device);
+
+    env.advance_time(Duration::from_secs(DAY_SECONDS));
+
+    // some activity is required to update the stats
+    api::get_anchor_info(&env, canister_id, principal(&ic0_app_device), anchor_number)?;
+
+    let stats = api::activity_stats(&env, canister_id)?;
+
+    let active_anchors = stats.active_anchors.expect("active anchor stats missing");
+    let completed_daily = active_anchors
+        .completed_daily
+        .expect("completed daily stats missing");
+    assert_eq!(completed_daily.active_anchors, 3);
+    assert_eq!(
+        active_anchors.ongoing_daily,
+        ActiveAnchorWindow {
+            start_timestamp: completed_daily.start_timestamp + DAY_NS,
+            active_anchors: 1,
+        }
+    );
+    assert_eq!(active_anchors.completed_monthly, None);
+    assert_eq!(
+        active_anchors
+            .ongoing_monthly
+            .iter()
+            .map(|window| window.active_anchors)
+            .collect::<Vec<_>>(),
+        vec![3, 1]
+    );
+
+    let domain_active_anchors = stats
+        .domain_active_anchors
+        .expect("domain active anchor stats missing");
+    assert_eq!(
+        domain_active_anchors
+            .completed_daily
+            .expect("completed daily domain stats missing")
+            .domains,
+        vec![DomainActiveAnchors {
+            ii_origins: vec![ICP0_APP_ORIGIN.to_string()],
+            active_anchors: 2,
+        }]
+    );
+
+    let authn_methods = stats
+        .active_authn_methods
+        .expect("authn method stats missing");
+    let completed_daily = authn_methods
+        .completed_daily
+        .expect("completed daily authn method stats missing");
+    assert_eq!(completed_daily.webauthn_auth, 2);
+    assert_eq!(completed_daily.recovery_phrase, 0);
+    assert_eq!(authn_methods.ongoing_daily.webauthn_auth, 1);
+    Ok(())
+}
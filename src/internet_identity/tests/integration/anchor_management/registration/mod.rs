@@ -202,10 +202,11 @@ fn should_limit_captcha_creation() -> Result<(), CallError> {
     }
     let result = api::create_challenge(&env, canister_id);
 
-    expect_user_error_with_message(
-        result,
-        CanisterCalledTrap,
-        Regex::new("too many inflight captchas").unwrap(),
+    expect_reject_with_message(result, Regex::new("too many inflight captchas").unwrap());
+    assert_metric(
+        &get_metrics(&env, canister_id),
+        "internet_identity_captchas{event=\"rejected_inflight_limit\"}",
+        1f64,
     );
     Ok(())
 }
@@ -239,11 +240,15 @@ fn should_rate_limit_register_calls() -> Result<(), CallError> {
         },
         None,
     );
-    expect_user_error_with_message(
+    expect_reject_with_message(
         result,
-        CanisterCalledTrap,
         Regex::new("rate limit reached, try again later").unwrap(),
     );
+    assert_metric(
+        &get_metrics(&env, canister_id),
+        "internet_identity_registrations{outcome=\"rate_limited\"}",
+        1f64,
+    );
     assert_metric(
         &get_metrics(&env, canister_id),
         "internet_identity_register_rate_limit_current_tokens",
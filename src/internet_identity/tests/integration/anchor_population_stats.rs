@@ -0,0 +1,136 @@
+//! Tests for the anchor population statistics.
+
+use canister_tests::api::internet_identity as api;
+use canister_tests::flows;
+use canister_tests::framework::{
+    assert_metric, device_data_2, env, get_metrics, install_ii_canister, principal_1,
+    recovery_device_data_1, upgrade_ii_canister, II_WASM, II_WASM_PREVIOUS,
+};
+use internet_identity_interface::internet_identity::types::{
+    AnchorPopulationStats, DevicesByKeyType,
+};
+use pocket_ic::CallError;
+
+/// Verifies that the population stats are updated when anchors are registered and modified.
+#[test]
+fn should_update_population_stats() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let user_number = flows::register_anchor(&env, canister_id);
+    flows::register_anchor_with_device(&env, canister_id, &device_data_2());
+
+    api::add(
+        &env,
+        canister_id,
+        principal_1(),
+        user_number,
+        &recovery_device_data_1(),
+    )?;
+
+    assert_eq!(
+        api::anchor_population_stats(&env, canister_id)?,
+        AnchorPopulationStats {
+            counted_anchors: 2,
+            anchors_by_device_count: vec![0, 1, 1],
+            anchors_with_recovery_phrase: 1,
+            anchors_with_recovery_device: 0,
+            devices_by_key_type: DevicesByKeyType {
+                unknown: 2,
+                seed_phrase: 1,
+                ..DevicesByKeyType::default()
+            },
+            protected_devices: 0,
+            unprotected_devices: 3,
+        }
+    );
+    Ok(())
+}
+
+/// Verifies that the population stats are exported as metrics.
+#[test]
+fn should_export_population_stats_metrics() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let user_number = flows::register_anchor(&env, canister_id);
+    flows::register_anchor_with_device(&env, canister_id, &device_data_2());
+    api::add(
+        &env,
+        canister_id,
+        principal_1(),
+        user_number,
+        &recovery_device_data_1(),
+    )?;
+
+    let metrics = get_metrics(&env, canister_id);
+    assert_metric(
+        &metrics,
+        "internet_identity_anchors_by_device_count_bucket{le=\"1\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_anchors_by_device_count_bucket{le=\"2\"}",
+        2f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_anchors_by_device_count_sum",
+        3f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_anchors_with_recovery_phrase",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_anchors_with_recovery_device",
+        0f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_devices_by_key_type{type=\"seed_phrase\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_devices_by_protection{protection=\"unprotected\"}",
+        3f64,
+    );
+    Ok(())
+}
+
+/// Verifies that anchors registered by a release without population stats are counted after the
+/// upgrade.
+#[test]
+fn should_backfill_population_stats_after_upgrade() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM_PREVIOUS.clone());
+    for _ in 0..3 {
+        flows::register_anchor(&env, canister_id);
+    }
+
+    upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+    // run the backfill timer
+    env.tick();
+
+    let stats = api::anchor_population_stats(&env, canister_id)?;
+    assert_eq!(stats.counted_anchors, 3);
+    assert_eq!(stats.anchors_by_device_count, vec![0, 3]);
+    Ok(())
+}
+
+/// Verifies that the population stats are kept across upgrades.
+#[test]
+fn should_keep_population_stats_across_upgrades() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    flows::register_anchor(&env, canister_id);
+    flows::register_anchor_with_device(&env, canister_id, &device_data_2());
+    let stats = api::anchor_population_stats(&env, canister_id)?;
+
+    upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+
+    assert_eq!(api::anchor_population_stats(&env, canister_id)?, stats);
+    Ok(())
+}
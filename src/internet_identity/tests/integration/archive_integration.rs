@@ -45,6 +45,8 @@ mod deployment_tests {
                     entries_buffer_limit: 0,
                     polling_interval_ns: 0,
                     entries_fetch_limit: 0,
+                    archive_integration: None,
+                    retention_period_ns: None,
                 }),
                 canister_creation_cycles_cost: Some(100_000_000_000), // current cost in application subnets
                 ..InternetIdentityInit::default()
@@ -177,6 +179,8 @@ mod deployment_tests {
                     entries_buffer_limit: 10,
                     polling_interval_ns: 5_000,
                     entries_fetch_limit: 10,
+                    archive_integration: None,
+                    retention_period_ns: None,
                 }),
                 ..InternetIdentityInit::default()
             }),
@@ -512,6 +516,80 @@ mod pull_entries_tests {
         Ok(())
     }
 
+    /// Test to verify that the archive pulls authentication events from II if the anchor opted in.
+    #[test]
+    fn should_record_authn_events_if_opted_in() -> Result<(), CallError> {
+        const FRONTEND_HOSTNAME: &str = "https://some-dapp.com";
+        let env = env();
+        let ii_canister = install_ii_canister_with_arg(
+            &env,
+            II_WASM.clone(),
+            arg_with_wasm_hash(ARCHIVE_WASM.clone()),
+        );
+
+        let archive_canister = deploy_archive_via_ii(&env, ii_canister);
+        assert!(env.canister_exists(archive_canister));
+
+        let device = DeviceData::auth_test_device();
+        let anchor = flows::register_anchor_with_device(&env, ii_canister, &device);
+
+        // not opted in yet --> not archived
+        ii_api::get_anchor_info(&env, ii_canister, device.principal(), anchor)?;
+
+        let metadata = HashMap::from_iter(vec![(
+            "archive_authn_events".to_string(),
+            MetadataEntry::String("true".to_string()),
+        )]);
+        ii_api::api_v2::identity_metadata_replace(
+            &env,
+            ii_canister,
+            device.principal(),
+            anchor,
+            &metadata,
+        )?;
+        ii_api::get_anchor_info(&env, ii_canister, device.principal(), anchor)?;
+        ii_api::prepare_delegation(
+            &env,
+            ii_canister,
+            device.principal(),
+            anchor,
+            FRONTEND_HOSTNAME,
+            &ByteBuf::from("session key"),
+            None,
+        )?;
+
+        // the archive polls for entries once per second
+        env.advance_time(Duration::from_secs(2));
+        // execute the timer
+        env.tick();
+
+        let entries = archive_api::get_anchor_entries(&env, archive_canister, anchor, None, None)?;
+        let operations: Vec<Operation> = entries
+            .entries
+            .into_iter()
+            .map(|entry| entry.unwrap().operation)
+            .collect();
+        assert_eq!(
+            operations,
+            vec![
+                Operation::RegisterAnchor {
+                    device: DeviceDataWithoutAlias::from(device.clone()),
+                },
+                Operation::IdentityMetadataReplace {
+                    metadata_keys: vec!["archive_authn_events".to_string()],
+                },
+                Operation::Authenticate {
+                    device: device.pubkey.clone(),
+                },
+                Operation::PrepareDelegation {
+                    device: device.pubkey.clone(),
+                    frontend: FRONTEND_HOSTNAME.to_string(),
+                },
+            ]
+        );
+        Ok(())
+    }
+
     /// Test to verify that the archive pulls the anchor operations from II periodically.
     #[test]
     fn should_fetch_multiple_times() -> Result<(), CallError> {
@@ -650,6 +728,8 @@ mod pull_entries_tests {
                 entries_buffer_limit: 20_000,
                 polling_interval_ns: Duration::from_secs(3).as_nanos() as u64,
                 entries_fetch_limit: 10,
+                archive_integration: None,
+                retention_period_ns: None,
             }),
             canister_creation_cycles_cost: Some(0),
             ..InternetIdentityInit::default()
@@ -782,6 +862,108 @@ mod pull_entries_tests {
         Ok(())
     }
 
+    /// Tests that II reports entries that were acknowledged without having been fetched.
+    #[test]
+    fn should_report_dropped_entries() -> Result<(), CallError> {
+        let env = env();
+        let ii_canister = install_ii_canister_with_arg(
+            &env,
+            II_WASM.clone(),
+            arg_with_wasm_hash(ARCHIVE_WASM.clone()),
+        );
+
+        let archive_canister = deploy_archive_via_ii(&env, ii_canister);
+        assert!(env.canister_exists(archive_canister));
+
+        for _ in 0..3 {
+            flows::register_anchor(&env, ii_canister);
+        }
+
+        // acknowledge entries that were never fetched
+        ii_api::acknowledge_entries(&env, ii_canister, archive_canister, 1)?;
+        let timestamp = env
+            .get_time()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+
+        // fetched entries are not reported as dropped
+        let entries = ii_api::fetch_entries(&env, ii_canister, archive_canister)?;
+        assert_eq!(entries.len(), 1);
+        ii_api::acknowledge_entries(&env, ii_canister, archive_canister, 2)?;
+
+        let dropped = ii_api::dropped_archive_entries(&env, ii_canister)?;
+        assert_eq!(
+            dropped,
+            vec![DroppedArchiveEntries {
+                first_sequence_number: 0,
+                last_sequence_number: 1,
+                timestamp,
+            }]
+        );
+        Ok(())
+    }
+
+    /// Tests that II reports the authentication events that were dropped because the buffer was
+    /// full.
+    #[test]
+    fn should_report_dropped_authn_events() -> Result<(), CallError> {
+        let env = env();
+        let ii_canister = install_ii_canister_with_arg(
+            &env,
+            II_WASM.clone(),
+            Some(InternetIdentityInit {
+                archive_config: Some(ArchiveConfig {
+                    module_hash: archive_wasm_hash(&ARCHIVE_WASM),
+                    entries_buffer_limit: 2,
+                    // make sure the entries are not fetched during the test
+                    polling_interval_ns: Duration::from_secs(60 * 60).as_nanos() as u64,
+                    entries_fetch_limit: 10,
+                    archive_integration: None,
+                    retention_period_ns: None,
+                }),
+                canister_creation_cycles_cost: Some(0),
+                ..InternetIdentityInit::default()
+            }),
+        );
+        let archive_canister = deploy_archive_via_ii(&env, ii_canister);
+
+        let device = DeviceData::auth_test_device();
+        let anchor = flows::register_anchor_with_device(&env, ii_canister, &device);
+        let metadata = HashMap::from_iter(vec![(
+            "archive_authn_events".to_string(),
+            MetadataEntry::String("true".to_string()),
+        )]);
+        ii_api::api_v2::identity_metadata_replace(
+            &env,
+            ii_canister,
+            device.principal(),
+            anchor,
+            &metadata,
+        )?;
+
+        // the buffer is full --> the authentication events are dropped
+        for _ in 0..2 {
+            ii_api::get_anchor_info(&env, ii_canister, device.principal(), anchor)?;
+        }
+        let dropped = ii_api::dropped_archive_entries(&env, ii_canister)?;
+        let ranges: Vec<(u64, u64)> = dropped
+            .iter()
+            .map(|range| (range.first_sequence_number, range.last_sequence_number))
+            .collect();
+        assert_eq!(ranges, vec![(2, 3)]);
+
+        // once the buffer has been emptied, the next event is archived after the dropped ones
+        let entries = ii_api::fetch_entries(&env, ii_canister, archive_canister)?;
+        assert_eq!(entries.len(), 2);
+        ii_api::acknowledge_entries(&env, ii_canister, archive_canister, 1)?;
+        ii_api::get_anchor_info(&env, ii_canister, device.principal(), anchor)?;
+        let entries = ii_api::fetch_entries(&env, ii_canister, archive_canister)?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].sequence_number, 4);
+        Ok(())
+    }
+
     /// Tests that only the archive canister can fetch entries.
     #[test]
     fn should_not_allow_wrong_caller_to_fetch_entries() {
@@ -830,3 +1012,131 @@ mod pull_entries_tests {
         );
     }
 }
+
+/// Test the functionality of pushing entries from II to the archive.
+#[cfg(test)]
+mod push_entries_tests {
+    use super::*;
+
+    fn arg_with_push_integration() -> Option<InternetIdentityInit> {
+        Some(InternetIdentityInit {
+            archive_config: Some(ArchiveConfig {
+                module_hash: archive_wasm_hash(&ARCHIVE_WASM),
+                entries_buffer_limit: 10_000,
+                // make sure the entries are not picked up by polling
+                polling_interval_ns: Duration::from_secs(60 * 60).as_nanos() as u64,
+                entries_fetch_limit: 10,
+                archive_integration: Some(ArchiveIntegration::Push),
+                retention_period_ns: None,
+            }),
+            canister_creation_cycles_cost: Some(0),
+            ..InternetIdentityInit::default()
+        })
+    }
+
+    /// Test to verify that II pushes the anchor operations to the archive without waiting for
+    /// the archive to poll.
+    #[test]
+    fn should_push_anchor_operations() -> Result<(), CallError> {
+        let env = env();
+        let ii_canister =
+            install_ii_canister_with_arg(&env, II_WASM.clone(), arg_with_push_integration());
+
+        let archive_canister = deploy_archive_via_ii(&env, ii_canister);
+        assert!(env.canister_exists(archive_canister));
+
+        let status = archive_api::status(&env, archive_canister)?;
+        assert_eq!(
+            status.init.archive_integration,
+            Some(ArchiveIntegration::Push)
+        );
+
+        for _ in 0..3 {
+            flows::register_anchor(&env, ii_canister);
+        }
+
+        // execute the push timer and the resulting calls
+        for _ in 0..5 {
+            env.tick();
+        }
+
+        let entries = archive_api::get_entries(&env, archive_canister, None, None)?;
+        assert_eq!(entries.entries.len(), 3);
+        let sequence_numbers: Vec<u64> = entries
+            .entries
+            .iter()
+            .map(|entry| entry.as_ref().unwrap().sequence_number)
+            .collect();
+        assert_eq!(sequence_numbers, vec![0, 1, 2]);
+
+        // the pushed entries are no longer buffered on the II side
+        let buffered = ii_api::fetch_entries(&env, ii_canister, archive_canister)?;
+        assert!(buffered.is_empty());
+        Ok(())
+    }
+
+    /// Test to verify that II retries pushing entries if the archive is not available.
+    #[test]
+    fn should_retry_push_after_failure() -> Result<(), CallError> {
+        let env = env();
+        let ii_canister =
+            install_ii_canister_with_arg(&env, II_WASM.clone(), arg_with_push_integration());
+
+        let archive_canister = deploy_archive_via_ii(&env, ii_canister);
+        assert!(env.canister_exists(archive_canister));
+
+        // stop the archive canister to provoke failures
+        env.stop_canister(archive_canister, None)?;
+        flows::register_anchor(&env, ii_canister);
+        for _ in 0..5 {
+            env.tick();
+        }
+
+        env.start_canister(archive_canister, None)?;
+        let entries = archive_api::get_entries(&env, archive_canister, None, None)?;
+        assert!(entries.entries.is_empty());
+
+        // wait for the retry
+        env.advance_time(Duration::from_secs(2));
+        for _ in 0..5 {
+            env.tick();
+        }
+
+        let entries = archive_api::get_entries(&env, archive_canister, None, None)?;
+        assert_eq!(entries.entries.len(), 1);
+        Ok(())
+    }
+
+    /// Test to verify that the entries_buffer_limit also applies when pushing entries.
+    #[test]
+    fn should_enforce_buffer_limit_in_push_mode() -> Result<(), CallError> {
+        let env = env();
+        let mut arg = arg_with_push_integration().unwrap();
+        arg.archive_config.as_mut().unwrap().entries_buffer_limit = 1;
+        let ii_canister = install_ii_canister_with_arg(&env, II_WASM.clone(), Some(arg));
+
+        let archive_canister = deploy_archive_via_ii(&env, ii_canister);
+        env.stop_canister(archive_canister, None)?;
+
+        flows::register_anchor(&env, ii_canister);
+        let result = ii_api::create_challenge(&env, ii_canister).and_then(|challenge| {
+            ii_api::register(
+                &env,
+                ii_canister,
+                principal_1(),
+                &device_data_1(),
+                &ChallengeAttempt {
+                    chars: "a".to_string(),
+                    key: challenge.challenge_key,
+                },
+                None,
+            )
+        });
+        expect_user_error_with_message(
+            result,
+            CanisterCalledTrap,
+            Regex::new("cannot archive operation, archive entries buffer limit reached").unwrap(),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! Tests for the configurable Content-Security-Policy and the collection of CSP violation reports.
+
+use crate::http::verify_response_certification;
+use candid::Principal;
+use canister_tests::api::{http_request, http_request_update, internet_identity as api};
+use canister_tests::framework::{
+    env, expect_user_error_with_message, install_ii_canister, install_ii_canister_with_arg,
+    principal_1, upgrade_ii_canister_with_arg, II_WASM,
+};
+use ic_cdk::api::management_canister::main::CanisterId;
+use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
+use internet_identity_interface::internet_identity::types::{
+    ContentSecurityPolicyConfig, InternetIdentityInit,
+};
+use pocket_ic::ErrorCode::CanisterCalledTrap;
+use pocket_ic::{CallError, PocketIc};
+use regex::Regex;
+use serde_bytes::ByteBuf;
+
+fn install_with_csp_config(env: &PocketIc, config: ContentSecurityPolicyConfig) -> CanisterId {
+    install_ii_canister_with_arg(
+        env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            content_security_policy: Some(config),
+            ..Default::default()
+        }),
+    )
+}
+
+fn get_index(env: &PocketIc, canister_id: CanisterId) -> Result<HttpResponse, CallError> {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/".to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(2),
+    };
+    let http_response = http_request(env, canister_id, &request)?;
+    verify_response_certification(env, canister_id, request, http_response.clone(), 2);
+    Ok(http_response)
+}
+
+fn header<'a>(http_response: &'a HttpResponse, name: &str) -> Option<&'a str> {
+    http_response
+        .headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn report_request(body: &str) -> HttpRequest {
+    HttpRequest {
+        method: "POST".to_string(),
+        url: "/csp-report".to_string(),
+        headers: vec![(
+            "Content-Type".to_string(),
+            "application/csp-report".to_string(),
+        )],
+        body: ByteBuf::from(body),
+        certificate_version: Some(2),
+    }
+}
+
+fn report(blocked_uri: &str) -> String {
+    format!(
+        r#"{{"csp-report": {{
+            "document-uri": "https://identity.ic0.app/#authorize",
+            "effective-directive": "connect-src",
+            "blocked-uri": "{blocked_uri}",
+            "disposition": "enforce"
+        }}}}"#
+    )
+}
+
+/// Verifies that the configured CSP is delivered in both the header and the HTML meta tag, and that
+/// the responses are certified with it.
+#[test]
+fn should_apply_configured_csp() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_with_csp_config(
+        &env,
+        ContentSecurityPolicyConfig {
+            connect_sources: Some(vec!["https://api.example.com".to_string()]),
+            upgrade_insecure_requests: Some(false),
+            report_violations: None,
+        },
+    );
+
+    let http_response = get_index(&env, canister_id)?;
+    let csp = header(&http_response, "Content-Security-Policy").expect("CSP header not found");
+    assert!(csp.contains(
+        "connect-src 'self' https://identity.internetcomputer.org https://api.example.com;"
+    ));
+    assert!(!csp.contains("upgrade-insecure-requests"));
+    assert!(!csp.contains("report-uri"));
+
+    let body = String::from_utf8_lossy(&http_response.body);
+    assert!(body.contains(
+        "connect-src 'self' https://identity.internetcomputer.org https://api.example.com;"
+    ));
+    Ok(())
+}
+
+/// Verifies that CSP violations are reported to the canister and recorded deduplicated.
+#[test]
+fn should_record_csp_violation_reports() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_with_csp_config(
+        &env,
+        ContentSecurityPolicyConfig {
+            report_violations: Some(true),
+            ..Default::default()
+        },
+    );
+
+    let http_response = get_index(&env, canister_id)?;
+    let csp = header(&http_response, "Content-Security-Policy").expect("CSP header not found");
+    assert!(csp.ends_with("report-uri /csp-report;report-to csp-endpoint;"));
+    assert_eq!(
+        header(&http_response, "Reporting-Endpoints"),
+        Some(r#"csp-endpoint="/csp-report""#)
+    );
+
+    // reports are upgraded to update calls
+    let request = report_request(&report("https://evil.com/a?secret=1"));
+    let http_response = http_request(&env, canister_id, &request)?;
+    assert_eq!(http_response.upgrade, Some(true));
+
+    for blocked_uri in [
+        "https://evil.com/a?secret=1",
+        "https://evil.com/b",
+        "https://evil.com/a?secret=2",
+    ] {
+        let http_response =
+            http_request_update(&env, canister_id, &report_request(&report(blocked_uri)))?;
+        assert_eq!(http_response.status_code, 204);
+    }
+    let http_response = http_request_update(&env, canister_id, &report_request("not json"))?;
+    assert_eq!(http_response.status_code, 400);
+
+    let reports = api::csp_violation_reports(&env, canister_id, Principal::anonymous())?;
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].blocked_uri, "https://evil.com/b");
+    assert_eq!(reports[0].count, 1);
+    assert_eq!(reports[1].blocked_uri, "https://evil.com/a");
+    assert_eq!(reports[1].document_uri, "https://identity.ic0.app/");
+    assert_eq!(reports[1].effective_directive, "connect-src");
+    assert_eq!(reports[1].count, 2);
+
+    let result = api::csp_violation_reports(&env, canister_id, principal_1());
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("is not a controller of this canister").unwrap(),
+    );
+    Ok(())
+}
+
+/// Verifies that reports are not accepted unless violation reporting is enabled.
+#[test]
+fn should_not_accept_reports_by_default() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let http_response = http_request(
+        &env,
+        canister_id,
+        &report_request(&report("https://evil.com/a")),
+    )?;
+    assert_eq!(http_response.status_code, 404);
+    assert_eq!(http_response.upgrade, None);
+
+    let reports = api::csp_violation_reports(&env, canister_id, Principal::anonymous())?;
+    assert!(reports.is_empty());
+    Ok(())
+}
+
+/// Verifies that sources that would add directives to the policy are rejected.
+#[test]
+fn should_reject_invalid_csp_config() {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let result = upgrade_ii_canister_with_arg(
+        &env,
+        canister_id,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            content_security_policy: Some(ContentSecurityPolicyConfig {
+                connect_sources: Some(vec!["https://a.com; script-src *".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+    );
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("Invalid CSP source").unwrap(),
+    );
+}
@@ -0,0 +1,193 @@
+//! Tests for the health checks, served by the `health_check` query and at `/health`.
+
+use crate::http::verify_response_certification;
+use canister_tests::api::{http_request, internet_identity as api};
+use canister_tests::flows;
+use canister_tests::framework::*;
+use internet_identity_interface::http_gateway::HttpRequest;
+use internet_identity_interface::internet_identity::types::{
+    ArchiveConfig, ArchiveIntegration, HealthReport, HealthStatus, InternetIdentityInit,
+};
+use pocket_ic::CallError;
+use serde_bytes::ByteBuf;
+use std::time::Duration;
+
+fn check_status(report: &HealthReport, name: &str) -> HealthStatus {
+    report
+        .checks
+        .iter()
+        .find(|check| check.name == name)
+        .unwrap_or_else(|| panic!("check {name} not found in {report:?}"))
+        .status
+}
+
+/// Interval at which the health report served at `/health` is re-certified.
+const CERTIFICATION_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+fn health_request() -> HttpRequest {
+    HttpRequest {
+        method: "GET".to_string(),
+        url: "/health".to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(2),
+    }
+}
+
+/// Verifies that all checks are run and that the salt is reported as not set until the first
+/// registration.
+#[test]
+fn should_report_health_checks() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let report = api::health_check(&env, canister_id)?;
+    let names: Vec<&str> = report
+        .checks
+        .iter()
+        .map(|check| check.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "storage",
+            "salt",
+            "stable_memory",
+            "archive_buffer",
+            "archive_fetch",
+            "signatures",
+            "captcha_backlog"
+        ]
+    );
+    assert_eq!(check_status(&report, "salt"), HealthStatus::Warn);
+    assert_eq!(report.status, HealthStatus::Warn);
+
+    flows::register_anchor(&env, canister_id);
+    let report = api::health_check(&env, canister_id)?;
+    assert_eq!(report.status, HealthStatus::Pass, "{report:?}");
+    Ok(())
+}
+
+/// Verifies that the health report is served certified at `/health` by queries (without upgrade to
+/// an update call) and that it is re-certified periodically.
+#[test]
+fn should_certify_health_report() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    flows::register_anchor(&env, canister_id);
+
+    let http_response = http_request(&env, canister_id, &health_request())?;
+    assert_eq!(http_response.status_code, 200);
+    assert_eq!(http_response.upgrade, None);
+    assert!(http_response
+        .headers
+        .contains(&("Content-Type".to_string(), "application/json".to_string())));
+    let json: serde_json::Value = serde_json::from_slice(&http_response.body).unwrap();
+    assert_eq!(json["status"], "pass");
+    assert_eq!(json["checks"][0]["name"], "storage");
+    verify_security_headers(&http_response.headers);
+    let result =
+        verify_response_certification(&env, canister_id, health_request(), http_response, 2);
+    assert!(result.response.is_some(), "health report is not certified");
+
+    // registering an anchor changes the storage check once the report is re-certified
+    flows::register_anchor(&env, canister_id);
+    env.advance_time(CERTIFICATION_REFRESH_INTERVAL);
+    env.tick();
+    let http_response = http_request(&env, canister_id, &health_request())?;
+    assert_eq!(http_response.upgrade, None);
+    let json: serde_json::Value = serde_json::from_slice(&http_response.body).unwrap();
+    assert!(json["checks"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("2 of"));
+    let result =
+        verify_response_certification(&env, canister_id, health_request(), http_response, 2);
+    assert!(result.response.is_some(), "health report is not certified");
+    Ok(())
+}
+
+/// Verifies that a full archive buffer and an archive not fetching the buffered entries are
+/// reported as failures, and that `/health` responds with 503 in that case.
+#[test]
+fn should_report_failing_archive() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister_with_arg(
+        &env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            archive_config: Some(ArchiveConfig {
+                module_hash: archive_wasm_hash(&ARCHIVE_WASM),
+                entries_buffer_limit: 1,
+                polling_interval_ns: Duration::from_secs(1).as_nanos() as u64,
+                entries_fetch_limit: 10,
+                archive_integration: None,
+                retention_period_ns: None,
+            }),
+            canister_creation_cycles_cost: Some(0),
+            ..InternetIdentityInit::default()
+        }),
+    );
+    let archive_canister = deploy_archive_via_ii(&env, canister_id);
+    flows::register_anchor(&env, canister_id);
+
+    let report = api::health_check(&env, canister_id)?;
+    assert_eq!(check_status(&report, "archive_buffer"), HealthStatus::Fail);
+    assert_eq!(check_status(&report, "archive_fetch"), HealthStatus::Pass);
+    assert_eq!(report.status, HealthStatus::Fail);
+
+    // stop the archive canister so that it does not fetch the entries while the report is
+    // re-certified
+    env.stop_canister(archive_canister, None)?;
+    env.advance_time(CERTIFICATION_REFRESH_INTERVAL);
+    env.tick();
+    let http_response = http_request(&env, canister_id, &health_request())?;
+    assert_eq!(http_response.status_code, 503);
+    verify_response_certification(&env, canister_id, health_request(), http_response, 2);
+
+    env.advance_time(Duration::from_secs(101));
+    let report = api::health_check(&env, canister_id)?;
+    assert_eq!(check_status(&report, "archive_fetch"), HealthStatus::Fail);
+    Ok(())
+}
+
+/// Verifies that with the push integration, the lag of the archive is measured against the maximum
+/// delay between two push attempts rather than the (unused) polling interval.
+#[test]
+fn should_report_archive_lag_with_push_integration() -> Result<(), CallError> {
+    const PUSH_RETRY_MAX_DELAY_SECS: u64 = 5 * 60;
+    let env = env();
+    let canister_id = install_ii_canister_with_arg(
+        &env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            archive_config: Some(ArchiveConfig {
+                module_hash: archive_wasm_hash(&ARCHIVE_WASM),
+                entries_buffer_limit: 10,
+                polling_interval_ns: Duration::from_secs(1).as_nanos() as u64,
+                entries_fetch_limit: 10,
+                archive_integration: Some(ArchiveIntegration::Push),
+                retention_period_ns: None,
+            }),
+            canister_creation_cycles_cost: Some(0),
+            ..InternetIdentityInit::default()
+        }),
+    );
+    let archive_canister = deploy_archive_via_ii(&env, canister_id);
+    // stop the archive canister so that the entries cannot be pushed
+    env.stop_canister(archive_canister, None)?;
+    flows::register_anchor(&env, canister_id);
+
+    env.advance_time(Duration::from_secs(101));
+    let report = api::health_check(&env, canister_id)?;
+    assert_eq!(check_status(&report, "archive_fetch"), HealthStatus::Pass);
+
+    env.advance_time(Duration::from_secs(10 * PUSH_RETRY_MAX_DELAY_SECS));
+    let report = api::health_check(&env, canister_id)?;
+    assert_eq!(check_status(&report, "archive_fetch"), HealthStatus::Warn);
+
+    env.advance_time(Duration::from_secs(90 * PUSH_RETRY_MAX_DELAY_SECS));
+    let report = api::health_check(&env, canister_id)?;
+    assert_eq!(check_status(&report, "archive_fetch"), HealthStatus::Fail);
+    Ok(())
+}
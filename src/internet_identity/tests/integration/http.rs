@@ -1,14 +1,16 @@
 //! Tests for the HTTP interactions according to the HTTP gateway spec: https://internetcomputer.org/docs/current/references/ic-interface-spec/#http-gateway
 //! Includes tests for the HTTP endpoint (including asset certification) and the metrics endpoint.
 
-use canister_tests::api::{http_request, internet_identity as api};
+use canister_tests::api::{http_request, http_request_update, internet_identity as api};
 use canister_tests::flows;
 use canister_tests::framework::*;
 use ic_cdk::api::management_canister::main::CanisterId;
 use ic_response_verification::types::{Request, Response, VerificationInfo};
 use ic_response_verification::verify_request_response_pair;
-use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
-use internet_identity_interface::internet_identity::types::ChallengeAttempt;
+use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse, StreamingStrategy};
+use internet_identity_interface::internet_identity::types::{
+    ChallengeAttempt, InternetIdentityInit, RegisterResponse,
+};
 use pocket_ic::{CallError, PocketIc};
 use serde_bytes::ByteBuf;
 use std::time::{Duration, UNIX_EPOCH};
@@ -20,6 +22,7 @@ fn ii_canister_serves_http_assets() -> Result<(), CallError> {
         ("/", None),
         ("/index.js", Some("gzip")),
         ("/.well-known/ic-domains", None),
+        ("/.well-known/webauthn", None),
     ];
     let env = env();
     let canister_id = install_ii_canister(&env, II_WASM.clone());
@@ -30,7 +33,7 @@ fn ii_canister_serves_http_assets() -> Result<(), CallError> {
             let request = HttpRequest {
                 method: "GET".to_string(),
                 url: asset.to_string(),
-                headers: vec![],
+                headers: vec![("Accept-Encoding".to_string(), "gzip".to_string())],
                 body: ByteBuf::new(),
                 certificate_version: Some(certification_version),
             };
@@ -65,6 +68,72 @@ fn ii_canister_serves_http_assets() -> Result<(), CallError> {
     Ok(())
 }
 
+/// Verifies that the encoding of assets available in several encodings is negotiated based on the
+/// Accept-Encoding header and that each encoding is certified. The built-in JS assets are only
+/// available gzip and brotli encoded, so gzip is served if neither is accepted.
+#[test]
+fn should_negotiate_asset_content_encoding() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    // (Accept-Encoding header, expected Content-Encoding header)
+    let cases: Vec<(Option<&str>, Option<&str>)> = vec![
+        (None, Some("gzip")),
+        (Some("identity"), Some("gzip")),
+        (Some("gzip"), Some("gzip")),
+        (Some("br"), Some("br")),
+        (Some("gzip, deflate, br"), Some("br")),
+        (Some("br;q=0, gzip"), Some("gzip")),
+        (Some("gzip;q=0.5, br;q=0.8"), Some("br")),
+        (Some("identity;q=0, gzip"), Some("gzip")),
+        (Some("deflate"), Some("gzip")),
+    ];
+    for (accept_encoding, expected_encoding) in cases {
+        let request = HttpRequest {
+            method: "GET".to_string(),
+            url: "/index.js".to_string(),
+            headers: accept_encoding
+                .map(|value| vec![("Accept-Encoding".to_string(), value.to_string())])
+                .unwrap_or_default(),
+            body: ByteBuf::new(),
+            certificate_version: Some(2),
+        };
+        let http_response = http_request_streamed(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 200);
+
+        let content_encoding = http_response
+            .headers
+            .iter()
+            .find(|(name, _)| name.to_lowercase() == "content-encoding")
+            .map(|(_, value)| value.as_str());
+        assert_eq!(
+            content_encoding, expected_encoding,
+            "unexpected Content-Encoding for Accept-Encoding {accept_encoding:?}"
+        );
+        assert!(http_response
+            .headers
+            .contains(&("Vary".to_string(), "Accept-Encoding".to_string())));
+
+        let result = verify_response_certification(&env, canister_id, request, http_response, 2);
+        assert_eq!(result.verification_version, 2);
+    }
+
+    // certification v1 only supports a single response per path, which is the gzip encoded one
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/index.js".to_string(),
+        headers: vec![("Accept-Encoding".to_string(), "br".to_string())],
+        body: ByteBuf::new(),
+        certificate_version: Some(1),
+    };
+    let http_response = http_request(&env, canister_id, &request)?;
+    assert!(http_response
+        .headers
+        .contains(&("Content-Encoding".to_string(), "gzip".to_string())));
+    verify_response_certification(&env, canister_id, request, http_response, 1);
+    Ok(())
+}
+
 /// Verifies that clients that do not indicate any certification version will get a v1 certificate.
 #[test]
 fn should_fallback_to_v1_certification() -> Result<(), CallError> {
@@ -129,6 +198,367 @@ fn should_set_cache_control_for_fonts() -> Result<(), CallError> {
     Ok(())
 }
 
+/// Verifies that assets have strong ETags and that conditional requests with a matching ETag are
+/// answered with a certified `304 Not Modified` response.
+#[test]
+fn should_serve_not_modified_for_matching_etag() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let request = |headers: Vec<(&str, &str)>, certification_version: u16| HttpRequest {
+        method: "GET".to_string(),
+        url: "/index.js".to_string(),
+        headers: headers
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        body: ByteBuf::new(),
+        certificate_version: Some(certification_version),
+    };
+    let etag_of = |http_response: &HttpResponse| {
+        http_response
+            .headers
+            .iter()
+            .find(|(name, _)| name == "ETag")
+            .map(|(_, value)| value.clone())
+            .expect("ETag header not found")
+    };
+
+    let http_response = http_request(
+        &env,
+        canister_id,
+        &request(vec![("Accept-Encoding", "br")], 2),
+    )?;
+    assert_eq!(http_response.status_code, 200);
+    assert!(http_response
+        .headers
+        .contains(&("Cache-Control".to_string(), "no-cache".to_string())));
+    let etag = etag_of(&http_response);
+    let gzip_response = http_request(
+        &env,
+        canister_id,
+        &request(vec![("Accept-Encoding", "gzip")], 2),
+    )?;
+    let gzip_etag = etag_of(&gzip_response);
+    assert_ne!(etag, gzip_etag);
+
+    for (if_none_match, accept_encoding, expected_etag) in [
+        (etag.clone(), "br", &etag),
+        (format!("\"other\", W/{etag}"), "br", &etag),
+        (gzip_etag.clone(), "gzip", &gzip_etag),
+        ("*".to_string(), "gzip", &gzip_etag),
+    ] {
+        let request = request(
+            vec![
+                ("If-None-Match", if_none_match.as_str()),
+                ("Accept-Encoding", accept_encoding),
+            ],
+            2,
+        );
+        let http_response = http_request(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 304);
+        assert!(http_response.body.is_empty());
+        assert!(http_response
+            .headers
+            .contains(&("ETag".to_string(), expected_etag.clone())));
+        verify_response_certification(&env, canister_id, request, http_response, 2);
+    }
+
+    // the representation served does not match
+    let request_other_variant = request(
+        vec![
+            ("If-None-Match", gzip_etag.as_str()),
+            ("Accept-Encoding", "br"),
+        ],
+        2,
+    );
+    let http_response = http_request(&env, canister_id, &request_other_variant)?;
+    assert_eq!(http_response.status_code, 200);
+    verify_response_certification(&env, canister_id, request_other_variant, http_response, 2);
+
+    // 304 responses cannot be certified with certification v1
+    let request_v1 = request(vec![("If-None-Match", gzip_etag.as_str())], 1);
+    let http_response = http_request(&env, canister_id, &request_v1)?;
+    assert_eq!(http_response.status_code, 200);
+    verify_response_certification(&env, canister_id, request_v1, http_response, 1);
+    Ok(())
+}
+
+/// Verifies that the configured custom II domains are listed in `/.well-known/ic-domains`.
+#[test]
+fn should_serve_configured_custom_domains() -> Result<(), CallError> {
+    const CERTIFICATION_VERSION: u16 = 2;
+    let env = env();
+    let canister_id = install_ii_canister_with_arg(
+        &env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            ii_origins: Some(vec![
+                "https://identity.ic0.app".to_string(),
+                "https://identity.example.com".to_string(),
+                "https://id.example.org".to_string(),
+            ]),
+            ..InternetIdentityInit::default()
+        }),
+    );
+
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/.well-known/ic-domains".to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(CERTIFICATION_VERSION),
+    };
+    let http_response = http_request(&env, canister_id, &request)?;
+
+    assert_eq!(http_response.status_code, 200);
+    assert_eq!(
+        http_response.body,
+        ByteBuf::from("identity.example.com\nid.example.org")
+    );
+
+    let result = verify_response_certification(
+        &env,
+        canister_id,
+        request,
+        http_response,
+        CERTIFICATION_VERSION,
+    );
+    assert_eq!(result.verification_version, CERTIFICATION_VERSION);
+
+    Ok(())
+}
+
+/// Verifies that all II origins are listed in the WebAuthn related origins document served at
+/// `/.well-known/webauthn`, and that the document follows changes to the II origins.
+#[test]
+fn should_serve_webauthn_related_origins() -> Result<(), CallError> {
+    const CERTIFICATION_VERSION: u16 = 2;
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/.well-known/webauthn".to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(CERTIFICATION_VERSION),
+    };
+
+    let http_response = http_request(&env, canister_id, &request)?;
+    assert_eq!(http_response.status_code, 200);
+    assert_eq!(
+        http_response.body,
+        ByteBuf::from(
+            r#"{"origins":["https://identity.ic0.app","https://identity.internetcomputer.org"]}"#
+        )
+    );
+    for header in [
+        ("Content-Type", "application/json"),
+        ("Access-Control-Allow-Origin", "*"),
+    ] {
+        assert!(http_response
+            .headers
+            .contains(&(header.0.to_string(), header.1.to_string())));
+    }
+    verify_response_certification(
+        &env,
+        canister_id,
+        request.clone(),
+        http_response,
+        CERTIFICATION_VERSION,
+    );
+
+    upgrade_ii_canister_with_arg(
+        &env,
+        canister_id,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            ii_origins: Some(vec![
+                "https://identity.ic0.app".to_string(),
+                "https://identity.example.com".to_string(),
+            ]),
+            ..InternetIdentityInit::default()
+        }),
+    )
+    .expect("II upgrade failed");
+
+    let http_response = http_request(&env, canister_id, &request)?;
+    assert_eq!(
+        http_response.body,
+        ByteBuf::from(r#"{"origins":["https://identity.ic0.app","https://identity.example.com"]}"#)
+    );
+    verify_response_certification(
+        &env,
+        canister_id,
+        request,
+        http_response,
+        CERTIFICATION_VERSION,
+    );
+    Ok(())
+}
+
+/// Verifies that redirects are served as certified responses (instead of being upgraded to update calls).
+#[test]
+fn should_certify_redirects() -> Result<(), CallError> {
+    const CERTIFICATION_VERSION: u16 = 2;
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/faq".to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(CERTIFICATION_VERSION),
+    };
+    let http_response = http_request(&env, canister_id, &request)?;
+
+    assert_eq!(http_response.status_code, 301);
+    assert_eq!(http_response.upgrade, None);
+    assert!(http_response.headers.contains(&(
+        "Location".to_string(),
+        "https://identitysupport.dfinity.org/hc/en-us".to_string()
+    )));
+    verify_security_headers(&http_response.headers);
+
+    let result = verify_response_certification(
+        &env,
+        canister_id,
+        request,
+        http_response,
+        CERTIFICATION_VERSION,
+    );
+    assert_eq!(result.verification_version, CERTIFICATION_VERSION);
+
+    Ok(())
+}
+
+/// Verifies that requests for unknown paths are answered with a certified 404 response.
+#[test]
+fn should_certify_not_found_responses() -> Result<(), CallError> {
+    const CERTIFICATION_VERSION: u16 = 2;
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    for path in [
+        "/does-not-exist",
+        "/some/nested/path",
+        "/faq/",
+        "/metrics/foo",
+    ] {
+        let request = HttpRequest {
+            method: "GET".to_string(),
+            url: path.to_string(),
+            headers: vec![],
+            body: ByteBuf::new(),
+            certificate_version: Some(CERTIFICATION_VERSION),
+        };
+        let http_response = http_request(&env, canister_id, &request)?;
+
+        assert_eq!(
+            http_response.status_code, 404,
+            "unexpected status for {path}"
+        );
+        assert_eq!(http_response.body, ByteBuf::from("Not found."));
+        verify_security_headers(&http_response.headers);
+
+        let result = verify_response_certification(
+            &env,
+            canister_id,
+            request,
+            http_response,
+            CERTIFICATION_VERSION,
+        );
+        assert_eq!(result.verification_version, CERTIFICATION_VERSION);
+    }
+
+    Ok(())
+}
+
+/// Verifies that the metrics are served certified by queries (without upgrade to an update call)
+/// and that they are re-certified periodically.
+#[test]
+fn should_certify_metrics() -> Result<(), CallError> {
+    const CERTIFICATION_VERSION: u16 = 2;
+    const CERTIFICATION_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let requests: Vec<HttpRequest> = [
+        ("/metrics", None),
+        (
+            "/metrics",
+            Some("application/openmetrics-text; version=1.0.0"),
+        ),
+        ("/metrics", Some("application/json")),
+        ("/metrics.json", None),
+        ("/metrics.openmetrics", None),
+    ]
+    .into_iter()
+    .map(|(path, accept)| HttpRequest {
+        method: "GET".to_string(),
+        url: path.to_string(),
+        headers: accept
+            .map(|accept| vec![("Accept".to_string(), accept.to_string())])
+            .unwrap_or_default(),
+        body: ByteBuf::new(),
+        certificate_version: Some(CERTIFICATION_VERSION),
+    })
+    .collect();
+
+    for request in requests.iter().cloned() {
+        let http_response = http_request(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 200);
+        assert_eq!(http_response.upgrade, None);
+        verify_security_headers(&http_response.headers);
+
+        let result = verify_response_certification(
+            &env,
+            canister_id,
+            request,
+            http_response,
+            CERTIFICATION_VERSION,
+        );
+        assert_eq!(result.verification_version, CERTIFICATION_VERSION);
+        assert!(result.response.is_some(), "metrics are not certified");
+    }
+
+    // the certified metrics are served until they are re-certified
+    flows::register_anchor(&env, canister_id);
+    let http_response = http_request(&env, canister_id, &requests[0])?;
+    assert_eq!(http_response.upgrade, None);
+    assert_metric(
+        &String::from_utf8_lossy(&http_response.body),
+        "internet_identity_user_count",
+        0f64,
+    );
+
+    env.advance_time(CERTIFICATION_REFRESH_INTERVAL);
+    env.tick();
+    for request in requests.iter().cloned() {
+        let http_response = http_request(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 200);
+        assert_eq!(http_response.upgrade, None);
+        if request.url == "/metrics" && request.headers.is_empty() {
+            assert_metric(
+                &String::from_utf8_lossy(&http_response.body),
+                "internet_identity_user_count",
+                1f64,
+            );
+        }
+
+        let result = verify_response_certification(
+            &env,
+            canister_id,
+            request,
+            http_response,
+            CERTIFICATION_VERSION,
+        );
+        assert!(result.response.is_some(), "metrics are not certified");
+    }
+
+    Ok(())
+}
+
 /// Verifies that all expected metrics are available via the HTTP endpoint.
 #[test]
 fn ii_canister_serves_http_metrics() -> Result<(), CallError> {
@@ -266,6 +696,49 @@ fn metrics_signature_and_delegation_count() -> Result<(), CallError> {
     Ok(())
 }
 
+/// Verifies that the metrics are served in all formats, with the same set of metrics.
+#[test]
+fn should_serve_metrics_in_all_formats() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    flows::register_anchor(&env, canister_id);
+
+    let (content_type, prometheus) = get_metrics_with_format(&env, canister_id, "/metrics", None);
+    assert_eq!(content_type, "text/plain; version=0.0.4");
+    let expected_names = metric_family_names(&content_type, &prometheus);
+    assert!(expected_names.contains("internet_identity_user_count"));
+
+    let requests = [
+        ("/metrics.json", None, "application/json"),
+        (
+            "/metrics.openmetrics",
+            None,
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        ),
+        ("/metrics", Some("application/json"), "application/json"),
+        (
+            "/metrics",
+            Some("application/openmetrics-text;version=1.0.0,text/plain;q=0.5"),
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        ),
+        (
+            "/metrics",
+            Some("text/plain;version=0.0.4"),
+            "text/plain; version=0.0.4",
+        ),
+    ];
+    for (path, accept, expected_content_type) in requests {
+        let (content_type, body) = get_metrics_with_format(&env, canister_id, path, accept);
+        assert_eq!(content_type, expected_content_type);
+        assert_eq!(
+            metric_family_names(&content_type, &body),
+            expected_names,
+            "metric families of {path} (Accept: {accept:?}) do not match"
+        );
+    }
+    Ok(())
+}
+
 /// Verifies that the stable memory pages count metric is updated correctly.
 #[test]
 fn metrics_stable_memory_pages_should_increase_with_more_users() -> Result<(), CallError> {
@@ -365,6 +838,188 @@ fn metrics_inflight_challenges() -> Result<(), CallError> {
     Ok(())
 }
 
+/// Verifies that the registration funnel metrics are updated correctly.
+#[test]
+fn metrics_registration_funnel() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let challenge_1 = api::create_challenge(&env, canister_id)?;
+    let challenge_2 = api::create_challenge(&env, canister_id)?;
+    api::create_challenge(&env, canister_id)?;
+
+    let result = api::register(
+        &env,
+        canister_id,
+        principal_1(),
+        &device_data_1(),
+        &ChallengeAttempt {
+            chars: "wrong".to_string(),
+            key: challenge_1.challenge_key,
+        },
+        None,
+    )?;
+    assert!(matches!(result, RegisterResponse::BadChallenge));
+    let result = api::register(
+        &env,
+        canister_id,
+        principal_1(),
+        &device_data_1(),
+        &ChallengeAttempt {
+            chars: "a".to_string(),
+            key: challenge_2.challenge_key,
+        },
+        Some(principal_2()),
+    )?;
+    let RegisterResponse::Registered { user_number } = result else {
+        panic!("expected registration to succeed, got {result:?}");
+    };
+    // use the temp key
+    api::get_anchor_info(&env, canister_id, principal_2(), user_number)?;
+
+    // long after expiry (we don't want this test to break, if we change the captcha expiration)
+    env.advance_time(Duration::from_secs(365 * 24 * 60 * 60));
+    api::create_challenge(&env, canister_id)?;
+
+    let metrics = get_metrics(&env, canister_id);
+    assert_metric(
+        &metrics,
+        "internet_identity_captchas{event=\"created\"}",
+        4f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_captchas{event=\"expired\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_captchas{event=\"solved\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_captchas{event=\"failed\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_registrations{outcome=\"registered\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_registrations{outcome=\"bad_challenge\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_temp_keys{event=\"added\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_temp_keys{event=\"authenticated\"}",
+        1f64,
+    );
+    Ok(())
+}
+
+/// Verifies that the registration funnel metrics are kept across upgrades.
+#[test]
+fn metrics_registration_funnel_should_survive_upgrade() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    flows::register_anchor(&env, canister_id);
+
+    upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+
+    let metrics = get_metrics(&env, canister_id);
+    assert_metric(
+        &metrics,
+        "internet_identity_captchas{event=\"created\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_registrations{outcome=\"registered\"}",
+        1f64,
+    );
+    assert_metric(
+        &metrics,
+        "internet_identity_anchor_operations_counter",
+        1f64,
+    );
+    Ok(())
+}
+
+/// Verifies that the instruction histograms of the profiled update endpoints are populated and that
+/// query endpoints are not profiled.
+#[test]
+fn metrics_instruction_histograms() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let user_number = flows::register_anchor(&env, canister_id);
+    api::prepare_delegation(
+        &env,
+        canister_id,
+        principal_1(),
+        user_number,
+        "https://some-dapp.com",
+        &ByteBuf::from("session key"),
+        None,
+    )?;
+    http_request_update(
+        &env,
+        canister_id,
+        &HttpRequest {
+            method: "GET".to_string(),
+            url: "/".to_string(),
+            headers: vec![],
+            body: ByteBuf::new(),
+            certificate_version: Some(2),
+        },
+    )?;
+
+    let metrics = get_metrics(&env, canister_id);
+    for endpoint in [
+        "create_challenge",
+        "register",
+        "prepare_delegation",
+        "http_request_update",
+    ] {
+        assert_metric(
+            &metrics,
+            &format!("internet_identity_{endpoint}_instructions_count"),
+            1f64,
+        );
+        let (instructions, _) = parse_metric(
+            &metrics,
+            &format!("internet_identity_{endpoint}_instructions_sum"),
+        );
+        assert!(instructions > 0f64);
+    }
+
+    // query calls are not profiled, as their state changes are discarded
+    api::get_delegation(
+        &env,
+        canister_id,
+        principal_1(),
+        user_number,
+        "https://some-dapp.com",
+        &ByteBuf::from("session key"),
+        0,
+    )?;
+    let metrics = get_metrics(&env, canister_id);
+    assert!(!metrics.contains("internet_identity_get_delegation_instructions"));
+    assert!(!metrics.contains("internet_identity_http_request_instructions"));
+    // ... which is stated in the help text of the histograms
+    assert!(metrics.contains(
+        "The query endpoints get_delegation, http_request are not profiled, as state changes of query calls are discarded."
+    ));
+    Ok(())
+}
+
 /// Verifies that the users in registration mode metric is updated correctly.
 #[test]
 fn metrics_device_registration_mode() -> Result<(), CallError> {
@@ -470,7 +1125,29 @@ fn metrics_anchor_operations() -> Result<(), CallError> {
     Ok(())
 }
 
-fn verify_response_certification(
+/// Like [http_request], but fetches the remaining chunks of streamed responses, returning the
+/// response with the complete body.
+pub fn http_request_streamed(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    request: &HttpRequest,
+) -> Result<HttpResponse, CallError> {
+    let mut http_response = http_request(env, canister_id, request)?;
+    let mut body = http_response.body.to_vec();
+    let mut token = http_response
+        .streaming_strategy
+        .take()
+        .map(|StreamingStrategy::Callback { token, .. }| token);
+    while let Some(current_token) = token {
+        let chunk = api::http_request_streaming_callback(env, canister_id, &current_token)?;
+        body.extend_from_slice(&chunk.body);
+        token = chunk.token;
+    }
+    http_response.body = ByteBuf::from(body);
+    Ok(http_response)
+}
+
+pub fn verify_response_certification(
     env: &PocketIc,
     canister_id: CanisterId,
     request: HttpRequest,
@@ -0,0 +1,231 @@
+//! Tests for the redirects and aliases served by the HTTP interface.
+
+use crate::http::verify_response_certification;
+use candid::Principal;
+use canister_tests::api::{http_request, internet_identity as api};
+use canister_tests::framework::{
+    env, expect_user_error_with_message, install_ii_canister, install_ii_canister_with_arg,
+    principal_1, upgrade_ii_canister, upgrade_ii_canister_with_arg, II_WASM,
+};
+use ic_cdk::api::management_canister::main::CanisterId;
+use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
+use internet_identity_interface::internet_identity::types::{
+    AssetUploadChunk, HttpAlias, HttpRedirect, HttpRoutes, InternetIdentityInit,
+};
+use pocket_ic::ErrorCode::CanisterCalledTrap;
+use pocket_ic::{CallError, PocketIc};
+use regex::Regex;
+use serde_bytes::ByteBuf;
+
+fn routes() -> HttpRoutes {
+    HttpRoutes {
+        redirects: vec![
+            HttpRedirect {
+                path: "/docs/*".to_string(),
+                target: "https://docs.example.com".to_string(),
+                status_code: 308,
+            },
+            HttpRedirect {
+                path: "/docs/faq".to_string(),
+                target: "https://faq.example.com".to_string(),
+                status_code: 302,
+            },
+        ],
+        aliases: vec![HttpAlias {
+            path: "/about".to_string(),
+            asset_path: "/index.html".to_string(),
+        }],
+    }
+}
+
+fn get(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    path: &str,
+    certification_version: u16,
+) -> Result<HttpResponse, CallError> {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: path.to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(certification_version),
+    };
+    let http_response = http_request(env, canister_id, &request)?;
+    verify_response_certification(
+        env,
+        canister_id,
+        request,
+        http_response.clone(),
+        certification_version,
+    );
+    Ok(http_response)
+}
+
+fn assert_redirect(http_response: &HttpResponse, status_code: u16, location: &str) {
+    assert_eq!(http_response.status_code, status_code);
+    assert!(http_response
+        .headers
+        .contains(&("Location".to_string(), location.to_string())));
+}
+
+/// Verifies that the configured redirects and aliases are served certified, with exact paths taking
+/// precedence over patterns.
+#[test]
+fn should_serve_configured_routes() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister_with_arg(
+        &env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            http_routes: Some(routes()),
+            ..Default::default()
+        }),
+    );
+
+    for path in ["/docs", "/docs/", "/docs/some/page"] {
+        assert_redirect(
+            &get(&env, canister_id, path, 2)?,
+            308,
+            "https://docs.example.com",
+        );
+    }
+    assert_redirect(
+        &get(&env, canister_id, "/docs/faq", 2)?,
+        302,
+        "https://faq.example.com",
+    );
+    // the default routes are replaced
+    assert_eq!(get(&env, canister_id, "/faq", 2)?.status_code, 404);
+
+    let index = get(&env, canister_id, "/", 2)?;
+    for certification_version in 1..=2 {
+        let about = get(&env, canister_id, "/about", certification_version)?;
+        assert_eq!(about.status_code, 200);
+        assert_eq!(about.body, index.body);
+    }
+
+    assert_eq!(api::http_routes(&env, canister_id)?, routes());
+    Ok(())
+}
+
+/// Verifies that the controllers can replace the routes at runtime and that the routes are kept
+/// across upgrades.
+#[test]
+fn should_update_routes() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    assert_redirect(
+        &get(&env, canister_id, "/faq", 2)?,
+        301,
+        "https://identitysupport.dfinity.org/hc/en-us",
+    );
+
+    api::http_routes_update(&env, canister_id, Principal::anonymous(), &routes())?;
+    assert_eq!(get(&env, canister_id, "/faq", 2)?.status_code, 404);
+    assert_eq!(get(&env, canister_id, "/about", 2)?.status_code, 200);
+    assert_redirect(
+        &get(&env, canister_id, "/docs/page", 2)?,
+        308,
+        "https://docs.example.com",
+    );
+
+    upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+    assert_eq!(api::http_routes(&env, canister_id)?, routes());
+    assert_eq!(get(&env, canister_id, "/about", 2)?.status_code, 200);
+
+    // removing the routes makes the paths fall back to the assets (if any)
+    api::http_routes_update(
+        &env,
+        canister_id,
+        Principal::anonymous(),
+        &HttpRoutes::default(),
+    )?;
+    assert_eq!(get(&env, canister_id, "/about", 2)?.status_code, 404);
+    assert_eq!(get(&env, canister_id, "/docs/page", 2)?.status_code, 404);
+    Ok(())
+}
+
+/// Verifies that aliases serve the current version of the aliased asset.
+#[test]
+fn should_serve_uploaded_asset_at_alias() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister_with_arg(
+        &env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            http_routes: Some(routes()),
+            ..Default::default()
+        }),
+    );
+    let content = b"<html><head></head><body>new</body></html>";
+    api::asset_upload(
+        &env,
+        canister_id,
+        Principal::anonymous(),
+        &AssetUploadChunk {
+            file_path: "/index.html".to_string(),
+            total_length: content.len() as u64,
+            offset: 0,
+            content: ByteBuf::from(content.as_slice()),
+        },
+    )?;
+
+    for certification_version in 1..=2 {
+        let about = get(&env, canister_id, "/about", certification_version)?;
+        assert_eq!(about.status_code, 200);
+        assert_eq!(about.body, ByteBuf::from(content.as_slice()));
+    }
+    Ok(())
+}
+
+/// Verifies that only the controllers can update the routes and that invalid routes are rejected.
+#[test]
+fn should_reject_invalid_route_updates() {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let result = api::http_routes_update(&env, canister_id, principal_1(), &routes());
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("is not a controller of this canister").unwrap(),
+    );
+
+    let invalid_routes = HttpRoutes {
+        redirects: vec![HttpRedirect {
+            path: "/faq".to_string(),
+            target: "https://faq.example.com".to_string(),
+            status_code: 307,
+        }],
+        aliases: vec![],
+    };
+    let result =
+        api::http_routes_update(&env, canister_id, Principal::anonymous(), &invalid_routes);
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("Unsupported status code 307").unwrap(),
+    );
+
+    let result = upgrade_ii_canister_with_arg(
+        &env,
+        canister_id,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            http_routes: Some(HttpRoutes {
+                redirects: vec![],
+                aliases: vec![HttpAlias {
+                    path: "/metrics".to_string(),
+                    asset_path: "/index.html".to_string(),
+                }],
+            }),
+            ..Default::default()
+        }),
+    );
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("reserved for the metrics").unwrap(),
+    );
+}
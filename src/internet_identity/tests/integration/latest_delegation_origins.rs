@@ -3,14 +3,17 @@
 use canister_tests::api::internet_identity as api;
 use canister_tests::flows;
 use canister_tests::framework::{
-    device_data_1, env, install_ii_canister, install_ii_canister_with_arg, principal_1,
-    upgrade_ii_canister, II_WASM,
+    device_data_1, env, expect_user_error_with_message, install_ii_canister,
+    install_ii_canister_with_arg, principal_1, upgrade_ii_canister, upgrade_ii_canister_with_arg,
+    II_WASM, II_WASM_PREVIOUS,
 };
 use ic_cdk::api::management_canister::main::CanisterId;
 use internet_identity_interface::internet_identity::types::{
-    AnchorNumber, DeviceData, InternetIdentityInit,
+    AnchorNumber, DevOriginFilter, DeviceData, InternetIdentityInit,
 };
+use pocket_ic::ErrorCode::CanisterCalledTrap;
 use pocket_ic::{CallError, PocketIc};
+use regex::Regex;
 use serde_bytes::ByteBuf;
 use std::time::Duration;
 
@@ -46,6 +49,126 @@ fn should_not_record_development_origins() -> Result<(), CallError> {
     Ok(())
 }
 
+/// Verifies that the dev origin filter can be configured.
+#[test]
+fn should_apply_configured_dev_origin_filter() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister_with_arg(
+        &env,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            dev_origin_filter: Some(DevOriginFilter {
+                hostname_suffixes: vec!["test.app".to_string()],
+                ip_ranges: vec!["100.64.0.0/10".to_string()],
+            }),
+            ..Default::default()
+        }),
+    );
+    let user_number = flows::register_anchor(&env, canister_id);
+
+    delegation_for_origin(&env, canister_id, user_number, "http://some-dapp.com")?;
+    delegation_for_origin(&env, canister_id, user_number, "https://dapp.test.app")?;
+    delegation_for_origin(&env, canister_id, user_number, "https://100.64.1.1:8443")?;
+    // not filtered with the configured rules
+    delegation_for_origin(&env, canister_id, user_number, "https://localhost:8443")?;
+
+    let latest_origins = api::stats(&env, canister_id)?.latest_delegation_origins;
+    assert_eq!(latest_origins, vec!["https://localhost:8443".to_string()]);
+    Ok(())
+}
+
+/// Verifies that invalid dev origin filters are rejected.
+#[test]
+fn should_reject_invalid_dev_origin_filter() {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let invalid_filters = [
+        DevOriginFilter {
+            hostname_suffixes: vec![".".to_string()],
+            ip_ranges: vec![],
+        },
+        DevOriginFilter {
+            hostname_suffixes: vec![],
+            ip_ranges: vec!["10.0.0.0/33".to_string()],
+        },
+        DevOriginFilter {
+            hostname_suffixes: vec![],
+            ip_ranges: vec!["10.0.0.0".to_string()],
+        },
+    ];
+    for filter in invalid_filters {
+        let result = upgrade_ii_canister_with_arg(
+            &env,
+            canister_id,
+            II_WASM.clone(),
+            Some(InternetIdentityInit {
+                dev_origin_filter: Some(filter),
+                ..Default::default()
+            }),
+        );
+        expect_user_error_with_message(
+            result,
+            CanisterCalledTrap,
+            Regex::new("invalid dev origin").unwrap(),
+        );
+    }
+}
+
+/// Verifies that the latest origins are ordered by the number of delegations.
+#[test]
+fn should_order_delegation_origins_by_usage() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let user_number = flows::register_anchor(&env, canister_id);
+
+    for (origin, count) in [
+        ("https://rarely-used.com", 1),
+        ("https://most-used.com", 3),
+        ("https://often-used.com", 2),
+    ] {
+        for _ in 0..count {
+            delegation_for_origin(&env, canister_id, user_number, origin)?;
+        }
+    }
+
+    let latest_origins = api::stats(&env, canister_id)?.latest_delegation_origins;
+    assert_eq!(
+        latest_origins,
+        vec![
+            "https://most-used.com".to_string(),
+            "https://often-used.com".to_string(),
+            "https://rarely-used.com".to_string(),
+        ]
+    );
+    Ok(())
+}
+
+/// Verifies that the usage of the latest origins decays over time.
+#[test]
+fn should_rank_recent_delegation_origins_higher() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let user_number = flows::register_anchor(&env, canister_id);
+
+    for _ in 0..3 {
+        delegation_for_origin(&env, canister_id, user_number, "https://formerly-used.com")?;
+    }
+    // usage halves every 7 days
+    env.advance_time(Duration::from_secs(14 * DAY_SECONDS));
+    delegation_for_origin(&env, canister_id, user_number, "https://recently-used.com")?;
+
+    let latest_origins = api::stats(&env, canister_id)?.latest_delegation_origins;
+    assert_eq!(
+        latest_origins,
+        vec![
+            "https://recently-used.com".to_string(),
+            "https://formerly-used.com".to_string(),
+        ]
+    );
+    Ok(())
+}
+
 /// Verifies that the configured limit for the number of latest used origins is respected.
 #[test]
 fn should_record_max_delegation_origins() -> Result<(), CallError> {
@@ -103,6 +226,28 @@ fn should_keep_latest_origins_across_upgrades() -> Result<(), CallError> {
     Ok(())
 }
 
+/// Verifies that the latest origins recorded by the previous release are kept on upgrade.
+#[test]
+fn should_keep_latest_origins_of_previous_release() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM_PREVIOUS.clone());
+    let user_number = flows::register_anchor(&env, canister_id);
+
+    delegation_for_origin(&env, canister_id, user_number, "https://some-dapp.com")?;
+    upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+    delegation_for_origin(&env, canister_id, user_number, "https://other-dapp.com")?;
+
+    let latest_origins = api::stats(&env, canister_id)?.latest_delegation_origins;
+    assert_eq!(
+        latest_origins,
+        vec![
+            "https://other-dapp.com".to_string(),
+            "https://some-dapp.com".to_string(),
+        ]
+    );
+    Ok(())
+}
+
 /// Verifies that origins are recorded only for devices on II domains.
 #[test]
 fn should_not_record_delegation_origin_for_devices_on_non_ii_domains() -> Result<(), CallError> {
@@ -0,0 +1,162 @@
+//! Tests for the localized HTML shells, negotiated using the `Accept-Language` header.
+
+use crate::http::verify_response_certification;
+use candid::Principal;
+use canister_tests::api::{http_request, internet_identity as api};
+use canister_tests::framework::{
+    env, expect_user_error_with_message, install_ii_canister, II_WASM,
+};
+use ic_cdk::api::management_canister::main::CanisterId;
+use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
+use internet_identity_interface::internet_identity::types::AssetUploadChunk;
+use pocket_ic::ErrorCode::CanisterCalledTrap;
+use pocket_ic::{CallError, PocketIc};
+use regex::Regex;
+use serde_bytes::ByteBuf;
+
+const GERMAN_NOSCRIPT: &str = "Internet Identity benötigt JavaScript.";
+
+fn get_shell(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    path: &str,
+    accept_language: Option<&str>,
+    certification_version: u16,
+) -> Result<HttpResponse, CallError> {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: path.to_string(),
+        headers: accept_language
+            .map(|value| ("Accept-Language".to_string(), value.to_string()))
+            .into_iter()
+            .collect(),
+        body: ByteBuf::new(),
+        certificate_version: Some(certification_version),
+    };
+    let http_response = http_request(env, canister_id, &request)?;
+    assert_eq!(http_response.status_code, 200);
+    verify_response_certification(
+        env,
+        canister_id,
+        request,
+        http_response.clone(),
+        certification_version,
+    );
+    Ok(http_response)
+}
+
+fn content_language(http_response: &HttpResponse) -> Option<&str> {
+    http_response
+        .headers
+        .iter()
+        .find(|(header, _)| header == "Content-Language")
+        .map(|(_, value)| value.as_str())
+}
+
+fn body(http_response: &HttpResponse) -> String {
+    String::from_utf8_lossy(&http_response.body).to_string()
+}
+
+/// Verifies that the shell at `/` is served in the language negotiated with certification v2 and
+/// in English otherwise.
+#[test]
+fn should_negotiate_shell_language() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let http_response = get_shell(&env, canister_id, "/", Some("de-CH, de;q=0.9, en;q=0.8"), 2)?;
+    assert_eq!(content_language(&http_response), Some("de"));
+    assert!(http_response.headers.contains(&(
+        "Vary".to_string(),
+        "Accept-Encoding, Accept-Language".to_string()
+    )));
+    assert!(body(&http_response).contains(r#"<html lang="de">"#));
+    assert!(body(&http_response).contains(GERMAN_NOSCRIPT));
+
+    for accept_language in [None, Some("ja"), Some("de;q=0, en")] {
+        let http_response = get_shell(&env, canister_id, "/", accept_language, 2)?;
+        assert_eq!(content_language(&http_response), Some("en"));
+        assert!(body(&http_response).contains(r#"<html lang="en">"#));
+    }
+
+    // certification v1 only covers a single response per path
+    let http_response = get_shell(&env, canister_id, "/", Some("de"), 1)?;
+    assert_eq!(content_language(&http_response), Some("en"));
+    Ok(())
+}
+
+/// Verifies that the localized shells are served (certified) at `/<lang>/` regardless of the
+/// `Accept-Language` header.
+#[test]
+fn should_serve_shells_at_language_paths() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    for certification_version in 1..=2 {
+        let http_response =
+            get_shell(&env, canister_id, "/fr/", Some("de"), certification_version)?;
+        assert_eq!(content_language(&http_response), Some("fr"));
+        assert!(body(&http_response).contains(r#"<html lang="fr">"#));
+        assert!(body(&http_response).contains("Internet Identity nécessite JavaScript."));
+    }
+    Ok(())
+}
+
+/// Verifies that the localized shells are served (certified with a wildcard expression path) at all
+/// paths with a `/<lang>/` prefix that have no more specific response.
+#[test]
+fn should_serve_shells_at_language_path_prefixes() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    for path in ["/de/manage", "/de/some/page?query=1"] {
+        let http_response = get_shell(&env, canister_id, path, Some("fr"), 2)?;
+        assert_eq!(content_language(&http_response), Some("de"));
+        assert!(body(&http_response).contains(GERMAN_NOSCRIPT));
+    }
+    Ok(())
+}
+
+/// Verifies that the localized shells are derived from an uploaded shell and cannot be uploaded
+/// themselves.
+#[test]
+fn should_localize_uploaded_shell() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let upload = |file_path: &str, content: &[u8]| {
+        api::asset_upload(
+            &env,
+            canister_id,
+            Principal::anonymous(),
+            &AssetUploadChunk {
+                file_path: file_path.to_string(),
+                total_length: content.len() as u64,
+                offset: 0,
+                content: ByteBuf::from(content),
+            },
+        )
+    };
+
+    upload(
+        "/index.html",
+        b"<html lang=\"en\"><head><meta replaceme-with-csp/></head><body>new</body></html>",
+    )?;
+    for (path, accept_language) in [("/", Some("de")), ("/de/", None)] {
+        let http_response = get_shell(&env, canister_id, path, accept_language, 2)?;
+        assert!(body(&http_response).contains(r#"<html lang="de">"#));
+        assert!(body(&http_response).contains("<body>new</body>"));
+    }
+
+    api::asset_delete(&env, canister_id, Principal::anonymous(), "/")?;
+    let http_response = get_shell(&env, canister_id, "/de/", None, 2)?;
+    assert!(!body(&http_response).contains("<body>new</body>"));
+    assert!(body(&http_response).contains(GERMAN_NOSCRIPT));
+
+    let result = upload("/de/index.html", b"<html></html>");
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("Asset path '/de/' is reserved for the localized HTML shell.").unwrap(),
+    );
+    Ok(())
+}
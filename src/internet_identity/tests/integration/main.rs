@@ -5,12 +5,18 @@
 //! See https://matklad.github.io/2021/02/27/delete-cargo-integration-tests.html#Implications for more details.
 
 mod activity_stats;
+mod anchor_population_stats;
 mod anchor_management;
 mod archive_integration;
+mod csp;
 mod delegation;
+mod health;
 mod http;
+mod http_routes;
 mod latest_delegation_origins;
+mod localization;
 mod rollback;
 mod stable_memory;
 mod upgrade;
+mod uploaded_assets;
 mod v2_api;
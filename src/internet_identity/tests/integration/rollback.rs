@@ -5,8 +5,12 @@ use candid::Principal;
 use canister_tests::api::internet_identity as api;
 use canister_tests::flows;
 use canister_tests::framework::*;
+use internet_identity_interface::internet_identity::types::{
+    ActivityWindowConfig, InternetIdentityInit,
+};
 use pocket_ic::CallError;
 use serde_bytes::ByteBuf;
+use std::time::Duration;
 
 /// Tests simple upgrade and downgrade.
 #[test]
@@ -14,9 +18,9 @@ fn ii_canister_can_be_upgraded_and_rolled_back() {
     let env = env();
     let canister_id = install_ii_canister(&env, II_WASM_PREVIOUS.clone());
     upgrade_ii_canister(&env, canister_id, II_WASM.clone());
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
     upgrade_ii_canister(&env, canister_id, II_WASM_PREVIOUS.clone());
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
 }
 
 /// Tests that the devices can still be read after upgrade and rollback.
@@ -29,9 +33,9 @@ fn upgrade_and_rollback_keeps_anchor_intact() {
         .unwrap()
         .devices;
     upgrade_ii_canister(&env, canister_id, II_WASM.clone());
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
     upgrade_ii_canister(&env, canister_id, II_WASM_PREVIOUS.clone());
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
     let mut devices_after = api::get_anchor_info(&env, canister_id, principal_1(), user_number)
         .unwrap()
         .devices;
@@ -93,3 +97,43 @@ fn should_keep_new_anchor_across_rollback() -> Result<(), CallError> {
     )?;
     Ok(())
 }
+
+/// Verifies that the activity stats collected by the new version (including additionally
+/// configured collection windows) are still collected after rolling back to the previous version.
+#[test]
+fn should_keep_activity_stats_across_rollback() -> Result<(), CallError> {
+    const DAY_SECONDS: u64 = 24 * 60 * 60;
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM_PREVIOUS.clone());
+
+    upgrade_ii_canister_with_arg(
+        &env,
+        canister_id,
+        II_WASM.clone(),
+        Some(InternetIdentityInit {
+            activity_windows: Some(vec![ActivityWindowConfig {
+                name: "weekly".to_string(),
+                length_ns: 7 * DAY_SECONDS * 1_000_000_000,
+                step_ns: DAY_SECONDS * 1_000_000_000,
+            }]),
+            ..InternetIdentityInit::default()
+        }),
+    )?;
+    let user_number = flows::register_anchor(&env, canister_id);
+    flows::register_anchor(&env, canister_id);
+
+    // roll back
+    upgrade_ii_canister(&env, canister_id, II_WASM_PREVIOUS.clone());
+    api::assert_alive(&env, canister_id);
+
+    env.advance_time(Duration::from_secs(DAY_SECONDS));
+    // some activity is required to update the stats
+    api::get_anchor_info(&env, canister_id, principal_1(), user_number)?;
+
+    assert_metric(
+        &get_metrics(&env, canister_id),
+        "internet_identity_daily_active_anchors",
+        2f64,
+    );
+    Ok(())
+}
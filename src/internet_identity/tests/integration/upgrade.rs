@@ -22,7 +22,7 @@ fn ii_upgrade_works() -> Result<(), CallError> {
         candid::encode_one(None::<InternetIdentityInit>).unwrap(),
         None,
     )?;
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
     Ok(())
 }
 
@@ -152,9 +152,9 @@ fn ii_canister_can_be_upgraded_and_rolled_back() {
     let env = env();
     let canister_id = install_ii_canister(&env, II_WASM_PREVIOUS.clone());
     upgrade_ii_canister(&env, canister_id, II_WASM.clone());
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
     upgrade_ii_canister(&env, canister_id, II_WASM_PREVIOUS.clone());
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
 }
 
 /// Tests that the devices can still be read after upgrade and rollback.
@@ -167,9 +167,9 @@ fn upgrade_and_rollback_keeps_anchor_intact() {
         .unwrap()
         .devices;
     upgrade_ii_canister(&env, canister_id, II_WASM.clone());
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
     upgrade_ii_canister(&env, canister_id, II_WASM_PREVIOUS.clone());
-    api::health_check(&env, canister_id);
+    api::assert_alive(&env, canister_id);
     let mut devices_after = api::get_anchor_info(&env, canister_id, principal_1(), user_number)
         .unwrap()
         .devices;
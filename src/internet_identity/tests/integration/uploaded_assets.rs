@@ -0,0 +1,439 @@
+//! Tests for assets uploaded by the controllers at runtime.
+
+use crate::http::verify_response_certification;
+use candid::Principal;
+use canister_tests::api::{http_request, internet_identity as api};
+use canister_tests::framework::{
+    env, expect_user_error_with_message, install_ii_canister, principal_1, upgrade_ii_canister,
+    II_WASM,
+};
+use ic_cdk::api::management_canister::main::CanisterId;
+use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse, StreamingStrategy};
+use internet_identity_interface::internet_identity::types::AssetUploadChunk;
+use pocket_ic::ErrorCode::CanisterCalledTrap;
+use pocket_ic::{CallError, PocketIc};
+use regex::Regex;
+use serde_bytes::ByteBuf;
+
+/// Size of the chunks large assets are split into by the canister.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The canisters installed by the test framework are controlled by the anonymous principal.
+fn controller() -> Principal {
+    Principal::anonymous()
+}
+
+fn upload(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    file_path: &str,
+    content: &[u8],
+    chunk_size: usize,
+) -> Result<(), CallError> {
+    let mut offset = 0;
+    for chunk in content.chunks(chunk_size) {
+        api::asset_upload(
+            env,
+            canister_id,
+            controller(),
+            &AssetUploadChunk {
+                file_path: file_path.to_string(),
+                total_length: content.len() as u64,
+                offset: offset as u64,
+                content: ByteBuf::from(chunk),
+            },
+        )?;
+        offset += chunk.len();
+    }
+    Ok(())
+}
+
+fn get_asset(
+    env: &PocketIc,
+    canister_id: CanisterId,
+    path: &str,
+    certification_version: u16,
+) -> Result<HttpResponse, CallError> {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: path.to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(certification_version),
+    };
+    let http_response = http_request(env, canister_id, &request)?;
+    verify_response_certification(
+        env,
+        canister_id,
+        request,
+        http_response.clone(),
+        certification_version,
+    );
+    Ok(http_response)
+}
+
+/// Verifies that an asset uploaded in chunks is served (and certified) once all chunks are uploaded.
+#[test]
+fn should_serve_uploaded_asset() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let content = br#"{"greeting": "Bonjour"}"#;
+
+    api::asset_upload(
+        &env,
+        canister_id,
+        controller(),
+        &AssetUploadChunk {
+            file_path: "/fr/strings.json".to_string(),
+            total_length: content.len() as u64,
+            offset: 0,
+            content: ByteBuf::from(&content[..10]),
+        },
+    )?;
+    let http_response = get_asset(&env, canister_id, "/fr/strings.json", 2)?;
+    assert_eq!(http_response.status_code, 404);
+
+    api::asset_upload(
+        &env,
+        canister_id,
+        controller(),
+        &AssetUploadChunk {
+            file_path: "/fr/strings.json".to_string(),
+            total_length: content.len() as u64,
+            offset: 10,
+            content: ByteBuf::from(&content[10..]),
+        },
+    )?;
+    for certification_version in 1..=2 {
+        let http_response =
+            get_asset(&env, canister_id, "/fr/strings.json", certification_version)?;
+        assert_eq!(http_response.status_code, 200);
+        assert_eq!(http_response.body, ByteBuf::from(content.as_slice()));
+        assert!(http_response
+            .headers
+            .contains(&("Content-Type".to_string(), "application/json".to_string())));
+    }
+    Ok(())
+}
+
+/// Verifies that uploaded assets replace the built-in assets and that the built-in assets are
+/// served again once the uploaded assets are deleted.
+#[test]
+fn should_override_built_in_asset_until_deleted() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let built_in_index = get_asset(&env, canister_id, "/", 2)?;
+
+    upload(
+        &env,
+        canister_id,
+        "/index.html",
+        b"<html><head><meta replaceme-with-csp/></head><body>new</body></html>",
+        16,
+    )?;
+    let http_response = get_asset(&env, canister_id, "/", 2)?;
+    assert_eq!(http_response.status_code, 200);
+    let body = String::from_utf8_lossy(&http_response.body);
+    assert!(body.contains("<body>new</body>"));
+    // uploaded HTML is fixed up like the built-in HTML
+    assert!(body.contains(r#"<meta http-equiv="Content-Security-Policy""#));
+
+    api::asset_delete(&env, canister_id, controller(), "/")?;
+    let http_response = get_asset(&env, canister_id, "/", 2)?;
+    assert_eq!(http_response.status_code, 200);
+    assert_eq!(http_response.body, built_in_index.body);
+    Ok(())
+}
+
+/// Verifies that the built-in variants of an asset in other encodings are no longer served once a
+/// variant of the asset has been uploaded, also after an upgrade.
+#[test]
+fn should_not_serve_built_in_variants_alongside_uploaded_asset() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let content = b"console.log('uploaded');";
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/index.js".to_string(),
+        headers: vec![("Accept-Encoding".to_string(), "gzip, br".to_string())],
+        body: ByteBuf::new(),
+        certificate_version: Some(2),
+    };
+
+    upload(&env, canister_id, "/index.js", content, 1024)?;
+    for upgrade in [false, true] {
+        if upgrade {
+            upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+        }
+        let http_response = http_request(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 200);
+        assert_eq!(http_response.body, ByteBuf::from(content.as_slice()));
+        assert!(!http_response
+            .headers
+            .iter()
+            .any(|(name, _)| name == "Content-Encoding"));
+        verify_response_certification(&env, canister_id, request.clone(), http_response, 2);
+    }
+    Ok(())
+}
+
+/// Verifies that deleting an asset that does not override a built-in asset removes it.
+#[test]
+fn should_delete_uploaded_asset() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    upload(&env, canister_id, "/logo.svg", b"<svg></svg>", 1024)?;
+    assert_eq!(
+        get_asset(&env, canister_id, "/logo.svg", 2)?.status_code,
+        200
+    );
+
+    api::asset_delete(&env, canister_id, controller(), "/logo.svg")?;
+    assert_eq!(
+        get_asset(&env, canister_id, "/logo.svg", 2)?.status_code,
+        404
+    );
+
+    let result = api::asset_delete(&env, canister_id, controller(), "/logo.svg");
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("No uploaded asset is served at '/logo.svg'").unwrap(),
+    );
+    Ok(())
+}
+
+/// Verifies that uploaded assets are kept across upgrades.
+#[test]
+fn should_keep_uploaded_assets_across_upgrades() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    upload(&env, canister_id, "/logo.svg", b"<svg></svg>", 4)?;
+
+    upgrade_ii_canister(&env, canister_id, II_WASM.clone());
+
+    for certification_version in 1..=2 {
+        let http_response = get_asset(&env, canister_id, "/logo.svg", certification_version)?;
+        assert_eq!(http_response.status_code, 200);
+        assert_eq!(http_response.body, ByteBuf::from("<svg></svg>"));
+    }
+    Ok(())
+}
+
+/// Verifies that only the controllers can upload and delete assets.
+#[test]
+fn should_only_allow_controllers_to_manage_assets() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+
+    let result = api::asset_upload(
+        &env,
+        canister_id,
+        principal_1(),
+        &AssetUploadChunk {
+            file_path: "/logo.svg".to_string(),
+            total_length: 11,
+            offset: 0,
+            content: ByteBuf::from("<svg></svg>"),
+        },
+    );
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("is not a controller of this canister").unwrap(),
+    );
+
+    let result = api::asset_delete(&env, canister_id, principal_1(), "/");
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("is not a controller of this canister").unwrap(),
+    );
+    Ok(())
+}
+
+/// Verifies that chunks are rejected if they do not continue the upload in progress or if the
+/// file type is not supported.
+#[test]
+fn should_reject_invalid_chunks() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let chunk = AssetUploadChunk {
+        file_path: "/logo.svg".to_string(),
+        total_length: 11,
+        offset: 0,
+        content: ByteBuf::from("<svg>"),
+    };
+    api::asset_upload(&env, canister_id, controller(), &chunk)?;
+
+    let result = api::asset_upload(
+        &env,
+        canister_id,
+        controller(),
+        &AssetUploadChunk {
+            offset: 6,
+            ..chunk.clone()
+        },
+    );
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("expected offset 5 of 11 bytes").unwrap(),
+    );
+
+    let result = api::asset_upload(
+        &env,
+        canister_id,
+        controller(),
+        &AssetUploadChunk {
+            file_path: "/script.exe".to_string(),
+            ..chunk
+        },
+    );
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("Unsupported asset file path '/script.exe'").unwrap(),
+    );
+    Ok(())
+}
+
+fn large_content() -> Vec<u8> {
+    (0..(2 * CHUNK_SIZE + 1000))
+        .map(|i| (i % 251) as u8)
+        .collect()
+}
+
+fn request_with_headers(
+    path: &str,
+    headers: Vec<(&str, &str)>,
+    certification_version: u16,
+) -> HttpRequest {
+    HttpRequest {
+        method: "GET".to_string(),
+        url: path.to_string(),
+        headers: headers
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        body: ByteBuf::new(),
+        certificate_version: Some(certification_version),
+    }
+}
+
+/// Verifies that large assets are streamed in chunks and that the complete (streamed) body is
+/// certified.
+#[test]
+fn should_stream_large_assets() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let content = large_content();
+    upload(&env, canister_id, "/large.json", &content, CHUNK_SIZE)?;
+
+    for certification_version in 1..=2 {
+        let request = request_with_headers("/large.json", vec![], certification_version);
+        let mut http_response = http_request(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 200);
+        assert_eq!(http_response.body.len(), CHUNK_SIZE);
+
+        let mut body = http_response.body.to_vec();
+        let mut num_chunks = 1;
+        let mut token = http_response
+            .streaming_strategy
+            .take()
+            .map(|StreamingStrategy::Callback { token, .. }| token);
+        while let Some(current_token) = token {
+            let chunk = api::http_request_streaming_callback(&env, canister_id, &current_token)?;
+            body.extend_from_slice(&chunk.body);
+            num_chunks += 1;
+            token = chunk.token;
+        }
+        assert_eq!(num_chunks, 3);
+        assert_eq!(body, content);
+
+        http_response.body = ByteBuf::from(body);
+        verify_response_certification(
+            &env,
+            canister_id,
+            request,
+            http_response,
+            certification_version,
+        );
+    }
+    Ok(())
+}
+
+/// Verifies that range requests for chunks of large assets are served as certified partial
+/// responses, and that other ranges are ignored.
+#[test]
+fn should_serve_certified_chunks_for_range_requests() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let content = large_content();
+    upload(&env, canister_id, "/large.json", &content, CHUNK_SIZE)?;
+
+    for (range, chunk_range) in [
+        ("bytes=0-".to_string(), 0..CHUNK_SIZE),
+        (
+            format!("bytes={CHUNK_SIZE}-{}", 2 * CHUNK_SIZE - 1),
+            CHUNK_SIZE..2 * CHUNK_SIZE,
+        ),
+        (
+            format!("bytes={}-", 2 * CHUNK_SIZE),
+            2 * CHUNK_SIZE..content.len(),
+        ),
+    ] {
+        let request = request_with_headers("/large.json", vec![("Range", &range)], 2);
+        let http_response = http_request(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 206);
+        assert!(http_response.headers.contains(&(
+            "Content-Range".to_string(),
+            format!(
+                "bytes {}-{}/{}",
+                chunk_range.start,
+                chunk_range.end - 1,
+                content.len()
+            )
+        )));
+        assert_eq!(http_response.body.as_slice(), &content[chunk_range]);
+        assert!(http_response.streaming_strategy.is_none());
+        verify_response_certification(&env, canister_id, request, http_response, 2);
+    }
+
+    // ranges not matching a chunk and range requests with certification v1 are served in full
+    for (range, certification_version) in [("bytes=10-20", 2), ("bytes=-100", 2), ("bytes=0-", 1)] {
+        let request =
+            request_with_headers("/large.json", vec![("Range", range)], certification_version);
+        let http_response = http_request(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 200);
+        assert!(http_response.streaming_strategy.is_some());
+    }
+    Ok(())
+}
+
+/// Verifies that streaming fails if the asset changes in between chunks, rather than mixing the
+/// chunks of different versions.
+#[test]
+fn should_not_stream_chunks_of_changed_asset() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_ii_canister(&env, II_WASM.clone());
+    let mut content = large_content();
+    upload(&env, canister_id, "/large.json", &content, CHUNK_SIZE)?;
+
+    let request = request_with_headers("/large.json", vec![], 2);
+    let http_response = http_request(&env, canister_id, &request)?;
+    let Some(StreamingStrategy::Callback { token, .. }) = http_response.streaming_strategy else {
+        panic!("expected the asset to be streamed");
+    };
+
+    content[0] = content[0].wrapping_add(1);
+    upload(&env, canister_id, "/large.json", &content, CHUNK_SIZE)?;
+
+    let result = api::http_request_streaming_callback(&env, canister_id, &token);
+    expect_user_error_with_message(
+        result,
+        CanisterCalledTrap,
+        Regex::new("Asset '/large.json' has changed or has been removed while streaming").unwrap(),
+    );
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use crate::internet_identity::types::{
-    AnchorNumber, CredentialId, DeviceKey, DeviceProtection, KeyType, PublicKey, Purpose, Timestamp,
+    AnchorNumber, ArchiveIntegration, CredentialId, DeviceKey, DeviceProtection, FrontendHostname,
+    KeyType, PublicKey, Purpose, Timestamp,
 };
 use candid::{CandidType, Deserialize, Principal};
 use ic_cdk::api::management_canister::main::CanisterStatusResponse;
@@ -29,6 +30,60 @@ pub enum Operation {
     // See the II candid interface for more details.
     #[serde(rename = "identity_metadata_replace")]
     IdentityMetadataReplace { metadata_keys: Vec<String> },
+
+    // Authentication events
+    // Only archived if the anchor opted in, see the II candid interface for more details.
+    #[serde(rename = "authenticate")]
+    Authenticate { device: PublicKey },
+    #[serde(rename = "prepare_delegation")]
+    PrepareDelegation {
+        device: PublicKey,
+        frontend: FrontendHostname,
+    },
+}
+
+/// Kind of an [Operation] (i.e. the variant without any of the associated data).
+/// Adding or replacing a recovery phrase is a kind of its own, so that it can be told apart from
+/// other device changes.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug, CandidType, Deserialize)]
+pub enum OperationType {
+    #[serde(rename = "register_anchor")]
+    RegisterAnchor,
+    #[serde(rename = "add_device")]
+    AddDevice,
+    #[serde(rename = "update_device")]
+    UpdateDevice,
+    #[serde(rename = "replace_device")]
+    ReplaceDevice,
+    #[serde(rename = "remove_device")]
+    RemoveDevice,
+    #[serde(rename = "identity_metadata_replace")]
+    IdentityMetadataReplace,
+    #[serde(rename = "authenticate")]
+    Authenticate,
+    #[serde(rename = "prepare_delegation")]
+    PrepareDelegation,
+    #[serde(rename = "recovery_phrase_change")]
+    RecoveryPhraseChange,
+}
+
+impl From<&Operation> for OperationType {
+    fn from(operation: &Operation) -> Self {
+        match operation {
+            Operation::RegisterAnchor { .. } => OperationType::RegisterAnchor,
+            Operation::AddDevice { device }
+            | Operation::ReplaceDevice {
+                new_device: device, ..
+            } if device.key_type == KeyType::SeedPhrase => OperationType::RecoveryPhraseChange,
+            Operation::AddDevice { .. } => OperationType::AddDevice,
+            Operation::UpdateDevice { .. } => OperationType::UpdateDevice,
+            Operation::ReplaceDevice { .. } => OperationType::ReplaceDevice,
+            Operation::RemoveDevice { .. } => OperationType::RemoveDevice,
+            Operation::IdentityMetadataReplace { .. } => OperationType::IdentityMetadataReplace,
+            Operation::Authenticate { .. } => OperationType::Authenticate,
+            Operation::PrepareDelegation { .. } => OperationType::PrepareDelegation,
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Clone, Debug, CandidType, Deserialize)]
@@ -88,6 +143,53 @@ pub struct AnchorEntries {
     pub entries: Vec<Option<Entry>>,
     // cursor pointing to the next entry not included in this response, if any
     pub cursor: Option<Cursor>,
+    // entries older than this watermark have been pruned due to the retention policy, if any
+    pub pruning_watermark: Option<PruningWatermark>,
+}
+
+/// Query for the entries of a single anchor, filtered by operation type and timestamp range.
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct AnchorEntriesQuery {
+    pub anchor: AnchorNumber,
+    // only return entries with one of the given operation types (all types if not set)
+    pub operation_types: Option<Vec<OperationType>>,
+    // only return entries with a timestamp >= from_timestamp (inclusive)
+    pub from_timestamp: Option<Timestamp>,
+    // only return entries with a timestamp < to_timestamp (exclusive)
+    pub to_timestamp: Option<Timestamp>,
+    // cursor returned by a previous query with the same parameters
+    pub cursor: Option<Cursor>,
+    pub limit: Option<u16>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct FilteredAnchorEntries {
+    // make this a vec of options to keep Entry extensible
+    pub entries: Vec<Option<Entry>>,
+    // cursor pointing to the next entry not scanned by this query, if any
+    pub cursor: Option<Cursor>,
+    // number of entries per operation type among the entries scanned by this query (regardless of
+    // the operation_types filter), sorted by operation type
+    pub operation_counts: Vec<OperationCount>,
+    // entries older than this watermark have been pruned due to the retention policy, if any
+    pub pruning_watermark: Option<PruningWatermark>,
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, CandidType, Deserialize)]
+pub struct OperationCount {
+    pub operation_type: OperationType,
+    pub count: u64,
+    // timestamp of the most recent entry of this operation type among the entries scanned
+    pub last_timestamp: Timestamp,
+}
+
+/// Boundary up to which archive entries have been pruned due to the retention policy.
+#[derive(Eq, PartialEq, Clone, Debug, CandidType, Deserialize)]
+pub struct PruningWatermark {
+    // all entries with a lower timestamp have been pruned
+    pub timestamp: Timestamp,
+    // log index of the oldest entry not pruned
+    pub log_index: u64,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -107,6 +209,8 @@ pub struct ArchiveInit {
     pub max_entries_per_call: u16,
     pub polling_interval_ns: u64,
     pub error_buffer_limit: u16,
+    pub archive_integration: Option<ArchiveIntegration>,
+    pub retention_period_ns: Option<u64>,
 }
 
 /// Encoded entry as buffered on the II side (until acknowledged by the archive).
@@ -124,6 +228,23 @@ pub struct ArchiveStatus {
     pub call_info: CallInfo,
     pub init: ArchiveInit,
     pub canister_status: CanisterStatusResponse,
+    pub pruning_watermark: Option<PruningWatermark>,
+    pub sequence_gaps: Vec<SequenceGap>,
+}
+
+/// Range of sequence numbers that were never archived.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct SequenceGap {
+    /// First missing sequence number.
+    pub first_sequence_number: u64,
+    /// Last missing sequence number (inclusive).
+    pub last_sequence_number: u64,
+    /// Timestamp when the archive detected the gap.
+    pub detected_at: Timestamp,
+    /// II timestamp of the last entry archived before the gap, if any.
+    pub timestamp_before: Option<Timestamp>,
+    /// II timestamp of the first entry archived after the gap.
+    pub timestamp_after: Timestamp,
 }
 
 /// Information about the calls the archive is making to II.
@@ -6,8 +6,16 @@ use serde_bytes::ByteBuf;
 
 pub type HeaderField = (String, String);
 
-#[derive(Clone, Debug, CandidType, Deserialize)]
-pub struct Token {}
+/// Identifies the next chunk of an asset to be streamed.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct Token {
+    pub path: String,
+    pub content_encoding: String,
+    pub index: u64,
+    /// Hash of the complete asset, so that streaming fails rather than mixing chunks of different
+    /// versions of the asset.
+    pub sha256: ByteBuf,
+}
 
 define_function!(pub StreamingCallbackFunction : (Token) -> (StreamingCallbackHttpResponse) query);
 
@@ -42,3 +50,50 @@ pub struct HttpResponse {
     pub upgrade: Option<bool>,
     pub streaming_strategy: Option<StreamingStrategy>,
 }
+
+/// Parses the value of an `Accept`, `Accept-Encoding` or `Accept-Language` header into its elements
+/// (i.e. media ranges, content codings or language ranges, without parameters) and their quality
+/// values, in the order they are listed. The elements are lowercased and have the quality value 1
+/// if none is given. Empty elements are skipped.
+pub fn parse_quality_values(header_value: &str) -> Vec<(String, f32)> {
+    header_value
+        .split(',')
+        .filter_map(|element| {
+            let mut params = element.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default().to_ascii_lowercase();
+            if name.is_empty() {
+                return None;
+            }
+            let quality = params
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|quality| quality.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((name, quality))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_quality_values() {
+        assert!(parse_quality_values("").is_empty());
+        assert_eq!(
+            parse_quality_values("gzip, BR;q=0.5 , ,identity;q=0"),
+            vec![
+                ("gzip".to_string(), 1.0),
+                ("br".to_string(), 0.5),
+                ("identity".to_string(), 0.0)
+            ]
+        );
+        assert_eq!(
+            parse_quality_values("application/openmetrics-text; version=1.0.0; q=0.8, */*;q=x"),
+            vec![
+                ("application/openmetrics-text".to_string(), 0.8),
+                ("*/*".to_string(), 1.0)
+            ]
+        );
+    }
+}
@@ -195,6 +195,116 @@ pub struct InternetIdentityInit {
     pub register_rate_limit: Option<RateLimitConfig>,
     pub max_num_latest_delegation_origins: Option<u64>,
     pub max_inflight_captchas: Option<u64>,
+    pub ii_origins: Option<Vec<FrontendHostname>>,
+    pub activity_windows: Option<Vec<ActivityWindowConfig>>,
+    pub dev_origin_filter: Option<DevOriginFilter>,
+    pub http_routes: Option<HttpRoutes>,
+    pub content_security_policy: Option<ContentSecurityPolicyConfig>,
+}
+
+/// Activity stats collection window in addition to the built-in `daily` and `monthly` windows.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ActivityWindowConfig {
+    /// Name of the window, used as part of the metric names (e.g. `weekly` results in
+    /// `internet_identity_weekly_active_anchors`).
+    pub name: String,
+    /// Length of a collection window.
+    pub length_ns: u64,
+    /// A new (overlapping) collection window is started every `step_ns`.
+    pub step_ns: u64,
+}
+
+/// Rules to filter out delegation origins that most likely point to development setups. Such
+/// origins are neither listed in the latest delegation origins nor counted in the per-dapp
+/// activity stats. Insecure (`http://`) origins are always filtered out.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DevOriginFilter {
+    /// Hostname suffixes, e.g. `localhost` matches both `localhost` and `app.localhost`.
+    pub hostname_suffixes: Vec<String>,
+    /// IP address ranges in CIDR notation, e.g. `10.0.0.0/8`.
+    pub ip_ranges: Vec<String>,
+}
+
+/// Configuration of the Content-Security-Policy of the responses served by the HTTP interface.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ContentSecurityPolicyConfig {
+    /// Sources allowed in the `connect-src` directive in addition to `'self'` and the II origins on
+    /// custom domains.
+    pub connect_sources: Option<Vec<String>>,
+    /// Whether to include the `upgrade-insecure-requests` directive.
+    pub upgrade_insecure_requests: Option<bool>,
+    /// Whether browsers should report CSP violations to the canister.
+    pub report_violations: Option<bool>,
+}
+
+/// A CSP violation reported by browsers, deduplicated by everything but the count and timestamps.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct CspViolationReport {
+    pub document_uri: String,
+    pub effective_directive: String,
+    pub blocked_uri: String,
+    pub source_file: Option<String>,
+    pub line_number: Option<u64>,
+    /// `enforce` or `report`.
+    pub disposition: String,
+    /// Number of times the violation has been reported.
+    pub count: u64,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+}
+
+/// Outcome of a health check, ordered by severity.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
+pub enum HealthStatus {
+    #[serde(rename = "pass")]
+    Pass,
+    /// The condition requires attention but does not (yet) affect users.
+    #[serde(rename = "warn")]
+    Warn,
+    #[serde(rename = "fail")]
+    Fail,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct HealthCheck {
+    pub name: String,
+    pub status: HealthStatus,
+    /// Human readable description of the checked condition.
+    pub message: String,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct HealthReport {
+    /// The most severe status of all checks.
+    pub status: HealthStatus,
+    pub checks: Vec<HealthCheck>,
+    /// Time the checks were evaluated at.
+    pub timestamp: Timestamp,
+}
+
+/// Routes served by the HTTP interface in addition to the assets.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Eq, PartialEq)]
+pub struct HttpRoutes {
+    pub redirects: Vec<HttpRedirect>,
+    pub aliases: Vec<HttpAlias>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct HttpRedirect {
+    /// Either an exact path (e.g. `/faq`) or a prefix pattern (e.g. `/docs/*`), which matches the
+    /// prefix itself and all paths below it.
+    pub path: String,
+    /// Value of the `Location` header, the same for all paths matching the pattern.
+    pub target: String,
+    /// One of 301, 302 and 308.
+    pub status_code: u16,
+}
+
+/// Serves the asset at `asset_path` also at `path`.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct HttpAlias {
+    pub path: String,
+    pub asset_path: String,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
@@ -208,6 +318,190 @@ pub struct InternetIdentityStats {
     pub latest_delegation_origins: Vec<FrontendHostname>,
 }
 
+/// Aggregate statistics over all anchors.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Eq, PartialEq)]
+pub struct AnchorPopulationStats {
+    /// Number of anchors (in order of registration) included in the statistics. After an upgrade
+    /// from a release without these statistics this is less than the number of registered anchors
+    /// until all anchors have been scanned.
+    pub counted_anchors: u64,
+    /// The element at index `i` is the number of anchors with `i` devices.
+    pub anchors_by_device_count: Vec<u64>,
+    pub anchors_with_recovery_phrase: u64,
+    /// Number of anchors with a recovery device that is not a recovery phrase.
+    pub anchors_with_recovery_device: u64,
+    pub devices_by_key_type: DevicesByKeyType,
+    pub protected_devices: u64,
+    pub unprotected_devices: u64,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DevicesByKeyType {
+    pub unknown: u64,
+    pub platform: u64,
+    pub cross_platform: u64,
+    pub seed_phrase: u64,
+    pub browser_storage_key: u64,
+}
+
+/// Retention statistics of the anchors registered in the same week.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct RegistrationCohortStats {
+    /// Cohorts of the most recent registration weeks, oldest first.
+    pub cohorts: Vec<RegistrationCohort>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct RegistrationCohort {
+    /// Start of the registration week.
+    pub start_timestamp: Timestamp,
+    pub registrations: u64,
+    /// The element at index `i` is the number of anchors of the cohort that were active in week
+    /// `i + 1` after the registration week.
+    pub active_anchors_by_week: Vec<u64>,
+}
+
+/// Activity statistics of the II canister. Each statistic is absent until its first event has
+/// been counted.
+///
+/// The collection windows are only rotated when an event is counted. Hence, the completed windows
+/// are not necessarily the most recent ones and the ongoing windows might already be expired.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct InternetIdentityActivityStats {
+    pub active_anchors: Option<ActiveAnchorStats>,
+    // II domains only
+    pub domain_active_anchors: Option<DomainActiveAnchorStats>,
+    // II domains only
+    pub active_authn_methods: Option<ActiveAuthnMethodStats>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ActiveAnchorStats {
+    pub completed_daily: Option<ActiveAnchorWindow>,
+    pub completed_monthly: Option<ActiveAnchorWindow>,
+    pub ongoing_daily: ActiveAnchorWindow,
+    // sliding 30-day windows, sorted by start timestamp
+    pub ongoing_monthly: Vec<ActiveAnchorWindow>,
+    // additional collection windows configured using the init argument, sorted by name
+    pub additional_windows: Option<Vec<ActiveAnchorWindowStats>>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ActiveAnchorWindowStats {
+    pub name: String,
+    pub length_ns: u64,
+    pub step_ns: u64,
+    pub completed: Option<ActiveAnchorWindow>,
+    // overlapping windows, each offset by step_ns, sorted by start timestamp
+    pub ongoing: Vec<ActiveAnchorWindow>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ActiveAnchorWindow {
+    pub start_timestamp: Timestamp,
+    pub active_anchors: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DomainActiveAnchorStats {
+    pub completed_daily: Option<DomainActiveAnchorWindow>,
+    pub completed_monthly: Option<DomainActiveAnchorWindow>,
+    pub ongoing_daily: DomainActiveAnchorWindow,
+    // sliding 30-day windows, sorted by start timestamp
+    pub ongoing_monthly: Vec<DomainActiveAnchorWindow>,
+    // additional collection windows configured using the init argument, sorted by name
+    pub additional_windows: Option<Vec<DomainActiveAnchorWindowStats>>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DomainActiveAnchorWindowStats {
+    pub name: String,
+    pub length_ns: u64,
+    pub step_ns: u64,
+    pub completed: Option<DomainActiveAnchorWindow>,
+    // overlapping windows, each offset by step_ns, sorted by start timestamp
+    pub ongoing: Vec<DomainActiveAnchorWindow>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DomainActiveAnchorWindow {
+    pub start_timestamp: Timestamp,
+    pub domains: Vec<DomainActiveAnchors>,
+}
+
+/// Number of unique anchors that were active on exactly the given (sorted) set of II origins.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DomainActiveAnchors {
+    pub ii_origins: Vec<FrontendHostname>,
+    pub active_anchors: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ActiveAuthnMethodStats {
+    pub completed_daily: Option<ActiveAuthnMethodWindow>,
+    pub completed_monthly: Option<ActiveAuthnMethodWindow>,
+    pub ongoing_daily: ActiveAuthnMethodWindow,
+    // sliding 30-day windows, sorted by start timestamp
+    pub ongoing_monthly: Vec<ActiveAuthnMethodWindow>,
+    // additional collection windows configured using the init argument, sorted by name
+    pub additional_windows: Option<Vec<ActiveAuthnMethodWindowStats>>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ActiveAuthnMethodWindowStats {
+    pub name: String,
+    pub length_ns: u64,
+    pub step_ns: u64,
+    pub completed: Option<ActiveAuthnMethodWindow>,
+    // overlapping windows, each offset by step_ns, sorted by start timestamp
+    pub ongoing: Vec<ActiveAuthnMethodWindow>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct ActiveAuthnMethodWindow {
+    pub start_timestamp: Timestamp,
+    pub webauthn_auth: u64,
+    pub webauthn_recovery: u64,
+    pub recovery_phrase: u64,
+    pub browser_storage_key: u64,
+    pub other: u64,
+}
+
+/// Unique active anchors per delegation frontend for the last completed daily and monthly
+/// collection windows (if any).
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DappActivityStats {
+    pub daily: Option<DappActivityWindow>,
+    pub monthly: Option<DappActivityWindow>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DappActivityWindow {
+    pub start_timestamp: Timestamp,
+    // sorted by descending number of active anchors
+    pub dapps: Vec<DappActiveAnchors>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DappActiveAnchors {
+    pub frontend: FrontendHostname,
+    pub active_anchors: u64,
+}
+
+/// Chunk of an asset uploaded by a controller, see `asset_upload`.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct AssetUploadChunk {
+    /// Path of the asset file as in the frontend build output, e.g. `/index.html` or
+    /// `/index.js.gz`. The file extension determines the content type and encoding.
+    pub file_path: String,
+    /// Size of the complete asset in bytes.
+    pub total_length: u64,
+    /// Position of this chunk in the asset. Chunks have to be uploaded in order, a chunk with
+    /// offset 0 (re)starts the upload.
+    pub offset: u64,
+    pub content: ByteBuf,
+}
+
 /// Information about the archive.
 #[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
 pub struct ArchiveInfo {
@@ -237,6 +531,35 @@ pub struct ArchiveConfig {
     pub polling_interval_ns: u64,
     // Max number of archive entries to be fetched in a single call.
     pub entries_fetch_limit: u16,
+    // How entries are transferred from II to the archive. Defaults to `Pull` if not set.
+    pub archive_integration: Option<ArchiveIntegration>,
+    // Archive entries older than this period (in nanoseconds) are pruned by the archive.
+    // Entries are kept forever if not set.
+    pub retention_period_ns: Option<u64>,
+}
+
+/// Range of archive entries that were never transferred to the archive, either because they were
+/// removed from the buffer or because the buffer was full (opt-in authentication events).
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq)]
+pub struct DroppedArchiveEntries {
+    // First dropped sequence number.
+    pub first_sequence_number: u64,
+    // Last dropped sequence number (inclusive).
+    pub last_sequence_number: u64,
+    // Timestamp when the (most recent entry of the) entries were dropped.
+    pub timestamp: Timestamp,
+}
+
+/// Mode of transferring buffered archive entries from II to the archive.
+#[derive(Clone, Debug, CandidType, Deserialize, Eq, PartialEq, Default)]
+pub enum ArchiveIntegration {
+    /// The archive periodically fetches and acknowledges entries buffered by II.
+    #[default]
+    #[serde(rename = "pull")]
+    Pull,
+    /// II pushes buffered entries to the archive as soon as they are available.
+    #[serde(rename = "push")]
+    Push,
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -5,3 +5,5 @@ pub mod archive;
 pub mod http_gateway;
 /// types and implementations related to Internet Identity
 pub mod internet_identity;
+/// formats of the `/metrics` endpoint exposed by the canisters
+pub mod metrics;
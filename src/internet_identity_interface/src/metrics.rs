@@ -0,0 +1,370 @@
+use crate::http_gateway::{parse_quality_values, HeaderField};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+#[cfg(test)]
+mod tests;
+
+/// Path of the metrics endpoint. The format is negotiated using the `Accept` header.
+pub const METRICS_PATH: &str = "/metrics";
+/// Path of the metrics endpoint that always uses the JSON format.
+pub const METRICS_JSON_PATH: &str = "/metrics.json";
+/// Path of the metrics endpoint that always uses the OpenMetrics format.
+pub const METRICS_OPENMETRICS_PATH: &str = "/metrics.openmetrics";
+
+/// Formats in which the canisters expose their metrics.
+///
+/// The metrics are collected in the Prometheus text exposition format (as written by
+/// `ic_metrics_encoder`) and converted to the other formats, so that the set of metrics is
+/// the same for all formats.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MetricsFormat {
+    /// Prometheus text exposition format 0.0.4
+    Prometheus,
+    /// OpenMetrics text format 1.0.0
+    OpenMetrics,
+    /// JSON document listing all metric families
+    Json,
+}
+
+impl MetricsFormat {
+    /// Returns the format to serve for a request to the given path, or `None` if the path is not
+    /// a metrics path.
+    pub fn for_request(path: &str, headers: &[HeaderField]) -> Option<Self> {
+        match path {
+            METRICS_PATH => Some(
+                headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("accept"))
+                    .map_or(MetricsFormat::Prometheus, |(_, accept)| {
+                        Self::from_accept_header(accept)
+                    }),
+            ),
+            METRICS_JSON_PATH => Some(MetricsFormat::Json),
+            METRICS_OPENMETRICS_PATH => Some(MetricsFormat::OpenMetrics),
+            _ => None,
+        }
+    }
+
+    /// Selects the format with the highest quality value among the media ranges of the given
+    /// `Accept` header. Ties are resolved in favour of the media range listed first. Falls back
+    /// to the Prometheus format if none of the listed media ranges is supported.
+    pub fn from_accept_header(accept: &str) -> Self {
+        let mut best: Option<(MetricsFormat, f32)> = None;
+        for (media_type, quality) in parse_quality_values(accept) {
+            let format = match media_type.as_str() {
+                "application/openmetrics-text" => MetricsFormat::OpenMetrics,
+                "application/json" => MetricsFormat::Json,
+                "text/plain" | "text/*" | "*/*" => MetricsFormat::Prometheus,
+                _ => continue,
+            };
+            if quality > 0.0 && best.map_or(true, |(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+        best.map_or(MetricsFormat::Prometheus, |(format, _)| format)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            MetricsFormat::Prometheus => "text/plain; version=0.0.4",
+            MetricsFormat::OpenMetrics => {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            }
+            MetricsFormat::Json => "application/json",
+        }
+    }
+
+    /// Converts metrics in the Prometheus text exposition format to this format.
+    pub fn encode(&self, prometheus_text: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        match self {
+            MetricsFormat::Prometheus => Ok(prometheus_text),
+            MetricsFormat::OpenMetrics => {
+                let families = parse_prometheus_text(prometheus_text)?;
+                Ok(encode_openmetrics(&families).into_bytes())
+            }
+            MetricsFormat::Json => {
+                let families = parse_prometheus_text(prometheus_text)?;
+                serde_json::to_vec_pretty(&JsonMetrics::from(families.as_slice()))
+                    .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct MetricFamily {
+    name: String,
+    help: String,
+    // as given in the `# TYPE` line, `untyped` if missing
+    metric_type: String,
+    samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Sample {
+    name: String,
+    labels: Vec<(String, String)>,
+    value: f64,
+    timestamp_ms: Option<i64>,
+}
+
+fn invalid_line(line: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid metrics line: {line}"),
+    )
+}
+
+fn parse_prometheus_text(text: Vec<u8>) -> std::io::Result<Vec<MetricFamily>> {
+    let text = String::from_utf8(text).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+    let mut families: Vec<MetricFamily> = vec![];
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut parts = comment.trim_start().splitn(3, ' ');
+            let (Some(keyword), Some(name)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let rest = parts.next().unwrap_or_default();
+            match keyword {
+                "HELP" => family_mut(&mut families, name).help = unescape(rest),
+                "TYPE" => family_mut(&mut families, name).metric_type = rest.to_string(),
+                _ => {}
+            }
+            continue;
+        }
+        let sample = parse_sample(line).ok_or_else(|| invalid_line(line))?;
+        let belongs_to_last = families
+            .last()
+            .map_or(false, |family| sample.name.starts_with(&family.name));
+        if !belongs_to_last {
+            family_mut(&mut families, &sample.name);
+        }
+        families
+            .last_mut()
+            .expect("bug: no metric family")
+            .samples
+            .push(sample);
+    }
+    Ok(families)
+}
+
+/// Returns the last family if it has the given name, otherwise adds a new one.
+fn family_mut<'a>(families: &'a mut Vec<MetricFamily>, name: &str) -> &'a mut MetricFamily {
+    if families.last().map_or(true, |family| family.name != name) {
+        families.push(MetricFamily {
+            name: name.to_string(),
+            help: String::new(),
+            metric_type: "untyped".to_string(),
+            samples: vec![],
+        });
+    }
+    families.last_mut().expect("bug: no metric family")
+}
+
+fn parse_sample(line: &str) -> Option<Sample> {
+    let name_end = line.find(|c: char| c == '{' || c.is_whitespace())?;
+    let name = line[..name_end].to_string();
+    let mut rest = &line[name_end..];
+    let mut labels = vec![];
+    if let Some(label_text) = rest.strip_prefix('{') {
+        let mut chars = label_text.char_indices();
+        loop {
+            // label name
+            let (start, _) = chars.find(|(_, c)| !c.is_whitespace() && *c != ',')?;
+            if label_text[start..].starts_with('}') {
+                rest = &label_text[start + 1..];
+                break;
+            }
+            let (eq, _) = chars.find(|(_, c)| *c == '=')?;
+            let label_name = label_text[start..eq].trim().to_string();
+            // quoted label value
+            chars.next().filter(|(_, c)| *c == '"')?;
+            let mut value = String::new();
+            loop {
+                match chars.next()? {
+                    (_, '"') => break,
+                    (_, '\\') => match chars.next()? {
+                        (_, 'n') => value.push('\n'),
+                        (_, c) => value.push(c),
+                    },
+                    (_, c) => value.push(c),
+                }
+            }
+            labels.push((label_name, value));
+        }
+    }
+    let mut fields = rest.split_whitespace();
+    let value = parse_value(fields.next()?)?;
+    let timestamp_ms = match fields.next() {
+        Some(timestamp) => Some(timestamp.parse().ok()?),
+        None => None,
+    };
+    Some(Sample {
+        name,
+        labels,
+        value,
+        timestamp_ms,
+    })
+}
+
+fn parse_value(value: &str) -> Option<f64> {
+    match value {
+        "+Inf" => Some(f64::INFINITY),
+        "-Inf" => Some(f64::NEG_INFINITY),
+        "NaN" => Some(f64::NAN),
+        value => value.parse().ok(),
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                result.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                result.push('\\');
+                chars.next();
+            }
+            (c, _) => result.push(c),
+        }
+    }
+    result
+}
+
+fn escape(text: &str, escape_quotes: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '"' if escape_quotes => result.push_str("\\\""),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn encode_openmetrics(families: &[MetricFamily]) -> String {
+    let mut out = String::new();
+    for family in families {
+        let (family_name, metric_type) = match family.metric_type.as_str() {
+            // OpenMetrics counter samples must have the suffix `_total`, which is not part of
+            // the family name.
+            "counter" => (
+                family
+                    .name
+                    .strip_suffix("_total")
+                    .unwrap_or(&family.name)
+                    .to_string(),
+                "counter",
+            ),
+            "gauge" | "histogram" | "summary" => (family.name.clone(), family.metric_type.as_str()),
+            _ => (family.name.clone(), "unknown"),
+        };
+        out.push_str(&format!("# TYPE {family_name} {metric_type}\n"));
+        if !family.help.is_empty() {
+            out.push_str(&format!(
+                "# HELP {family_name} {}\n",
+                escape(&family.help, false)
+            ));
+        }
+        for sample in &family.samples {
+            let sample_name = if metric_type == "counter" && !sample.name.ends_with("_total") {
+                format!("{}_total", sample.name)
+            } else {
+                sample.name.clone()
+            };
+            out.push_str(&sample_name);
+            if !sample.labels.is_empty() {
+                let labels: Vec<String> = sample
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{name}=\"{}\"", escape(value, true)))
+                    .collect();
+                out.push_str(&format!("{{{}}}", labels.join(",")));
+            }
+            out.push_str(&format!(" {}", format_value(sample.value)));
+            // OpenMetrics timestamps are in seconds
+            if let Some(timestamp_ms) = sample.timestamp_ms {
+                out.push_str(&format!(
+                    " {}.{:03}",
+                    timestamp_ms.div_euclid(1000),
+                    timestamp_ms.rem_euclid(1000)
+                ));
+            }
+            out.push('\n');
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+#[derive(Serialize)]
+struct JsonMetrics {
+    metrics: Vec<JsonMetricFamily>,
+}
+
+#[derive(Serialize)]
+struct JsonMetricFamily {
+    name: String,
+    help: String,
+    #[serde(rename = "type")]
+    metric_type: String,
+    samples: Vec<JsonSample>,
+}
+
+#[derive(Serialize)]
+struct JsonSample {
+    name: String,
+    labels: BTreeMap<String, String>,
+    // non-finite values are encoded as strings ("+Inf", "-Inf", "NaN") as JSON has no
+    // representation for them
+    value: serde_json::Value,
+    timestamp_ms: Option<i64>,
+}
+
+impl From<&[MetricFamily]> for JsonMetrics {
+    fn from(families: &[MetricFamily]) -> Self {
+        JsonMetrics {
+            metrics: families
+                .iter()
+                .map(|family| JsonMetricFamily {
+                    name: family.name.clone(),
+                    help: family.help.clone(),
+                    metric_type: family.metric_type.clone(),
+                    samples: family
+                        .samples
+                        .iter()
+                        .map(|sample| JsonSample {
+                            name: sample.name.clone(),
+                            labels: sample.labels.iter().cloned().collect(),
+                            value: serde_json::Number::from_f64(sample.value).map_or_else(
+                                || serde_json::Value::String(format_value(sample.value)),
+                                serde_json::Value::Number,
+                            ),
+                            timestamp_ms: sample.timestamp_ms,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use super::*;
+use std::path::PathBuf;
+
+/// Metrics in the Prometheus text exposition format, in the form written by `ic_metrics_encoder`.
+const PROMETHEUS_METRICS: &str = include_str!("golden/metrics.prom");
+
+/// Compares the given output to the golden file. Set `UPDATE_GOLDEN_FILES=1` to overwrite the
+/// golden file with the output instead.
+fn assert_golden(output: Vec<u8>, golden_file: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/metrics/golden")
+        .join(golden_file);
+    if std::env::var("UPDATE_GOLDEN_FILES").is_ok() {
+        std::fs::write(&path, &output).unwrap();
+    }
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+}
+
+#[test]
+fn should_keep_prometheus_format() {
+    let output = MetricsFormat::Prometheus
+        .encode(PROMETHEUS_METRICS.as_bytes().to_vec())
+        .unwrap();
+    assert_golden(output, "metrics.prom");
+}
+
+#[test]
+fn should_encode_openmetrics_format() {
+    let output = MetricsFormat::OpenMetrics
+        .encode(PROMETHEUS_METRICS.as_bytes().to_vec())
+        .unwrap();
+    assert_golden(output, "metrics.openmetrics");
+}
+
+#[test]
+fn should_encode_json_format() {
+    let output = MetricsFormat::Json
+        .encode(PROMETHEUS_METRICS.as_bytes().to_vec())
+        .unwrap();
+    assert_golden(output, "metrics.json");
+}
+
+#[test]
+fn should_reject_invalid_metrics() {
+    let result = MetricsFormat::Json.encode(b"metric_without_value\n".to_vec());
+    assert!(result.is_err());
+}
+
+#[test]
+fn should_select_format_by_path() {
+    assert_eq!(
+        MetricsFormat::for_request(METRICS_PATH, &[]),
+        Some(MetricsFormat::Prometheus)
+    );
+    assert_eq!(
+        MetricsFormat::for_request(METRICS_JSON_PATH, &[]),
+        Some(MetricsFormat::Json)
+    );
+    assert_eq!(
+        MetricsFormat::for_request(METRICS_OPENMETRICS_PATH, &[]),
+        Some(MetricsFormat::OpenMetrics)
+    );
+    assert_eq!(MetricsFormat::for_request("/index.html", &[]), None);
+}
+
+#[test]
+fn should_negotiate_format_using_accept_header() {
+    let headers = vec![("Accept".to_string(), "application/json".to_string())];
+    assert_eq!(
+        MetricsFormat::for_request(METRICS_PATH, &headers),
+        Some(MetricsFormat::Json)
+    );
+
+    let cases = [
+        ("text/plain", MetricsFormat::Prometheus),
+        ("*/*", MetricsFormat::Prometheus),
+        (
+            "application/openmetrics-text; version=1.0.0",
+            MetricsFormat::OpenMetrics,
+        ),
+        (
+            "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5,*/*;q=0.1",
+            MetricsFormat::OpenMetrics,
+        ),
+        ("text/plain;q=0.5, application/json", MetricsFormat::Json),
+        (
+            "application/json;q=0, text/plain",
+            MetricsFormat::Prometheus,
+        ),
+        ("text/html", MetricsFormat::Prometheus),
+        ("", MetricsFormat::Prometheus),
+    ];
+    for (accept, expected) in cases {
+        assert_eq!(
+            MetricsFormat::from_accept_header(accept),
+            expected,
+            "unexpected format for Accept: {accept}"
+        );
+    }
+}
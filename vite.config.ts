@@ -46,7 +46,10 @@ export default defineConfig(({ mode }: UserConfig): UserConfig => {
         output: {
           entryFileNames: `[name].js`,
           // II canister only supports resources that contains a single dot in their filenames. qr-creator.js.gz = ok. qr-creator.min.js.gz not ok. qr-creator.es6.min.js.gz no ok.
-          chunkFileNames: ({ name }) => `${name.replace(/.es6|.min/gm, "")}.js`,
+          // Chunks are only referenced by the other bundles, so they are served from "/bundles/" with the content hash in
+          // their filenames and cached as immutable by the canister.
+          chunkFileNames: ({ name }) =>
+            `bundles/${name.replace(/.es6|.min/gm, "")}-[hash].js`,
           assetFileNames: `[name].[ext]`,
         },
       },
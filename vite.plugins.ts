@@ -3,7 +3,7 @@ import { readFileSync } from "fs";
 import { minify } from "html-minifier-terser";
 import { extname } from "path";
 import { Plugin } from "vite";
-import viteCompression from "vite-plugin-compression";
+import { brotliCompressSync, gzipSync } from "zlib";
 
 /**
  * Read the II canister ID from dfx's local state
@@ -48,17 +48,36 @@ export const injectCanisterIdPlugin = (): {
 });
 
 /**
- * GZip generated resources e.g. index.js => index.js.gz
+ * GZip and Brotli compress generated resources e.g. index.js => index.js.gz, index.js.br
  */
-export const compression = (): Plugin =>
-  viteCompression({
-    // II canister only supports one content type per resource. That is why we remove the original file.
-    deleteOriginFile: true,
-    filter: (file: string): boolean =>
-      ![".html", ".css", ".webp", ".png", ".ico", ".svg"].includes(
-        extname(file)
-      ),
-  });
+export const compression = (): Plugin => ({
+  name: "compression",
+  apply: "build",
+  // after the other plugins, which still refer to the original files (e.g. to inject them into HTML)
+  enforce: "post",
+  // The original file is removed, as all files in dist are embedded into the II canister wasm
+  // module. The canister serves the gzip encoded file to clients that accept neither encoding.
+  generateBundle(_options, bundle): void {
+    const uncompressed = [".html", ".css", ".webp", ".png", ".ico", ".svg"];
+    for (const [fileName, output] of Object.entries(bundle)) {
+      if (uncompressed.includes(extname(fileName))) {
+        continue;
+      }
+      const source = output.type === "chunk" ? output.code : output.source;
+      this.emitFile({
+        type: "asset",
+        fileName: `${fileName}.gz`,
+        source: gzipSync(source, { level: 9 }),
+      });
+      this.emitFile({
+        type: "asset",
+        fileName: `${fileName}.br`,
+        source: brotliCompressSync(source),
+      });
+      delete bundle[fileName];
+    }
+  },
+});
 
 /**
  * Minify HTML